@@ -8,8 +8,10 @@ use daemon::bdk::bitcoin::Network;
 use daemon::bdk::bitcoin::Txid;
 use daemon::connection::connect;
 use daemon::connection::ConnectionStatus;
+use daemon::identify;
 use daemon::libp2p_utils::create_connect_multiaddr;
 use daemon::maia_core::secp256k1_zkp::XOnlyPublicKey;
+use daemon::notifier;
 use daemon::projection;
 use daemon::projection::Cfd;
 use daemon::projection::Feeds;
@@ -29,11 +31,14 @@ use model::OpeningFee;
 use model::OrderId;
 use model::Position;
 use model::Price;
+use model::Timestamp;
 use model::TxFeeRate;
 use model::Usd;
 use model::SETTLEMENT_INTERVAL;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
@@ -50,13 +55,14 @@ use tracing_subscriber::EnvFilter;
 use xtra::Actor;
 use xtra_bitmex_price_feed::Quote;
 use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::limits::Limits;
 use xtra_libp2p::multiaddress_ext::MultiaddrExt;
 
 pub mod flow;
 pub mod maia;
 pub mod mocks;
 
-fn oracle_pk() -> XOnlyPublicKey {
+pub fn oracle_pk() -> XOnlyPublicKey {
     XOnlyPublicKey::from_str("ddd4636845a90185991826be5a494cde9f4a6947b1727217afedc6292fa4caf7")
         .unwrap()
 }
@@ -73,7 +79,7 @@ pub async fn start_both() -> (Maker, Taker) {
     (maker, taker)
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct MakerConfig {
     oracle_pk: XOnlyPublicKey,
     seed: RandomSeed,
@@ -81,6 +87,7 @@ pub struct MakerConfig {
     n_payouts: usize,
     dedicated_port: Option<u16>,
     dedicated_libp2p_port: Option<u16>,
+    auto_accept_policy: Option<maker::auto_accept::Policy>,
 }
 
 impl MakerConfig {
@@ -97,6 +104,13 @@ impl MakerConfig {
             ..self
         }
     }
+
+    pub fn with_auto_accept_policy(self, policy: maker::auto_accept::Policy) -> Self {
+        Self {
+            auto_accept_policy: Some(policy),
+            ..self
+        }
+    }
 }
 
 impl Default for MakerConfig {
@@ -108,6 +122,7 @@ impl Default for MakerConfig {
             n_payouts: N_PAYOUTS,
             dedicated_port: None,
             dedicated_libp2p_port: None,
+            auto_accept_policy: None,
         }
     }
 }
@@ -119,6 +134,12 @@ pub struct TakerConfig {
     n_payouts: usize,
 }
 
+impl TakerConfig {
+    pub fn peer_id(&self) -> PeerId {
+        self.seed.derive_identities().peer_id()
+    }
+}
+
 impl Default for TakerConfig {
     fn default() -> Self {
         Self {
@@ -234,10 +255,18 @@ impl Maker {
             settlement_interval,
             config.n_payouts,
             projection_actor,
+            notifier::Actor::new(db.clone(), Vec::new(), HashSet::new())
+                .create(None)
+                .spawn(&mut tasks),
+            None,
             identities.clone(),
             config.heartbeat_interval,
             address,
             endpoint_listen.clone(),
+            None,
+            Limits::default(),
+            config.auto_accept_policy.clone(),
+            None,
         )
         .unwrap();
 
@@ -275,6 +304,8 @@ impl Maker {
             funding_rate_short,
             opening_fee,
             leverage_choices,
+            expiry,
+            settlement_interval,
         } = offer_params;
         self.system
             .set_offer_params(
@@ -287,6 +318,8 @@ impl Maker {
                 funding_rate_short,
                 opening_fee,
                 leverage_choices,
+                expiry.map(|expiry| Timestamp::new(expiry.unix_timestamp())),
+                settlement_interval.map(|interval| interval.whole_hours() as u32),
             )
             .await
             .unwrap();
@@ -350,12 +383,46 @@ impl Taker {
         &mut self.system.maker_online_status_feed_receiver
     }
 
+    pub fn maker_compatibility_feed(
+        &mut self,
+    ) -> &mut watch::Receiver<Option<identify::Compatibility>> {
+        &mut self.system.maker_compatibility_feed_receiver
+    }
+
+    pub fn makers_status_feed(
+        &mut self,
+    ) -> &mut watch::Receiver<HashMap<PeerId, ConnectionStatus>> {
+        &mut self.system.makers_online_status_feed_receiver
+    }
+
     pub async fn start(
         config: &TakerConfig,
         maker_address: SocketAddr,
         maker_identity: Identity,
         maker_multiaddr: Multiaddr,
     ) -> Self {
+        Self::start_with_makers(
+            config,
+            maker_address,
+            vec![daemon::Maker {
+                identity: maker_identity,
+                multiaddrs: vec![maker_multiaddr],
+            }],
+        )
+        .await
+    }
+
+    /// Start a taker connecting to all given makers.
+    ///
+    /// Only the first maker is connected to via legacy networking, at `maker_address`.
+    pub async fn start_with_makers(
+        config: &TakerConfig,
+        maker_address: SocketAddr,
+        makers: Vec<daemon::Maker>,
+    ) -> Self {
+        let maker_identity = makers[0].identity;
+        let maker_multiaddr = makers[0].multiaddrs[0].clone();
+
         let identities = config.seed.derive_identities();
 
         let db = sqlite_db::memory().await.unwrap();
@@ -371,7 +438,9 @@ impl Taker {
 
         let mut oracle_mock = None;
         let mut monitor_mock = None;
-        tracing::info!("Connecting to maker {maker_multiaddr}");
+        for maker in makers.iter() {
+            tracing::info!("Connecting to maker {}", maker.multiaddrs[0]);
+        }
 
         let taker = daemon::TakerActorSystem::new(
             db.clone(),
@@ -391,11 +460,16 @@ impl Taker {
                 Ok(monitor)
             },
             move || price_feed.clone(),
+            None,
             config.n_payouts,
             Duration::from_secs(10),
             projection_actor,
-            maker_identity,
-            maker_multiaddr.clone(),
+            notifier::Actor::new(db.clone(), Vec::new(), HashSet::new())
+                .create(None)
+                .spawn(&mut tasks),
+            None,
+            makers,
+            None,
             Environment::Test,
         )
         .unwrap();
@@ -539,6 +613,8 @@ pub fn dummy_offer_params(position_maker: Position) -> maker::cfd::OfferParams {
         funding_rate_short: FundingRate::new(dec!(0.00024)).unwrap(),
         opening_fee: OpeningFee::new(Amount::from_sat(2)),
         leverage_choices: vec![Leverage::TWO],
+        expiry: None,
+        settlement_interval: None,
     }
 }
 
@@ -559,6 +635,7 @@ pub fn init_tracing() -> DefaultGuard {
         .add_directive("xtra_libp2p=debug".parse().unwrap())
         .add_directive("xtra_libp2p_offer=debug".parse().unwrap())
         .add_directive("xtra_libp2p_ping=debug".parse().unwrap())
+        .add_directive("xtra_libp2p_identify=debug".parse().unwrap())
         .add_directive("rocket=warn".parse().unwrap());
 
     tracing_subscriber::fmt()
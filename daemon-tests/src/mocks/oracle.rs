@@ -48,6 +48,10 @@ impl OracleActor {
 
     async fn handle(&mut self, _msg: oracle::MonitorAttestation) {}
 
+    async fn handle(&mut self, _msg: oracle::SyncAnnouncement) {}
+
+    async fn handle(&mut self, _msg: oracle::SettlementIntervalInUse) {}
+
     async fn handle(&mut self, _msg: oracle::SyncAnnouncements) {}
 
     async fn handle(&mut self, _msg: oracle::SyncAttestations) {}
@@ -0,0 +1,432 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use daemon::bdk::bitcoin::Network;
+use daemon::command;
+use daemon::contract_setup;
+use daemon::monitor;
+use daemon::notifier;
+use daemon::position_metrics;
+use daemon::process_manager;
+use daemon::projection;
+use daemon::projection::CfdState;
+use daemon::seed::Identities;
+use daemon::seed::RandomSeed;
+use daemon::seed::Seed;
+use daemon::N_PAYOUTS;
+use daemon_tests::init_tracing;
+use daemon_tests::maia::OliviaData;
+use daemon_tests::mocks::oracle::OracleActor;
+use daemon_tests::mocks::wallet;
+use daemon_tests::mocks::wallet::WalletActor;
+use daemon_tests::oracle_pk;
+use model::libp2p::PeerId;
+use model::olivia::BitMexPriceEventId;
+use model::Cfd;
+use model::ContractType;
+use model::FundingRate;
+use model::Identity;
+use model::Leverage;
+use model::OpeningFee;
+use model::Order;
+use model::OrderId;
+use model::Origin;
+use model::Position;
+use model::Price;
+use model::Role;
+use model::TxFeeRate;
+use model::Usd;
+use rust_decimal_macros::dec;
+use std::time::Duration;
+use tokio_tasks::Tasks;
+use xtra::message_channel::MessageChannel;
+use xtra::Actor as _;
+use xtra::Address;
+use xtra::Context;
+use xtra_libp2p::endpoint::Subscribers;
+use xtra_libp2p::libp2p::multiaddr::Protocol;
+use xtra_libp2p::libp2p::transport::MemoryTransport;
+use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::Connect;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p::ListenOn;
+use xtra_libp2p::NewInboundSubstream;
+use xtra_productivity::xtra_productivity;
+
+#[tokio::test]
+async fn taker_and_maker_set_up_contract_over_libp2p() {
+    let _guard = init_tracing();
+    let mut tasks = Tasks::default();
+    let order = dummy_order();
+
+    let maker = Party::new(Role::Maker, &mut tasks).await;
+    let taker = Party::new(Role::Taker, &mut tasks).await;
+    let maker_address = maker
+        .listen_with_contract_setup(&order, Decision::Accept, &mut tasks)
+        .await;
+    let taker_contract_setup = taker.connect_to(maker_address, &mut tasks).await;
+
+    taker.insert_cfd(&order, &maker).await;
+    taker_contract_setup
+        .send(take_order(&order, &maker))
+        .await
+        .unwrap()
+        .unwrap();
+
+    maker.wait_for_state(order.id, CfdState::PendingOpen).await;
+    taker.wait_for_state(order.id, CfdState::PendingOpen).await;
+}
+
+#[tokio::test]
+async fn taker_records_rejection_of_maker() {
+    let _guard = init_tracing();
+    let mut tasks = Tasks::default();
+    let order = dummy_order();
+
+    let maker = Party::new(Role::Maker, &mut tasks).await;
+    let taker = Party::new(Role::Taker, &mut tasks).await;
+    let maker_address = maker
+        .listen_with_contract_setup(&order, Decision::Reject, &mut tasks)
+        .await;
+    let taker_contract_setup = taker.connect_to(maker_address, &mut tasks).await;
+
+    taker.insert_cfd(&order, &maker).await;
+    taker_contract_setup
+        .send(take_order(&order, &maker))
+        .await
+        .unwrap()
+        .unwrap();
+
+    maker.wait_for_state(order.id, CfdState::Rejected).await;
+    taker.wait_for_state(order.id, CfdState::Rejected).await;
+}
+
+/// Taking an order fails without touching the CFD if the maker does not speak the protocol, which
+/// is what allows the taker to fall back to the legacy contract setup.
+#[tokio::test]
+async fn taking_order_of_maker_without_libp2p_contract_setup_leaves_cfd_untouched() {
+    let _guard = init_tracing();
+    let mut tasks = Tasks::default();
+    let order = dummy_order();
+
+    let maker = Party::new(Role::Maker, &mut tasks).await;
+    let taker = Party::new(Role::Taker, &mut tasks).await;
+    let maker_address = maker.listen([], &mut tasks).await;
+    let taker_contract_setup = taker.connect_to(maker_address, &mut tasks).await;
+
+    taker.insert_cfd(&order, &maker).await;
+    let result = taker_contract_setup
+        .send(take_order(&order, &maker))
+        .await
+        .unwrap();
+
+    assert!(result.is_err(), "taking the order should fail");
+    assert_eq!(taker.state(order.id).await, CfdState::PendingSetup);
+}
+
+/// One side of the protocol with everything it needs to record the events of its CFDs.
+struct Party {
+    db: sqlite_db::Connection,
+    executor: command::Executor,
+    identities: Identities,
+    wallet: Address<WalletActor>,
+    oracle: Address<OracleActor>,
+}
+
+#[derive(Clone, Copy)]
+enum Decision {
+    Accept,
+    Reject,
+}
+
+impl Party {
+    async fn new(role: Role, tasks: &mut Tasks) -> Self {
+        let db = sqlite_db::memory().await.unwrap();
+
+        let (process_manager, process_manager_ctx) = Context::new(None);
+        let executor = command::Executor::new(db.clone(), process_manager);
+
+        let (oracle, oracle_mock) = OracleActor::new(executor.clone());
+        oracle_mock
+            .lock()
+            .await
+            .set_announcement(OliviaData::example_0().announcement());
+        let oracle = oracle.create(None).spawn(tasks);
+
+        let (wallet, wallet_mock) = WalletActor::new();
+        {
+            let mut wallet_mock = wallet_mock.lock().await;
+            #[allow(clippy::redundant_closure)] // clippy is in the wrong here
+            wallet_mock
+                .expect_build_party_params()
+                .returning(|msg| wallet::build_party_params(msg));
+            wallet_mock.expect_sign().returning(|msg| Ok(msg.psbt));
+        }
+        let wallet = wallet.create(None).spawn(tasks);
+
+        let sink = Sink.create(None).spawn(tasks);
+        tasks.add(process_manager_ctx.run(process_manager::Actor::new(
+            db.clone(),
+            role,
+            sink.clone().into(),
+            sink.clone().into(),
+            sink.clone().into(),
+            sink.clone().into(),
+            sink.clone().into(),
+            sink.clone().into(),
+            sink.into(),
+            oracle.clone().into(),
+            None,
+        )));
+
+        Self {
+            db,
+            executor,
+            identities: RandomSeed::default().derive_identities(),
+            wallet,
+            oracle,
+        }
+    }
+
+    fn peer_id(&self) -> PeerId {
+        self.identities.peer_id()
+    }
+
+    fn identity(&self) -> Identity {
+        Identity::new(self.identities.identity_pk)
+    }
+
+    /// Listen for takers, recording and deciding upon orders in place of the maker's CFD actor.
+    async fn listen_with_contract_setup(
+        &self,
+        order: &Order,
+        decision: Decision,
+        tasks: &mut Tasks,
+    ) -> Multiaddr {
+        let (contract_setup, contract_setup_ctx) = Context::new(None);
+
+        let offers = Offers {
+            db: self.db.clone(),
+            order: order.clone(),
+            contract_setup: contract_setup.clone(),
+            decision,
+        }
+        .create(None)
+        .spawn(tasks);
+
+        tasks.add(contract_setup_ctx.run(contract_setup::maker::Actor::new(
+            self.executor.clone(),
+            oracle_pk(),
+            self.oracle.clone().into(),
+            (self.wallet.clone().into(), self.wallet.clone().into()),
+            (offers.clone().into(), offers.into()),
+            N_PAYOUTS,
+        )));
+
+        self.listen([(contract_setup::PROTOCOL, contract_setup.into())], tasks)
+            .await
+    }
+
+    async fn listen<const N: usize>(
+        &self,
+        substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); N],
+        tasks: &mut Tasks,
+    ) -> Multiaddr {
+        let endpoint = self.endpoint(substream_handlers, tasks);
+
+        let listen_address = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+        endpoint
+            .send(ListenOn(listen_address.clone()))
+            .await
+            .unwrap();
+
+        listen_address.with(Protocol::P2p(self.peer_id().inner().into()))
+    }
+
+    async fn connect_to(
+        &self,
+        maker_address: Multiaddr,
+        tasks: &mut Tasks,
+    ) -> Address<contract_setup::taker::Actor> {
+        let endpoint = self.endpoint([], tasks);
+
+        endpoint
+            .send(Connect(maker_address))
+            .await
+            .unwrap()
+            .unwrap();
+
+        contract_setup::taker::Actor::new(
+            endpoint,
+            self.executor.clone(),
+            oracle_pk(),
+            (self.wallet.clone().into(), self.wallet.clone().into()),
+            self.identity(),
+            N_PAYOUTS,
+        )
+        .create(None)
+        .spawn(tasks)
+    }
+
+    fn endpoint<const N: usize>(
+        &self,
+        substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); N],
+        tasks: &mut Tasks,
+    ) -> Address<Endpoint> {
+        Endpoint::new(
+            Box::new(MemoryTransport::default),
+            self.identities.libp2p.clone(),
+            Duration::from_secs(10),
+            substream_handlers,
+            Subscribers::default(),
+        )
+        .create(None)
+        .spawn(tasks)
+    }
+
+    async fn insert_cfd(&self, order: &Order, maker: &Party) {
+        let cfd = Cfd::from_order(
+            order,
+            quantity(),
+            maker.identity(),
+            Some(maker.peer_id()),
+            Role::Taker,
+            Leverage::TWO,
+        );
+
+        self.db.insert_cfd(&cfd).await.unwrap();
+    }
+
+    async fn state(&self, order_id: OrderId) -> CfdState {
+        self.db
+            .load_open_cfd::<projection::Cfd>(order_id, Network::Testnet)
+            .await
+            .unwrap()
+            .state
+    }
+
+    async fn wait_for_state(&self, order_id: OrderId, state: CfdState) {
+        tokio::time::timeout(Duration::from_secs(60), async {
+            while self.state(order_id).await != state {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("CFD to reach state {state:?}"));
+    }
+}
+
+fn take_order(order: &Order, maker: &Party) -> contract_setup::taker::TakeOrder {
+    contract_setup::taker::TakeOrder {
+        order_id: order.id,
+        quantity: quantity(),
+        leverage: Leverage::TWO,
+        maker_peer_id: maker.peer_id(),
+        announcement: OliviaData::example_0().announcement(),
+    }
+}
+
+fn dummy_order() -> Order {
+    Order::new(
+        Position::Short,
+        Price::new(dec!(1000)).unwrap(),
+        Usd::new(dec!(100)),
+        Usd::new(dec!(1000)),
+        Origin::Ours,
+        OliviaData::example_0().announcement().id,
+        time::Duration::hours(24),
+        TxFeeRate::default(),
+        FundingRate::default(),
+        OpeningFee::default(),
+        vec![Leverage::TWO],
+        ContractType::Perpetual,
+    )
+}
+
+fn quantity() -> Usd {
+    Usd::new(dec!(100))
+}
+
+/// Stands in for the maker's CFD actor, recording taken orders and deciding upon them.
+struct Offers {
+    db: sqlite_db::Connection,
+    order: Order,
+    contract_setup: Address<contract_setup::maker::Actor>,
+    decision: Decision,
+}
+
+#[async_trait]
+impl xtra::Actor for Offers {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[xtra_productivity(message_impl = false)]
+impl Offers {
+    async fn handle(
+        &mut self,
+        msg: contract_setup::maker::OrderReceived,
+    ) -> Result<BitMexPriceEventId> {
+        let cfd = Cfd::from_order(
+            &self.order,
+            msg.quantity,
+            msg.taker_id,
+            Some(msg.taker_peer_id),
+            Role::Maker,
+            msg.leverage,
+        );
+        self.db.insert_cfd(&cfd).await?;
+
+        Ok(self.order.oracle_event_id)
+    }
+
+    async fn handle(&mut self, msg: contract_setup::maker::OrderTaken) {
+        let order_id = msg.order_id;
+
+        let result = match self.decision {
+            Decision::Accept => {
+                self.contract_setup
+                    .send(contract_setup::maker::Accept { order_id })
+                    .await
+            }
+            Decision::Reject => {
+                self.contract_setup
+                    .send(contract_setup::maker::Reject { order_id })
+                    .await
+            }
+        };
+
+        result.unwrap().unwrap();
+    }
+}
+
+/// Swallows everything the process manager dispatches after recording an event.
+struct Sink;
+
+#[async_trait]
+impl xtra::Actor for Sink {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[xtra_productivity(message_impl = false)]
+impl Sink {
+    async fn handle(&mut self, _: projection::CfdChanged) {}
+
+    async fn handle(&mut self, _: position_metrics::CfdChanged) {}
+
+    async fn handle(&mut self, _: notifier::Notify) {}
+
+    async fn handle(&mut self, _: monitor::TryBroadcastTransaction) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle(&mut self, _: monitor::StartMonitoring) {}
+
+    async fn handle(&mut self, _: monitor::MonitorCetFinality) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle(&mut self, _: monitor::MonitorCollaborativeSettlement) {}
+}
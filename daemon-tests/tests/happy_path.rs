@@ -3,6 +3,7 @@ use daemon::bdk::bitcoin::Amount;
 use daemon::bdk::bitcoin::SignedAmount;
 use daemon::bdk::bitcoin::Txid;
 use daemon::connection::ConnectionStatus;
+use daemon::identify::Compatibility;
 use daemon::projection::CfdOrder;
 use daemon::projection::CfdState;
 use daemon::projection::MakerOffers;
@@ -24,7 +25,9 @@ use daemon_tests::Maker;
 use daemon_tests::MakerConfig;
 use daemon_tests::Taker;
 use daemon_tests::TakerConfig;
+use maker::auto_accept;
 use maker::cfd::OfferParams;
+use maker::funding_rate;
 use model::olivia;
 use model::olivia::BitMexPriceEventId;
 use model::FeeAccount;
@@ -34,12 +37,19 @@ use model::Leverage;
 use model::OpeningFee;
 use model::OrderId;
 use model::Position;
+use model::Price;
 use model::Role;
 use model::Usd;
 use model::SETTLEMENT_INTERVAL;
+use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
+use tokio_tasks::Tasks;
+use xtra::Actor;
 
 macro_rules! confirm {
     (lock transaction, $id:expr, $maker:expr, $taker:expr) => {
@@ -143,6 +153,70 @@ async fn taker_receives_order_from_maker_on_publication() {
     assert_eq_offers(published, received);
 }
 
+#[tokio::test]
+async fn taker_receives_order_with_settlement_interval_chosen_by_maker() {
+    let _guard = init_tracing();
+    let (mut maker, mut taker) = start_both().await;
+
+    let settlement_interval = time::Duration::hours(6);
+    maker
+        .set_offer_params(OfferParams {
+            settlement_interval: Some(settlement_interval),
+            ..dummy_offer_params(Position::Short)
+        })
+        .await;
+
+    let (published, received) = next_maker_offers(maker.offers_feed(), taker.offers_feed())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        received
+            .short
+            .as_ref()
+            .unwrap()
+            .settlement_time_interval_in_secs,
+        settlement_interval.whole_seconds() as u64
+    );
+    assert_eq_offers(published, received);
+}
+
+#[tokio::test]
+async fn maker_derives_funding_rate_from_reference_feed() {
+    let _guard = init_tracing();
+    let (mut maker, mut taker) = start_both().await;
+
+    maker
+        .set_offer_params(dummy_offer_params(Position::Short))
+        .await;
+    next_maker_offers(maker.offers_feed(), taker.offers_feed())
+        .await
+        .unwrap();
+
+    let reference_feed =
+        std::env::temp_dir().join(format!("funding-rate-{}", rand::random::<u64>()));
+    std::fs::write(&reference_feed, "0.0002").unwrap();
+
+    let mut tasks = Tasks::default();
+    let _funding_rate_actor = funding_rate::Actor::new(
+        Arc::new(funding_rate::File::new(reference_feed.clone())),
+        funding_rate::Params::new(dec!(0.0005), Decimal::ONE).unwrap(),
+        Duration::from_secs(1),
+        maker.system.cfd_actor.clone().into(),
+    )
+    .create(None)
+    .spawn(&mut tasks);
+
+    let (_, received) = next_maker_offers(maker.offers_feed(), taker.offers_feed())
+        .await
+        .unwrap();
+    std::fs::remove_file(reference_feed).unwrap();
+
+    let hourly_percent =
+        Decimal::from_str(&received.short.unwrap().funding_rate_hourly_percent).unwrap();
+    assert_eq!(hourly_percent, dec!(0.02));
+}
+
 fn assert_eq_offers(published: MakerOffers, received: MakerOffers) {
     match (published.long, received.long) {
         (None, None) => (),
@@ -318,6 +392,152 @@ async fn taker_takes_order_and_maker_accepts_and_contract_setup() {
     wait_next_state!(order_id, maker, taker, CfdState::Open);
 }
 
+#[tokio::test]
+async fn taker_takes_best_offer_across_makers_and_sets_up_cfd_with_its_maker() {
+    let _guard = init_tracing();
+
+    let mut maker_a = Maker::start(&MakerConfig::default()).await;
+    let mut maker_b = Maker::start(&MakerConfig::default()).await;
+    let mut taker = Taker::start_with_makers(
+        &TakerConfig::default(),
+        maker_a.listen_addr,
+        vec![
+            daemon::Maker {
+                identity: maker_a.identity,
+                multiaddrs: vec![maker_a.connect_addr.clone()],
+            },
+            daemon::Maker {
+                identity: maker_b.identity,
+                multiaddrs: vec![maker_b.connect_addr.clone()],
+            },
+        ],
+    )
+    .await;
+
+    next_with(taker.makers_status_feed(), |statuses| {
+        (statuses.len() == 2
+            && statuses
+                .values()
+                .all(|status| *status == ConnectionStatus::Online))
+        .then(|| ())
+    })
+    .await
+    .unwrap();
+
+    maker_a
+        .set_offer_params(OfferParams {
+            price_short: Some(Price::new(dec!(50_000)).unwrap()),
+            ..dummy_offer_params(Position::Long)
+        })
+        .await;
+    // Maker B's short offer is cheaper for the taker going long
+    maker_b
+        .set_offer_params(OfferParams {
+            price_short: Some(Price::new(dec!(49_000)).unwrap()),
+            ..dummy_offer_params(Position::Short)
+        })
+        .await;
+
+    let offer_a = next(maker_a.offers_feed()).await.unwrap();
+    let offer_b = next(maker_b.offers_feed()).await.unwrap();
+    let long_order_id = offer_a.long.unwrap().id;
+    let order_id = offer_b.short.unwrap().id;
+
+    next_with(taker.offers_feed(), |offers| {
+        (offers.long.map(|long| long.id) == Some(long_order_id)
+            && offers.short.map(|short| short.id) == Some(order_id))
+        .then(|| ())
+    })
+    .await
+    .unwrap();
+
+    taker.mocks.mock_oracle_announcement().await;
+    maker_b.mocks.mock_oracle_announcement().await;
+
+    taker
+        .system
+        .take_offer(order_id, Usd::new(dec!(100)), Leverage::TWO)
+        .await
+        .unwrap();
+    wait_next_state!(order_id, maker_b, taker, CfdState::PendingSetup);
+
+    maker_b.mocks.mock_party_params().await;
+    taker.mocks.mock_party_params().await;
+
+    maker_b.mocks.mock_wallet_sign_and_broadcast().await;
+    taker.mocks.mock_wallet_sign_and_broadcast().await;
+
+    maker_b.system.accept_order(order_id).await.unwrap();
+    wait_next_state!(order_id, maker_b, taker, CfdState::ContractSetup);
+
+    sleep(Duration::from_secs(5)).await; // need to wait a bit until both transition
+    wait_next_state!(order_id, maker_b, taker, CfdState::PendingOpen);
+
+    assert!(
+        maker_a
+            .cfd_feed()
+            .borrow()
+            .as_ref()
+            .map_or(true, |cfds| cfds.is_empty()),
+        "CFD should only be set up with the maker whose offer was taken"
+    );
+}
+
+#[tokio::test]
+async fn maker_auto_accepts_order_of_known_taker_within_policy() {
+    let _guard = init_tracing();
+
+    let taker_config = TakerConfig::default();
+    let policy = auto_accept::Policy {
+        orders: Some(auto_accept::OrderPolicy {
+            max_quantity: Usd::new(dec!(100)),
+            known_takers: HashSet::from([taker_config.peer_id()]),
+        }),
+        ..auto_accept::Policy::default()
+    };
+    let mut maker = Maker::start(&MakerConfig::default().with_auto_accept_policy(policy)).await;
+    let mut taker = Taker::start(
+        &taker_config,
+        maker.listen_addr,
+        maker.identity,
+        maker.connect_addr.clone(),
+    )
+    .await;
+
+    is_next_offers_none(taker.offers_feed()).await.unwrap();
+
+    maker
+        .set_offer_params(dummy_offer_params(Position::Short))
+        .await;
+
+    let (_, received) = next_maker_offers(maker.offers_feed(), taker.offers_feed())
+        .await
+        .unwrap();
+
+    let order_id = received.short.unwrap().id;
+
+    taker.mocks.mock_oracle_announcement().await;
+    maker.mocks.mock_oracle_announcement().await;
+
+    maker.mocks.mock_party_params().await;
+    taker.mocks.mock_party_params().await;
+
+    maker.mocks.mock_wallet_sign_and_broadcast().await;
+    taker.mocks.mock_wallet_sign_and_broadcast().await;
+
+    taker
+        .system
+        .take_offer(order_id, Usd::new(dec!(100)), Leverage::TWO)
+        .await
+        .unwrap();
+
+    // The maker never calls `accept_order`
+    wait_next_state!(order_id, maker, taker, CfdState::ContractSetup);
+
+    sleep(Duration::from_secs(5)).await; // need to wait a bit until both transition
+    wait_next_state!(order_id, maker, taker, CfdState::PendingOpen);
+}
+
 #[tokio::test]
 async fn collaboratively_close_an_open_cfd_maker_going_short() {
     let _guard = init_tracing();
@@ -893,6 +1113,19 @@ async fn taker_notices_lack_of_maker() {
     );
 }
 
+#[tokio::test]
+async fn taker_identifies_compatible_maker() {
+    let _guard = init_tracing();
+    let (_maker, mut taker) = start_both().await;
+
+    sleep(Duration::from_secs(5)).await; // wait a bit until taker identifies maker
+
+    assert_eq!(
+        Some(Compatibility::Compatible),
+        *taker.maker_compatibility_feed().borrow()
+    );
+}
+
 #[tokio::test]
 async fn maker_notices_lack_of_taker() {
     let _guard = init_tracing();
@@ -0,0 +1,356 @@
+use crate::integrity;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use model::Identity;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::OffsetDateTime;
+use tokio_tasks::Tasks;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+/// Timestamp in the file name of backups, sorts in chronological order.
+const BACKUP_TIMESTAMP_FORMAT: &[FormatItem] =
+    format_description!("[year][month][day]T[hour][minute][second]Z");
+
+/// The files SQLite keeps next to a database in WAL mode.
+const SIDECAR_SUFFIXES: [&str; 2] = ["-wal", "-shm"];
+
+/// Takes backups of the database while the daemon is running.
+///
+/// Backups are taken on a schedule and whenever a [`Backup`] message is received, e.g. after a
+/// contract setup or rollover completed, so that the latest signed transactions are never only
+/// stored in the live database. Only the most recent backups are kept.
+pub struct Actor {
+    db_path: PathBuf,
+    dir: PathBuf,
+    /// Prefix of the backup file names, e.g. `taker`.
+    name: String,
+    interval: Duration,
+    keep: usize,
+    tasks: Tasks,
+}
+
+impl Actor {
+    pub fn new(
+        db_path: PathBuf,
+        dir: PathBuf,
+        name: impl Into<String>,
+        interval: Duration,
+        keep: usize,
+    ) -> Self {
+        Self {
+            db_path,
+            dir,
+            name: name.into(),
+            interval,
+            keep,
+            tasks: Tasks::default(),
+        }
+    }
+
+    async fn backup(&self) -> Result<PathBuf> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        let timestamp = OffsetDateTime::now_utc().format(&BACKUP_TIMESTAMP_FORMAT)?;
+        let destination = self.dir.join(format!("{}-{timestamp}.sqlite", self.name));
+
+        let source = self.db_path.clone();
+        let backup = destination.clone();
+        tokio::task::spawn_blocking(move || sqlite_db::backup(&source, &backup)).await??;
+
+        Ok(destination)
+    }
+
+    /// Delete all but the `keep` most recent backups.
+    async fn prune(&self) -> Result<()> {
+        let prefix = format!("{}-", self.name);
+
+        let mut backups = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+
+            if file_name.starts_with(&prefix) && file_name.ends_with(".sqlite") {
+                backups.push(entry.path());
+            }
+        }
+
+        backups.sort();
+
+        let excess = backups.len().saturating_sub(self.keep);
+        for backup in backups.into_iter().take(excess) {
+            tokio::fs::remove_file(&backup)
+                .await
+                .with_context(|| format!("Failed to delete old backup {}", backup.display()))?;
+
+            tracing::debug!(path = %backup.display(), "Deleted old backup");
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+
+        self.tasks.add(this.send_interval(self.interval, || Backup));
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: Backup) {
+        match self.backup().await {
+            Ok(path) => tracing::info!(path = %path.display(), "Backed up database"),
+            Err(e) => {
+                tracing::error!("Failed to back up database: {e:#}");
+                return;
+            }
+        }
+
+        if let Err(e) = self.prune().await {
+            tracing::warn!("Failed to delete old backups: {e:#}");
+        }
+    }
+}
+
+/// Take a backup of the database now.
+#[derive(Clone, Copy)]
+pub struct Backup;
+
+/// Record the identity derived from the seed in the database, so that backups can be matched to
+/// the seed they belong to.
+///
+/// Databases created before identities were recorded are claimed by the current seed. Fails if
+/// the database belongs to a different seed, as its CFDs could not be signed for.
+pub async fn record_identity(db: &sqlite_db::Connection, identity: Identity) -> Result<()> {
+    match db.load_local_identity().await? {
+        None => db.insert_local_identity(identity).await?,
+        Some(recorded) if recorded == identity => {}
+        Some(recorded) => {
+            bail!("Database belongs to identity {recorded}, but the seed derives {identity}")
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the database at `db_path` with the given backup.
+///
+/// The backup is only restored if it belongs to `identity`, passes the SQLite integrity check and
+/// its schema can be migrated. Inconsistent event logs are reported, but do not prevent the
+/// restore, as they may be what the backup is supposed to recover from. The database that is
+/// replaced is moved aside rather than deleted.
+///
+/// Must not be called while a daemon is using the database.
+pub async fn restore(backup: &Path, db_path: &Path, identity: Identity) -> Result<()> {
+    let restored = with_suffix(db_path, ".restore");
+
+    remove_database(&restored).await?;
+    let source = backup.to_path_buf();
+    let destination = restored.clone();
+    tokio::task::spawn_blocking(move || sqlite_db::backup(&source, &destination)).await??;
+
+    if let Err(e) = validate(&restored, identity).await {
+        remove_database(&restored).await?;
+        return Err(e.context(format!("Refusing to restore {}", backup.display())));
+    }
+
+    if tokio::fs::metadata(db_path).await.is_ok() {
+        let unix_timestamp = OffsetDateTime::now_utc().unix_timestamp();
+        let replaced = with_suffix(db_path, &format!("-{unix_timestamp}-pre-restore"));
+
+        move_database(db_path, &replaced).await?;
+        tracing::info!(path = %replaced.display(), "Moved previous database aside");
+    }
+
+    move_database(&restored, db_path).await?;
+    tracing::info!(
+        backup = %backup.display(),
+        path = %db_path.display(),
+        "Restored database from backup"
+    );
+
+    Ok(())
+}
+
+async fn validate(path: &Path, identity: Identity) -> Result<()> {
+    let db = sqlite_db::connect(path.to_path_buf()).await?;
+
+    let result: Result<()> = async {
+        match db.load_local_identity().await? {
+            Some(recorded) if recorded == identity => {}
+            Some(recorded) => {
+                bail!("Backup belongs to identity {recorded}, the seed derives {identity}")
+            }
+            None => bail!("Backup does not record the identity it belongs to"),
+        }
+
+        let problems = db.check_integrity().await?;
+        if !problems.is_empty() {
+            bail!("Backup is corrupted: {}", problems.join("; "));
+        }
+
+        for inconsistency in integrity::check_event_logs(&db).await? {
+            tracing::warn!("Backup contains inconsistent event log: {inconsistency}");
+        }
+
+        Ok(())
+    }
+    .await;
+
+    db.close().await;
+
+    result
+}
+
+/// Move the database file at `from` to `to`, along with the files SQLite keeps next to it.
+async fn move_database(from: &Path, to: &Path) -> Result<()> {
+    tokio::fs::rename(from, to)
+        .await
+        .with_context(|| format!("Failed to move {} to {}", from.display(), to.display()))?;
+
+    for suffix in SIDECAR_SUFFIXES {
+        let from = with_suffix(from, suffix);
+
+        if tokio::fs::metadata(&from).await.is_ok() {
+            let to = with_suffix(to, suffix);
+
+            tokio::fs::rename(&from, &to).await.with_context(|| {
+                format!("Failed to move {} to {}", from.display(), to.display())
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete the database file at `path` and the files SQLite keeps next to it, if they exist.
+async fn remove_database(path: &Path) -> Result<()> {
+    let sidecars = SIDECAR_SUFFIXES.map(|suffix| with_suffix(path, suffix));
+
+    for path in std::iter::once(path.to_path_buf()).chain(sidecars) {
+        if tokio::fs::metadata(&path).await.is_ok() {
+            tokio::fs::remove_file(&path)
+                .await
+                .with_context(|| format!("Failed to delete {}", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn recording_a_different_identity_fails() {
+        let db = sqlite_db::memory().await.unwrap();
+
+        record_identity(&db, identity(1)).await.unwrap();
+        record_identity(&db, identity(1)).await.unwrap();
+
+        assert!(record_identity(&db, identity(2)).await.is_err());
+        assert_eq!(db.load_local_identity().await.unwrap(), Some(identity(1)));
+    }
+
+    #[tokio::test]
+    async fn restore_moves_previous_database_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("taker.sqlite");
+        let backup_path = dir.path().join("backup.sqlite");
+        create_database(&db_path, identity(1)).await;
+        sqlite_db::backup(&db_path, &backup_path).unwrap();
+
+        restore(&backup_path, &db_path, identity(1)).await.unwrap();
+
+        assert!(db_path.exists());
+        assert!(!with_suffix(&db_path, ".restore").exists());
+        assert_eq!(files_ending_with(dir.path(), "-pre-restore").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_backup_of_different_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("taker.sqlite");
+        let backup_path = dir.path().join("backup.sqlite");
+        create_database(&db_path, identity(1)).await;
+        create_database(&backup_path, identity(2)).await;
+
+        let result = restore(&backup_path, &db_path, identity(1)).await;
+
+        assert!(result.is_err());
+        assert_database_untouched(&db_path).await;
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_corrupted_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("taker.sqlite");
+        let backup_path = dir.path().join("backup.sqlite");
+        create_database(&db_path, identity(1)).await;
+        sqlite_db::backup(&db_path, &backup_path).unwrap();
+
+        // Overwrite everything after the header of the first page
+        let mut content = tokio::fs::read(&backup_path).await.unwrap();
+        content[100..].fill(0xff);
+        tokio::fs::write(&backup_path, content).await.unwrap();
+
+        let result = restore(&backup_path, &db_path, identity(1)).await;
+
+        assert!(result.is_err());
+        assert_database_untouched(&db_path).await;
+    }
+
+    async fn create_database(path: &Path, identity: Identity) {
+        let db = sqlite_db::connect(path.to_path_buf()).await.unwrap();
+        record_identity(&db, identity).await.unwrap();
+        db.close().await;
+    }
+
+    async fn assert_database_untouched(db_path: &Path) {
+        assert!(db_path.exists());
+        assert!(!with_suffix(db_path, ".restore").exists());
+        assert!(files_ending_with(db_path.parent().unwrap(), "-pre-restore")
+            .await
+            .is_empty());
+    }
+
+    async fn files_ending_with(dir: &Path, suffix: &str) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            if entry.file_name().to_string_lossy().ends_with(suffix) {
+                files.push(entry.path());
+            }
+        }
+
+        files
+    }
+
+    fn identity(byte: u8) -> Identity {
+        Identity::new(x25519_dalek::PublicKey::from([byte; 32]))
+    }
+}
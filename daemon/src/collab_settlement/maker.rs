@@ -1,5 +1,6 @@
 use crate::collab_settlement::protocol::*;
 use crate::command;
+use crate::correlation::CorrelationId;
 use crate::future_ext::FutureExt;
 use anyhow::anyhow;
 use anyhow::Context;
@@ -17,15 +18,20 @@ use model::SettlementProposal;
 use model::SettlementTransaction;
 use std::collections::HashMap;
 use tokio_tasks::Tasks;
+use tracing::Instrument;
+use tracing::Span;
+use xtra::message_channel::MessageChannel;
 use xtra_libp2p::NewInboundSubstream;
 use xtra_libp2p::Substream;
 use xtra_productivity::xtra_productivity;
+use xtras::SendAsyncSafe;
 
 type ListenerConnection = (
     Framed<Substream, JsonCodec<ListenerMessage, DialerMessage>>,
     SettlementTransaction,
     SettlementProposal,
     PeerId,
+    Span,
 );
 
 /// Permanent actor to handle incoming substreams for the `/itchysats/collab-settlement/1.0.0`
@@ -39,16 +45,22 @@ pub struct Actor {
     pending_protocols: HashMap<OrderId, ListenerConnection>,
     executor: command::Executor,
     n_payouts: usize,
+    settlement_proposed: MessageChannel<SettlementProposed, ()>,
 }
 
 impl Actor {
-    pub fn new(executor: command::Executor, n_payouts: usize) -> Self {
+    pub fn new(
+        executor: command::Executor,
+        n_payouts: usize,
+        settlement_proposed: MessageChannel<SettlementProposed, ()>,
+    ) -> Self {
         Self {
             tasks: Tasks::default(),
             protocol_tasks: HashMap::default(),
             pending_protocols: HashMap::default(),
             executor,
             n_payouts,
+            settlement_proposed,
         }
     }
 }
@@ -78,11 +90,19 @@ impl Actor {
                     .context("Failed to decode Propose")?
                     .into_propose()?;
 
+                let span = tracing::info_span!(
+                    "collab_settlement",
+                    order_id = %propose.id,
+                    correlation_id = tracing::field::Empty
+                );
+                CorrelationId::continue_or_new(propose.correlation_id, &span);
+
                 address
                     .send(ProposeReceived {
                         propose,
                         framed,
                         peer,
+                        span,
                     })
                     .await?;
 
@@ -102,6 +122,7 @@ impl Actor {
             propose,
             framed,
             peer,
+            span,
         } = msg;
         let order_id = propose.id;
 
@@ -128,16 +149,24 @@ impl Actor {
         };
 
         self.pending_protocols
-            .insert(order_id, (framed, transaction, proposal, peer));
+            .insert(order_id, (framed, transaction, proposal, peer, span));
+
+        if let Err(e) = self
+            .settlement_proposed
+            .send_async_safe(SettlementProposed { proposal })
+            .await
+        {
+            tracing::warn!(%order_id, "Failed to announce settlement proposal: {e:#}");
+        }
     }
 
     async fn handle(&mut self, msg: Accept) -> Result<()> {
         let Accept { order_id } = msg;
 
-        let (mut framed, transaction, proposal, _peer) =
-            self.pending_protocols
-                .remove(&order_id)
-                .with_context(|| format!("No active protocol for order {order_id}"))?;
+        let (mut framed, transaction, proposal, _peer, span) = self
+            .pending_protocols
+            .remove(&order_id)
+            .with_context(|| format!("No active protocol for order {order_id}"))?;
 
         let mut tasks = Tasks::default();
         tasks.add_fallible(
@@ -193,6 +222,7 @@ impl Actor {
                     emit_completed(order_id, settlement, &executor).await;
                     Ok(())
                 }
+                .instrument(span)
             },
             {
                 let executor = self.executor.clone();
@@ -221,7 +251,7 @@ impl Actor {
     async fn handle(&mut self, msg: Reject) -> Result<()> {
         let Reject { order_id } = msg;
 
-        let (mut framed, ..) = self
+        let (mut framed, _, _, _, span) = self
             .pending_protocols
             .remove(&order_id)
             .with_context(|| format!("No active protocol for order {order_id}"))?;
@@ -233,7 +263,8 @@ impl Actor {
                 framed
                     .send(ListenerMessage::Decision(Decision::Reject))
                     .await
-            },
+            }
+            .instrument(span),
             move |e| async move {
                 tracing::debug!(%order_id, "Failed to reject collaborative settlement: {e:#}")
             },
@@ -248,6 +279,13 @@ struct ProposeReceived {
     propose: Propose,
     framed: Framed<Substream, JsonCodec<ListenerMessage, DialerMessage>>,
     peer: PeerId,
+    span: Span,
+}
+
+/// Message sent to the owner of the maker's offers once a settlement proposal awaits a decision.
+#[derive(Clone, Copy, Debug)]
+pub struct SettlementProposed {
+    pub proposal: SettlementProposal,
 }
 
 #[derive(Clone, Copy)]
@@ -4,6 +4,7 @@ use crate::bitcoin::secp256k1::ecdsa::Signature;
 use crate::bitcoin::Transaction;
 use crate::collab_settlement::PROTOCOL;
 use crate::command;
+use crate::correlation::CorrelationId;
 use crate::future_ext::FutureExt;
 use anyhow::anyhow;
 use anyhow::Context;
@@ -35,6 +36,7 @@ pub async fn dialer(
     order_id: OrderId,
     counterparty: PeerId,
     collab_settlement_tx: SettlementTransaction,
+    correlation_id: CorrelationId,
 ) -> Result<CollaborativeSettlement, DialerFailed> {
     let substream = endpoint
         .send(OpenSubstream::single_protocol(counterparty, PROTOCOL))
@@ -53,6 +55,7 @@ pub async fn dialer(
             id: order_id,
             price: collab_settlement_tx.price(),
             unsigned_tx: unsigned_tx.clone(),
+            correlation_id: Some(correlation_id),
         }))
         .await
         .context("Failed to send Propose")?;
@@ -192,6 +195,11 @@ pub struct Propose {
     /// side wants to perform collaborative settlement.
     #[serde(with = "hex_transaction")]
     pub unsigned_tx: Transaction,
+    /// Correlates the logs and traces of both sides of the protocol run.
+    ///
+    /// Optional for compatibility with takers that don't send it yet.
+    #[serde(default)]
+    pub correlation_id: Option<CorrelationId>,
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize)]
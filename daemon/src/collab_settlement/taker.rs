@@ -1,5 +1,6 @@
 use crate::collab_settlement::protocol::*;
 use crate::command;
+use crate::correlation::CorrelationId;
 use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
@@ -7,6 +8,7 @@ use model::libp2p::PeerId;
 use model::OrderId;
 use model::Price;
 use tokio_tasks::Tasks;
+use tracing::Instrument;
 use xtra::Address;
 use xtra_libp2p::Endpoint;
 use xtra_productivity::xtra_productivity;
@@ -60,6 +62,13 @@ impl Actor {
             .await
             .context("could not start closing position")?;
 
+        let span = tracing::info_span!(
+            "collab_settlement",
+            %order_id,
+            correlation_id = tracing::field::Empty
+        );
+        let correlation_id = CorrelationId::new(&span);
+
         self.tasks.add_fallible(
             {
                 let endpoint = self.endpoint.clone();
@@ -70,12 +79,14 @@ impl Actor {
                         order_id,
                         maker_peer_id.inner(),
                         collab_settlement_tx.clone(),
+                        correlation_id,
                     )
                     .await?;
 
                     emit_completed(order_id, settlement, &executor).await;
                     Ok(())
                 }
+                .instrument(span)
             },
             {
                 let executor = self.executor.clone();
@@ -0,0 +1,413 @@
+use crate::command;
+use crate::contract_setup::protocol::*;
+use crate::correlation::CorrelationId;
+use crate::oracle;
+use crate::oracle::NoAnnouncement;
+use crate::setup_contract;
+use crate::wallet;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use asynchronous_codec::Framed;
+use asynchronous_codec::JsonCodec;
+use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
+use futures::future;
+use futures::SinkExt;
+use futures::StreamExt;
+use maia_core::secp256k1_zkp::XOnlyPublicKey;
+use maia_core::PartyParams;
+use model::libp2p::PeerId;
+use model::olivia;
+use model::olivia::BitMexPriceEventId;
+use model::Identity;
+use model::Leverage;
+use model::OrderId;
+use model::Role;
+use model::Usd;
+use std::collections::HashMap;
+use tokio_tasks::TaskMap;
+use tokio_tasks::Tasks;
+use tracing::Instrument;
+use tracing::Span;
+use xtra::message_channel::MessageChannel;
+use xtra_libp2p::NewInboundSubstream;
+use xtra_libp2p::Substream;
+use xtra_productivity::xtra_productivity;
+use xtras::SendAsyncSafe;
+
+use super::protocol;
+
+type ListenerConnection = (
+    Framed<Substream, JsonCodec<ListenerMessage, DialerMessage>>,
+    BitMexPriceEventId,
+    Span,
+);
+
+/// Permanent actor to handle incoming substreams for the `/itchysats/contract-setup/1.0.0`
+/// protocol.
+///
+/// There is only one instance of this actor for all connections, meaning we must always spawn a
+/// task whenever we interact with a substream to not block the execution of other connections.
+pub struct Actor {
+    tasks: Tasks,
+    protocol_tasks: TaskMap<OrderId>,
+    oracle_pk: XOnlyPublicKey,
+    get_announcement:
+        MessageChannel<oracle::GetAnnouncement, Result<olivia::Announcement, NoAnnouncement>>,
+    build_party_params: MessageChannel<wallet::BuildPartyParams, Result<PartyParams>>,
+    sign: MessageChannel<wallet::Sign, Result<PartiallySignedTransaction>>,
+    order_received: MessageChannel<OrderReceived, Result<BitMexPriceEventId>>,
+    order_taken: MessageChannel<OrderTaken, ()>,
+    n_payouts: usize,
+    pending_protocols: HashMap<OrderId, ListenerConnection>,
+    executor: command::Executor,
+}
+
+impl Actor {
+    pub fn new(
+        executor: command::Executor,
+        oracle_pk: XOnlyPublicKey,
+        get_announcement: MessageChannel<
+            oracle::GetAnnouncement,
+            Result<olivia::Announcement, NoAnnouncement>,
+        >,
+        (build_party_params, sign): (
+            MessageChannel<wallet::BuildPartyParams, Result<PartyParams>>,
+            MessageChannel<wallet::Sign, Result<PartiallySignedTransaction>>,
+        ),
+        (order_received, order_taken): (
+            MessageChannel<OrderReceived, Result<BitMexPriceEventId>>,
+            MessageChannel<OrderTaken, ()>,
+        ),
+        n_payouts: usize,
+    ) -> Self {
+        Self {
+            tasks: Tasks::default(),
+            protocol_tasks: TaskMap::default(),
+            oracle_pk,
+            get_announcement,
+            build_party_params,
+            sign,
+            order_received,
+            order_taken,
+            n_payouts,
+            pending_protocols: HashMap::default(),
+            executor,
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[xtra_productivity(message_impl = false)]
+impl Actor {
+    async fn handle(&mut self, msg: NewInboundSubstream, ctx: &mut xtra::Context<Self>) {
+        let NewInboundSubstream { peer, stream } = msg;
+        let address = ctx.address().expect("we are alive");
+        let order_received = self.order_received.clone();
+
+        self.tasks.add_fallible(
+            async move {
+                let mut framed =
+                    Framed::new(stream, JsonCodec::<ListenerMessage, DialerMessage>::new());
+
+                let TakeOrder {
+                    order_id,
+                    quantity,
+                    leverage,
+                    taker_id,
+                    correlation_id,
+                } = framed
+                    .next()
+                    .await
+                    .context("End of stream while receiving TakeOrder")?
+                    .context("Failed to decode TakeOrder")?
+                    .into_take_order()?;
+
+                let span = tracing::info_span!(
+                    "contract_setup",
+                    %order_id,
+                    correlation_id = tracing::field::Empty
+                );
+                CorrelationId::continue_or_new(correlation_id, &span);
+
+                // The order is validated outside of this actor's handler so that the owner of the
+                // offers can dispatch `Accept` and `Reject` to us without deadlocking.
+                let oracle_event_id = match order_received
+                    .send(OrderReceived {
+                        order_id,
+                        quantity,
+                        leverage,
+                        taker_id,
+                        taker_peer_id: peer.into(),
+                    })
+                    .await
+                    .context("Failed to deliver order to take")?
+                {
+                    Ok(oracle_event_id) => oracle_event_id,
+                    Err(e) => {
+                        tracing::warn!(%order_id, "Rejecting order to take: {e:#}");
+
+                        framed
+                            .send(ListenerMessage::Decision(Decision::Reject(
+                                protocol::Reject { order_id },
+                            )))
+                            .await
+                            .context("Failed to send reject to the taker")?;
+
+                        return Ok(());
+                    }
+                };
+
+                address
+                    .send(TakeOrderReceived {
+                        order_id,
+                        taker_peer_id: peer.into(),
+                        oracle_event_id,
+                        framed,
+                        span,
+                    })
+                    .await?;
+
+                anyhow::Ok(())
+            },
+            move |e| async move {
+                tracing::warn!(%peer, "Failed to handle incoming contract setup protocol: {e:#}")
+            },
+        );
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, msg: TakeOrderReceived, ctx: &mut xtra::Context<Self>) {
+        let TakeOrderReceived {
+            order_id,
+            taker_peer_id,
+            oracle_event_id,
+            framed,
+            span,
+        } = msg;
+
+        self.pending_protocols
+            .insert(order_id, (framed, oracle_event_id, span));
+
+        // The taker gives up waiting for our decision after the timeout, and so do we. Accepting
+        // or rejecting the order replaces this task.
+        let this = ctx.address().expect("we are alive");
+        self.protocol_tasks.add(order_id, async move {
+            tokio::time::sleep(DECISION_TIMEOUT).await;
+
+            if let Err(e) = this.send_async_safe(DecisionTimedOut { order_id }).await {
+                tracing::warn!(%order_id, "Failed to time out decision: {e:#}");
+            }
+        });
+
+        if let Err(e) = self
+            .order_taken
+            .send_async_safe(OrderTaken {
+                order_id,
+                taker_peer_id,
+            })
+            .await
+        {
+            tracing::warn!(%order_id, "Failed to announce taken order: {e:#}");
+        }
+    }
+
+    async fn handle(&mut self, msg: Accept, ctx: &mut xtra::Context<Self>) -> Result<()> {
+        let Accept { order_id } = msg;
+
+        let (mut framed, oracle_event_id, span) =
+            self.pending_protocols.remove(&order_id).with_context(|| {
+                format!("No active protocol for {order_id} when accepting contract setup")
+            })?;
+
+        let this = ctx.address().expect("we are alive");
+        self.protocol_tasks.add_fallible(
+            order_id,
+            {
+                let executor = self.executor.clone();
+                let this = this.clone();
+                let get_announcement = self.get_announcement.clone();
+                let build_party_params = self.build_party_params.clone();
+                let sign = self.sign.clone();
+                let oracle_pk = self.oracle_pk;
+                let n_payouts = self.n_payouts;
+                async move {
+                    let announcement = get_announcement
+                        .send(oracle::GetAnnouncement(oracle_event_id))
+                        .await
+                        .context("Oracle actor disconnected")?
+                        .context("Failed to get announcement")?;
+
+                    let (setup_params, position) = executor
+                        .execute(order_id, |cfd| cfd.start_contract_setup())
+                        .await?;
+
+                    framed
+                        .send(ListenerMessage::Decision(Decision::Confirm(Confirm {
+                            order_id,
+                        })))
+                        .await
+                        .context("Failed to send contract setup confirmation message")?;
+
+                    let (sink, stream) = framed.split();
+
+                    let dlc = setup_contract::new(
+                        sink.sink_map_err(anyhow::Error::from)
+                            .with(|msg| future::ok(ListenerMessage::SetupMsg(Box::new(msg)))),
+                        stream.filter_map(|msg| {
+                            future::ready(
+                                match msg
+                                    .context("Unable to decode dialer message")
+                                    .and_then(DialerMessage::into_setup_msg)
+                                {
+                                    Ok(setup_msg) => Some(setup_msg),
+                                    Err(e) => {
+                                        tracing::warn!(%order_id, "Discarding message: {e:#}");
+                                        None
+                                    }
+                                },
+                            )
+                        }),
+                        (oracle_pk, announcement),
+                        setup_params,
+                        build_party_params,
+                        sign,
+                        Role::Maker,
+                        position,
+                        n_payouts,
+                    )
+                    .await?;
+
+                    emit_completed(order_id, dlc, &executor).await;
+                    notify_finished(&this, order_id).await;
+
+                    Ok(())
+                }
+                .instrument(span)
+            },
+            {
+                let executor = self.executor.clone();
+                move |e| async move {
+                    emit_failed(order_id, e, &executor).await;
+                    notify_finished(&this, order_id).await;
+                }
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, msg: Reject, ctx: &mut xtra::Context<Self>) -> Result<()> {
+        let Reject { order_id } = msg;
+
+        let (mut framed, _, span) =
+            self.pending_protocols.remove(&order_id).with_context(|| {
+                format!("No active protocol for {order_id} when rejecting contract setup")
+            })?;
+
+        emit_rejected(order_id, anyhow::anyhow!("maker decision"), &self.executor).await;
+
+        let this = ctx.address().expect("we are alive");
+        self.protocol_tasks.add(
+            order_id,
+            async move {
+                if let Err(e) = framed
+                    .send(ListenerMessage::Decision(Decision::Reject(
+                        protocol::Reject { order_id },
+                    )))
+                    .await
+                {
+                    tracing::debug!(%order_id, "Failed to send reject order to the taker: {e:#}")
+                }
+
+                notify_finished(&this, order_id).await;
+            }
+            .instrument(span),
+        );
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, msg: DecisionTimedOut) {
+        let DecisionTimedOut { order_id } = msg;
+
+        // The order was accepted or rejected in the meantime
+        if self.pending_protocols.remove(&order_id).is_none() {
+            return;
+        }
+        self.protocol_tasks.remove(&order_id);
+
+        emit_failed(
+            order_id,
+            anyhow::anyhow!("No decision within {} seconds", DECISION_TIMEOUT.as_secs()),
+            &self.executor,
+        )
+        .await;
+    }
+
+    async fn handle(&mut self, msg: ProtocolFinished) {
+        self.protocol_tasks.remove(&msg.order_id);
+    }
+}
+
+async fn notify_finished(this: &xtra::Address<Actor>, order_id: OrderId) {
+    if let Err(e) = this.send_async_safe(ProtocolFinished { order_id }).await {
+        tracing::warn!(%order_id, "Failed to clean up after contract setup: {e:#}");
+    }
+}
+
+/// Message sent to the owner of the maker's offers whenever a taker requests to take one of them.
+///
+/// The handler is expected to validate the request and record the CFD, returning the ID of the
+/// oracle event the CFD will settle on. An error results in the order being rejected.
+pub struct OrderReceived {
+    pub order_id: OrderId,
+    pub quantity: Usd,
+    pub leverage: Leverage,
+    pub taker_id: Identity,
+    pub taker_peer_id: PeerId,
+}
+
+/// Message sent to the owner of the maker's offers once a taken order awaits a decision.
+#[derive(Clone, Copy, Debug)]
+pub struct OrderTaken {
+    pub order_id: OrderId,
+    /// The peer id of the taker, as authenticated by the connection.
+    pub taker_peer_id: PeerId,
+}
+
+struct TakeOrderReceived {
+    order_id: OrderId,
+    taker_peer_id: PeerId,
+    oracle_event_id: BitMexPriceEventId,
+    framed: Framed<Substream, JsonCodec<ListenerMessage, DialerMessage>>,
+    span: Span,
+}
+
+/// Module private message to notify ourselves that the taker stopped waiting for our decision.
+struct DecisionTimedOut {
+    order_id: OrderId,
+}
+
+/// Module private message to notify ourselves that a protocol run finished, successfully or not.
+struct ProtocolFinished {
+    order_id: OrderId,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Accept {
+    pub order_id: OrderId,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Reject {
+    pub order_id: OrderId,
+}
@@ -0,0 +1,130 @@
+use crate::command;
+use crate::correlation::CorrelationId;
+use crate::wire::SetupMsg;
+use anyhow::anyhow;
+use anyhow::Result;
+use model::Dlc;
+use model::Identity;
+use model::Leverage;
+use model::OrderId;
+use model::Usd;
+use serde::Deserialize;
+use serde::Serialize;
+use std::time::Duration;
+
+/// The maximum amount of time the taker waits for the maker to accept or reject the order.
+pub(crate) const DECISION_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum DialerMessage {
+    TakeOrder(TakeOrder),
+    SetupMsg(Box<SetupMsg>),
+}
+
+impl DialerMessage {
+    pub fn into_take_order(self) -> Result<TakeOrder> {
+        match self {
+            DialerMessage::TakeOrder(take_order) => Ok(take_order),
+            DialerMessage::SetupMsg(_) => Err(anyhow!("Expected TakeOrder but got SetupMsg")),
+        }
+    }
+
+    pub fn into_setup_msg(self) -> Result<SetupMsg> {
+        match self {
+            DialerMessage::SetupMsg(setup_msg) => Ok(*setup_msg),
+            DialerMessage::TakeOrder(_) => Err(anyhow!("Expected SetupMsg but got TakeOrder")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum ListenerMessage {
+    Decision(Decision),
+    SetupMsg(Box<SetupMsg>),
+}
+
+impl ListenerMessage {
+    pub fn into_decision(self) -> Result<Decision> {
+        match self {
+            ListenerMessage::Decision(decision) => Ok(decision),
+            ListenerMessage::SetupMsg(_) => Err(anyhow!("Expected Decision but got SetupMsg")),
+        }
+    }
+
+    pub fn into_setup_msg(self) -> Result<SetupMsg> {
+        match self {
+            ListenerMessage::SetupMsg(setup_msg) => Ok(*setup_msg),
+            ListenerMessage::Decision(_) => Err(anyhow!("Expected SetupMsg but got Decision")),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct TakeOrder {
+    pub order_id: OrderId,
+    pub quantity: Usd,
+    pub leverage: Leverage,
+    /// The taker's identity on the legacy network.
+    ///
+    /// CFDs are still keyed by this identity, hence the taker has to tell us about it until the
+    /// legacy connection is removed.
+    pub taker_id: Identity,
+    /// Correlates the logs and traces of both sides of the protocol run.
+    ///
+    /// Optional for compatibility with takers that don't send it yet.
+    #[serde(default)]
+    pub correlation_id: Option<CorrelationId>,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub enum Decision {
+    Confirm(Confirm),
+    Reject(Reject),
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Confirm {
+    pub order_id: OrderId,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Reject {
+    pub order_id: OrderId,
+}
+
+pub(crate) async fn emit_completed(order_id: OrderId, dlc: Dlc, executor: &command::Executor) {
+    if let Err(e) = executor
+        .execute(order_id, |cfd| cfd.complete_contract_setup(dlc))
+        .await
+    {
+        tracing::error!(%order_id, "Failed to execute contract setup completed: {e:#}")
+    }
+
+    tracing::info!(%order_id, "Contract setup completed");
+}
+
+pub(crate) async fn emit_rejected(
+    order_id: OrderId,
+    reason: anyhow::Error,
+    executor: &command::Executor,
+) {
+    if let Err(e) = executor
+        .execute(order_id, |cfd| cfd.reject_contract_setup(reason))
+        .await
+    {
+        tracing::error!(%order_id, "Failed to execute contract setup rejected: {e:#}")
+    }
+
+    tracing::info!(%order_id, "Contract setup rejected");
+}
+
+pub(crate) async fn emit_failed(order_id: OrderId, e: anyhow::Error, executor: &command::Executor) {
+    tracing::error!(%order_id, "Contract setup failed: {e:#}");
+
+    if let Err(e) = executor
+        .execute(order_id, |cfd| Ok(cfd.fail_contract_setup(e)))
+        .await
+    {
+        tracing::error!(%order_id, "Failed to execute contract setup failed: {e:#}")
+    }
+}
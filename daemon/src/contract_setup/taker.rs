@@ -0,0 +1,242 @@
+use crate::command;
+use crate::contract_setup;
+use crate::contract_setup::protocol::*;
+use crate::correlation::CorrelationId;
+use crate::future_ext::FutureExt;
+use crate::setup_contract;
+use crate::wallet;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
+use futures::future;
+use futures::SinkExt;
+use futures::StreamExt;
+use maia_core::secp256k1_zkp::XOnlyPublicKey;
+use maia_core::PartyParams;
+use model::libp2p::PeerId;
+use model::olivia;
+use model::Identity;
+use model::Leverage;
+use model::OrderId;
+use model::Role;
+use model::Usd;
+use tokio_tasks::TaskMap;
+use tracing::Instrument;
+use xtra::message_channel::MessageChannel;
+use xtra::Address;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p::OpenSubstream;
+use xtra_libp2p::Substream;
+use xtra_productivity::xtra_productivity;
+use xtras::SendAsyncSafe;
+
+/// One actor to take all the orders
+pub struct Actor {
+    endpoint: Address<Endpoint>,
+    oracle_pk: XOnlyPublicKey,
+    build_party_params: MessageChannel<wallet::BuildPartyParams, Result<PartyParams>>,
+    sign: MessageChannel<wallet::Sign, Result<PartiallySignedTransaction>>,
+    identity: Identity,
+    n_payouts: usize,
+    tasks: TaskMap<OrderId>,
+    executor: command::Executor,
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Take an order of the maker by setting up a contract with them.
+///
+/// The CFD is expected to already be recorded. Failing to reach the maker is reported back to
+/// the sender without touching the CFD, allowing it to fall back to the legacy connection.
+#[derive(Clone)]
+pub struct TakeOrder {
+    pub order_id: OrderId,
+    pub quantity: Usd,
+    pub leverage: Leverage,
+    pub maker_peer_id: PeerId,
+    pub announcement: olivia::Announcement,
+}
+
+impl Actor {
+    pub fn new(
+        endpoint: Address<Endpoint>,
+        executor: command::Executor,
+        oracle_pk: XOnlyPublicKey,
+        (build_party_params, sign): (
+            MessageChannel<wallet::BuildPartyParams, Result<PartyParams>>,
+            MessageChannel<wallet::Sign, Result<PartiallySignedTransaction>>,
+        ),
+        identity: Identity,
+        n_payouts: usize,
+    ) -> Self {
+        Self {
+            endpoint,
+            oracle_pk,
+            build_party_params,
+            sign,
+            identity,
+            n_payouts,
+            tasks: TaskMap::default(),
+            executor,
+        }
+    }
+}
+
+impl Actor {
+    async fn open_substream(&self, peer_id: PeerId) -> Result<Substream> {
+        Ok(self
+            .endpoint
+            .send(OpenSubstream::single_protocol(
+                peer_id.inner(),
+                contract_setup::PROTOCOL,
+            ))
+            .await
+            .context("Endpoint is disconnected")
+            .context("Failed to open substream")??)
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    pub async fn handle(&mut self, msg: TakeOrder, ctx: &mut xtra::Context<Self>) -> Result<()> {
+        let TakeOrder {
+            order_id,
+            quantity,
+            leverage,
+            maker_peer_id,
+            announcement,
+        } = msg;
+
+        let substream = self.open_substream(maker_peer_id).await?;
+
+        let span = tracing::info_span!(
+            "contract_setup",
+            %order_id,
+            correlation_id = tracing::field::Empty
+        );
+        let correlation_id = CorrelationId::new(&span);
+
+        let this = ctx.address().expect("we are alive");
+        self.tasks.add_fallible(
+            order_id,
+            {
+                let executor = self.executor.clone();
+                let this = this.clone();
+                let build_party_params = self.build_party_params.clone();
+                let sign = self.sign.clone();
+                let oracle_pk = self.oracle_pk;
+                let n_payouts = self.n_payouts;
+                let taker_id = self.identity;
+                async move {
+                    let mut framed = asynchronous_codec::Framed::new(
+                        substream,
+                        asynchronous_codec::JsonCodec::<DialerMessage, ListenerMessage>::new(),
+                    );
+
+                    framed
+                        .send(DialerMessage::TakeOrder(contract_setup::protocol::TakeOrder {
+                            order_id,
+                            quantity,
+                            leverage,
+                            taker_id,
+                            correlation_id: Some(correlation_id),
+                        }))
+                        .await
+                        .context("Failed to send TakeOrder")?;
+
+                    match framed
+                        .next()
+                        .timeout(DECISION_TIMEOUT)
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "Maker did not accept/reject within {} seconds.",
+                                DECISION_TIMEOUT.as_secs()
+                            )
+                        })?
+                        .context("End of stream while receiving order decision from maker")?
+                        .context("Failed to decode order decision from maker")?
+                        .into_decision()?
+                    {
+                        Decision::Confirm(Confirm { .. }) => {
+                            tracing::info!(%order_id, "Order got accepted");
+
+                            let (setup_params, position) = executor
+                                .execute(order_id, |cfd| cfd.start_contract_setup())
+                                .await?;
+
+                            let (sink, stream) = framed.split();
+
+                            let dlc = setup_contract::new(
+                                sink.sink_map_err(anyhow::Error::from).with(|msg| {
+                                    future::ok(DialerMessage::SetupMsg(Box::new(msg)))
+                                }),
+                                stream.filter_map(|msg| {
+                                    future::ready(
+                                        match msg
+                                            .context("Unable to decode listener message")
+                                            .and_then(ListenerMessage::into_setup_msg)
+                                        {
+                                            Ok(setup_msg) => Some(setup_msg),
+                                            Err(e) => {
+                                                tracing::warn!(%order_id, "Discarding message: {e:#}");
+                                                None
+                                            }
+                                        },
+                                    )
+                                }),
+                                (oracle_pk, announcement),
+                                setup_params,
+                                build_party_params,
+                                sign,
+                                Role::Taker,
+                                position,
+                                n_payouts,
+                            )
+                            .await?;
+
+                            emit_completed(order_id, dlc, &executor).await;
+                        }
+                        Decision::Reject(_) => {
+                            emit_rejected(order_id, anyhow::anyhow!("Unknown"), &executor).await;
+                        }
+                    }
+                    notify_finished(&this, order_id).await;
+
+                    Ok(())
+                }
+                .instrument(span)
+            },
+            {
+                let executor = self.executor.clone();
+                move |e| async move {
+                    emit_failed(order_id, e, &executor).await;
+                    notify_finished(&this, order_id).await;
+                }
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, msg: ProtocolFinished) {
+        self.tasks.remove(&msg.order_id);
+    }
+}
+
+async fn notify_finished(this: &Address<Actor>, order_id: OrderId) {
+    if let Err(e) = this.send_async_safe(ProtocolFinished { order_id }).await {
+        tracing::warn!(%order_id, "Failed to clean up after contract setup: {e:#}");
+    }
+}
+
+/// Module private message to notify ourselves that a protocol run finished, successfully or not.
+struct ProtocolFinished {
+    order_id: OrderId,
+}
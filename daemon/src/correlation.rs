@@ -0,0 +1,165 @@
+use anyhow::Context as _;
+use opentelemetry::trace::SpanContext;
+use opentelemetry::trace::SpanId;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::trace::TraceFlags;
+use opentelemetry::trace::TraceId;
+use opentelemetry::trace::TraceState;
+use opentelemetry::Context;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use uuid::Uuid;
+
+/// Identifies a single run of a protocol across both peers.
+///
+/// The dialer derives the ID from the span of its protocol run and sends it along with the first
+/// message. The listener makes the span of its side of the protocol a child of the dialer's span,
+/// so that a tracing backend shows both sides of the protocol run within a single trace. Without
+/// an exporter, the ID is random and is only recorded in the logs of both peers.
+///
+/// The ID is encoded as `<trace-id>-<span-id>` in hex, mirroring the W3C `traceparent` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CorrelationId {
+    trace_id: TraceId,
+    span_id: SpanId,
+}
+
+impl CorrelationId {
+    /// Derive the correlation ID of a protocol run from the span it is executed in.
+    ///
+    /// The span is expected to declare an empty `correlation_id` field, which is filled in.
+    pub fn new(span: &Span) -> Self {
+        let context = span.context();
+        let span_context = context.span().span_context().clone();
+
+        let id = if span_context.is_valid() {
+            Self {
+                trace_id: span_context.trace_id(),
+                span_id: span_context.span_id(),
+            }
+        } else {
+            Self::random()
+        };
+
+        span.record("correlation_id", &tracing::field::display(id));
+
+        id
+    }
+
+    /// Continue the protocol run of the counterparty within the given span.
+    ///
+    /// The span is expected to declare an empty `correlation_id` field, which is filled in.
+    pub fn attach(&self, span: &Span) {
+        span.record("correlation_id", &tracing::field::display(self));
+
+        let remote = SpanContext::new(
+            self.trace_id,
+            self.span_id,
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        span.set_parent(Context::new().with_remote_span_context(remote));
+    }
+
+    /// Continue the protocol run of the counterparty if they sent a correlation ID, otherwise
+    /// start a new one.
+    pub fn continue_or_new(correlation_id: Option<Self>, span: &Span) -> Self {
+        match correlation_id {
+            Some(id) => {
+                id.attach(span);
+                id
+            }
+            None => Self::new(span),
+        }
+    }
+
+    fn random() -> Self {
+        Self {
+            trace_id: TraceId::from_bytes(Uuid::new_v4().as_u128().to_be_bytes()),
+            span_id: SpanId::from_bytes(Uuid::new_v4().as_u64_pair().0.to_be_bytes()),
+        }
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:032x}-{:016x}",
+            u128::from_be_bytes(self.trace_id.to_bytes()),
+            u64::from_be_bytes(self.span_id.to_bytes())
+        )
+    }
+}
+
+impl FromStr for CorrelationId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (trace_id, span_id) = s
+            .split_once('-')
+            .context("Correlation ID must be of the form <trace-id>-<span-id>")?;
+
+        Ok(Self {
+            trace_id: TraceId::from_hex(trace_id).context("Invalid trace ID")?,
+            span_id: SpanId::from_hex(span_id).context("Invalid span ID")?,
+        })
+    }
+}
+
+impl TryFrom<String> for CorrelationId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<CorrelationId> for String {
+    fn from(id: CorrelationId) -> Self {
+        id.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_correlation_id() {
+        let id = CorrelationId::random();
+
+        let serialized = serde_json::to_string(&id).unwrap();
+        let deserialized = serde_json::from_str::<CorrelationId>(&serialized).unwrap();
+
+        assert_eq!(deserialized, id);
+    }
+
+    #[test]
+    fn correlation_id_is_formatted_like_traceparent() {
+        let id = "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7"
+            .parse::<CorrelationId>()
+            .unwrap();
+
+        assert_eq!(
+            id.to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7"
+        );
+    }
+
+    #[test]
+    fn span_without_exporter_gets_random_correlation_id() {
+        let span = tracing::info_span!("protocol", correlation_id = tracing::field::Empty);
+
+        let first = CorrelationId::new(&span);
+        let second = CorrelationId::new(&span);
+
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::libp2p_utils::with_peer_id;
+use crate::Maker;
+use anyhow::Context as _;
+use anyhow::Result;
+use libp2p_dns::TokioDnsConfig;
+use libp2p_tcp::TokioTcpConfig;
+use std::net::SocketAddr;
+use tokio_tasks::Tasks;
+use xtra::Context;
+use xtra_libp2p::endpoint;
+use xtra_libp2p::libp2p::identity::Keypair;
+use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::multiaddress_ext::MultiaddrExt;
+use xtra_libp2p::shared::SharedTransport;
+use xtra_libp2p::tor::TorTransport;
+use xtra_libp2p::Connect;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p_directory::Announcement;
+
+/// Fetch the verified announcements of all makers listed in the directory at `address`.
+///
+/// The directory is queried through a short-lived endpoint with a throwaway identity, so that
+/// looking up makers does not reveal who we are.
+pub async fn fetch_announcements(
+    address: Multiaddr,
+    tor_socks5_proxy: Option<SocketAddr>,
+) -> Result<Vec<Announcement>> {
+    let directory = address
+        .clone()
+        .extract_peer_id()
+        .context("Directory address must end with a peer id")?;
+
+    let mut tasks = Tasks::default();
+    let (endpoint_addr, endpoint_context) = Context::new(None);
+    let dns_transport = SharedTransport::new(
+        TokioDnsConfig::system(TokioTcpConfig::new())
+            .context("Failed to read system DNS configuration")?,
+    );
+    let endpoint = Endpoint::new(
+        Box::new(move || {
+            let transport = TorTransport::new(dns_transport.clone());
+            match tor_socks5_proxy {
+                Some(tor_socks5_proxy) => transport.with_socks_proxy(tor_socks5_proxy),
+                None => transport,
+            }
+        }),
+        Keypair::generate_ed25519(),
+        crate::ENDPOINT_CONNECTION_TIMEOUT,
+        [],
+        endpoint::Subscribers::default(),
+    );
+    tasks.add(endpoint_context.run(endpoint));
+
+    endpoint_addr
+        .send(Connect(address.clone()))
+        .await?
+        .with_context(|| format!("Failed to connect to directory {address}"))?;
+
+    xtra_libp2p_directory::taker::fetch(endpoint_addr, directory).await
+}
+
+impl TryFrom<Announcement> for Maker {
+    type Error = anyhow::Error;
+
+    /// Only addresses which belong to the announced peer are kept.
+    fn try_from(announcement: Announcement) -> Result<Self> {
+        let peer_id = announcement.peer_id.inner();
+        let multiaddrs = announcement
+            .multiaddrs
+            .into_iter()
+            .filter_map(|multiaddr| with_peer_id(multiaddr, peer_id).ok())
+            .collect::<Vec<_>>();
+
+        anyhow::ensure!(
+            !multiaddrs.is_empty(),
+            "Maker {peer_id} did not announce any address of its own"
+        );
+
+        Ok(Self {
+            identity: announcement.identity,
+            multiaddrs,
+        })
+    }
+}
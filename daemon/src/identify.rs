@@ -0,0 +1,126 @@
+use crate::collab_settlement;
+use crate::rollover;
+use crate::version;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use xtra_libp2p_identify::PeerInfo;
+
+/// Optional capabilities advertised to peers through the identify protocol.
+///
+/// Protocol names only tell a peer whether we speak a protocol at all. Capabilities that change
+/// the behaviour of an existing protocol without warranting a new protocol version are listed
+/// here instead.
+pub const FEATURES: &[&str] = &[];
+
+/// The protocols a taker needs the maker to support in order to manage the lifecycle of a CFD.
+pub const TAKER_REQUIRED_MAKER_PROTOCOLS: &[&str] =
+    &[rollover::PROTOCOL, collab_settlement::PROTOCOL];
+
+pub fn features() -> HashSet<String> {
+    FEATURES.iter().map(|feature| feature.to_string()).collect()
+}
+
+/// The result of checking a peer's [`PeerInfo`] against what we require from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compatibility {
+    Compatible,
+    /// The peer lacks protocols we need and is running an older version than us.
+    PeerOutdated,
+    /// The peer lacks protocols we need and is running a newer version than us, i.e. it dropped
+    /// support for protocols we still rely on.
+    LocalOutdated,
+}
+
+/// Assess whether the peer supports all `required_protocols`.
+///
+/// If it does not, the daemon versions are compared to determine which party has to upgrade.
+/// Versions which cannot be parsed are treated as outdated peers because we cannot reason about
+/// them.
+pub fn assess(peer: &PeerInfo, required_protocols: &[&str]) -> Compatibility {
+    let missing = required_protocols
+        .iter()
+        .filter(|protocol| !peer.protocols.contains(**protocol))
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        return Compatibility::Compatible;
+    }
+
+    tracing::warn!(
+        peer_version = %peer.daemon_version,
+        ?missing,
+        "Peer does not support all required protocols"
+    );
+
+    match compare_versions(&peer.daemon_version, version::version()) {
+        Some(Ordering::Greater) => Compatibility::LocalOutdated,
+        _ => Compatibility::PeerOutdated,
+    }
+}
+
+/// Compare two daemon versions by their release, ignoring any pre-release or build metadata.
+///
+/// Our versions are derived from `git describe`, which appends the number of commits since the
+/// last tag as a pre-release identifier. Semver would order such versions _before_ the tagged
+/// release, which is the opposite of what we want.
+fn compare_versions(left: &str, right: &str) -> Option<Ordering> {
+    let left = semver::Version::parse(left).ok()?;
+    let right = semver::Version::parse(right).ok()?;
+
+    Some((left.major, left.minor, left.patch).cmp(&(right.major, right.minor, right.patch)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_supporting_all_required_protocols_is_compatible() {
+        let peer = peer_info("0.0.1", &[rollover::PROTOCOL, collab_settlement::PROTOCOL]);
+
+        assert_eq!(
+            assess(&peer, TAKER_REQUIRED_MAKER_PROTOCOLS),
+            Compatibility::Compatible
+        );
+    }
+
+    #[test]
+    fn older_peer_missing_protocol_is_outdated() {
+        let peer = peer_info("0.0.1", &[rollover::PROTOCOL]);
+
+        assert_eq!(
+            assess(&peer, TAKER_REQUIRED_MAKER_PROTOCOLS),
+            Compatibility::PeerOutdated
+        );
+    }
+
+    #[test]
+    fn newer_peer_missing_protocol_means_we_are_outdated() {
+        let peer = peer_info("999.0.0", &[collab_settlement::PROTOCOL]);
+
+        assert_eq!(
+            assess(&peer, TAKER_REQUIRED_MAKER_PROTOCOLS),
+            Compatibility::LocalOutdated
+        );
+    }
+
+    #[test]
+    fn git_describe_suffix_does_not_affect_ordering() {
+        assert_eq!(
+            compare_versions("0.4.21-5-g1a2b3c4", "0.4.21"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare_versions("0.4.22-1-g1a2b3c4", "0.4.21"),
+            Some(Ordering::Greater)
+        );
+    }
+
+    fn peer_info(daemon_version: &str, protocols: &[&str]) -> PeerInfo {
+        PeerInfo {
+            daemon_version: daemon_version.to_string(),
+            protocols: protocols.iter().map(|p| p.to_string()).collect(),
+            features: HashSet::default(),
+        }
+    }
+}
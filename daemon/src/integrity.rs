@@ -0,0 +1,235 @@
+use anyhow::Result;
+use model::CfdEvent;
+use model::EventKind;
+use model::OrderId;
+use model::Timestamp;
+use std::fmt;
+
+/// Replay the event log of every CFD in the database and report all inconsistencies found.
+///
+/// Every event is applied through [`model::Cfd::apply`], so CFDs whose event log cannot be loaded
+/// at all are reported as well.
+pub async fn check_event_logs(db: &sqlite_db::Connection) -> Result<Vec<Inconsistency>> {
+    let mut inconsistencies = Vec::new();
+
+    for id in db.load_open_cfd_ids().await? {
+        match db.load_open_cfd::<Replay>(id, ()).await {
+            Ok(replay) => {
+                inconsistencies.extend(replay.check.issues.into_iter().map(|description| {
+                    Inconsistency {
+                        order_id: id,
+                        description,
+                    }
+                }))
+            }
+            Err(sqlite_db::Error::OpenCfdNotFound) => {}
+            Err(e) => inconsistencies.push(Inconsistency {
+                order_id: id,
+                description: format!("Failed to replay event log: {e:#}"),
+            }),
+        }
+    }
+
+    Ok(inconsistencies)
+}
+
+/// An event log which does not describe a valid history of its CFD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inconsistency {
+    pub order_id: OrderId,
+    pub description: String,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CFD {}: {}", self.order_id, self.description)
+    }
+}
+
+/// Aggregate which replays an event log through [`model::Cfd`] while checking it.
+#[derive(Debug, Clone)]
+struct Replay {
+    cfd: model::Cfd,
+    check: EventLogCheck,
+}
+
+impl sqlite_db::CfdAggregate for Replay {
+    type CtorArgs = ();
+
+    fn new(args: Self::CtorArgs, cfd: sqlite_db::Cfd) -> Self {
+        Self {
+            cfd: <model::Cfd as sqlite_db::CfdAggregate>::new(args, cfd),
+            check: EventLogCheck::default(),
+        }
+    }
+
+    fn apply(self, event: CfdEvent) -> Self {
+        Self {
+            check: self.check.apply(&event),
+            cfd: self.cfd.apply(event),
+        }
+    }
+
+    fn version(&self) -> u32 {
+        self.cfd.version()
+    }
+}
+
+/// The rules an event log has to follow, independent of the state of the CFD.
+#[derive(Debug, Clone, Default)]
+struct EventLogCheck {
+    last_timestamp: Option<Timestamp>,
+    contract_setup_started: bool,
+    rollover_started: bool,
+    settlement_started: bool,
+    /// The event after which no other event is expected.
+    terminal_event: Option<String>,
+    /// The event which spent the lock transaction.
+    spending_event: Option<String>,
+    issues: Vec<String>,
+}
+
+impl EventLogCheck {
+    fn apply(mut self, event: &CfdEvent) -> Self {
+        use EventKind::*;
+
+        if let Some(last_timestamp) = self.last_timestamp {
+            if event.timestamp < last_timestamp {
+                self.issues.push(format!(
+                    "{} at {} is recorded after an event at {}",
+                    event.event,
+                    event.timestamp.seconds(),
+                    last_timestamp.seconds()
+                ));
+            }
+        }
+        self.last_timestamp = Some(event.timestamp);
+
+        if let Some(terminal_event) = &self.terminal_event {
+            self.issues.push(format!(
+                "{} is recorded after {terminal_event}",
+                event.event
+            ));
+        }
+
+        match &event.event {
+            ContractSetupStarted => self.contract_setup_started = true,
+            ContractSetupCompleted { .. } => {
+                if !self.contract_setup_started {
+                    self.issues
+                        .push("ContractSetupCompleted without ContractSetupStarted".to_owned());
+                }
+            }
+            ContractSetupFailed | OfferRejected => {
+                self.terminal_event = Some(event.event.to_string());
+            }
+            RolloverStarted => self.rollover_started = true,
+            RolloverCompleted { .. } => {
+                if !self.rollover_started {
+                    self.issues
+                        .push("RolloverCompleted without RolloverStarted".to_owned());
+                }
+                self.rollover_started = false;
+            }
+            RolloverRejected | RolloverFailed => self.rollover_started = false,
+            CollaborativeSettlementStarted { .. } => self.settlement_started = true,
+            CollaborativeSettlementCompleted { .. } => {
+                if !self.settlement_started {
+                    self.issues.push(
+                        "CollaborativeSettlementCompleted without CollaborativeSettlementStarted"
+                            .to_owned(),
+                    );
+                }
+            }
+            CollaborativeSettlementRejected | CollaborativeSettlementFailed => {
+                self.settlement_started = false
+            }
+            CetConfirmed | RefundConfirmed | CollaborativeSettlementConfirmed => {
+                match &self.spending_event {
+                    Some(spending_event) => self.issues.push(format!(
+                        "{} is recorded after {spending_event}",
+                        event.event
+                    )),
+                    None => self.spending_event = Some(event.event.to_string()),
+                }
+            }
+            _ => {}
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_regular_event_log() {
+        let check = replay(&[
+            (1, EventKind::ContractSetupStarted),
+            (2, EventKind::ContractSetupCompleted { dlc: None }),
+            (3, EventKind::LockConfirmed),
+            (4, EventKind::CetConfirmed),
+        ]);
+
+        assert!(check.issues.is_empty(), "{:?}", check.issues);
+    }
+
+    #[test]
+    fn reports_events_out_of_order() {
+        let check = replay(&[
+            (2, EventKind::ContractSetupStarted),
+            (1, EventKind::ContractSetupCompleted { dlc: None }),
+        ]);
+
+        assert_eq!(check.issues.len(), 1);
+    }
+
+    #[test]
+    fn reports_completion_without_start_and_events_after_failure() {
+        let check = replay(&[
+            (1, EventKind::ContractSetupCompleted { dlc: None }),
+            (2, EventKind::ContractSetupFailed),
+            (3, EventKind::LockConfirmed),
+        ]);
+
+        assert_eq!(
+            check.issues,
+            vec![
+                "ContractSetupCompleted without ContractSetupStarted".to_owned(),
+                "LockConfirmed is recorded after ContractSetupFailed".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_lock_spent_twice() {
+        let check = replay(&[
+            (1, EventKind::ContractSetupStarted),
+            (2, EventKind::ContractSetupCompleted { dlc: None }),
+            (3, EventKind::RefundConfirmed),
+            (4, EventKind::CetConfirmed),
+        ]);
+
+        assert_eq!(
+            check.issues,
+            vec!["CetConfirmed is recorded after RefundConfirmed".to_owned()]
+        );
+    }
+
+    fn replay(events: &[(i64, EventKind)]) -> EventLogCheck {
+        let id = OrderId::default();
+
+        events
+            .iter()
+            .cloned()
+            .fold(EventLogCheck::default(), |check, (timestamp, event)| {
+                check.apply(&CfdEvent {
+                    timestamp: Timestamp::new(timestamp),
+                    id,
+                    event,
+                })
+            })
+    }
+}
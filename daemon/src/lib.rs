@@ -9,6 +9,7 @@ use bdk::bitcoin::Amount;
 use bdk::FeeRate;
 use connection::ConnectionStatus;
 use libp2p_core::Multiaddr;
+use libp2p_dns::TokioDnsConfig;
 use libp2p_tcp::TokioTcpConfig;
 use maia_core::secp256k1_zkp::XOnlyPublicKey;
 use model::libp2p::PeerId;
@@ -22,22 +23,30 @@ use model::Role;
 use model::Usd;
 use parse_display::Display;
 use seed::Identities;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::net::SocketAddr;
 use std::time::Duration;
 use time::ext::NumericalDuration;
 use tokio::sync::watch;
 use tokio_tasks::Tasks;
+use xtra::message_channel::MessageChannel;
 use xtra::prelude::*;
 use xtra_bitmex_price_feed::QUOTE_INTERVAL_MINUTES;
 use xtra_libp2p::dialer;
 use xtra_libp2p::endpoint;
 use xtra_libp2p::multiaddress_ext::MultiaddrExt;
+use xtra_libp2p::shared::SharedTransport;
+use xtra_libp2p::tor::TorTransport;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p_ping::ping;
 use xtra_libp2p_ping::pong;
+use xtras::backoff::Backoff;
 use xtras::supervisor;
-use xtras::supervisor::always_restart;
 use xtras::supervisor::always_restart_after;
+use xtras::supervisor::always_restart_with_backoff;
 use xtras::HandlerTimeoutExt;
+use xtras::InstrumentExt;
 
 pub use bdk;
 pub use maia;
@@ -46,16 +55,26 @@ pub use maia_core;
 pub mod archive_closed_cfds;
 pub mod archive_failed_cfds;
 pub mod auto_rollover;
+pub mod backup;
 pub mod collab_settlement;
 pub mod command;
 pub mod connection;
+pub mod contract_setup;
+pub mod correlation;
+pub mod directory;
 mod future_ext;
+pub mod identify;
+pub mod integrity;
 pub mod libp2p_utils;
+pub mod margin_health;
 pub mod monitor;
 pub mod noise;
+pub mod notifier;
 mod online_status;
 pub mod oracle;
+pub mod pnl_history;
 pub mod position_metrics;
+pub mod price_history;
 pub mod process_manager;
 pub mod projection;
 pub mod rollover;
@@ -79,11 +98,39 @@ pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 /// a failure.
 pub const RESTART_INTERVAL: Duration = Duration::from_secs(5);
 
+/// Bounds of the backoff between restarts of a crashing price feed.
+pub const PRICE_FEED_MIN_BACKOFF: Duration = Duration::from_secs(1);
+pub const PRICE_FEED_MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// How often the price feed may be restarted within [`PRICE_FEED_RESTART_WINDOW`] before its
+/// supervisor gives up.
+pub const PRICE_FEED_MAX_RESTARTS: usize = 10;
+pub const PRICE_FEED_RESTART_WINDOW: Duration = Duration::from_secs(600);
+
 pub const ENDPOINT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(20);
 pub const PING_INTERVAL: Duration = Duration::from_secs(5);
 
 pub const N_PAYOUTS: usize = 200;
 
+/// A maker the taker connects to.
+#[derive(Debug, Clone)]
+pub struct Maker {
+    pub identity: Identity,
+    /// The libp2p addresses of the maker, including its peer id.
+    pub multiaddrs: Vec<Multiaddr>,
+}
+
+impl Maker {
+    fn peer_id(&self) -> Result<libp2p_core::PeerId> {
+        self.multiaddrs
+            .first()
+            .context("No maker address provided")?
+            .clone()
+            .extract_peer_id()
+            .context("Unable to extract peer id from maker address")
+    }
+}
+
 pub struct TakerActorSystem<O, W, P> {
     pub cfd_actor: Address<taker_cfd::Actor<O, W>>,
     pub connection_actor: Address<connection::Actor>,
@@ -96,18 +143,28 @@ pub struct TakerActorSystem<O, W, P> {
     _price_feed_supervisor: Address<supervisor::Actor<P, xtra_bitmex_price_feed::Error>>,
     _collab_settlement_supervisor:
         Address<supervisor::Actor<collab_settlement::taker::Actor, supervisor::UnitReason>>,
+    _contract_setup_supervisor:
+        Address<supervisor::Actor<contract_setup::taker::Actor, supervisor::UnitReason>>,
     _rollover_supervisor:
         Address<supervisor::Actor<rollover::taker::Actor, supervisor::UnitReason>>,
-    _dialer_supervisor: Address<supervisor::Actor<dialer::Actor, dialer::Error>>,
+    _dialer_supervisors: Vec<Address<supervisor::Actor<dialer::Actor, dialer::Error>>>,
     _offers_supervisor:
         Address<supervisor::Actor<xtra_libp2p_offer::taker::Actor, supervisor::UnitReason>>,
     _ping_supervisor: Address<supervisor::Actor<ping::Actor, supervisor::UnitReason>>,
+    _identify_dialer_supervisor:
+        Address<supervisor::Actor<xtra_libp2p_identify::dialer::Actor, supervisor::UnitReason>>,
+    _identify_listener_actor: Address<xtra_libp2p_identify::listener::Actor>,
     _close_cfds_actor: Address<archive_closed_cfds::Actor>,
     _archive_failed_cfds_actor: Address<archive_failed_cfds::Actor>,
     _pong_actor: Address<pong::Actor>,
     _online_status_actor: Address<online_status::Actor>,
 
+    /// Status of the first maker, which we also connect to via legacy networking.
     pub maker_online_status_feed_receiver: watch::Receiver<ConnectionStatus>,
+    pub maker_compatibility_feed_receiver: watch::Receiver<Option<identify::Compatibility>>,
+    pub maker_address_stats_feed_receiver: watch::Receiver<Vec<dialer::AddressStats>>,
+    /// Status of each maker.
+    pub makers_online_status_feed_receiver: watch::Receiver<HashMap<PeerId, ConnectionStatus>>,
 
     _tasks: Tasks,
 }
@@ -118,7 +175,9 @@ where
         + Handler<
             oracle::GetAnnouncement,
             Return = Result<olivia::Announcement, oracle::NoAnnouncement>,
-        > + Actor<Stop = ()>,
+        > + Handler<oracle::SyncAnnouncement, Return = ()>
+        + Handler<oracle::SettlementIntervalInUse, Return = ()>
+        + Actor<Stop = ()>,
     W: Handler<wallet::BuildPartyParams, Return = Result<maia_core::PartyParams>>
         + Handler<wallet::Sign, Return = Result<PartiallySignedTransaction>>
         + Handler<wallet::Withdraw, Return = Result<Txid>>
@@ -127,6 +186,10 @@ where
     P: Handler<xtra_bitmex_price_feed::LatestQuote, Return = Option<xtra_bitmex_price_feed::Quote>>
         + Actor<Stop = xtra_bitmex_price_feed::Error>,
 {
+    /// Create the taker's actor system, connecting to all given `makers`.
+    ///
+    /// The offers of all makers are aggregated and each CFD is set up with the maker whose offer
+    /// was taken. Only the first maker is reachable via legacy networking.
     #[allow(clippy::too_many_arguments)]
     pub fn new<M>(
         db: sqlite_db::Connection,
@@ -136,11 +199,14 @@ where
         oracle_constructor: impl FnOnce(command::Executor) -> O,
         monitor_constructor: impl FnOnce(command::Executor) -> Result<M>,
         price_feed_constructor: impl (Fn() -> P) + Send + 'static,
+        price_feed_escalate_to: Option<MessageChannel<supervisor::Escalated, ()>>,
         n_payouts: usize,
         connect_timeout: Duration,
         projection_actor: Address<projection::Actor>,
-        maker_identity: Identity,
-        maker_multiaddr: Multiaddr,
+        notifier_actor: Address<notifier::Actor>,
+        backup_actor: Option<Address<backup::Actor>>,
+        makers: Vec<Maker>,
+        tor_socks5_proxy: Option<SocketAddr>,
         environment: Environment,
     ) -> Result<Self>
     where
@@ -153,6 +219,27 @@ where
     {
         let (maker_online_status_feed_sender, maker_online_status_feed_receiver) =
             watch::channel(ConnectionStatus::Offline { reason: None });
+        let (maker_compatibility_feed_sender, maker_compatibility_feed_receiver) =
+            watch::channel(None);
+        let (maker_address_stats_feed_sender, maker_address_stats_feed_receiver) =
+            watch::channel(Vec::new());
+        let (makers_online_status_feed_sender, makers_online_status_feed_receiver) =
+            watch::channel(HashMap::new());
+
+        let maker_peer_ids = makers
+            .iter()
+            .map(Maker::peer_id)
+            .collect::<Result<Vec<_>>>()?;
+        let maker_peer_id = *maker_peer_ids.first().context("No maker provided")?;
+        let maker_identities = maker_peer_ids
+            .iter()
+            .zip(makers.iter())
+            .map(|(peer_id, maker)| (PeerId::from(*peer_id), maker.identity))
+            .collect::<HashMap<_, _>>();
+        anyhow::ensure!(
+            maker_identities.len() == makers.len(),
+            "Makers must have distinct peer ids"
+        );
 
         let (monitor_addr, monitor_ctx) = Context::new(None);
         let (oracle_addr, oracle_ctx) = Context::new(None);
@@ -166,17 +253,23 @@ where
             .create(None)
             .spawn(&mut tasks);
 
-        tasks.add(process_manager_ctx.run(process_manager::Actor::new(
-            db.clone(),
-            Role::Taker,
-            projection_actor.clone().into(),
-            position_metrics_actor.into(),
-            monitor_addr.clone().into(),
-            monitor_addr.clone().into(),
-            monitor_addr.clone().into(),
-            monitor_addr.into(),
-            oracle_addr.clone().into(),
-        )));
+        tasks.add(
+            process_manager_ctx
+                .instrumented()
+                .run(process_manager::Actor::new(
+                    db.clone(),
+                    Role::Taker,
+                    projection_actor.clone().into(),
+                    position_metrics_actor.into(),
+                    notifier_actor.into(),
+                    monitor_addr.clone().into(),
+                    monitor_addr.clone().into(),
+                    monitor_addr.clone().into(),
+                    monitor_addr.into(),
+                    oracle_addr.clone().into(),
+                    backup_actor.map(Into::into),
+                )),
+        );
 
         let (endpoint_addr, endpoint_context) = Context::new(None);
 
@@ -195,6 +288,27 @@ where
         let collab_settlement_supervisor =
             collab_settlement_supervisor.create(None).spawn(&mut tasks);
 
+        let (contract_setup_supervisor, libp2p_contract_setup_addr) = supervisor::Actor::new({
+            let endpoint_addr = endpoint_addr.clone();
+            let executor = executor.clone();
+            let wallet_actor_addr = wallet_actor_addr.clone();
+            let identity = Identity::new(identity.identity_pk);
+            move || {
+                contract_setup::taker::Actor::new(
+                    endpoint_addr.clone(),
+                    executor.clone(),
+                    oracle_pk,
+                    (
+                        wallet_actor_addr.clone().into(),
+                        wallet_actor_addr.clone().into(),
+                    ),
+                    identity,
+                    n_payouts,
+                )
+            }
+        });
+        let contract_setup_supervisor = contract_setup_supervisor.create(None).spawn(&mut tasks);
+
         let (connection_actor_addr, connection_actor_ctx) = Context::new(None);
         let cfd_actor_addr = taker_cfd::Actor::new(
             db.clone(),
@@ -205,14 +319,10 @@ where
             connection_actor_addr.clone(),
             oracle_addr.clone(),
             libp2p_collab_settlement_addr,
+            libp2p_contract_setup_addr,
             n_payouts,
-            maker_identity,
-            PeerId::from(
-                maker_multiaddr
-                    .clone()
-                    .extract_peer_id()
-                    .context("Unable to extract peer id from maker address")?,
-            ),
+            maker_identities,
+            PeerId::from(maker_peer_id),
         )
         .create(None)
         .spawn(&mut tasks);
@@ -238,11 +348,20 @@ where
 
         let online_status_actor = online_status::Actor::new(
             endpoint_addr.clone(),
-            maker_multiaddr
-                .clone()
-                .extract_peer_id()
-                .expect("to be able to extract peer id"),
-            maker_online_status_feed_sender,
+            (
+                maker_peer_id,
+                maker_peer_ids
+                    .iter()
+                    .skip(1)
+                    .copied()
+                    .collect::<HashSet<_>>(),
+            ),
+            (
+                maker_online_status_feed_sender,
+                makers_online_status_feed_sender,
+            ),
+            maker_compatibility_feed_sender,
+            maker_address_stats_feed_sender,
         )
         .create(None)
         .spawn(&mut tasks);
@@ -258,18 +377,43 @@ where
                 )),
         );
 
-        tasks.add(monitor_ctx.run(monitor_constructor(executor.clone())?));
-        tasks.add(oracle_ctx.run(oracle_constructor(executor.clone())));
-
-        let dialer_constructor = {
-            let endpoint_addr = endpoint_addr.clone();
-            move || dialer::Actor::new(endpoint_addr.clone(), maker_multiaddr.clone())
-        };
-        let (dialer_supervisor, dialer_actor) = supervisor::Actor::with_policy(
-            dialer_constructor,
-            always_restart_after(RESTART_INTERVAL),
+        tasks.add(
+            monitor_ctx
+                .instrumented()
+                .run(monitor_constructor(executor.clone())?),
+        );
+        tasks.add(
+            oracle_ctx
+                .instrumented()
+                .run(oracle_constructor(executor.clone())),
         );
 
+        // Only the statistics of the first maker's addresses are reported
+        let (dialer_supervisors, dialer_actors): (Vec<_>, Vec<_>) = makers
+            .into_iter()
+            .enumerate()
+            .map(|(i, maker)| {
+                let endpoint_addr = endpoint_addr.clone();
+                let subscribers = if i == 0 {
+                    vec![online_status_actor.clone().into()]
+                } else {
+                    vec![]
+                };
+                let dialer_constructor = move || {
+                    dialer::Actor::new(
+                        endpoint_addr.clone(),
+                        maker.multiaddrs.clone(),
+                        subscribers.clone(),
+                    )
+                };
+
+                supervisor::Actor::with_policy(
+                    dialer_constructor,
+                    always_restart_after(RESTART_INTERVAL),
+                )
+            })
+            .unzip();
+
         let (offers_supervisor, libp2p_offer_addr) = supervisor::Actor::new({
             let cfd_actor_addr = cfd_actor_addr.clone();
             move || xtra_libp2p_offer::taker::Actor::new(cfd_actor_addr.clone().into())
@@ -278,28 +422,67 @@ where
         let pong_address = pong::Actor::default().create(None).spawn(&mut tasks);
 
         let (supervisor, ping_actor) = supervisor::Actor::new({
+            let endpoint_addr = endpoint_addr.clone();
             move || ping::Actor::new(endpoint_addr.clone(), PING_INTERVAL)
         });
         let ping_supervisor = supervisor.create(None).spawn(&mut tasks);
 
+        let identify_listener_actor = xtra_libp2p_identify::listener::Actor::new(
+            endpoint_addr.clone(),
+            version::version().to_string(),
+            identify::features(),
+        )
+        .create(None)
+        .spawn(&mut tasks);
+
+        let (supervisor, identify_dialer_actor) = supervisor::Actor::new({
+            let online_status_actor = online_status_actor.clone();
+            move || {
+                xtra_libp2p_identify::dialer::Actor::new(
+                    endpoint_addr.clone(),
+                    vec![online_status_actor.clone().into()],
+                )
+            }
+        });
+        let identify_dialer_supervisor = supervisor.create(None).spawn(&mut tasks);
+
+        let dns_transport = SharedTransport::new(
+            TokioDnsConfig::system(TokioTcpConfig::new())
+                .context("Failed to read system DNS configuration")?,
+        );
         let endpoint = Endpoint::new(
-            Box::new(TokioTcpConfig::new),
+            Box::new(move || {
+                let transport = TorTransport::new(dns_transport.clone());
+                match tor_socks5_proxy {
+                    Some(tor_socks5_proxy) => transport.with_socks_proxy(tor_socks5_proxy),
+                    None => transport,
+                }
+            }),
             identity.libp2p,
             ENDPOINT_CONNECTION_TIMEOUT,
             [
                 (xtra_libp2p_ping::PROTOCOL_NAME, pong_address.clone().into()),
                 (xtra_libp2p_offer::PROTOCOL_NAME, libp2p_offer_addr.into()),
+                (
+                    xtra_libp2p_identify::PROTOCOL_NAME,
+                    identify_listener_actor.clone().into(),
+                ),
             ],
             endpoint::Subscribers::new(
                 vec![
                     online_status_actor.clone().into(),
                     ping_actor.clone().into(),
+                    identify_dialer_actor.clone().into(),
                 ],
-                vec![
-                    dialer_actor.into(),
-                    ping_actor.into(),
-                    online_status_actor.clone().into(),
-                ],
+                dialer_actors
+                    .into_iter()
+                    .map(Into::into)
+                    .chain([
+                        ping_actor.into(),
+                        online_status_actor.clone().into(),
+                        identify_dialer_actor.into(),
+                    ])
+                    .collect(),
                 vec![],
                 vec![],
             ),
@@ -307,11 +490,23 @@ where
 
         tasks.add(endpoint_context.run(endpoint));
 
-        let dialer_supervisor = dialer_supervisor.create(None).spawn(&mut tasks);
+        let dialer_supervisors = dialer_supervisors
+            .into_iter()
+            .map(|supervisor| supervisor.create(None).spawn(&mut tasks))
+            .collect();
         let offers_supervisor = offers_supervisor.create(None).spawn(&mut tasks);
 
-        let (supervisor, price_feed_actor) =
-            supervisor::Actor::with_policy(price_feed_constructor, always_restart());
+        let (mut supervisor, price_feed_actor) = supervisor::Actor::with_policy(
+            price_feed_constructor,
+            always_restart_with_backoff(Backoff::new(
+                PRICE_FEED_MIN_BACKOFF,
+                PRICE_FEED_MAX_BACKOFF,
+            ))
+            .with_max_restarts(PRICE_FEED_MAX_RESTARTS, PRICE_FEED_RESTART_WINDOW),
+        );
+        if let Some(parent) = price_feed_escalate_to {
+            supervisor = supervisor.escalate_to(parent);
+        }
 
         let price_feed_supervisor = supervisor.create(None).spawn(&mut tasks);
 
@@ -334,13 +529,19 @@ where
             _price_feed_supervisor: price_feed_supervisor,
             _rollover_supervisor: rollover_supervisor,
             _collab_settlement_supervisor: collab_settlement_supervisor,
-            _dialer_supervisor: dialer_supervisor,
+            _contract_setup_supervisor: contract_setup_supervisor,
+            _dialer_supervisors: dialer_supervisors,
             _offers_supervisor: offers_supervisor,
             _ping_supervisor: ping_supervisor,
+            _identify_dialer_supervisor: identify_dialer_supervisor,
+            _identify_listener_actor: identify_listener_actor,
             _close_cfds_actor: close_cfds_actor,
             _archive_failed_cfds_actor: archive_failed_cfds_actor,
             _tasks: tasks,
             maker_online_status_feed_receiver,
+            maker_compatibility_feed_receiver,
+            maker_address_stats_feed_receiver,
+            makers_online_status_feed_receiver,
             _online_status_actor: online_status_actor,
             _pong_actor: pong_address,
         })
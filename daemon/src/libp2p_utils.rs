@@ -4,8 +4,10 @@ use anyhow::Result;
 use std::net::IpAddr;
 use std::net::SocketAddr;
 
+use libp2p_core::multiaddr::Protocol;
 use libp2p_core::Multiaddr;
 use libp2p_core::PeerId;
+use xtra_libp2p::tor::OnionService;
 
 /// Creates MultiAddr from SocketAddr and PeerId
 pub fn create_connect_tcp_multiaddr(
@@ -14,13 +16,44 @@ pub fn create_connect_tcp_multiaddr(
 ) -> Result<Multiaddr> {
     let ip = socket_addr.ip();
     let port = socket_addr.port();
-    ensure!(socket_addr.is_ipv4(), "only ipv4 is supported");
+    let ip_protocol = match ip {
+        IpAddr::V4(_) => "ip4",
+        IpAddr::V6(_) => "ip6",
+    };
 
-    format!("/ip4/{ip}/tcp/{port}/p2p/{peer_id}")
+    format!("/{ip_protocol}/{ip}/tcp/{port}/p2p/{peer_id}")
         .parse::<Multiaddr>()
         .with_context(|| "failed to construct multiaddr")
 }
 
+/// Creates MultiAddr from a hostname, port and PeerId
+///
+/// The hostname is resolved every time the address is dialed.
+pub fn create_connect_dns_multiaddr(host: &str, port: u16, peer_id: PeerId) -> Result<Multiaddr> {
+    format!("/dns/{host}/tcp/{port}/p2p/{peer_id}")
+        .parse::<Multiaddr>()
+        .with_context(|| "failed to construct multiaddr")
+}
+
+/// Appends the `/p2p` suffix to the given MultiAddr unless it is already present
+///
+/// An existing suffix has to match the given PeerId.
+pub fn with_peer_id(multiaddr: Multiaddr, peer_id: PeerId) -> Result<Multiaddr> {
+    match multiaddr.iter().last() {
+        Some(Protocol::P2p(hash)) => {
+            let existing = PeerId::from_multihash(hash)
+                .map_err(|_| anyhow::anyhow!("invalid peer id in {multiaddr}"))?;
+            ensure!(
+                existing == peer_id,
+                "{multiaddr} does not belong to peer {peer_id}"
+            );
+
+            Ok(multiaddr)
+        }
+        _ => Ok(multiaddr.with(Protocol::P2p(peer_id.into()))),
+    }
+}
+
 /// Construct a Multiaddr that can dial in to other party given their MultiAddr
 /// and PeerId
 pub fn create_connect_multiaddr(
@@ -41,12 +74,31 @@ pub fn create_listen_tcp_multiaddr(ip: &IpAddr, port: u16) -> Result<Multiaddr>
         .with_context(|| "failed to construct multiaddr")
 }
 
+/// Creates an [`OnionService`] that Tor forwards to the same port on localhost
+pub fn create_onion_service(address: Multiaddr) -> Result<OnionService> {
+    let port = match address.iter().next() {
+        Some(Protocol::Onion3(onion)) => onion.port(),
+        _ => anyhow::bail!("{address} is not an onion service address"),
+    };
+    let target = create_listen_tcp_multiaddr(&IpAddr::from([127, 0, 0, 1]), port)?;
+
+    Ok(OnionService { address, target })
+}
+
 /// By convention we increment the port by 1 for libp2p-based connections.
 ///
 /// The obvious drawback is that when doing blue/green deployment, we need to
 /// increment/decrement ports by 2.
 pub fn libp2p_socket_from_legacy_networking(legacy_addr: &SocketAddr) -> SocketAddr {
-    SocketAddr::new(legacy_addr.ip(), legacy_addr.port() + 1)
+    SocketAddr::new(
+        legacy_addr.ip(),
+        libp2p_port_from_legacy_networking(legacy_addr.port()),
+    )
+}
+
+/// See [`libp2p_socket_from_legacy_networking`].
+pub fn libp2p_port_from_legacy_networking(legacy_port: u16) -> u16 {
+    legacy_port + 1
 }
 
 /// Determine whether to use libp2p or fallback to a legacy protocol
@@ -56,3 +108,45 @@ pub fn can_use_libp2p(cfd: &model::Cfd) -> bool {
     // can't dial in to them using libp2p.
     cfd.counterparty_peer_id().is_some()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_tcp_multiaddr_for_ipv6() {
+        let peer_id = PeerId::random();
+        let socket_addr = "[::1]:10001".parse().unwrap();
+
+        let multiaddr = create_connect_tcp_multiaddr(&socket_addr, peer_id).unwrap();
+
+        assert_eq!(
+            multiaddr,
+            format!("/ip6/::1/tcp/10001/p2p/{peer_id}").parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn appends_missing_peer_id() {
+        let peer_id = PeerId::random();
+        let multiaddr = "/dns4/maker.example.com/tcp/10001".parse().unwrap();
+
+        let multiaddr = with_peer_id(multiaddr, peer_id).unwrap();
+
+        assert_eq!(
+            multiaddr,
+            format!("/dns4/maker.example.com/tcp/10001/p2p/{peer_id}")
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_address_of_other_peer() {
+        let multiaddr = format!("/ip4/127.0.0.1/tcp/10001/p2p/{}", PeerId::random())
+            .parse()
+            .unwrap();
+
+        assert!(with_peer_id(multiaddr, PeerId::random()).is_err());
+    }
+}
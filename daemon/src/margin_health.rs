@@ -0,0 +1,616 @@
+use crate::notifier;
+use crate::notifier::Notification;
+use crate::projection;
+use crate::projection::CfdAction;
+use crate::taker_cfd;
+use anyhow::Result;
+use async_trait::async_trait;
+use model::market_closing_price;
+use model::OrderId;
+use model::Position;
+use model::Price;
+use model::Timestamp;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use sqlite_db::quotes::Quote;
+use std::collections::HashSet;
+use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::sync::watch;
+use tokio_tasks::Tasks;
+use xtra::message_channel::MessageChannel;
+use xtra_bitmex_price_feed::LatestQuote;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+/// The name of the event operators are notified about when a CFD crosses the alert threshold.
+pub const LIQUIDATION_THRESHOLD_CROSSED: &str = "LiquidationThresholdCrossed";
+
+/// How often we check the health of the open CFDs.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The period of recorded quotes from which we estimate the volatility of the price.
+const VOLATILITY_WINDOW: time::Duration = time::Duration::hours(24);
+
+/// How far a CFD has to recover above the threshold before we alert about it again.
+///
+/// Keeps us from alerting about every tick while the price hovers around the threshold.
+const REARM_MARGIN_PERCENT: Decimal = dec!(1);
+
+/// How close a CFD is to being liquidated at the current price.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarginHealth {
+    pub order_id: OrderId,
+    pub liquidation_price: Price,
+    /// The price at which the CFD would be closed right now.
+    pub mark_price: Price,
+    /// How far the price has to move against the position to reach the liquidation price, in
+    /// percent of the mark price.
+    #[serde(with = "rust_decimal::serde::float")]
+    pub distance_percent: Decimal,
+    /// How long it takes the price to cover the distance to the liquidation price with a move of
+    /// one standard deviation, at the volatility of the last 24 hours.
+    ///
+    /// `0` if the price is already beyond the liquidation price. `None` if we do not have enough
+    /// recorded quotes to estimate the volatility.
+    pub seconds_to_liquidation: Option<u64>,
+    /// Whether the distance is below the alert threshold.
+    pub at_risk: bool,
+}
+
+/// Watches how close the open CFDs are to their liquidation price.
+///
+/// The health of all open CFDs is published on a feed. Operators are notified when a CFD crosses
+/// the alert threshold and, if `auto_close` is given, we propose to settle the CFD at the current
+/// price.
+pub struct Actor {
+    db: sqlite_db::Connection,
+    cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+    price_feed: MessageChannel<LatestQuote, Option<xtra_bitmex_price_feed::Quote>>,
+    /// Alert when the distance to the liquidation price drops below this many percent.
+    threshold_percent: Decimal,
+    notifier: MessageChannel<notifier::Notify, ()>,
+    auto_close: Option<MessageChannel<taker_cfd::ProposeSettlement, Result<()>>>,
+    feed: watch::Sender<Vec<MarginHealth>>,
+    /// The CFDs we already alerted about and which did not recover since.
+    alerted: HashSet<OrderId>,
+    /// The CFDs we proposed to close, so that we do so only once while they are open.
+    ///
+    /// CFDs are forgotten if proposing fails or once they leave the open state, e.g. because the
+    /// proposal was rejected, so that we try again.
+    closing: HashSet<OrderId>,
+    tasks: Tasks,
+}
+
+impl Actor {
+    pub fn new(
+        db: sqlite_db::Connection,
+        cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+        price_feed: MessageChannel<LatestQuote, Option<xtra_bitmex_price_feed::Quote>>,
+        threshold_percent: Decimal,
+        notifier: MessageChannel<notifier::Notify, ()>,
+        auto_close: Option<MessageChannel<taker_cfd::ProposeSettlement, Result<()>>>,
+    ) -> (Self, watch::Receiver<Vec<MarginHealth>>) {
+        let (feed, feed_receiver) = watch::channel(Vec::new());
+
+        let actor = Self {
+            db,
+            cfds,
+            price_feed,
+            threshold_percent,
+            notifier,
+            auto_close,
+            feed,
+            alerted: HashSet::new(),
+            closing: HashSet::new(),
+            tasks: Tasks::default(),
+        };
+
+        (actor, feed_receiver)
+    }
+
+    async fn volatility(&self, now: OffsetDateTime) -> Option<f64> {
+        match self.db.load_quotes(now - VOLATILITY_WINDOW, now).await {
+            Ok(quotes) => volatility(&quotes),
+            Err(e) => {
+                tracing::warn!("Failed to load quotes to estimate volatility: {e:#}");
+                None
+            }
+        }
+    }
+
+    async fn alert(&self, health: &MarginHealth) {
+        let order_id = health.order_id;
+        tracing::warn!(
+            %order_id,
+            distance_percent = %health.distance_percent,
+            liquidation_price = %health.liquidation_price,
+            "CFD is close to its liquidation price"
+        );
+
+        let notification = Notification {
+            order_id,
+            event: LIQUIDATION_THRESHOLD_CROSSED.to_owned(),
+            timestamp: Timestamp::now(),
+        };
+        if let Err(e) = self.notifier.send(notifier::Notify(notification)).await {
+            tracing::warn!(%order_id, "Failed to notify about CFD at risk: {e:#}");
+        }
+    }
+
+    async fn close(&mut self, order_id: OrderId, quote: &xtra_bitmex_price_feed::Quote) {
+        let auto_close = match &self.auto_close {
+            Some(auto_close) => auto_close,
+            None => return,
+        };
+
+        if !self.closing.insert(order_id) {
+            return;
+        }
+
+        let result = match settlement_proposal(order_id, quote) {
+            Ok(proposal) => {
+                tracing::info!(%order_id, "Proposing to settle CFD close to its liquidation price");
+                match auto_close.send(proposal).await {
+                    Ok(result) => result,
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            tracing::warn!(%order_id, "Failed to close CFD at risk: {e:#}");
+            self.closing.remove(&order_id);
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+
+        self.tasks
+            .add(this.send_interval(CHECK_INTERVAL, || CheckHealth));
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: CheckHealth) {
+        let quote = match self.price_feed.send(LatestQuote).await {
+            Ok(Some(quote)) => quote,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::debug!("Price feed not available: {e:#}");
+                return;
+            }
+        };
+
+        let (bid, ask) = match (Price::new(quote.bid), Price::new(quote.ask)) {
+            (Ok(bid), Ok(ask)) => (bid, ask),
+            (Err(e), _) | (_, Err(e)) => {
+                tracing::warn!(
+                    "Failed to check health of CFDs because latest price is invalid: {e}"
+                );
+                return;
+            }
+        };
+
+        let open_cfds = match self.cfds.borrow().as_ref() {
+            Some(cfds) => cfds
+                .iter()
+                .filter(|cfd| cfd.state == projection::CfdState::Open)
+                .cloned()
+                .collect::<Vec<_>>(),
+            None => return,
+        };
+
+        let volatility = self.volatility(OffsetDateTime::now_utc()).await;
+
+        let mut healths = Vec::with_capacity(open_cfds.len());
+        for cfd in open_cfds {
+            let mark_price = market_closing_price(bid, ask, cfd.role, cfd.position);
+            let distance_percent =
+                distance_percent(cfd.position, mark_price, cfd.liquidation_price);
+            let health = MarginHealth {
+                order_id: cfd.order_id,
+                liquidation_price: cfd.liquidation_price,
+                mark_price,
+                distance_percent,
+                seconds_to_liquidation: if distance_percent <= Decimal::ZERO {
+                    Some(0)
+                } else {
+                    volatility.and_then(|volatility| {
+                        seconds_to_liquidation(mark_price, cfd.liquidation_price, volatility)
+                    })
+                },
+                at_risk: distance_percent < self.threshold_percent,
+            };
+
+            if health.at_risk {
+                if self.alerted.insert(cfd.order_id) {
+                    self.alert(&health).await;
+                }
+                if cfd.actions.contains(&CfdAction::Settle) {
+                    self.close(cfd.order_id, &quote).await;
+                }
+            } else if distance_percent >= self.threshold_percent + REARM_MARGIN_PERCENT {
+                self.alerted.remove(&cfd.order_id);
+            }
+
+            healths.push(health);
+        }
+
+        let open = healths
+            .iter()
+            .map(|health| health.order_id)
+            .collect::<HashSet<_>>();
+        self.alerted.retain(|order_id| open.contains(order_id));
+        self.closing.retain(|order_id| open.contains(order_id));
+
+        let _ = self.feed.send(healths);
+    }
+}
+
+/// Private message to check the health of the open CFDs.
+struct CheckHealth;
+
+fn settlement_proposal(
+    order_id: OrderId,
+    quote: &xtra_bitmex_price_feed::Quote,
+) -> Result<taker_cfd::ProposeSettlement> {
+    Ok(taker_cfd::ProposeSettlement {
+        order_id,
+        bid: Price::new(quote.bid)?,
+        ask: Price::new(quote.ask)?,
+        quote_timestamp: quote.timestamp.format(&Rfc3339)?,
+    })
+}
+
+/// How far the price has to move against a position to reach its liquidation price, in percent of
+/// the `mark_price`.
+///
+/// Negative if the price has already moved beyond the liquidation price.
+fn distance_percent(position: Position, mark_price: Price, liquidation_price: Price) -> Decimal {
+    let mark_price = mark_price.into_decimal();
+    let liquidation_price = liquidation_price.into_decimal();
+
+    let distance = match position {
+        Position::Long => mark_price - liquidation_price,
+        Position::Short => liquidation_price - mark_price,
+    };
+
+    (distance / mark_price * dec!(100)).round_dp(2)
+}
+
+/// The time for a move of one standard deviation to cover the distance between the prices.
+///
+/// `volatility` is the standard deviation of the log returns per square root of a second.
+fn seconds_to_liquidation(
+    mark_price: Price,
+    liquidation_price: Price,
+    volatility: f64,
+) -> Option<u64> {
+    let mark_price = mark_price.into_decimal().to_f64()?;
+    let liquidation_price = liquidation_price.into_decimal().to_f64()?;
+
+    let log_distance = (liquidation_price / mark_price).ln();
+    let seconds = (log_distance / volatility).powi(2);
+
+    seconds.is_finite().then(|| seconds as u64)
+}
+
+/// The standard deviation of the log returns of the mid price per square root of a second.
+///
+/// `None` if there are not enough quotes or the price did not move at all.
+fn volatility(quotes: &[Quote]) -> Option<f64> {
+    let variances = quotes
+        .windows(2)
+        .filter_map(|pair| {
+            let seconds = (pair[1].timestamp - pair[0].timestamp).as_seconds_f64();
+            let log_return = (mid(&pair[1])? / mid(&pair[0])?).ln();
+
+            (seconds > 0.0).then(|| log_return.powi(2) / seconds)
+        })
+        .collect::<Vec<_>>();
+
+    if variances.is_empty() {
+        return None;
+    }
+
+    let variance = variances.iter().sum::<f64>() / variances.len() as f64;
+
+    (variance > 0.0).then(|| variance.sqrt())
+}
+
+fn mid(quote: &Quote) -> Option<f64> {
+    ((quote.bid + quote.ask) / dec!(2)).to_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::Amount;
+    use model::ContractType;
+    use model::FundingRate;
+    use model::Leverage;
+    use model::OpeningFee;
+    use model::Role;
+    use model::TxFeeRate;
+    use model::Usd;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use xtra::Actor as _;
+
+    #[test]
+    fn distance_is_positive_until_liquidation_price_is_reached() {
+        let liquidation_price = Price::new(dec!(10_000)).unwrap();
+
+        assert_eq!(
+            distance_percent(
+                Position::Long,
+                Price::new(dec!(20_000)).unwrap(),
+                liquidation_price
+            ),
+            dec!(50)
+        );
+        assert_eq!(
+            distance_percent(
+                Position::Short,
+                Price::new(dec!(8_000)).unwrap(),
+                liquidation_price
+            ),
+            dec!(25)
+        );
+        assert_eq!(
+            distance_percent(
+                Position::Long,
+                Price::new(dec!(8_000)).unwrap(),
+                liquidation_price
+            ),
+            dec!(-25)
+        );
+    }
+
+    #[test]
+    fn volatility_of_constant_price_is_unknown() {
+        let quotes = [
+            quote(1657065600, dec!(20_000)),
+            quote(1657065660, dec!(20_000)),
+        ];
+
+        assert_eq!(volatility(&quotes), None);
+        assert_eq!(volatility(&quotes[..1]), None);
+    }
+
+    #[test]
+    fn higher_volatility_means_less_time_to_liquidation() {
+        let calm = volatility(&[
+            quote(1657065600, dec!(20_000)),
+            quote(1657065660, dec!(20_010)),
+            quote(1657065720, dec!(20_000)),
+        ])
+        .unwrap();
+        let wild = volatility(&[
+            quote(1657065600, dec!(20_000)),
+            quote(1657065660, dec!(20_100)),
+            quote(1657065720, dec!(20_000)),
+        ])
+        .unwrap();
+
+        let mark_price = Price::new(dec!(20_000)).unwrap();
+        let liquidation_price = Price::new(dec!(15_000)).unwrap();
+
+        assert!(
+            seconds_to_liquidation(mark_price, liquidation_price, wild).unwrap()
+                < seconds_to_liquidation(mark_price, liquidation_price, calm).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn alerts_once_until_cfd_recovers_beyond_rearm_margin() {
+        let mut margin_health = MarginHealthFixture::new(false).await;
+
+        for price in [dec!(10_500), dec!(11_000), dec!(11_200)] {
+            margin_health.check_at(price).await;
+        }
+        assert_eq!(margin_health.mock.lock().await.notifications, 1);
+        assert!(!margin_health.feed.borrow()[0].at_risk);
+
+        margin_health.check_at(dec!(11_300)).await;
+        margin_health.check_at(dec!(10_500)).await;
+
+        assert_eq!(margin_health.mock.lock().await.notifications, 2);
+        assert!(margin_health.feed.borrow()[0].at_risk);
+    }
+
+    #[tokio::test]
+    async fn retries_closing_until_proposal_succeeds() {
+        let mut margin_health = MarginHealthFixture::new(true).await;
+
+        margin_health.mock.lock().await.fail_proposals = true;
+        margin_health.check_at(dec!(10_500)).await;
+        margin_health.check_at(dec!(10_500)).await;
+        assert_eq!(margin_health.mock.lock().await.proposals, 2);
+
+        margin_health.mock.lock().await.fail_proposals = false;
+        margin_health.check_at(dec!(10_500)).await;
+        margin_health.check_at(dec!(10_500)).await;
+        assert_eq!(margin_health.mock.lock().await.proposals, 3);
+        assert_eq!(margin_health.mock.lock().await.notifications, 1);
+    }
+
+    #[tokio::test]
+    async fn forgets_cfds_which_are_no_longer_open() {
+        let mut margin_health = MarginHealthFixture::new(true).await;
+        let open = margin_health.cfds.borrow().clone().unwrap();
+
+        margin_health.check_at(dec!(10_500)).await;
+
+        let mut pending_close = open.clone();
+        pending_close[0].state = projection::CfdState::PendingClose;
+        margin_health.cfds.send(Some(pending_close)).unwrap();
+        margin_health.check_at(dec!(10_500)).await;
+        assert!(margin_health.feed.borrow().is_empty());
+
+        margin_health.cfds.send(Some(open)).unwrap();
+        margin_health.check_at(dec!(10_500)).await;
+
+        assert_eq!(margin_health.mock.lock().await.notifications, 2);
+        assert_eq!(margin_health.mock.lock().await.proposals, 2);
+    }
+
+    #[tokio::test]
+    async fn cfd_beyond_liquidation_price_is_due_now() {
+        let mut margin_health = MarginHealthFixture::new(false).await;
+
+        margin_health.check_at(dec!(9_000)).await;
+
+        let health = margin_health.feed.borrow()[0].clone();
+        assert_eq!(health.distance_percent, dec!(-11.11));
+        assert_eq!(health.seconds_to_liquidation, Some(0));
+    }
+
+    /// Watches a long taker CFD with a liquidation price of 10,000 and an alert threshold of 10%.
+    struct MarginHealthFixture {
+        actor: Actor,
+        context: xtra::Context<Actor>,
+        mock: Arc<Mutex<MockDaemon>>,
+        cfds: watch::Sender<Option<Vec<projection::Cfd>>>,
+        feed: watch::Receiver<Vec<MarginHealth>>,
+        _tasks: Tasks,
+    }
+
+    impl MarginHealthFixture {
+        async fn new(auto_close: bool) -> Self {
+            let db = sqlite_db::memory().await.unwrap();
+            let cfd = dummy_cfd();
+            db.insert_cfd(&cfd).await.unwrap();
+
+            let mut cfd = db
+                .load_open_cfd::<projection::Cfd>(cfd.id(), bdk::bitcoin::Network::Testnet)
+                .await
+                .unwrap();
+            cfd.state = projection::CfdState::Open;
+            cfd.liquidation_price = Price::new(dec!(10_000)).unwrap();
+            cfd.actions = HashSet::from([CfdAction::Settle]);
+            let (cfds, cfds_receiver) = watch::channel(Some(vec![cfd]));
+
+            let mock = Arc::new(Mutex::new(MockDaemon::default()));
+            let mut tasks = Tasks::default();
+            let daemon = MockDaemonActor { mock: mock.clone() }
+                .create(None)
+                .spawn(&mut tasks);
+
+            let (actor, feed) = Actor::new(
+                db,
+                cfds_receiver,
+                daemon.clone().into(),
+                dec!(10),
+                daemon.clone().into(),
+                auto_close.then(|| daemon.into()),
+            );
+            let (_, context) = xtra::Context::new(None);
+
+            Self {
+                actor,
+                context,
+                mock,
+                cfds,
+                feed,
+                _tasks: tasks,
+            }
+        }
+
+        async fn check_at(&mut self, price: Decimal) {
+            self.mock.lock().await.price = price;
+
+            xtra::Handler::handle(&mut self.actor, CheckHealth, &mut self.context).await;
+        }
+    }
+
+    fn dummy_cfd() -> model::Cfd {
+        model::Cfd::new(
+            OrderId::default(),
+            Position::Long,
+            Price::new(dec!(20_000)).unwrap(),
+            Leverage::TWO,
+            time::Duration::hours(24),
+            Role::Taker,
+            Usd::new(dec!(1_000)),
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"
+                .parse()
+                .unwrap(),
+            None,
+            OpeningFee::new(Amount::from_sat(2000)),
+            FundingRate::default(),
+            TxFeeRate::default(),
+            ContractType::Perpetual,
+        )
+    }
+
+    /// Stands in for the price feed, the notifier and the CFD actor.
+    #[derive(Default)]
+    struct MockDaemon {
+        price: Decimal,
+        notifications: usize,
+        proposals: usize,
+        fail_proposals: bool,
+    }
+
+    struct MockDaemonActor {
+        mock: Arc<Mutex<MockDaemon>>,
+    }
+
+    #[async_trait]
+    impl xtra::Actor for MockDaemonActor {
+        type Stop = ();
+
+        async fn stopped(self) -> Self::Stop {}
+    }
+
+    #[xtra_productivity(message_impl = false)]
+    impl MockDaemonActor {
+        async fn handle(&mut self, _: LatestQuote) -> Option<xtra_bitmex_price_feed::Quote> {
+            let price = self.mock.lock().await.price;
+
+            Some(xtra_bitmex_price_feed::Quote {
+                timestamp: OffsetDateTime::now_utc(),
+                bid: price,
+                ask: price,
+            })
+        }
+
+        async fn handle(&mut self, _: notifier::Notify) {
+            self.mock.lock().await.notifications += 1;
+        }
+
+        async fn handle(&mut self, _: taker_cfd::ProposeSettlement) -> Result<()> {
+            let mut mock = self.mock.lock().await;
+            mock.proposals += 1;
+
+            if mock.fail_proposals {
+                anyhow::bail!("Maker is offline");
+            }
+
+            Ok(())
+        }
+    }
+
+    fn quote(timestamp: i64, mid: Decimal) -> Quote {
+        Quote {
+            timestamp: OffsetDateTime::from_unix_timestamp(timestamp).unwrap(),
+            bid: mid - dec!(0.5),
+            ask: mid + dec!(0.5),
+        }
+    }
+}
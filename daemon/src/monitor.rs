@@ -299,7 +299,8 @@ impl Cfd {
             | CollaborativeSettlementStarted { .. }
             | CollaborativeSettlementRejected
             | CollaborativeSettlementFailed
-            | CollaborativeSettlementProposalAccepted => self,
+            | CollaborativeSettlementProposalAccepted
+            | AutoAccepted { .. } => self,
             RevokeConfirmed => {
                 tracing::error!("Revoked logic not implemented");
                 self
@@ -0,0 +1,326 @@
+use crate::margin_health;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use bdk::bitcoin::hashes::hmac;
+use bdk::bitcoin::hashes::sha256;
+use bdk::bitcoin::hashes::Hash;
+use bdk::bitcoin::hashes::HashEngine;
+use model::CfdEvent;
+use model::EventKind;
+use model::OrderId;
+use model::Timestamp;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Url;
+use serde::Serialize;
+use sqlite_db::notifications::NotificationDelivery;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio_tasks::Tasks;
+use xtra_productivity::xtra_productivity;
+
+/// The events operators are notified about unless configured otherwise.
+///
+/// There is no dedicated event for a liquidation: a liquidated CFD is closed by publishing the CET
+/// for the attested price, which is reported as `CetConfirmed`.
+pub const DEFAULT_EVENTS: &[&str] = &[
+    "RolloverStarted",
+    "RolloverFailed",
+    "OracleAttestedPriorCetTimelock",
+    "OracleAttestedPostCetTimelock",
+    "ManualCommit",
+    "CommitConfirmed",
+    "CetConfirmed",
+    "RefundConfirmed",
+    "CollaborativeSettlementConfirmed",
+    margin_health::LIQUIDATION_THRESHOLD_CROSSED,
+];
+
+/// Whether operators can be notified about the event with the given name.
+pub fn is_known_event(name: &str) -> bool {
+    EventKind::NAMES.contains(&name) || name == margin_health::LIQUIDATION_THRESHOLD_CROSSED
+}
+
+/// The header carrying the signature of a webhook's body.
+///
+/// The value is of the form `sha256=<hex>`, where `<hex>` is the HMAC-SHA256 of the body keyed
+/// with the webhook secret.
+pub const SIGNATURE_HEADER: &str = "X-ItchySats-Signature";
+
+/// How often we try to deliver a notification to a sink before giving up.
+const MAX_DELIVERY_ATTEMPTS: u32 = 6;
+
+/// How long we wait before retrying a failed delivery, doubled for every retry.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notifies operators about events in the lifecycle of a CFD.
+///
+/// Every notification is delivered to all sinks independently, retrying failed deliveries with
+/// exponential backoff. All delivery attempts are recorded in the database.
+pub struct Actor {
+    db: sqlite_db::Connection,
+    sinks: Vec<Arc<dyn Sink>>,
+    events: HashSet<String>,
+    tasks: Tasks,
+}
+
+impl Actor {
+    /// Create a notifier delivering the given `events` to all `sinks`.
+    ///
+    /// Events are identified by their name, e.g. `RolloverStarted`.
+    pub fn new(
+        db: sqlite_db::Connection,
+        sinks: Vec<Arc<dyn Sink>>,
+        events: HashSet<String>,
+    ) -> Self {
+        Self {
+            db,
+            sinks,
+            events,
+            tasks: Tasks::default(),
+        }
+    }
+}
+
+/// A channel over which operators are notified, e.g. a webhook.
+#[async_trait]
+pub trait Sink: Send + Sync + 'static {
+    /// Identifies the sink in the logs and the delivery log.
+    fn name(&self) -> String;
+
+    async fn deliver(&self, notification: &Notification) -> Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub order_id: OrderId,
+    /// The name of the event, e.g. `RolloverStarted`.
+    pub event: String,
+    pub timestamp: Timestamp,
+}
+
+impl Notification {
+    pub fn new(event: &CfdEvent) -> Self {
+        Self {
+            order_id: event.id,
+            event: event.event.to_string(),
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+/// Message to notify about an event which was just recorded for a CFD.
+pub struct Notify(pub Notification);
+
+#[xtra_productivity]
+impl Actor {
+    fn handle(&mut self, msg: Notify) {
+        let notification = msg.0;
+
+        if !self.events.contains(&notification.event) {
+            return;
+        }
+
+        for sink in self.sinks.iter() {
+            self.tasks.add(deliver(
+                self.db.clone(),
+                sink.clone(),
+                notification.clone(),
+                RETRY_INTERVAL,
+            ));
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+async fn deliver(
+    db: sqlite_db::Connection,
+    sink: Arc<dyn Sink>,
+    notification: Notification,
+    mut retry_interval: Duration,
+) {
+    let sink_name = sink.name();
+    let order_id = notification.order_id;
+    let event = notification.event.as_str();
+
+    for attempt in 1..=MAX_DELIVERY_ATTEMPTS {
+        let error = sink
+            .deliver(&notification)
+            .await
+            .err()
+            .map(|e| format!("{e:#}"));
+
+        let delivery = NotificationDelivery {
+            order_id,
+            event: event.to_owned(),
+            sink: sink_name.clone(),
+            attempt,
+            error: error.clone(),
+            attempted_at: OffsetDateTime::now_utc(),
+        };
+        if let Err(e) = db.insert_notification_delivery(&delivery).await {
+            tracing::warn!(%order_id, "Failed to record notification delivery: {e:#}");
+        }
+
+        let error = match error {
+            None => {
+                tracing::debug!(%order_id, %event, sink = %sink_name, "Delivered notification");
+                return;
+            }
+            Some(error) => error,
+        };
+
+        if attempt == MAX_DELIVERY_ATTEMPTS {
+            tracing::warn!(
+                %order_id,
+                %event,
+                sink = %sink_name,
+                "Giving up on notification after {attempt} attempts: {error}"
+            );
+            return;
+        }
+
+        tracing::debug!(
+            %order_id,
+            %event,
+            sink = %sink_name,
+            "Failed to deliver notification, retrying in {}s: {error}",
+            retry_interval.as_secs()
+        );
+        tokio::time::sleep(retry_interval).await;
+        retry_interval *= 2;
+    }
+}
+
+/// Delivers notifications as JSON via HTTP POST, signed with a shared secret.
+///
+/// See [`SIGNATURE_HEADER`] for how to verify a notification.
+pub struct Webhook {
+    url: Url,
+    secret: String,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub fn new(url: Url, secret: String) -> Self {
+        Self {
+            url,
+            secret,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for Webhook {
+    fn name(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    async fn deliver(&self, notification: &Notification) -> Result<()> {
+        let body = serde_json::to_vec(notification)?;
+        let signature = sign(&self.secret, &body);
+
+        self.client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, format!("sha256={signature}"))
+            .body(body)
+            .timeout(WEBHOOK_TIMEOUT)
+            .send()
+            .await
+            .context("Failed to send webhook")?
+            .error_for_status()
+            .context("Webhook rejected notification")?;
+
+        Ok(())
+    }
+}
+
+/// Compute the hex-encoded HMAC-SHA256 of `body` keyed with `secret`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(body);
+
+    hmac::Hmac::<sha256::Hash>::from_engine(engine).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn signature_is_hmac_sha256() {
+        // Test case 2 of RFC 4231
+        let signature = sign("Jefe", b"what do ya want for nothing?");
+
+        assert_eq!(
+            signature,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn default_events_are_known() {
+        for event in DEFAULT_EVENTS {
+            assert!(is_known_event(event), "{event} should be known");
+        }
+        assert!(!is_known_event("RolloverStared"));
+    }
+
+    #[tokio::test]
+    async fn retries_failed_delivery_and_records_attempts() {
+        let db = sqlite_db::memory().await.unwrap();
+        let sink = Arc::new(FlakySink {
+            failures_left: AtomicU32::new(2),
+        });
+        let notification = Notification {
+            order_id: OrderId::default(),
+            event: "RolloverStarted".to_owned(),
+            timestamp: Timestamp::now(),
+        };
+
+        deliver(db.clone(), sink, notification, Duration::ZERO).await;
+
+        let deliveries = db.load_notification_deliveries(10).await.unwrap();
+        let attempts = deliveries
+            .iter()
+            .map(|delivery| (delivery.attempt, delivery.error.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(attempts, vec![(3, false), (2, true), (1, true)]);
+    }
+
+    struct FlakySink {
+        failures_left: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Sink for FlakySink {
+        fn name(&self) -> String {
+            "flaky".to_owned()
+        }
+
+        async fn deliver(&self, _: &Notification) -> Result<()> {
+            let failures_left = self.failures_left.load(Ordering::SeqCst);
+            if failures_left > 0 {
+                self.failures_left
+                    .store(failures_left - 1, Ordering::SeqCst);
+                anyhow::bail!("Connection refused");
+            }
+
+            Ok(())
+        }
+    }
+}
@@ -1,36 +1,79 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::time::Duration;
 
 use async_trait::async_trait;
 use libp2p_core::PeerId;
 use tokio::sync::watch;
 use xtra::prelude::*;
+use xtra_libp2p::dialer::AddressStats;
+use xtra_libp2p::dialer::LatestAddressStats;
 use xtra_libp2p::endpoint;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p::GetConnectionStats;
+use xtra_libp2p_identify::dialer::PeerInfoReceived;
 use xtra_productivity::xtra_productivity;
 
 use crate::connection::ConnectionStatus;
+use crate::identify;
+use crate::identify::Compatibility;
 
 /// Actor that transmits updates of ConnectionStatus of a specified PeerId based on
 /// information transmitted by the Endpoint via a watch channel.
+///
+/// Once the watched peer identified itself, the actor additionally transmits whether the peer is
+/// compatible with us. The dialer's statistics about the addresses of the peer are forwarded
+/// as-is.
+///
+/// The ConnectionStatus of the other watched peers is transmitted alongside the one of the
+/// watched peer in a separate watch channel.
 pub struct Actor {
     endpoint: Address<Endpoint>,
     watched_peer: PeerId,
+    other_watched_peers: HashSet<PeerId>,
     sender: watch::Sender<ConnectionStatus>,
+    statuses_sender: watch::Sender<HashMap<model::libp2p::PeerId, ConnectionStatus>>,
+    compatibility_sender: watch::Sender<Option<Compatibility>>,
+    address_stats_sender: watch::Sender<Vec<AddressStats>>,
 }
 
 impl Actor {
     pub fn new(
         endpoint: Address<Endpoint>,
-        watched_peer: PeerId,
-        sender: watch::Sender<ConnectionStatus>,
+        (watched_peer, other_watched_peers): (PeerId, HashSet<PeerId>),
+        (sender, statuses_sender): (
+            watch::Sender<ConnectionStatus>,
+            watch::Sender<HashMap<model::libp2p::PeerId, ConnectionStatus>>,
+        ),
+        compatibility_sender: watch::Sender<Option<Compatibility>>,
+        address_stats_sender: watch::Sender<Vec<AddressStats>>,
     ) -> Self {
         Self {
             endpoint,
             watched_peer,
+            other_watched_peers,
             sender,
+            statuses_sender,
+            compatibility_sender,
+            address_stats_sender,
         }
     }
+
+    fn is_watched(&self, peer: &PeerId) -> bool {
+        *peer == self.watched_peer || self.other_watched_peers.contains(peer)
+    }
+
+    fn send_status(&self, peer: PeerId, status: ConnectionStatus) {
+        if peer == self.watched_peer {
+            self.sender
+                .send(status.clone())
+                .expect("Receiver to outlive this actor");
+        }
+
+        self.statuses_sender.send_modify(|statuses| {
+            statuses.insert(peer.into(), status);
+        });
+    }
 }
 
 #[async_trait]
@@ -45,17 +88,18 @@ impl xtra::Actor for Actor {
 
         match self.endpoint.send(GetConnectionStats).await {
             Ok(connection_stats) => {
-                let status = if connection_stats
-                    .connected_peers
-                    .contains(&self.watched_peer)
-                {
-                    ConnectionStatus::Online
-                } else {
-                    ConnectionStatus::Offline { reason: None }
-                };
-                self.sender
-                    .send(status)
-                    .expect("Receiver to outlive this actor");
+                let watched_peers = std::iter::once(self.watched_peer)
+                    .chain(self.other_watched_peers.iter().copied())
+                    .collect::<Vec<_>>();
+
+                for peer in watched_peers {
+                    let status = if connection_stats.connected_peers.contains(&peer) {
+                        ConnectionStatus::Online
+                    } else {
+                        ConnectionStatus::Offline { reason: None }
+                    };
+                    self.send_status(peer, status);
+                }
             }
             Err(e) => {
                 tracing::error!(
@@ -83,10 +127,8 @@ impl Actor {
             "Adding newly established connection to online_status: {:?}",
             msg.peer
         );
-        if msg.peer == self.watched_peer {
-            self.sender
-                .send(ConnectionStatus::Online)
-                .expect("Receiver to outlive this actor");
+        if self.is_watched(&msg.peer) {
+            self.send_status(msg.peer, ConnectionStatus::Online);
         }
     }
 
@@ -96,10 +138,38 @@ impl Actor {
             msg.peer
         );
 
+        if self.is_watched(&msg.peer) {
+            self.send_status(msg.peer, ConnectionStatus::Offline { reason: None });
+        }
+
         if msg.peer == self.watched_peer {
-            self.sender
-                .send(ConnectionStatus::Offline { reason: None })
+            self.compatibility_sender
+                .send(None)
                 .expect("Receiver to outlive this actor");
         }
     }
+
+    async fn handle_peer_info_received(&mut self, msg: PeerInfoReceived) {
+        if msg.peer != self.watched_peer {
+            return;
+        }
+
+        let compatibility = identify::assess(&msg.info, identify::TAKER_REQUIRED_MAKER_PROTOCOLS);
+
+        tracing::debug!(
+            peer_version = %msg.info.daemon_version,
+            ?compatibility,
+            "Assessed compatibility of watched peer"
+        );
+
+        self.compatibility_sender
+            .send(Some(compatibility))
+            .expect("Receiver to outlive this actor");
+    }
+
+    async fn handle_latest_address_stats(&mut self, msg: LatestAddressStats) {
+        self.address_stats_sender
+            .send(msg.0)
+            .expect("Receiver to outlive this actor");
+    }
 }
@@ -8,7 +8,10 @@ use model::olivia;
 use model::olivia::next_announcement_after;
 use model::olivia::BitMexPriceEventId;
 use model::CfdEvent;
+use model::ContractType;
 use model::EventKind;
+use model::MAX_SETTLEMENT_INTERVAL;
+use model::SETTLEMENT_INTERVAL;
 use sqlite_db;
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -39,26 +42,35 @@ pub struct Actor {
     tasks: Tasks,
     db: sqlite_db::Connection,
     client: reqwest::Client,
+    /// The largest settlement interval of the perpetual contracts we offered or took.
+    settlement_interval: Duration,
 }
 
-/// We want to fetch at least this much announcements into the future
+/// How many announcements we fetch beyond the largest settlement interval in use
 ///
 /// For a rollover to happen successfully we need to know the oracle announcement details.
 /// Our actor is checking if a new announcement can be fetched every SYNC_ANNOUNCEMENTS_INTERVAL and
-/// ANNOUNCEMENT_LOOKAHEAD hours into the future. Given we rollover every for
-/// hour model::SETTLEMENT_INTERVAL into the future, we want to have at least
-/// model::SETTLEMENT_INTERVAL announcements ready. Due to sync interval coincidence, it might
+/// [`announcement_lookahead`] hours into the future. Given we rollover every hour up to the
+/// settlement interval of a CFD into the future, we want to have at least as many announcements
+/// ready as the largest settlement interval in use. Due to sync interval coincidence, it might
 /// happen that we do not have synced for a specific announcement yet. Hence, we need to fetch more
-/// announcements. We fetch model::SETTLEMENT_INTERVAL + 2 announcement into the future because of
-/// this example:
+/// announcements. We fetch 2 more announcements because of this example:
 ///
 /// Assume the last fetch was at 01.01.2022 00:59:55, i.e. 5 seconds before midnight and we would
-/// have synced for model::SETTLEMENT_INTERVAL+1 we would have synced announcements until 02.01.2022
-/// 01:00:00. A rollover request happening exactly at 01.01.2022 01:00:00 would ask for the
-/// announcement at 02.01.2022 02:00:00 because of how olivia::next_announcement_after works. Note:
-/// even if the underlying logic of olivia::next_announcement_after changes, fetching model::
-/// SETTLEMENT_INTERVAL + 2 won't hurt.
-const ANNOUNCEMENT_LOOKAHEAD: Duration = Duration::hours(26);
+/// have synced for a settlement interval of 24+1 hours we would have synced announcements until
+/// 02.01.2022 01:00:00. A rollover request happening exactly at 01.01.2022 01:00:00 would ask for
+/// the announcement at 02.01.2022 02:00:00 because of how olivia::next_announcement_after works.
+/// Note: even if the underlying logic of olivia::next_announcement_after changes, fetching
+/// 2 more won't hurt.
+const ANNOUNCEMENT_LOOKAHEAD_MARGIN: Duration = Duration::hours(2);
+
+/// How far into the future we fetch announcements, given the largest settlement interval in use.
+///
+/// Settlement intervals beyond [`MAX_SETTLEMENT_INTERVAL`] are never used for perpetual contracts,
+/// so we do not fetch announcements for them either.
+fn announcement_lookahead(settlement_interval: Duration) -> Duration {
+    settlement_interval.min(MAX_SETTLEMENT_INTERVAL) + ANNOUNCEMENT_LOOKAHEAD_MARGIN
+}
 
 #[derive(Clone, Copy)]
 pub struct SyncAnnouncements;
@@ -74,16 +86,33 @@ pub struct MonitorAttestation {
 #[derive(Clone)]
 struct MonitorAttestations {
     pub event_ids: Vec<BitMexPriceEventId>,
+    pub settlement_intervals: Vec<Duration>,
 }
 
 /// Message used to request the `Announcement` from the
 /// `oracle::Actor`'s local state.
 ///
 /// The `Announcement` corresponds to the [`BitMexPriceEventId`] included in
-/// the message.
+/// the message. Announcements that have not been synced yet are fetched in the background, so
+/// asking again later may succeed.
 #[derive(Clone, Copy)]
 pub struct GetAnnouncement(pub BitMexPriceEventId);
 
+/// Sync the announcement of the given event ahead of time.
+///
+/// Needed for announcements beyond [`announcement_lookahead`], e.g. the ones fixed-expiry
+/// contracts settle on, so that they are available once [`GetAnnouncement`] asks for them.
+#[derive(Clone, Copy)]
+pub struct SyncAnnouncement(pub BitMexPriceEventId);
+
+/// Sync announcements far enough into the future to roll over perpetual contracts with the given
+/// settlement interval.
+///
+/// Should be sent for every settlement interval offered or taken. Intervals shorter than the
+/// largest one in use have no effect.
+#[derive(Clone, Copy)]
+pub struct SettlementIntervalInUse(pub Duration);
+
 #[derive(Debug, Clone)]
 pub struct Attestation(olivia::Attestation);
 
@@ -105,6 +134,8 @@ struct NewAttestationFetched {
 #[derive(Default, Clone)]
 struct Cfd {
     pending_attestation: Option<BitMexPriceEventId>,
+    /// The settlement interval of perpetual contracts, which are rolled over
+    settlement_interval: Option<Duration>,
     version: u32,
 }
 
@@ -136,8 +167,16 @@ impl Cfd {
 impl sqlite_db::CfdAggregate for Cfd {
     type CtorArgs = ();
 
-    fn new(_: Self::CtorArgs, _: sqlite_db::Cfd) -> Self {
-        Self::default()
+    fn new(_: Self::CtorArgs, cfd: sqlite_db::Cfd) -> Self {
+        let settlement_interval = match cfd.contract_type {
+            ContractType::Perpetual => Some(cfd.settlement_interval),
+            ContractType::FixedExpiry => None,
+        };
+
+        Self {
+            settlement_interval,
+            ..Self::default()
+        }
     }
 
     fn apply(self, event: CfdEvent) -> Self {
@@ -158,56 +197,44 @@ impl Actor {
             tasks: Tasks::default(),
             db,
             client: reqwest::Client::new(),
+            settlement_interval: SETTLEMENT_INTERVAL,
         }
     }
 
     fn ensure_having_announcements(&mut self, ctx: &mut xtra::Context<Self>) {
-        for hour in 1..ANNOUNCEMENT_LOOKAHEAD.whole_hours() {
+        for hour in 1..announcement_lookahead(self.settlement_interval).whole_hours() {
             let event_id =
                 next_announcement_after(OffsetDateTime::now_utc() + Duration::hours(hour));
 
-            if self.announcements.get(&event_id).is_some() {
-                continue;
-            }
-            let this = ctx.address().expect("self to be alive");
-            let client = self.client.clone();
-
-            self.tasks.add_fallible(
-                async move {
-                    let url = event_id.to_olivia_url();
-
-                    tracing::debug!(event_id = %event_id, "Fetching announcement");
-
-                    let response = client
-                        .get(url.clone())
-                        .send()
-                        .await
-                        .with_context(|| format!("Failed to GET {url}"))?;
-
-                    let code = response.status();
-                    if !code.is_success() {
-                        anyhow::bail!("GET {url} responded with {code}");
-                    }
-
-                    let announcement = response
-                        .json::<olivia::Announcement>()
-                        .await
-                        .context("Failed to deserialize as Announcement")?;
-
-                    this.send(NewAnnouncementFetched {
-                        id: event_id,
-                        nonce_pks: announcement.nonce_pks,
-                        expected_outcome_time: announcement.expected_outcome_time,
-                    })
-                    .await?;
+            self.sync_announcement(event_id, ctx);
+        }
+    }
 
-                    Ok(())
-                },
-                |e| async move {
-                    tracing::debug!("Failed to fetch announcement: {:#}", e);
-                },
-            );
+    /// Fetch the announcement of `event_id` in the background, unless we already have it.
+    fn sync_announcement(&mut self, event_id: BitMexPriceEventId, ctx: &mut xtra::Context<Self>) {
+        if self.announcements.get(&event_id).is_some() {
+            return;
         }
+        let this = ctx.address().expect("self to be alive");
+        let client = self.client.clone();
+
+        self.tasks.add_fallible(
+            async move {
+                let announcement = fetch_announcement(&client, event_id).await?;
+
+                this.send(NewAnnouncementFetched {
+                    id: event_id,
+                    nonce_pks: announcement.nonce_pks,
+                    expected_outcome_time: announcement.expected_outcome_time,
+                })
+                .await?;
+
+                Ok(())
+            },
+            |e| async move {
+                tracing::debug!("Failed to fetch announcement: {:#}", e);
+            },
+        );
     }
 
     fn update_pending_attestations(&mut self, ctx: &mut xtra::Context<Self>) {
@@ -259,6 +286,21 @@ impl Actor {
         }
     }
 
+    fn use_settlement_interval(
+        &mut self,
+        settlement_interval: Duration,
+        ctx: &mut xtra::Context<Self>,
+    ) {
+        if settlement_interval <= self.settlement_interval {
+            return;
+        }
+
+        tracing::debug!(%settlement_interval, "Syncing announcements for larger settlement interval");
+
+        self.settlement_interval = settlement_interval;
+        self.ensure_having_announcements(ctx);
+    }
+
     fn add_pending_attestation(&mut self, event_id: BitMexPriceEventId) {
         if !self.pending_attestations.insert(event_id) {
             tracing::trace!("Attestation for {event_id} already being monitored");
@@ -272,24 +314,50 @@ impl Actor {
         self.add_pending_attestation(msg.event_id)
     }
 
-    fn handle_monitor_attestations(&mut self, msg: MonitorAttestations) {
+    fn handle_monitor_attestations(
+        &mut self,
+        msg: MonitorAttestations,
+        ctx: &mut xtra::Context<Self>,
+    ) {
         for id in msg.event_ids.into_iter() {
             self.add_pending_attestation(id);
         }
+
+        if let Some(settlement_interval) = msg.settlement_intervals.into_iter().max() {
+            self.use_settlement_interval(settlement_interval, ctx);
+        }
+    }
+
+    fn handle_settlement_interval_in_use(
+        &mut self,
+        msg: SettlementIntervalInUse,
+        ctx: &mut xtra::Context<Self>,
+    ) {
+        self.use_settlement_interval(msg.0, ctx);
     }
 
     fn handle_get_announcement(
         &mut self,
         msg: GetAnnouncement,
+        ctx: &mut xtra::Context<Self>,
     ) -> Result<olivia::Announcement, NoAnnouncement> {
-        self.announcements
-            .get_key_value(&msg.0)
-            .map(|(id, (time, nonce_pks))| olivia::Announcement {
-                id: *id,
+        let event_id = msg.0;
+
+        match self.announcements.get(&event_id) {
+            Some((time, nonce_pks)) => Ok(olivia::Announcement {
+                id: event_id,
                 expected_outcome_time: *time,
                 nonce_pks: nonce_pks.clone(),
-            })
-            .ok_or(NoAnnouncement(msg.0))
+            }),
+            None => {
+                self.sync_announcement(event_id, ctx);
+                Err(NoAnnouncement(event_id))
+            }
+        }
+    }
+
+    fn handle_sync_announcement(&mut self, msg: SyncAnnouncement, ctx: &mut xtra::Context<Self>) {
+        self.sync_announcement(msg.0, ctx);
     }
 
     fn handle_new_announcement_fetched(&mut self, msg: NewAnnouncementFetched) {
@@ -326,6 +394,34 @@ impl Actor {
     }
 }
 
+async fn fetch_announcement(
+    client: &reqwest::Client,
+    event_id: BitMexPriceEventId,
+) -> Result<olivia::Announcement> {
+    let url = event_id.to_olivia_url();
+
+    tracing::debug!(event_id = %event_id, "Fetching announcement");
+
+    let response = client
+        .get(url.clone())
+        .timeout(REQWEST_TIMEOUT)
+        .send()
+        .await
+        .with_context(|| format!("Failed to GET {url}"))?;
+
+    let code = response.status();
+    if !code.is_success() {
+        anyhow::bail!("GET {url} responded with {code}");
+    }
+
+    let announcement = response
+        .json::<olivia::Announcement>()
+        .await
+        .context("Failed to deserialize as Announcement")?;
+
+    Ok(announcement)
+}
+
 #[derive(Debug, Clone, thiserror::Error, Copy)]
 #[error("Announcement {0} not found")]
 pub struct NoAnnouncement(pub BitMexPriceEventId);
@@ -343,14 +439,11 @@ impl xtra::Actor for Actor {
         self.tasks.add({
             let db = self.db.clone();
             async move {
-                let pending_attestations = db
+                let cfds = db
                     .load_all_open_cfds::<Cfd>(())
                     .filter_map(|res| async move {
                         match res {
-                            Ok(Cfd {
-                                pending_attestation,
-                                ..
-                            }) => pending_attestation,
+                            Ok(cfd) => Some(cfd),
                             Err(e) => {
                                 tracing::warn!("Failed to load CFD from database: {e:#}");
                                 None
@@ -362,7 +455,14 @@ impl xtra::Actor for Actor {
 
                 let _: Result<(), xtra::Error> = this
                     .send(MonitorAttestations {
-                        event_ids: pending_attestations,
+                        event_ids: cfds
+                            .iter()
+                            .filter_map(|cfd| cfd.pending_attestation)
+                            .collect(),
+                        settlement_intervals: cfds
+                            .iter()
+                            .filter_map(|cfd| cfd.settlement_interval)
+                            .collect(),
                     })
                     .await;
 
@@ -395,12 +495,29 @@ impl Attestation {
 
 #[cfg(test)]
 pub mod tests {
+    use super::*;
+
+    #[test]
+    fn lookahead_covers_settlement_interval_in_use() {
+        assert_eq!(
+            announcement_lookahead(SETTLEMENT_INTERVAL),
+            SETTLEMENT_INTERVAL + Duration::hours(2)
+        );
+        assert_eq!(
+            announcement_lookahead(Duration::hours(6)),
+            Duration::hours(8)
+        );
+    }
+
     #[test]
-    fn ensure_lookahead_constant() {
-        use time::Duration;
+    fn lookahead_is_capped_at_max_settlement_interval() {
+        assert_eq!(
+            announcement_lookahead(MAX_SETTLEMENT_INTERVAL),
+            MAX_SETTLEMENT_INTERVAL + Duration::hours(2)
+        );
         assert_eq!(
-            crate::oracle::ANNOUNCEMENT_LOOKAHEAD,
-            model::SETTLEMENT_INTERVAL + Duration::hours(2)
+            announcement_lookahead(MAX_SETTLEMENT_INTERVAL + Duration::days(7)),
+            MAX_SETTLEMENT_INTERVAL + Duration::hours(2)
         );
     }
 }
@@ -0,0 +1,327 @@
+use crate::projection;
+use crate::projection::CfdState;
+use async_trait::async_trait;
+use bdk::bitcoin::Amount;
+use bdk::bitcoin::SignedAmount;
+use serde::Serialize;
+use sqlite_db::pnl_snapshots::EquityPoint;
+use sqlite_db::pnl_snapshots::PnlSnapshot;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::watch;
+use tokio_tasks::Tasks;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+/// How often we take a snapshot of the open CFDs.
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often we delete snapshots which are older than the retention period.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Records the mark-to-market profit, accumulated fees and margin of all open CFDs, so that we
+/// can show how the exposure evolved over time.
+///
+/// The snapshots are taken from the CFDs of the projection, i.e. at the latest quote of the price
+/// feed. CFDs whose profit cannot be computed at the time, e.g. because there is no quote yet,
+/// are skipped.
+pub struct Actor {
+    db: sqlite_db::Connection,
+    cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+    /// Snapshots older than this are deleted, unless it is `None`.
+    retention: Option<time::Duration>,
+    tasks: Tasks,
+}
+
+impl Actor {
+    pub fn new(
+        db: sqlite_db::Connection,
+        cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+        retention: Option<time::Duration>,
+    ) -> Self {
+        Self {
+            db,
+            cfds,
+            retention,
+            tasks: Tasks::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+
+        self.tasks.add(
+            this.clone()
+                .send_interval(SNAPSHOT_INTERVAL, || TakeSnapshot),
+        );
+        self.tasks
+            .add(this.send_interval(PRUNE_INTERVAL, || PruneSnapshots));
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: TakeSnapshot) {
+        let timestamp = OffsetDateTime::now_utc();
+
+        let snapshots = match self.cfds.borrow().as_ref() {
+            Some(cfds) => cfds
+                .iter()
+                .filter_map(|cfd| snapshot(cfd, timestamp))
+                .collect::<Vec<_>>(),
+            None => {
+                tracing::debug!("Skipping PnL snapshot until CFDs are loaded");
+                return;
+            }
+        };
+
+        if snapshots.is_empty() {
+            return;
+        }
+
+        match self.db.insert_pnl_snapshots(&snapshots).await {
+            Ok(()) => tracing::trace!(cfds = snapshots.len(), "Recorded PnL snapshot"),
+            Err(e) => tracing::warn!("Failed to record PnL snapshot: {e:#}"),
+        }
+    }
+
+    async fn handle(&mut self, _: PruneSnapshots) {
+        let retention = match self.retention {
+            Some(retention) => retention,
+            None => return,
+        };
+
+        match self
+            .db
+            .delete_pnl_snapshots_before(OffsetDateTime::now_utc() - retention)
+            .await
+        {
+            Ok(0) => {}
+            Ok(deleted) => {
+                tracing::debug!(%deleted, "Deleted PnL snapshots past retention period")
+            }
+            Err(e) => tracing::warn!("Failed to delete old PnL snapshots: {e:#}"),
+        }
+    }
+}
+
+/// Private message to take a snapshot of the open CFDs.
+struct TakeSnapshot;
+
+/// Private message to delete snapshots which are older than the retention period.
+struct PruneSnapshots;
+
+fn snapshot(cfd: &projection::Cfd, timestamp: OffsetDateTime) -> Option<PnlSnapshot> {
+    if !has_exposure(cfd.state) {
+        return None;
+    }
+
+    Some(PnlSnapshot {
+        order_id: cfd.order_id,
+        timestamp,
+        profit: cfd.profit_btc?,
+        accumulated_fees: cfd.accumulated_fees,
+        margin: cfd.margin,
+    })
+}
+
+/// Whether the outcome of a CFD in the given state still depends on the price.
+///
+/// Once the closing transaction is published, i.e. the CFD is pending close, its payout is fixed.
+fn has_exposure(state: CfdState) -> bool {
+    match state {
+        CfdState::PendingOpen
+        | CfdState::Open
+        | CfdState::PendingCommit
+        | CfdState::PendingCet
+        | CfdState::OpenCommitted
+        | CfdState::IncomingSettlementProposal
+        | CfdState::OutgoingSettlementProposal
+        | CfdState::IncomingRolloverProposal
+        | CfdState::OutgoingRolloverProposal
+        | CfdState::RolloverSetup => true,
+        CfdState::PendingSetup
+        | CfdState::ContractSetup
+        | CfdState::PendingClose
+        | CfdState::Rejected
+        | CfdState::Closed
+        | CfdState::PendingRefund
+        | CfdState::Refunded
+        | CfdState::SetupFailed => false,
+    }
+}
+
+/// A snapshot of a single CFD as exposed through the API.
+#[derive(Debug, Clone, Serialize)]
+pub struct PnlPoint {
+    /// Seconds since the epoch.
+    pub timestamp: i64,
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    pub profit: SignedAmount,
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    pub accumulated_fees: SignedAmount,
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    pub margin: Amount,
+}
+
+impl From<PnlSnapshot> for PnlPoint {
+    fn from(snapshot: PnlSnapshot) -> Self {
+        Self {
+            timestamp: snapshot.timestamp.unix_timestamp(),
+            profit: snapshot.profit,
+            accumulated_fees: snapshot.accumulated_fees,
+            margin: snapshot.margin,
+        }
+    }
+}
+
+/// The value of all open CFDs at a point in time, as exposed through the API.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortfolioPoint {
+    /// Seconds since the epoch.
+    pub timestamp: i64,
+    /// The margin locked in the open CFDs plus their profit, i.e. what we would get if we closed
+    /// all of them at the time.
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    pub equity: SignedAmount,
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    pub profit: SignedAmount,
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    pub accumulated_fees: SignedAmount,
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    pub margin: Amount,
+    pub positions: u32,
+}
+
+impl From<EquityPoint> for PortfolioPoint {
+    fn from(point: EquityPoint) -> Self {
+        let margin = point
+            .margin
+            .to_signed()
+            .expect("Amount to fit into signed amount");
+
+        Self {
+            timestamp: point.timestamp.unix_timestamp(),
+            equity: margin + point.profit,
+            profit: point.profit,
+            accumulated_fees: point.accumulated_fees,
+            margin: point.margin,
+            positions: point.positions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::ContractType;
+    use model::FundingRate;
+    use model::Leverage;
+    use model::OpeningFee;
+    use model::OrderId;
+    use model::Position;
+    use model::Price;
+    use model::Role;
+    use model::TxFeeRate;
+    use model::Usd;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn cfds_with_fixed_payout_have_no_exposure() {
+        assert!(has_exposure(CfdState::Open));
+        assert!(has_exposure(CfdState::OutgoingSettlementProposal));
+        assert!(!has_exposure(CfdState::PendingClose));
+        assert!(!has_exposure(CfdState::Closed));
+        assert!(!has_exposure(CfdState::ContractSetup));
+    }
+
+    #[tokio::test]
+    async fn snapshot_of_open_cfd_with_profit() {
+        let (_, cfd) = open_cfd(Some(SignedAmount::from_sat(1_000))).await;
+        let timestamp = OffsetDateTime::now_utc();
+
+        let snapshot = snapshot(&cfd, timestamp).unwrap();
+
+        assert_eq!(snapshot.order_id, cfd.order_id);
+        assert_eq!(snapshot.timestamp, timestamp);
+        assert_eq!(snapshot.profit, SignedAmount::from_sat(1_000));
+        assert_eq!(snapshot.margin, cfd.margin);
+    }
+
+    #[tokio::test]
+    async fn no_snapshot_without_profit() {
+        let (_, cfd) = open_cfd(None).await;
+
+        assert_eq!(snapshot(&cfd, OffsetDateTime::now_utc()), None);
+    }
+
+    #[tokio::test]
+    async fn no_snapshot_of_pending_close_cfd() {
+        let (_, mut cfd) = open_cfd(Some(SignedAmount::from_sat(1_000))).await;
+        cfd.state = CfdState::PendingClose;
+
+        assert_eq!(snapshot(&cfd, OffsetDateTime::now_utc()), None);
+    }
+
+    #[tokio::test]
+    async fn records_snapshots_and_prunes_them_after_retention_period() {
+        let (db, cfd) = open_cfd(Some(SignedAmount::from_sat(1_000))).await;
+        let (_cfds_sender, cfds) = watch::channel(Some(vec![cfd.clone()]));
+        let mut actor = Actor::new(db.clone(), cfds, Some(time::Duration::days(1)));
+        let (_, mut context) = xtra::Context::new(None);
+
+        let old = PnlSnapshot {
+            timestamp: OffsetDateTime::now_utc() - time::Duration::days(2),
+            ..snapshot(&cfd, OffsetDateTime::now_utc()).unwrap()
+        };
+        db.insert_pnl_snapshots(&[old]).await.unwrap();
+
+        xtra::Handler::handle(&mut actor, TakeSnapshot, &mut context).await;
+        assert_eq!(db.load_pnl_snapshots(cfd.order_id).await.unwrap().len(), 2);
+
+        xtra::Handler::handle(&mut actor, PruneSnapshots, &mut context).await;
+        let snapshots = db.load_pnl_snapshots(cfd.order_id).await.unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert!(snapshots[0].timestamp > old.timestamp);
+    }
+
+    async fn open_cfd(
+        profit_btc: Option<SignedAmount>,
+    ) -> (sqlite_db::Connection, projection::Cfd) {
+        let db = sqlite_db::memory().await.unwrap();
+        let cfd = model::Cfd::new(
+            OrderId::default(),
+            Position::Long,
+            Price::new(dec!(20_000)).unwrap(),
+            Leverage::TWO,
+            time::Duration::hours(24),
+            Role::Taker,
+            Usd::new(dec!(1_000)),
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"
+                .parse()
+                .unwrap(),
+            None,
+            OpeningFee::new(Amount::from_sat(2000)),
+            FundingRate::default(),
+            TxFeeRate::default(),
+            ContractType::Perpetual,
+        );
+        db.insert_cfd(&cfd).await.unwrap();
+
+        let mut cfd = db
+            .load_open_cfd::<projection::Cfd>(cfd.id(), bdk::bitcoin::Network::Testnet)
+            .await
+            .unwrap();
+        cfd.state = CfdState::Open;
+        cfd.profit_btc = profit_btc;
+
+        (db, cfd)
+    }
+}
@@ -219,6 +219,7 @@ impl Cfd {
                 state: AggregatedState::Closed,
                 ..self
             },
+            AutoAccepted { .. } => self,
             ManualCommit { .. } | CommitConfirmed => Self {
                 // we don't know yet if the position will be closed immediately (e.g. through
                 // punishing) or a bit later after the oracle has attested to the price
@@ -0,0 +1,577 @@
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlite_db::quotes::Quote;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio_tasks::Tasks;
+use xtra::message_channel::MessageChannel;
+use xtra_bitmex_price_feed::LatestQuote;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+/// How often we record the latest quote of the price feed.
+///
+/// The price feed publishes one quote per minute; recording more often makes sure we do not miss
+/// any, quotes we already have are skipped.
+const RECORD_INTERVAL: Duration = Duration::from_secs(20);
+
+/// How often we delete quotes which are older than the retention period.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Gaps longer than this are only filled for the most recent part.
+const MAX_BACKFILL: time::Duration = time::Duration::days(7);
+
+/// The maximum number of quotes BitMEX returns per request.
+const BITMEX_PAGE_SIZE: u32 = 1000;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Records the quotes of the price feed, so that we can show how the price evolved.
+///
+/// Gaps in the recorded quotes, e.g. while the daemon was not running, are filled from the
+/// `history` on startup.
+pub struct Actor {
+    db: sqlite_db::Connection,
+    price_feed: MessageChannel<LatestQuote, Option<xtra_bitmex_price_feed::Quote>>,
+    history: Option<Arc<dyn History>>,
+    /// Quotes older than this are deleted, unless it is `None`.
+    retention: Option<time::Duration>,
+    tasks: Tasks,
+}
+
+impl Actor {
+    pub fn new(
+        db: sqlite_db::Connection,
+        price_feed: MessageChannel<LatestQuote, Option<xtra_bitmex_price_feed::Quote>>,
+        history: Option<Arc<dyn History>>,
+        retention: Option<time::Duration>,
+    ) -> Self {
+        Self {
+            db,
+            price_feed,
+            history,
+            retention,
+            tasks: Tasks::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+
+        if let Some(history) = self.history.clone() {
+            let db = self.db.clone();
+            let retention = self.retention;
+
+            self.tasks.add_fallible(
+                async move { backfill(db, history, retention).await },
+                |e| async move { tracing::warn!("Failed to backfill price history: {e:#}") },
+            );
+        }
+
+        self.tasks
+            .add(this.clone().send_interval(RECORD_INTERVAL, || RecordQuote));
+        self.tasks
+            .add(this.send_interval(PRUNE_INTERVAL, || PruneQuotes));
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: RecordQuote) {
+        let quote = match self.price_feed.send(LatestQuote).await {
+            Ok(Some(quote)) => quote,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::debug!("Price feed not available: {e:#}");
+                return;
+            }
+        };
+
+        let quote = Quote {
+            timestamp: quote.timestamp,
+            bid: quote.bid,
+            ask: quote.ask,
+        };
+        if let Err(e) = self.db.insert_quotes(&[quote]).await {
+            tracing::warn!("Failed to record quote: {e:#}");
+        }
+    }
+
+    async fn handle(&mut self, _: PruneQuotes) {
+        let retention = match self.retention {
+            Some(retention) => retention,
+            None => return,
+        };
+
+        match self
+            .db
+            .delete_quotes_before(OffsetDateTime::now_utc() - retention)
+            .await
+        {
+            Ok(0) => {}
+            Ok(deleted) => tracing::debug!(%deleted, "Deleted quotes past retention period"),
+            Err(e) => tracing::warn!("Failed to delete old quotes: {e:#}"),
+        }
+    }
+}
+
+/// Private message to record the latest quote.
+struct RecordQuote;
+
+/// Private message to delete quotes which are older than the retention period.
+struct PruneQuotes;
+
+/// Fill the gap between the latest recorded quote and now.
+///
+/// Without any recorded quotes there is no gap to fill.
+async fn backfill(
+    db: sqlite_db::Connection,
+    history: Arc<dyn History>,
+    retention: Option<time::Duration>,
+) -> Result<()> {
+    let latest = match db.load_latest_quote_timestamp().await? {
+        Some(latest) => latest,
+        None => return Ok(()),
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let earliest = now - retention.map_or(MAX_BACKFILL, |retention| retention.min(MAX_BACKFILL));
+    let mut from = (latest + time::Duration::MINUTE).max(earliest);
+
+    let mut backfilled = 0;
+    while from < now - time::Duration::MINUTE {
+        let quotes = history.quotes_since(from).await?;
+        let last = match quotes.last() {
+            Some(last) if last.timestamp >= from => last.timestamp,
+            _ => break,
+        };
+
+        backfilled += db.insert_quotes(&quotes).await?;
+        from = last + time::Duration::SECOND;
+    }
+
+    if backfilled > 0 {
+        tracing::info!(%backfilled, source = %history.name(), "Backfilled price history");
+    }
+
+    Ok(())
+}
+
+/// A source of past quotes to fill gaps in the price history.
+#[async_trait]
+pub trait History: Send + Sync + 'static {
+    /// Identifies the source in the logs.
+    fn name(&self) -> String;
+
+    /// A batch of consecutive quotes starting at `from`, oldest first.
+    ///
+    /// Returns no quotes if there are none since `from`.
+    async fn quotes_since(&self, from: OffsetDateTime) -> Result<Vec<Quote>>;
+}
+
+/// The one minute quote bins of BitMEX's XBTUSD perpetual swap, the same the price feed uses.
+pub struct Bitmex {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Bitmex {
+    pub fn new(network: xtra_bitmex_price_feed::Network) -> Self {
+        let url = format!("https://{}/api/v1/quote/bucketed", network.to_url());
+
+        Self {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BucketedQuote {
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+    #[serde(with = "rust_decimal::serde::float")]
+    bid_price: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    ask_price: Decimal,
+}
+
+#[async_trait]
+impl History for Bitmex {
+    fn name(&self) -> String {
+        "BitMEX XBTUSD".to_owned()
+    }
+
+    async fn quotes_since(&self, from: OffsetDateTime) -> Result<Vec<Quote>> {
+        let start_time = from.format(&Rfc3339)?;
+        let count = BITMEX_PAGE_SIZE.to_string();
+
+        let quotes = self
+            .client
+            .get(&self.url)
+            .query(&[
+                ("binSize", "1m"),
+                ("symbol", "XBTUSD"),
+                ("partial", "false"),
+                ("startTime", start_time.as_str()),
+                ("count", count.as_str()),
+            ])
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .with_context(|| format!("Failed to GET {}", self.url))?
+            .error_for_status()?
+            .json::<Vec<BucketedQuote>>()
+            .await
+            .context("Failed to deserialize quotes")?;
+
+        Ok(quotes
+            .into_iter()
+            .map(|quote| Quote {
+                timestamp: quote.timestamp,
+                bid: quote.bid_price,
+                ask: quote.ask_price,
+            })
+            .collect())
+    }
+}
+
+/// The length of the period a [`Candle`] covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interval {
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "1d")]
+    OneDay,
+}
+
+impl Interval {
+    pub fn duration(&self) -> time::Duration {
+        match self {
+            Interval::OneMinute => time::Duration::MINUTE,
+            Interval::OneHour => time::Duration::HOUR,
+            Interval::OneDay => time::Duration::DAY,
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let interval = match s {
+            "1m" => Interval::OneMinute,
+            "1h" => Interval::OneHour,
+            "1d" => Interval::OneDay,
+            _ => bail!("Unknown interval {s}, expected one of 1m, 1h or 1d"),
+        };
+
+        Ok(interval)
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Interval::OneMinute => "1m",
+            Interval::OneHour => "1h",
+            Interval::OneDay => "1d",
+        };
+
+        s.fmt(f)
+    }
+}
+
+/// The open, high, low and close mid price of BTC/USD over one [`Interval`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Candle {
+    /// The start of the period, in seconds since the epoch.
+    pub timestamp: i64,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub open: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub high: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub low: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub close: Decimal,
+}
+
+/// Load the candles of the given interval in `[from, to)`, oldest first.
+///
+/// Periods without any quotes have no candle.
+pub async fn load_candles(
+    db: &sqlite_db::Connection,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    interval: Interval,
+) -> Result<Vec<Candle>> {
+    let candles = db
+        .load_candles(from, to, interval.duration())
+        .await?
+        .into_iter()
+        .map(|candle| Candle {
+            timestamp: candle.start.unix_timestamp(),
+            open: candle.open,
+            high: candle.high,
+            low: candle.low,
+            close: candle.close,
+        })
+        .collect();
+
+    Ok(candles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn aggregates_quotes_into_candles_of_mid_price() {
+        let db = sqlite_db::memory().await.unwrap();
+        db.insert_quotes(&[
+            dummy_quote(1657065600, dec!(20000)),
+            dummy_quote(1657065660, dec!(20100)),
+            dummy_quote(1657065720, dec!(19900)),
+            dummy_quote(1657065780, dec!(20050)),
+            dummy_quote(1657069200, dec!(21000)),
+        ])
+        .await
+        .unwrap();
+
+        let candles = load_candles(&db, at(1657065600), at(1657072800), Interval::OneHour)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            candles,
+            vec![
+                Candle {
+                    timestamp: 1657065600,
+                    open: dec!(20000.5),
+                    high: dec!(20100.5),
+                    low: dec!(19900.5),
+                    close: dec!(20050.5),
+                },
+                Candle {
+                    timestamp: 1657069200,
+                    open: dec!(21000.5),
+                    high: dec!(21000.5),
+                    low: dec!(21000.5),
+                    close: dec!(21000.5),
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn candles_start_at_beginning_of_period() {
+        let db = sqlite_db::memory().await.unwrap();
+        db.insert_quotes(&[dummy_quote(1657065659, dec!(20000))])
+            .await
+            .unwrap();
+
+        for interval in [Interval::OneMinute, Interval::OneDay] {
+            let candles = load_candles(&db, at(1657065600), at(1657065660), interval)
+                .await
+                .unwrap();
+
+            assert_eq!(candles[0].timestamp, 1657065600);
+        }
+    }
+
+    #[test]
+    fn parses_interval() {
+        for interval in [Interval::OneMinute, Interval::OneHour, Interval::OneDay] {
+            assert_eq!(interval.to_string().parse::<Interval>().unwrap(), interval);
+        }
+        assert!("1w".parse::<Interval>().is_err());
+    }
+
+    #[tokio::test]
+    async fn backfill_without_recorded_quotes_does_nothing() {
+        let db = sqlite_db::memory().await.unwrap();
+        let history = MockHistory::new(BITMEX_PAGE_SIZE as usize);
+
+        backfill(db.clone(), history.clone(), None).await.unwrap();
+
+        assert!(history.requests().is_empty());
+        assert_eq!(db.load_latest_quote_timestamp().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn backfill_fills_gap_up_to_now() {
+        let db = sqlite_db::memory().await.unwrap();
+        let latest = start_of_minute(OffsetDateTime::now_utc()) - time::Duration::minutes(10);
+        db.insert_quotes(&[quote_at(latest)]).await.unwrap();
+        let history = MockHistory::new(BITMEX_PAGE_SIZE as usize);
+
+        backfill(db.clone(), history.clone(), None).await.unwrap();
+
+        assert_eq!(
+            history.requests()[0],
+            latest + time::Duration::MINUTE,
+            "should continue right after the latest recorded quote"
+        );
+        assert_quotes_every_minute_since(&db, latest).await;
+    }
+
+    #[tokio::test]
+    async fn backfill_pages_through_history() {
+        let db = sqlite_db::memory().await.unwrap();
+        let latest = start_of_minute(OffsetDateTime::now_utc()) - time::Duration::minutes(10);
+        db.insert_quotes(&[quote_at(latest)]).await.unwrap();
+        let history = MockHistory::new(3);
+
+        backfill(db.clone(), history.clone(), None).await.unwrap();
+
+        let requests = history.requests();
+        assert!(
+            requests.len() >= 3,
+            "10 minutes need at least 3 pages of 3 quotes"
+        );
+        assert!(
+            requests.windows(2).all(|pair| pair[0] < pair[1]),
+            "every page should start after the previous one"
+        );
+        assert_quotes_every_minute_since(&db, latest).await;
+    }
+
+    #[tokio::test]
+    async fn backfill_is_limited_to_max_backfill() {
+        let db = sqlite_db::memory().await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        db.insert_quotes(&[quote_at(now - time::Duration::days(30))])
+            .await
+            .unwrap();
+        let history = MockHistory::new(0);
+
+        backfill(db, history.clone(), None).await.unwrap();
+
+        assert!(history.requests()[0] >= now - MAX_BACKFILL);
+    }
+
+    #[tokio::test]
+    async fn backfill_is_limited_to_retention_period() {
+        let db = sqlite_db::memory().await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        db.insert_quotes(&[quote_at(now - time::Duration::days(30))])
+            .await
+            .unwrap();
+        let history = MockHistory::new(0);
+        let retention = time::Duration::days(1);
+
+        backfill(db, history.clone(), Some(retention))
+            .await
+            .unwrap();
+
+        assert!(history.requests()[0] >= now - retention);
+    }
+
+    /// Assert that there is a quote for every minute from `since` up to the last complete minute.
+    async fn assert_quotes_every_minute_since(db: &sqlite_db::Connection, since: OffsetDateTime) {
+        let now = OffsetDateTime::now_utc();
+        let quotes = db.load_quotes(since, now).await.unwrap();
+
+        assert!(
+            quotes.len() >= 10,
+            "expected 10 quotes, got {}",
+            quotes.len()
+        );
+        assert!(
+            quotes
+                .windows(2)
+                .all(|pair| pair[1].timestamp - pair[0].timestamp == time::Duration::MINUTE),
+            "quotes should be one minute apart"
+        );
+    }
+
+    /// Serves a quote for every complete minute since `from`, at most `page_size` at once.
+    struct MockHistory {
+        page_size: usize,
+        requests: Mutex<Vec<OffsetDateTime>>,
+    }
+
+    impl MockHistory {
+        fn new(page_size: usize) -> Arc<Self> {
+            Arc::new(Self {
+                page_size,
+                requests: Mutex::new(Vec::new()),
+            })
+        }
+
+        /// The `from` of every call to [`History::quotes_since`].
+        fn requests(&self) -> Vec<OffsetDateTime> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl History for MockHistory {
+        fn name(&self) -> String {
+            "mock".to_owned()
+        }
+
+        async fn quotes_since(&self, from: OffsetDateTime) -> Result<Vec<Quote>> {
+            self.requests.lock().unwrap().push(from);
+
+            let end = start_of_minute(OffsetDateTime::now_utc());
+            let mut timestamp = start_of_minute(from + time::Duration::seconds(59));
+
+            let mut quotes = Vec::new();
+            while timestamp < end && quotes.len() < self.page_size {
+                quotes.push(quote_at(timestamp));
+                timestamp += time::Duration::MINUTE;
+            }
+
+            Ok(quotes)
+        }
+    }
+
+    fn start_of_minute(timestamp: OffsetDateTime) -> OffsetDateTime {
+        let seconds = timestamp.unix_timestamp();
+
+        at(seconds - seconds.rem_euclid(60))
+    }
+
+    fn quote_at(timestamp: OffsetDateTime) -> Quote {
+        Quote {
+            timestamp,
+            bid: dec!(20000),
+            ask: dec!(20001),
+        }
+    }
+
+    fn dummy_quote(timestamp: i64, bid: Decimal) -> Quote {
+        Quote {
+            timestamp: at(timestamp),
+            bid,
+            ask: bid + dec!(1),
+        }
+    }
+
+    fn at(timestamp: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(timestamp).unwrap()
+    }
+}
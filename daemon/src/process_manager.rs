@@ -1,9 +1,11 @@
+use crate::backup;
 use crate::monitor::MonitorCetFinality;
 use crate::monitor::MonitorCollaborativeSettlement;
 use crate::monitor::MonitorParams;
 use crate::monitor::StartMonitoring;
 use crate::monitor::TransactionKind;
 use crate::monitor::TryBroadcastTransaction;
+use crate::notifier;
 use crate::oracle;
 use crate::position_metrics;
 use crate::projection;
@@ -22,11 +24,13 @@ pub struct Actor {
     role: Role,
     cfds_changed: MessageChannel<projection::CfdChanged, ()>,
     cfd_changed_metrics: MessageChannel<position_metrics::CfdChanged, ()>,
+    notify: MessageChannel<notifier::Notify, ()>,
     try_broadcast_transaction: MessageChannel<TryBroadcastTransaction, Result<()>>,
     start_monitoring: MessageChannel<StartMonitoring, ()>,
     monitor_cet_finality: MessageChannel<MonitorCetFinality, Result<()>>,
     monitor_collaborative_settlement: MessageChannel<MonitorCollaborativeSettlement, ()>,
     monitor_attestation: MessageChannel<oracle::MonitorAttestation, ()>,
+    backup: Option<MessageChannel<backup::Backup, ()>>,
 }
 
 pub struct Event(CfdEvent);
@@ -44,22 +48,26 @@ impl Actor {
         role: Role,
         cfds_changed: MessageChannel<projection::CfdChanged, ()>,
         cfd_changed_metrics: MessageChannel<position_metrics::CfdChanged, ()>,
+        notify: MessageChannel<notifier::Notify, ()>,
         try_broadcast_transaction: MessageChannel<TryBroadcastTransaction, Result<()>>,
         start_monitoring: MessageChannel<StartMonitoring, ()>,
         monitor_cet_finality: MessageChannel<MonitorCetFinality, Result<()>>,
         monitor_collaborative_settlement: MessageChannel<MonitorCollaborativeSettlement, ()>,
         monitor_attestation: MessageChannel<oracle::MonitorAttestation, ()>,
+        backup: Option<MessageChannel<backup::Backup, ()>>,
     ) -> Self {
         Self {
             db,
             role,
             cfds_changed,
             cfd_changed_metrics,
+            notify,
             try_broadcast_transaction,
             start_monitoring,
             monitor_cet_finality,
             monitor_collaborative_settlement,
             monitor_attestation,
+            backup,
         }
     }
 }
@@ -71,9 +79,14 @@ impl Actor {
 
         // 1. Safe in DB
         self.db.append_event(event.clone()).await?;
+        let notification = notifier::Notification::new(&event);
 
         // 2. Post process event
         use EventKind::*;
+        let signed_new_transactions = matches!(
+            event.event,
+            ContractSetupCompleted { dlc: Some(_), .. } | RolloverCompleted { dlc: Some(_), .. }
+        );
         match event.event {
             ContractSetupCompleted { dlc: Some(dlc), .. } => {
                 let lock_tx = dlc.lock.0.clone();
@@ -228,7 +241,8 @@ impl Actor {
             | CollaborativeSettlementConfirmed
             | CollaborativeSettlementRejected
             | CollaborativeSettlementFailed
-            | CetTimelockExpiredPriorOracleAttestation => {}
+            | CetTimelockExpiredPriorOracleAttestation
+            | AutoAccepted { .. } => {}
         }
 
         // 3. Update UI
@@ -241,6 +255,16 @@ impl Actor {
             .send_async_safe(position_metrics::CfdChanged(event.id))
             .await?;
 
+        // 5. Notify operators
+        self.notify
+            .send_async_safe(notifier::Notify(notification))
+            .await?;
+
+        // 6. Back up the transactions we just signed
+        if let (true, Some(backup_actor)) = (signed_new_transactions, &self.backup) {
+            backup_actor.send_async_safe(backup::Backup).await?;
+        }
+
         Ok(())
     }
 }
@@ -23,6 +23,7 @@ use model::long_and_short_leverage;
 use model::market_closing_price;
 use model::CfdEvent;
 use model::ClosedCfd;
+use model::ContractType;
 use model::Dlc;
 use model::EventKind;
 use model::FailedCfd;
@@ -51,10 +52,13 @@ use serde::Serialize;
 use sqlite_db;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::sync::Arc;
 use std::time::Duration;
 use time::OffsetDateTime;
 use tokio::sync::watch;
 use tokio_tasks::Tasks;
+use utoipa::ToSchema;
 use xtra::prelude::MessageChannel;
 use xtra_productivity::xtra_productivity;
 use xtras::SendAsyncSafe;
@@ -85,6 +89,7 @@ pub struct Feeds {
     pub offers: watch::Receiver<MakerOffers>,
     pub connected_takers: watch::Receiver<Vec<model::Identity>>,
     pub cfds: watch::Receiver<Option<Vec<Cfd>>>,
+    pub cfd_feed: watch::Receiver<Option<CfdFeed>>,
 }
 
 impl Actor {
@@ -97,6 +102,7 @@ impl Actor {
         >,
     ) -> (Self, Feeds) {
         let (tx_cfds, rx_cfds) = watch::channel(None);
+        let (tx_cfd_feed, rx_cfd_feed) = watch::channel(None);
         let (tx_order, rx_order) = watch::channel(MakerOffers {
             long: None,
             short: None,
@@ -108,6 +114,7 @@ impl Actor {
             db,
             tx: Tx {
                 cfds: tx_cfds,
+                cfd_feed: tx_cfd_feed,
                 order: tx_order,
                 quote: tx_quote,
                 connected_takers: tx_connected_takers,
@@ -118,6 +125,7 @@ impl Actor {
         };
         let feeds = Feeds {
             cfds: rx_cfds,
+            cfd_feed: rx_cfd_feed,
             offers: rx_order,
             quote: rx_quote,
             connected_takers: rx_connected_takers,
@@ -127,7 +135,223 @@ impl Actor {
     }
 }
 
-#[derive(Derivative, Clone, Debug, Serialize)]
+/// The number of CFD deltas retained for clients resuming the feed.
+///
+/// Clients which missed more deltas than this receive all CFDs again.
+const CFD_DELTA_HISTORY: usize = 1000;
+
+/// A change to a single CFD, identified by a monotonically increasing event ID.
+#[derive(Debug, Clone)]
+pub struct CfdDelta {
+    pub event_id: u64,
+    pub change: CfdChange,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CfdChange {
+    /// The CFD was added or its state changed.
+    Updated(Cfd),
+    /// The CFD is no longer part of the feed.
+    Removed(OrderId),
+}
+
+/// The part of a CFD that depends on the latest quote.
+///
+/// It changes with every quote and is therefore published separately from the [`CfdDelta`]s,
+/// which would otherwise be dominated by price movements.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct CfdPnl {
+    pub order_id: OrderId,
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc::opt")]
+    #[schema(value_type = Option<f64>)]
+    pub profit_btc: Option<SignedAmount>,
+    pub profit_percent: Option<String>,
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc::opt")]
+    #[schema(value_type = Option<f64>)]
+    pub payout: Option<SignedAmount>,
+}
+
+impl From<&Cfd> for CfdPnl {
+    fn from(cfd: &Cfd) -> Self {
+        Self {
+            order_id: cfd.order_id,
+            profit_btc: cfd.profit_btc,
+            profit_percent: cfd.profit_percent.clone(),
+            payout: cfd.payout,
+        }
+    }
+}
+
+/// All CFDs together with the most recent changes to them.
+///
+/// Allows clients of the feed to catch up on the changes they missed instead of receiving all
+/// CFDs again after every change. Changes which are only caused by a new quote are not recorded
+/// as deltas, clients are kept up to date with [`Self::pnl`] instead.
+#[derive(Debug, Clone)]
+pub struct CfdFeed {
+    cfds: Arc<Vec<Cfd>>,
+    last_event_id: u64,
+    deltas: VecDeque<Arc<CfdDelta>>,
+    pnl: Arc<Vec<CfdPnl>>,
+    pnl_revision: u64,
+}
+
+impl CfdFeed {
+    /// Event IDs start at the current UNIX time in microseconds, so that they keep increasing
+    /// across restarts and an event ID from before a restart is never mistaken for a recent one.
+    fn new() -> Self {
+        let now = OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000;
+
+        Self {
+            cfds: Arc::new(Vec::new()),
+            last_event_id: u64::try_from(now).unwrap_or_default(),
+            deltas: VecDeque::new(),
+            pnl: Arc::new(Vec::new()),
+            pnl_revision: 0,
+        }
+    }
+
+    /// All CFDs, the most recently created one first.
+    pub fn cfds(&self) -> &[Cfd] {
+        &self.cfds
+    }
+
+    /// The ID of the latest delta, which is also the ID of the current state of [`Self::cfds`].
+    pub fn last_event_id(&self) -> u64 {
+        self.last_event_id
+    }
+
+    /// The deltas following the event with the given ID.
+    ///
+    /// Returns `None` if some of those deltas are no longer retained or the event ID is unknown,
+    /// in which case the client has to start over from [`Self::cfds`].
+    pub fn deltas_since(&self, event_id: u64) -> Option<impl Iterator<Item = &CfdDelta>> {
+        let oldest_retained = self
+            .deltas
+            .front()
+            .map_or(self.last_event_id.saturating_add(1), |delta| delta.event_id);
+
+        if event_id > self.last_event_id || event_id.saturating_add(1) < oldest_retained {
+            return None;
+        }
+
+        let deltas = self
+            .deltas
+            .iter()
+            .filter(move |delta| delta.event_id > event_id)
+            .map(|delta| delta.as_ref());
+
+        Some(deltas)
+    }
+
+    /// The profit and payout of all CFDs at the latest quote, in the order of [`Self::cfds`].
+    pub fn pnl(&self) -> &[CfdPnl] {
+        &self.pnl
+    }
+
+    /// Incremented whenever [`Self::pnl`] changes, so clients can tell whether they are up to
+    /// date.
+    pub fn pnl_revision(&self) -> u64 {
+        self.pnl_revision
+    }
+
+    /// Replace all CFDs, recording a delta for every CFD that was added, changed or removed.
+    fn update(&mut self, cfds: Vec<Cfd>) {
+        let previous = self
+            .cfds
+            .iter()
+            .map(|cfd| (cfd.order_id, cfd))
+            .collect::<HashMap<_, _>>();
+        let current = cfds.iter().map(|cfd| cfd.order_id).collect::<HashSet<_>>();
+
+        let updated = cfds
+            .iter()
+            .filter(|cfd| match previous.get(&cfd.order_id) {
+                Some(previous) => !previous.eq_ignoring_quote(cfd),
+                None => true,
+            })
+            .cloned()
+            .map(CfdChange::Updated);
+        let removed = self
+            .cfds
+            .iter()
+            .filter(|cfd| !current.contains(&cfd.order_id))
+            .map(|cfd| CfdChange::Removed(cfd.order_id));
+        let changes = updated.chain(removed).collect::<Vec<_>>();
+
+        for change in changes {
+            self.last_event_id += 1;
+            self.deltas.push_back(Arc::new(CfdDelta {
+                event_id: self.last_event_id,
+                change,
+            }));
+
+            if self.deltas.len() > CFD_DELTA_HISTORY {
+                self.deltas.pop_front();
+            }
+        }
+
+        let pnl = cfds.iter().map(CfdPnl::from).collect::<Vec<_>>();
+        if pnl != *self.pnl {
+            self.pnl = Arc::new(pnl);
+            self.pnl_revision += 1;
+        }
+
+        self.cfds = Arc::new(cfds);
+    }
+}
+
+/// How far a client of the [`CfdFeed`] has been brought up to date.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CfdFeedCursor {
+    last_event_id: Option<u64>,
+    pnl_revision: Option<u64>,
+}
+
+/// An update to send to a client of the [`CfdFeed`].
+#[derive(Debug)]
+pub enum CfdFeedUpdate<'a> {
+    /// All CFDs, including their current PnL.
+    Snapshot(&'a CfdFeed),
+    Delta(&'a CfdDelta),
+    Pnl(&'a [CfdPnl]),
+}
+
+impl CfdFeedCursor {
+    /// Resume the feed for a client that received all events up to `last_event_id`.
+    pub fn resume(last_event_id: Option<u64>) -> Self {
+        Self {
+            last_event_id,
+            pnl_revision: None,
+        }
+    }
+
+    /// The updates bringing the client up to date with `feed`.
+    ///
+    /// Only the CFDs which changed are sent, unless the client did not receive any events yet or
+    /// missed more changes than the feed retains, in which case all CFDs are sent. The PnL is sent
+    /// after the deltas if it changed since the client last received it.
+    pub fn advance<'a>(&mut self, feed: &'a CfdFeed) -> Vec<CfdFeedUpdate<'a>> {
+        let mut updates = match self.last_event_id.and_then(|id| feed.deltas_since(id)) {
+            Some(deltas) => deltas.map(CfdFeedUpdate::Delta).collect(),
+            None => {
+                self.pnl_revision = Some(feed.pnl_revision());
+                vec![CfdFeedUpdate::Snapshot(feed)]
+            }
+        };
+
+        if self.pnl_revision != Some(feed.pnl_revision()) {
+            updates.push(CfdFeedUpdate::Pnl(feed.pnl()));
+        }
+
+        self.last_event_id = Some(feed.last_event_id());
+        self.pnl_revision = Some(feed.pnl_revision());
+
+        updates
+    }
+}
+
+#[derive(Derivative, Clone, Debug, Serialize, ToSchema)]
 #[derivative(PartialEq)]
 pub struct Cfd {
     pub order_id: OrderId,
@@ -138,6 +362,7 @@ pub struct Cfd {
     ///
     /// Includes the opening fee and all fees that were already charged.
     #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    #[schema(value_type = f64)]
     pub accumulated_fees: SignedAmount,
 
     /// The taker leverage
@@ -145,6 +370,7 @@ pub struct Cfd {
     pub leverage_taker: Leverage,
     pub trading_pair: TradingPair,
     pub position: Position,
+    pub contract_type: ContractType,
     #[serde(with = "round_to_two_dp")]
     pub liquidation_price: Price,
 
@@ -152,13 +378,16 @@ pub struct Cfd {
     pub quantity_usd: Usd,
 
     #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    #[schema(value_type = f64)]
     pub margin: Amount,
     #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    #[schema(value_type = f64)]
     pub margin_counterparty: Amount,
     pub role: Role,
 
     /// Projected or final profit amount
     #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc::opt")]
+    #[schema(value_type = Option<f64>)]
     pub profit_btc: Option<SignedAmount>,
     /// Projected or final profit percent
     pub profit_percent: Option<String>,
@@ -172,10 +401,12 @@ pub struct Cfd {
     /// represented as option. If we already know the final payout (based on CET or
     /// collborative close) then this is the final payout.
     #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc::opt")]
+    #[schema(value_type = Option<f64>)]
     pub payout: Option<SignedAmount>,
     pub closing_price: Option<Price>,
 
     pub state: CfdState,
+    #[schema(value_type = Vec<CfdAction>)]
     pub actions: HashSet<CfdAction>,
 
     // TODO: This `CfdDetails` wrapper is useless and could be removed, but that would be a
@@ -183,6 +414,7 @@ pub struct Cfd {
     pub details: CfdDetails,
 
     #[serde(with = "::time::serde::timestamp::option")]
+    #[schema(value_type = Option<i64>)]
     pub expiry_timestamp: Option<OffsetDateTime>,
 
     pub counterparty: model::Identity,
@@ -335,6 +567,8 @@ impl Cfd {
             role,
             opening_fee,
             initial_funding_rate,
+            settlement_interval,
+            contract_type,
             ..
         }: sqlite_db::Cfd,
         network: Network,
@@ -362,7 +596,7 @@ impl Cfd {
             long_leverage,
             short_leverage,
             initial_funding_rate,
-            SETTLEMENT_INTERVAL.whole_hours(),
+            settlement_interval.whole_hours(),
         )
         .expect("values from db to be sane");
 
@@ -383,6 +617,7 @@ impl Cfd {
             leverage_taker: taker_leverage,
             trading_pair: TradingPair::BtcUsd,
             position,
+            contract_type,
             liquidation_price,
             quantity_usd,
             margin,
@@ -542,6 +777,7 @@ impl Cfd {
 
                 self.aggregated.state = CfdState::PendingCet;
             }
+            AutoAccepted { .. } => {}
             ManualCommit { .. } => {
                 self.aggregated.commit_published = true;
 
@@ -583,6 +819,18 @@ impl Cfd {
         self
     }
 
+    /// Whether both CFDs are equal apart from the fields derived from the latest quote.
+    fn eq_ignoring_quote(&self, other: &Self) -> bool {
+        let without_quote = |cfd: &Self| Self {
+            profit_btc: None,
+            profit_percent: None,
+            payout: None,
+            ..cfd.clone()
+        };
+
+        without_quote(self) == without_quote(other)
+    }
+
     pub fn with_current_quote(self, latest_quote: Option<xtra_bitmex_price_feed::Quote>) -> Self {
         // If the payout was already set we don't care about the current quote, this applies to
         // closed CFDs
@@ -779,6 +1027,7 @@ impl Cfd {
 /// Internal struct to keep all the senders around in one place
 struct Tx {
     cfds: watch::Sender<Option<Vec<Cfd>>>,
+    cfd_feed: watch::Sender<Option<CfdFeed>>,
     pub order: watch::Sender<MakerOffers>,
     pub quote: watch::Sender<Option<Quote>>,
     // TODO: Use this channel to communicate maker status as well with generic
@@ -801,8 +1050,12 @@ impl Tx {
                     &a.aggregated.creation_timestamp,
                 )
             })
-            .collect();
+            .collect::<Vec<_>>();
 
+        let mut cfd_feed = self.cfd_feed.borrow().clone().unwrap_or_else(CfdFeed::new);
+        cfd_feed.update(cfds_with_quote.clone());
+
+        let _ = self.cfd_feed.send(Some(cfd_feed));
         let _ = self.cfds.send(Some(cfds_with_quote));
     }
 
@@ -881,6 +1134,7 @@ impl sqlite_db::ClosedCfdAggregate for Cfd {
             counterparty_network_identity,
             role,
             fees,
+            contract_type,
             expiry_timestamp,
             lock,
             settlement,
@@ -987,6 +1241,7 @@ impl sqlite_db::ClosedCfdAggregate for Cfd {
             leverage_taker: taker_leverage,
             trading_pair: TradingPair::BtcUsd,
             position,
+            contract_type,
             liquidation_price,
             quantity_usd,
             margin,
@@ -1021,6 +1276,7 @@ impl sqlite_db::FailedCfdAggregate for Cfd {
             counterparty_network_identity,
             role,
             fees,
+            contract_type,
             kind,
             creation_timestamp,
             ..
@@ -1062,6 +1318,7 @@ impl sqlite_db::FailedCfdAggregate for Cfd {
             leverage_taker: taker_leverage,
             trading_pair: TradingPair::BtcUsd,
             position,
+            contract_type,
             liquidation_price,
             quantity_usd,
             margin,
@@ -1237,7 +1494,7 @@ impl From<xtra_bitmex_price_feed::Quote> for Quote {
 }
 
 /// Maker offers represents the offers as created by the maker
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
 pub struct MakerOffers {
     /// The offer where the maker's position is long
     pub long: Option<CfdOrder>,
@@ -1245,7 +1502,7 @@ pub struct MakerOffers {
     pub short: Option<CfdOrder>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
 pub struct CfdOrder {
     pub id: OrderId,
 
@@ -1262,6 +1519,7 @@ pub struct CfdOrder {
     ///
     /// Note: It's a flat fee on top of the fee calculated based on funding rate
     #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc::opt")]
+    #[schema(value_type = Option<f64>)]
     pub opening_fee: Option<Amount>,
 
     /// The interest as annualized percentage
@@ -1292,9 +1550,15 @@ pub struct CfdOrder {
 
     pub creation_timestamp: Timestamp,
     pub settlement_time_interval_in_secs: u64,
+
+    pub contract_type: ContractType,
+    /// When a fixed-expiry contract settles, not set for perpetual contracts
+    #[serde(with = "::time::serde::timestamp::option")]
+    #[schema(value_type = Option<i64>)]
+    pub expiry_timestamp: Option<OffsetDateTime>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
 pub struct LeverageDetails {
     pub leverage: Leverage,
     /// Own liquidation price according to position and leverage
@@ -1305,6 +1569,7 @@ pub struct LeverageDetails {
     /// Since this is a calculated value that we need in the UI this value is based on the
     /// perspective the role (i.e. taker/maker)
     #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    #[schema(value_type = f64)]
     pub margin_per_lot: Amount,
 
     /// Initial funding fee per lot from the perspective of the role
@@ -1312,6 +1577,7 @@ pub struct LeverageDetails {
     /// Since this is a calculated value that we need in the UI this value is based on the
     /// perspective the role (i.e. taker/maker)
     #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc")]
+    #[schema(value_type = f64)]
     pub initial_funding_fee_per_lot: SignedAmount,
 }
 
@@ -1349,7 +1615,7 @@ impl TryFrom<Order> for CfdOrder {
                     long_leverage,
                     short_leverage,
                     order.funding_rate,
-                    SETTLEMENT_INTERVAL.whole_hours(),
+                    order.settlement_interval.whole_hours(),
                 )
                 .context("unable to calculate initial funding fee")?;
 
@@ -1387,11 +1653,16 @@ impl TryFrom<Order> for CfdOrder {
             funding_rate_annualized_percent: AnnualisedFundingPercent::from(order.funding_rate)
                 .to_string(),
             funding_rate_hourly_percent: HourlyFundingPercent::from(order.funding_rate).to_string(),
+            contract_type: order.contract_type,
+            expiry_timestamp: match order.contract_type {
+                ContractType::Perpetual => None,
+                ContractType::FixedExpiry => Some(order.oracle_event_id.timestamp()),
+            },
         })
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
 pub enum CfdState {
     PendingSetup,
     ContractSetup,
@@ -1413,12 +1684,15 @@ pub enum CfdState {
     SetupFailed,
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
 pub struct CfdDetails {
+    #[schema(value_type = Vec<TxUrl>)]
     tx_url_list: HashSet<TxUrl>,
 }
 
-#[derive(Debug, Clone, Copy, Display, FromStr, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(
+    Debug, Clone, Copy, Display, FromStr, Serialize, Deserialize, PartialEq, Eq, Hash, ToSchema,
+)]
 #[serde(rename_all = "camelCase")]
 #[display(style = "camelCase")]
 pub enum CfdAction {
@@ -1527,8 +1801,8 @@ pub fn to_mempool_url(txid: Txid, network: Network) -> String {
 }
 
 /// Link to transaction on mempool.space for UI representation
-#[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash)]
-struct TxUrl {
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash, ToSchema)]
+pub struct TxUrl {
     pub label: TxLabel,
     pub url: String,
 }
@@ -1565,7 +1839,7 @@ impl TxUrl {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Eq, Hash, ToSchema)]
 pub enum TxLabel {
     Lock,
     Commit,
@@ -1665,6 +1939,7 @@ mod tests {
             OpeningFee::new(Amount::from_sat(2000)),
             FundingRate::default(),
             TxFeeRate::default(),
+            ContractType::Perpetual,
         )
     }
 
@@ -1703,6 +1978,7 @@ mod tests {
             OpeningFee::new(Amount::ZERO),
             FundingRate::default(),
             TxFeeRate::default(),
+            ContractType::Perpetual,
         );
 
         let contract_setup_completed =
@@ -1858,4 +2134,97 @@ mod tests {
         // from a closed CFD
         assert_eq!(projection_open, projection_closed);
     }
+
+    #[tokio::test]
+    async fn cfd_feed_only_records_changed_cfds() {
+        let (first, second) = two_projected_cfds().await;
+
+        let mut feed = CfdFeed::new();
+        feed.update(vec![first.clone(), second.clone()]);
+        let event_id = feed.last_event_id();
+
+        let mut changed = second.clone();
+        changed.state = CfdState::Open;
+        feed.update(vec![first, changed.clone()]);
+
+        let deltas = feed.deltas_since(event_id).unwrap().collect::<Vec<_>>();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].event_id, event_id + 1);
+        assert_eq!(deltas[0].change, CfdChange::Updated(changed));
+        assert_eq!(feed.last_event_id(), event_id + 1);
+    }
+
+    #[tokio::test]
+    async fn cfd_feed_publishes_quote_changes_as_pnl_only() {
+        let (cfd, _) = two_projected_cfds().await;
+
+        let mut feed = CfdFeed::new();
+        feed.update(vec![cfd.clone()]);
+        let event_id = feed.last_event_id();
+        let pnl_revision = feed.pnl_revision();
+
+        let repriced = Cfd {
+            profit_btc: Some(SignedAmount::from_sat(1_000)),
+            profit_percent: Some("1".to_owned()),
+            payout: Some(SignedAmount::from_sat(101_000)),
+            ..cfd
+        };
+        feed.update(vec![repriced.clone()]);
+
+        assert_eq!(feed.last_event_id(), event_id);
+        assert_eq!(feed.pnl_revision(), pnl_revision + 1);
+        assert_eq!(feed.pnl(), &[CfdPnl::from(&repriced)]);
+    }
+
+    #[tokio::test]
+    async fn cfd_feed_records_removed_cfds() {
+        let (first, second) = two_projected_cfds().await;
+
+        let mut feed = CfdFeed::new();
+        feed.update(vec![first.clone(), second.clone()]);
+        let event_id = feed.last_event_id();
+
+        feed.update(vec![first]);
+
+        let deltas = feed.deltas_since(event_id).unwrap().collect::<Vec<_>>();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].change, CfdChange::Removed(second.order_id));
+    }
+
+    #[tokio::test]
+    async fn cfd_feed_requires_snapshot_if_deltas_were_dropped() {
+        let (mut cfd, _) = two_projected_cfds().await;
+
+        let mut feed = CfdFeed::new();
+        let initial_event_id = feed.last_event_id();
+        for _ in 0..=CFD_DELTA_HISTORY {
+            cfd.pending_settlement_proposal_price = match cfd.pending_settlement_proposal_price {
+                Some(_) => None,
+                None => Some(Price::new(dec!(42_000)).unwrap()),
+            };
+            feed.update(vec![cfd.clone()]);
+        }
+
+        assert!(feed.deltas_since(initial_event_id).is_none());
+        assert!(feed.deltas_since(initial_event_id + 1).is_some());
+        assert_eq!(feed.deltas_since(feed.last_event_id()).unwrap().count(), 0);
+        assert!(feed.deltas_since(feed.last_event_id() + 1).is_none());
+    }
+
+    async fn two_projected_cfds() -> (Cfd, Cfd) {
+        let db = memory().await.unwrap();
+
+        let first = dummy_cfd();
+        db.insert_cfd(&first).await.unwrap();
+        let first = db
+            .load_open_cfd::<Cfd>(first.id(), bdk::bitcoin::Network::Testnet)
+            .await
+            .unwrap()
+            .with_current_quote(None);
+
+        let mut second = first.clone();
+        second.order_id = OrderId::default();
+
+        (first, second)
+    }
 }
@@ -1,4 +1,5 @@
 use crate::command;
+use crate::correlation::CorrelationId;
 use crate::future_ext::FutureExt;
 use crate::oracle;
 use crate::oracle::NoAnnouncement;
@@ -25,10 +26,13 @@ use model::RolloverVersion;
 use model::TxFeeRate;
 use std::collections::HashMap;
 use tokio_tasks::Tasks;
+use tracing::Instrument;
+use tracing::Span;
 use xtra::message_channel::MessageChannel;
 use xtra_libp2p::NewInboundSubstream;
 use xtra_libp2p::Substream;
 use xtra_productivity::xtra_productivity;
+use xtras::SendAsyncSafe;
 
 use super::protocol;
 
@@ -36,6 +40,7 @@ type ListenerConnection = (
     Framed<Substream, JsonCodec<ListenerMessage, DialerMessage>>,
     PeerId,
     (BitMexPriceEventId, model::CompleteFee),
+    Span,
 );
 
 /// Permanent actor to handle incoming substreams for the `/itchysats/rollover/1.0.0`
@@ -52,6 +57,7 @@ pub struct Actor {
     n_payouts: usize,
     pending_protocols: HashMap<OrderId, ListenerConnection>,
     executor: command::Executor,
+    rollover_proposed: MessageChannel<RolloverProposed, ()>,
 }
 
 impl Actor {
@@ -63,6 +69,7 @@ impl Actor {
             Result<olivia::Announcement, NoAnnouncement>,
         >,
         n_payouts: usize,
+        rollover_proposed: MessageChannel<RolloverProposed, ()>,
     ) -> Self {
         Self {
             tasks: Tasks::default(),
@@ -72,6 +79,7 @@ impl Actor {
             n_payouts,
             pending_protocols: HashMap::default(),
             executor,
+            rollover_proposed,
         }
     }
 }
@@ -101,11 +109,19 @@ impl Actor {
                     .context("Failed to decode Propose")?
                     .into_propose()?;
 
+                let span = tracing::info_span!(
+                    "rollover",
+                    order_id = %propose.order_id,
+                    correlation_id = tracing::field::Empty
+                );
+                CorrelationId::continue_or_new(propose.correlation_id, &span);
+
                 address
                     .send(ProposeReceived {
                         propose,
                         framed,
                         peer,
+                        span,
                     })
                     .await?;
 
@@ -125,6 +141,7 @@ impl Actor {
             propose,
             framed,
             peer,
+            span,
         } = msg;
         let order_id = propose.order_id;
 
@@ -152,8 +169,18 @@ impl Actor {
         // In case we fail to accept/reject some proposals might never get cleaned up. Given that
         // the taker will retry this should not do much harm because we will replace the proposal
         // with a new one. This is acceptable for now.
-        self.pending_protocols
-            .insert(order_id, (framed, peer, (from_event_id, from_complete_fee)));
+        self.pending_protocols.insert(
+            order_id,
+            (framed, peer, (from_event_id, from_complete_fee), span),
+        );
+
+        if let Err(e) = self
+            .rollover_proposed
+            .send_async_safe(RolloverProposed { order_id })
+            .await
+        {
+            tracing::warn!(%order_id, "Failed to announce rollover proposal: {e:#}");
+        }
     }
 
     async fn handle(&mut self, msg: Accept) -> Result<()> {
@@ -164,7 +191,7 @@ impl Actor {
             short_funding_rate,
         } = msg;
 
-        let (mut framed, _, from_params) =
+        let (mut framed, _, from_params, span) =
             self.pending_protocols.remove(&order_id).with_context(|| {
                 format!("No active protocol for {order_id} when accepting rollover")
             })?;
@@ -349,6 +376,7 @@ impl Actor {
 
                     Ok(())
                 }
+                .instrument(span)
             },
             {
                 let executor = self.executor.clone();
@@ -365,9 +393,10 @@ impl Actor {
     async fn handle(&mut self, msg: Reject) -> Result<()> {
         let Reject { order_id } = msg;
 
-        let (mut framed, ..) = self.pending_protocols.remove(&order_id).with_context(|| {
-            format!("No active protocol for {order_id} when rejecting rollover")
-        })?;
+        let (mut framed, _, _, span) =
+            self.pending_protocols.remove(&order_id).with_context(|| {
+                format!("No active protocol for {order_id} when rejecting rollover")
+            })?;
 
         emit_rejected(order_id, &self.executor).await;
 
@@ -379,7 +408,8 @@ impl Actor {
                         protocol::Reject { order_id },
                     )))
                     .await
-            },
+            }
+            .instrument(span),
             move |e| async move {
                 tracing::debug!(%order_id, "Failed to send reject rollover to the taker: {e:#}")
             },
@@ -394,6 +424,13 @@ struct ProposeReceived {
     propose: Propose,
     framed: Framed<Substream, JsonCodec<ListenerMessage, DialerMessage>>,
     peer: PeerId,
+    span: Span,
+}
+
+/// Message sent to the owner of the maker's offers once a rollover proposal awaits a decision.
+#[derive(Clone, Copy, Debug)]
+pub struct RolloverProposed {
+    pub order_id: OrderId,
 }
 
 /// Upon accepting Rollover maker sends the current estimated transaction fee and
@@ -1,6 +1,7 @@
 use crate::bitcoin::secp256k1::SecretKey;
 use crate::bitcoin::PublicKey;
 use crate::command;
+use crate::correlation::CorrelationId;
 use crate::shared_protocol::verify_adaptor_signature;
 use crate::shared_protocol::verify_cets;
 use crate::shared_protocol::verify_signature;
@@ -126,6 +127,11 @@ pub struct Propose {
     pub order_id: OrderId,
     pub timestamp: Timestamp,
     pub from_commit_txid: Txid,
+    /// Correlates the logs and traces of both sides of the protocol run.
+    ///
+    /// Optional for compatibility with takers that don't send it yet.
+    #[serde(default)]
+    pub correlation_id: Option<CorrelationId>,
 }
 
 #[derive(Copy, Clone, Serialize, Deserialize)]
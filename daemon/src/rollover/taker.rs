@@ -1,4 +1,5 @@
 use crate::command;
+use crate::correlation::CorrelationId;
 use crate::future_ext::FutureExt;
 use crate::oracle;
 use crate::oracle::NoAnnouncement;
@@ -21,6 +22,7 @@ use model::Role;
 use model::Timestamp;
 use std::time::Duration;
 use tokio_tasks::Tasks;
+use tracing::Instrument;
 use xtra::message_channel::MessageChannel;
 use xtra::Address;
 use xtra_libp2p::Endpoint;
@@ -115,6 +117,13 @@ impl Actor {
             }
         };
 
+        let span = tracing::info_span!(
+            "rollover",
+            %order_id,
+            correlation_id = tracing::field::Empty
+        );
+        let correlation_id = CorrelationId::new(&span);
+
         self.tasks.add_fallible(
             {
                 let executor = self.executor.clone();
@@ -136,6 +145,7 @@ impl Actor {
                             order_id,
                             timestamp: Timestamp::now(),
                             from_commit_txid,
+                            correlation_id: Some(correlation_id),
                         }))
                         .await
                         .context("Failed to send Msg0")?;
@@ -331,6 +341,7 @@ impl Actor {
                     }
                     Ok(())
                 }
+                .instrument(span)
             },
             {
                 let executor = self.executor.clone();
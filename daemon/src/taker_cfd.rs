@@ -2,6 +2,7 @@ use crate::bitcoin::util::psbt::PartiallySignedTransaction;
 use crate::collab_settlement;
 use crate::collab_settlement::taker::Settle;
 use crate::connection;
+use crate::contract_setup;
 use crate::oracle;
 use crate::process_manager;
 use crate::projection;
@@ -17,6 +18,7 @@ use model::libp2p::PeerId;
 use model::market_closing_price;
 use model::olivia;
 use model::Cfd;
+use model::ContractType;
 use model::Identity;
 use model::Leverage;
 use model::MakerOffers;
@@ -26,11 +28,14 @@ use model::Price;
 use model::Role;
 use model::Usd;
 use sqlite_db;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use time::OffsetDateTime;
 use tokio_tasks::Tasks;
 use xtra::Actor as _;
 use xtra_productivity::xtra_productivity;
 use xtras::AddressMap;
+use xtras::SendAsyncSafe;
 
 #[derive(Clone)]
 pub struct CurrentMakerOffers(pub Option<MakerOffers>);
@@ -59,12 +64,17 @@ pub struct Actor<O, W> {
     conn_actor: xtra::Address<connection::Actor>,
     setup_actors: AddressMap<OrderId, setup_taker::Actor>,
     libp2p_collab_settlement_actor: xtra::Address<collab_settlement::taker::Actor>,
+    libp2p_contract_setup_actor: xtra::Address<contract_setup::taker::Actor>,
     oracle_actor: xtra::Address<O>,
     n_payouts: usize,
     tasks: Tasks,
-    current_maker_offers: Option<MakerOffers>,
-    maker_identity: Identity,
-    maker_peer_id: PeerId,
+    /// The latest offers of each maker, from the taker's perspective.
+    ///
+    /// Ordered by peer id, so that ties between equally good offers are broken deterministically.
+    current_maker_offers: BTreeMap<PeerId, MakerOffers>,
+    maker_identities: HashMap<PeerId, Identity>,
+    /// The maker we are connected to via legacy networking.
+    legacy_maker_peer_id: PeerId,
 }
 
 impl<O, W> Actor<O, W>
@@ -81,9 +91,10 @@ where
         conn_actor: xtra::Address<connection::Actor>,
         oracle_actor: xtra::Address<O>,
         libp2p_collab_settlement_actor: xtra::Address<collab_settlement::taker::Actor>,
+        libp2p_contract_setup_actor: xtra::Address<contract_setup::taker::Actor>,
         n_payouts: usize,
-        maker_identity: Identity,
-        maker_peer_id: PeerId,
+        maker_identities: HashMap<PeerId, Identity>,
+        legacy_maker_peer_id: PeerId,
     ) -> Self {
         Self {
             db,
@@ -94,20 +105,49 @@ where
             conn_actor,
             oracle_actor,
             libp2p_collab_settlement_actor,
+            libp2p_contract_setup_actor,
             n_payouts,
             setup_actors: AddressMap::default(),
             tasks: Tasks::default(),
-            current_maker_offers: None,
-            maker_identity,
-            maker_peer_id,
+            current_maker_offers: BTreeMap::new(),
+            maker_identities,
+            legacy_maker_peer_id,
         }
     }
 }
 
-#[xtra_productivity(message_impl = false)]
 impl<O, W> Actor<O, W> {
+    /// The best offers across all makers, as shown to the user.
+    fn best_maker_offers(&self) -> Option<MakerOffers> {
+        self.current_maker_offers
+            .values()
+            .cloned()
+            .reduce(MakerOffers::best_of)
+    }
+
+    async fn update_projection_offers(&self) -> Result<()> {
+        self.projection_actor
+            .send(projection::Update(self.best_maker_offers()))
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[xtra_productivity(message_impl = false)]
+impl<O, W> Actor<O, W>
+where
+    O: xtra::Handler<oracle::SyncAnnouncement, Return = ()>
+        + xtra::Handler<oracle::SettlementIntervalInUse, Return = ()>,
+{
     async fn handle_current_offers(&mut self, msg: xtra_libp2p_offer::taker::LatestMakerOffers) {
-        let takers_perspective_of_maker_offers = msg.0.map(|mut maker_offers| {
+        let maker_peer_id = PeerId::from(msg.peer);
+        if !self.maker_identities.contains_key(&maker_peer_id) {
+            tracing::debug!(peer = %maker_peer_id, "Ignoring offers of unknown maker");
+            return;
+        }
+
+        let takers_perspective_of_maker_offers = msg.offers.map(|mut maker_offers| {
             maker_offers.long = maker_offers.long.map(|mut long| {
                 long.origin = Origin::Theirs;
                 long
@@ -120,16 +160,43 @@ impl<O, W> Actor<O, W> {
             maker_offers
         });
 
-        self.current_maker_offers = takers_perspective_of_maker_offers.clone();
-        tracing::trace!("new maker offers {:?}", takers_perspective_of_maker_offers);
+        tracing::trace!(
+            maker = %maker_peer_id,
+            "new maker offers {:?}",
+            takers_perspective_of_maker_offers
+        );
 
-        if let Err(e) = self
-            .projection_actor
-            .send(projection::Update(
-                takers_perspective_of_maker_offers.clone(),
-            ))
-            .await
-        {
+        // Fixed-expiry orders settle beyond the announcements the oracle actor syncs on its own
+        if let Some(offers) = &takers_perspective_of_maker_offers {
+            for order in offers.long.iter().chain(offers.short.iter()) {
+                if let Err(e) = self
+                    .oracle_actor
+                    .send_async_safe(oracle::SyncAnnouncement(order.oracle_event_id))
+                    .await
+                {
+                    tracing::warn!("Failed to sync announcement of offered order: {e:#}");
+                }
+
+                // Rolling over perpetual contracts needs announcements up to one settlement
+                // interval into the future
+                if order.contract_type == ContractType::Perpetual {
+                    if let Err(e) = self
+                        .oracle_actor
+                        .send_async_safe(oracle::SettlementIntervalInUse(order.settlement_interval))
+                        .await
+                    {
+                        tracing::warn!("Failed to sync announcements for offered order: {e:#}");
+                    }
+                }
+            }
+        }
+
+        match takers_perspective_of_maker_offers {
+            Some(offers) => self.current_maker_offers.insert(maker_peer_id, offers),
+            None => self.current_maker_offers.remove(&maker_peer_id),
+        };
+
+        if let Err(e) = self.update_projection_offers().await {
             tracing::warn!("Failed to send current offers to projection actor: {e:#}");
         };
     }
@@ -190,29 +257,39 @@ where
                 format!("Contract setup for order {order_id} is already in progress")
             })?;
 
-        let (order_to_take, maker_offers) = self
-            .current_maker_offers
-            .clone()
-            .context("No maker offers available to take")?
-            .take_order(order_id);
+        anyhow::ensure!(
+            !self.current_maker_offers.is_empty(),
+            "No maker offers available to take"
+        );
 
-        let order_to_take = order_to_take.context("Order to take could not be found in current maker offers, you might have an outdated offer")?;
+        // The CFD is routed to the maker whose offer we take
+        let (maker_peer_id, order_to_take, maker_offers) = self
+            .current_maker_offers
+            .iter()
+            .find_map(|(maker_peer_id, offers)| match offers.clone().take_order(order_id) {
+                (Some(order), offers) => Some((*maker_peer_id, order, offers)),
+                (None, _) => None,
+            })
+            .context("Order to take could not be found in current maker offers, you might have an outdated offer")?;
+        let maker_identity = *self
+            .maker_identities
+            .get(&maker_peer_id)
+            .with_context(|| format!("Unknown maker {maker_peer_id}"))?;
 
         // The offer we are instructed to take is removed from the
         // set of available offers immediately so that we don't attempt
         // to take it more than once
         {
-            self.current_maker_offers.replace(maker_offers);
-            self.projection_actor
-                .send(projection::Update(self.current_maker_offers.clone()))
-                .await?;
+            self.current_maker_offers
+                .insert(maker_peer_id, maker_offers);
+            self.update_projection_offers().await?;
         }
 
         if !order_to_take.is_safe_to_take(OffsetDateTime::now_utc()) {
             bail!("The maker's offer appears to be outdated, refusing to take offer",);
         }
 
-        tracing::info!("Taking current order: {:?}", &order_to_take);
+        tracing::info!(maker = %maker_peer_id, "Taking current order: {:?}", &order_to_take);
 
         // We create the cfd here without any events yet, only static data
         // Once the contract setup completes (rejected / accepted / failed) the first event will be
@@ -220,8 +297,8 @@ where
         let cfd = Cfd::from_order(
             &order_to_take,
             quantity,
-            self.maker_identity,
-            Some(self.maker_peer_id),
+            maker_identity,
+            Some(maker_peer_id),
             Role::Taker,
             leverage,
         );
@@ -238,6 +315,34 @@ where
             .await?
             .with_context(|| format!("Announcement {price_event_id} not found"))?;
 
+        // We try to take the order via libp2p first
+        match self
+            .libp2p_contract_setup_actor
+            .send(contract_setup::taker::TakeOrder {
+                order_id: cfd.id(),
+                quantity: cfd.quantity(),
+                leverage: cfd.taker_leverage(),
+                maker_peer_id,
+                announcement: announcement.clone(),
+            })
+            .await
+        {
+            // Return early if dispatch to libp2p contract setup worked
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(error)) if maker_peer_id != self.legacy_maker_peer_id => {
+                return Err(error.context(format!(
+                    "Unable to take order via libp2p and maker {maker_peer_id} is not reachable via legacy networking"
+                )));
+            }
+            Ok(Err(error)) => {
+                tracing::debug!("Try fallback to legacy contract setup because unable to take order via libp2p: {error:#}");
+            }
+            Err(error) => {
+                // we should never see this given that the libp2p actor is always running
+                tracing::error!("Try fallback to legacy contract setup because unable to dispatch take order to libp2p actor: {error:#}");
+            }
+        }
+
         let addr = setup_taker::Actor::new(
             self.db.clone(),
             self.process_manager_actor.clone(),
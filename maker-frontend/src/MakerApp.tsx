@@ -8,6 +8,8 @@ import {
     Grid,
     GridItem,
     HStack,
+    Input,
+    Select,
     Stack,
     Switch,
     Tab,
@@ -27,15 +29,29 @@ import ConnectedTakers, { TakerId } from "./components/ConnectedTakers";
 import CurrencyInputField from "./components/CurrencyInputField";
 import CurrentPrice from "./components/CurrentPrice";
 import createErrorToast from "./components/ErrorToast";
-import useLatestEvent from "./components/Hooks";
+import useLatestEvent, { useCfds } from "./components/Hooks";
 import OrderTile from "./components/OrderTile";
-import { Cfd, intoCfd, MakerOffer, PriceInfo, StateGroupKey, WalletInfo } from "./components/Types";
+import { MakerOffer, PriceInfo, StateGroupKey, WalletInfo } from "./components/Types";
 import Wallet from "./components/Wallet";
 import { CfdNewOfferParamsPayload, putCfdNewOfferParamsRequest, triggerWalletSync } from "./MakerClient";
 
 const SPREAD_ASK = 1.01;
 const SPREAD_BID = 0.99;
 
+const SECONDS_PER_HOUR = 3600;
+
+// The expiry has to be on the full hour because that is when the oracle attests to the price
+function expiryInHours(hours: string): number | undefined {
+    if (hours === "") {
+        return undefined;
+    }
+
+    const now = Math.floor(Date.now() / 1000);
+    const expiry = now + Number.parseInt(hours) * SECONDS_PER_HOUR;
+
+    return Math.ceil(expiry / SECONDS_PER_HOUR) * SECONDS_PER_HOUR;
+}
+
 export default function App() {
     document.title = "Hermes Maker";
 
@@ -43,8 +59,7 @@ export default function App() {
 
     let [leverages, setLeverages] = useState(["1", "2", "3"]);
 
-    const cfdsOrUndefined = useLatestEvent<Cfd[]>(source, "cfds", intoCfd);
-    let cfds = cfdsOrUndefined ? cfdsOrUndefined! : [];
+    const cfds = useCfds(source);
     const makerLongOrder = useLatestEvent<MakerOffer>(source, "long_offer");
     const makerShortOrder = useLatestEvent<MakerOffer>(source, "short_offer");
     const walletInfo = useLatestEvent<WalletInfo>(source, "wallet");
@@ -56,6 +71,8 @@ export default function App() {
 
     let [minQuantity, setMinQuantity] = useState<string>("100");
     let [maxQuantity, setMaxQuantity] = useState<string>("1000");
+    let [expiryHours, setExpiryHours] = useState<string>("");
+    let [settlementIntervalHours, setSettlementIntervalHours] = useState<string>("24");
     let [shortPrice, setShortPrice] = useState<string>("0");
     let [longPrice, setLongPrice] = useState<string>("0");
     let [autoRefreshShort, setAutoRefreshShort] = useState(true);
@@ -185,6 +202,24 @@ export default function App() {
                             </Stack>
                         </CheckboxGroup>
 
+                        <Text>Settlement Interval:</Text>
+                        <Select
+                            value={settlementIntervalHours}
+                            onChange={(event) => setSettlementIntervalHours(event.target.value)}
+                        >
+                            <option value="6">6 hours</option>
+                            <option value="24">24 hours</option>
+                            <option value="168">7 days</option>
+                        </Select>
+
+                        <Text>Expires in (hours):</Text>
+                        <Input
+                            placeholder="Perpetual"
+                            type="number"
+                            value={expiryHours}
+                            onChange={(event) => setExpiryHours(event.target.value)}
+                        />
+
                         <GridItem colSpan={2}>
                             <Divider colSpan={2} />
                         </GridItem>
@@ -208,6 +243,8 @@ export default function App() {
                                         // TODO: This is is in sats which is not really in line with other APIs for the maker
                                         opening_fee: Number.parseFloat("100"),
                                         leverage_choices: leverages.map((val) => Number.parseInt(val)),
+                                        expiry: expiryInHours(expiryHours),
+                                        settlement_interval_hours: Number.parseInt(settlementIntervalHours),
                                     };
                                     makeNewCfdSellOrder(payload);
                                 }}
@@ -10,6 +10,10 @@ export interface CfdNewOfferParamsPayload {
     tx_fee_rate: number;
     opening_fee?: number;
     leverage_choices: number[];
+    // unix timestamp on the full hour, offers perpetual contracts if not set
+    expiry?: number;
+    // between 6 hours and 7 days, defaults to 24 hours if not set
+    settlement_interval_hours?: number;
 }
 
 export async function putCfdNewOfferParamsRequest(payload: CfdNewOfferParamsPayload) {
@@ -1,5 +1,6 @@
 import { useState } from "react";
 import { useEventSourceListener } from "react-sse-hooks";
+import { Cfd, CfdPnl, CfdRemoved, intoCfd } from "./Types";
 
 export default function useLatestEvent<T,>(
     source: EventSource,
@@ -25,3 +26,99 @@ export default function useLatestEvent<T,>(
 
     return state;
 }
+
+// Keeps all CFDs up to date: the feed sends all CFDs in a `cfds` event and afterwards only the CFDs that changed as
+// `cfd` events, the CFDs that were removed as `cfd_removed` events and the profit of all CFDs at the latest quote as
+// `pnl` events.
+export function useCfds(source: EventSource): Cfd[] {
+    const [cfds, setCfds] = useState<Cfd[]>([]);
+
+    useEventSourceListener<Cfd[]>(
+        {
+            source: source,
+            startOnInit: true,
+            event: {
+                name: "cfds",
+                listener: ({ event }) => {
+                    // @ts-ignore - yes, there is a data field on event
+                    setCfds(JSON.parse(event.data, intoCfd));
+                },
+            },
+        },
+        [source],
+    );
+
+    useEventSourceListener<Cfd>(
+        {
+            source: source,
+            startOnInit: true,
+            event: {
+                name: "cfd",
+                listener: ({ event }) => {
+                    // @ts-ignore - yes, there is a data field on event
+                    const cfd: Cfd = JSON.parse(event.data, intoCfd);
+                    setCfds((cfds) => {
+                        const index = cfds.findIndex((existing) => existing.order_id === cfd.order_id);
+                        if (index === -1) {
+                            return [cfd, ...cfds];
+                        }
+
+                        const updated = [...cfds];
+                        updated[index] = cfd;
+                        return updated;
+                    });
+                },
+            },
+        },
+        [source],
+    );
+
+    useEventSourceListener<CfdRemoved>(
+        {
+            source: source,
+            startOnInit: true,
+            event: {
+                name: "cfd_removed",
+                listener: ({ event }) => {
+                    // @ts-ignore - yes, there is a data field on event
+                    const removed: CfdRemoved = JSON.parse(event.data);
+                    setCfds((cfds) => cfds.filter((cfd) => cfd.order_id !== removed.order_id));
+                },
+            },
+        },
+        [source],
+    );
+
+    useEventSourceListener<CfdPnl[]>(
+        {
+            source: source,
+            startOnInit: true,
+            event: {
+                name: "pnl",
+                listener: ({ event }) => {
+                    // @ts-ignore - yes, there is a data field on event
+                    const pnl: CfdPnl[] = JSON.parse(event.data);
+                    const byOrderId = new Map(pnl.map((entry) => [entry.order_id, entry]));
+                    setCfds((cfds) =>
+                        cfds.map((cfd) => {
+                            const entry = byOrderId.get(cfd.order_id);
+                            if (!entry) {
+                                return cfd;
+                            }
+
+                            return {
+                                ...cfd,
+                                profit_btc: entry.profit_btc,
+                                profit_percent: entry.profit_percent,
+                                payout: entry.payout,
+                            };
+                        })
+                    );
+                },
+            },
+        },
+        [source],
+    );
+
+    return cfds;
+}
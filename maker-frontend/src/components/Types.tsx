@@ -13,8 +13,13 @@ export interface MakerOffer {
     funding_rate_annualized_percent: number; // e.g. "18.5" (does not include % char)
     funding_rate_hourly_percent: number; // e.g. "0.002345" (does not include % char)
     creation_timestamp: number;
+    contract_type: ContractType;
+    // only set for fixed-expiry contracts
+    expiry_timestamp?: number;
 }
 
+export type ContractType = "Perpetual" | "FixedExpiry";
+
 export interface LeverageDetails {
     leverage: number;
     liquidation_price: number;
@@ -60,11 +65,25 @@ export interface Cfd {
     state: State;
     actions: Action[];
     details: CfdDetails;
+    contract_type: ContractType;
     expiry_timestamp?: number;
 
     counterparty: string;
 }
 
+// The profit of a CFD at the latest quote, sent for all CFDs in `pnl` events.
+export interface CfdPnl {
+    order_id: string;
+    profit_btc?: number;
+    profit_percent?: number;
+    payout?: number;
+}
+
+// Sent as `cfd_removed` event when a CFD is no longer part of the feed.
+export interface CfdRemoved {
+    order_id: string;
+}
+
 export interface CfdDetails {
     tx_url_list: Tx[];
 }
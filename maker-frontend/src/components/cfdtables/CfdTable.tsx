@@ -95,7 +95,7 @@ export function CfdTable(
             },
             {
                 Header: "Details",
-                accessor: ({ details, expiry_timestamp }) => {
+                accessor: ({ details, contract_type, expiry_timestamp }) => {
                     const txs = details.tx_url_list.map((tx) => {
                         return (
                             <Link href={tx.url} key={tx.url} isExternal>
@@ -109,6 +109,7 @@ export function CfdTable(
                         <Box>
                             <VStack>
                                 {txs}
+                                {contract_type === "FixedExpiry" && <Badge>Fixed expiry</Badge>}
                                 {expiry_timestamp && (
                                     <HStack>
                                         <Text>Expires on:</Text>
@@ -1,6 +1,8 @@
+use crate::auto_accept;
 use crate::cfd;
 use crate::connection;
 use crate::metrics::time_to_first_position;
+use anyhow::Context as _;
 use anyhow::Result;
 use bdk::bitcoin;
 use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
@@ -8,9 +10,13 @@ use bdk::bitcoin::Amount;
 use bdk::bitcoin::Txid;
 use daemon::archive_closed_cfds;
 use daemon::archive_failed_cfds;
+use daemon::backup;
 use daemon::collab_settlement;
 use daemon::command;
+use daemon::contract_setup;
+use daemon::identify;
 use daemon::monitor;
+use daemon::notifier;
 use daemon::oracle;
 use daemon::oracle::NoAnnouncement;
 use daemon::position_metrics;
@@ -18,35 +24,48 @@ use daemon::process_manager;
 use daemon::projection;
 use daemon::rollover;
 use daemon::seed::Identities;
+use daemon::version;
 use daemon::wallet;
+use libp2p_dns::TokioDnsConfig;
 use libp2p_tcp::TokioTcpConfig;
 use maia_core::secp256k1_zkp::XOnlyPublicKey;
 use maia_core::PartyParams;
 use model::olivia::Announcement;
 use model::FundingRate;
+use model::Identity;
 use model::Leverage;
 use model::OpeningFee;
 use model::OrderId;
 use model::Price;
 use model::Role;
+use model::Timestamp;
 use model::TxFeeRate;
 use model::Usd;
 use std::net::SocketAddr;
 use std::time::Duration;
+use time::OffsetDateTime;
 use tokio_tasks::Tasks;
 use xtra::Actor;
 use xtra::Address;
 use xtra::Context;
 use xtra::Handler;
+use xtra_libp2p::dialer;
 use xtra_libp2p::endpoint;
 use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::limits::Limits;
 use xtra_libp2p::listener;
+use xtra_libp2p::multiaddress_ext::MultiaddrExt;
+use xtra_libp2p::shared::SharedTransport;
+use xtra_libp2p::tor::OnionService;
+use xtra_libp2p::tor::TorTransport;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p_ping::ping;
 use xtra_libp2p_ping::pong;
 use xtras::supervisor;
 use xtras::supervisor::always_restart_after;
 use xtras::HandlerTimeoutExt;
+use xtras::InstrumentExt;
 
 const ENDPOINT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(20);
 const PING_INTERVAL: Duration = Duration::from_secs(5);
@@ -55,6 +74,25 @@ const PING_INTERVAL: Duration = Duration::from_secs(5);
 /// a failure.
 pub const RESTART_INTERVAL: Duration = Duration::from_secs(5);
 
+/// Let the directory connect regardless of the other peers, since the maker dials it itself and
+/// would otherwise drop its own listing.
+fn reserve_for_directory(mut limits: Limits, directory: PeerId) -> Limits {
+    if let Some(allowed_peers) = limits.allowed_peers.as_mut() {
+        allowed_peers.insert(directory);
+    }
+    limits.reserved_peers.insert(directory);
+
+    limits
+}
+
+/// A directory in which the maker keeps itself listed.
+pub struct Directory {
+    /// The address of the directory, including its peer id.
+    pub address: Multiaddr,
+    /// The addresses under which takers can reach the maker, including its peer id.
+    pub announced_multiaddrs: Vec<Multiaddr>,
+}
+
 pub struct ActorSystem<O: 'static, W: 'static> {
     pub cfd_actor: Address<cfd::Actor<O, connection::Actor, W>>,
     wallet_actor: Address<W>,
@@ -63,21 +101,34 @@ pub struct ActorSystem<O: 'static, W: 'static> {
     executor: command::Executor,
     _tasks: Tasks,
     _listener_supervisor: Address<supervisor::Actor<listener::Actor, listener::Error>>,
+    _onion_listener_supervisor:
+        Option<Address<supervisor::Actor<listener::Actor, listener::Error>>>,
     _ping_supervisor: Address<supervisor::Actor<ping::Actor, supervisor::UnitReason>>,
     _collab_settlement_supervisor:
         Address<supervisor::Actor<collab_settlement::maker::Actor, supervisor::UnitReason>>,
     _rollover_supervisor:
         Address<supervisor::Actor<rollover::maker::Actor, supervisor::UnitReason>>,
+    _contract_setup_supervisor:
+        Address<supervisor::Actor<contract_setup::maker::Actor, supervisor::UnitReason>>,
     _maker_offer_supervisor:
         Address<supervisor::Actor<xtra_libp2p_offer::maker::Actor, supervisor::UnitReason>>,
     _position_metrics_actor: Address<position_metrics::Actor>,
     _pong_actor: Address<pong::Actor>,
+    _identify_dialer_supervisor:
+        Address<supervisor::Actor<xtra_libp2p_identify::dialer::Actor, supervisor::UnitReason>>,
+    _identify_listener_actor: Address<xtra_libp2p_identify::listener::Actor>,
+    _directory_dialer_supervisor: Option<Address<supervisor::Actor<dialer::Actor, dialer::Error>>>,
+    _directory_publisher_supervisor: Option<
+        Address<supervisor::Actor<xtra_libp2p_directory::maker::Actor, supervisor::UnitReason>>,
+    >,
 }
 
 impl<O, W> ActorSystem<O, W>
 where
     O: Handler<oracle::MonitorAttestation, Return = ()>
         + Handler<oracle::GetAnnouncement, Return = Result<Announcement, NoAnnouncement>>
+        + Handler<oracle::SyncAnnouncement, Return = ()>
+        + Handler<oracle::SettlementIntervalInUse, Return = ()>
         + Actor<Stop = ()>,
     W: Handler<wallet::BuildPartyParams, Return = Result<PartyParams>>
         + Handler<wallet::Sign, Return = Result<PartiallySignedTransaction>>
@@ -95,10 +146,16 @@ where
         settlement_interval: time::Duration,
         n_payouts: usize,
         projection_actor: Address<projection::Actor>,
+        notifier_actor: Address<notifier::Actor>,
+        backup_actor: Option<Address<backup::Actor>>,
         identity: Identities,
         heartbeat_interval: Duration,
         p2p_socket: SocketAddr,
         listen_multiaddr: Multiaddr,
+        onion_service: Option<OnionService>,
+        limits: Limits,
+        auto_accept: Option<auto_accept::Policy>,
+        directory: Option<Directory>,
     ) -> Result<Self>
     where
         M: Handler<monitor::StartMonitoring, Return = ()>
@@ -113,6 +170,7 @@ where
         let (inc_conn_addr, inc_conn_ctx) = Context::new(None);
         let (process_manager_addr, process_manager_ctx) = Context::new(None);
         let (time_to_first_position_addr, time_to_first_position_ctx) = Context::new(None);
+        let (cfd_actor_addr, cfd_actor_ctx) = Context::new(None);
 
         let executor = command::Executor::new(db.clone(), process_manager_addr.clone());
 
@@ -122,22 +180,35 @@ where
             .create(None)
             .spawn(&mut tasks);
 
-        tasks.add(process_manager_ctx.run(process_manager::Actor::new(
-            db.clone(),
-            Role::Maker,
-            projection_actor.clone().into(),
-            position_metrics_actor.clone().into(),
-            monitor_addr.clone().into(),
-            monitor_addr.clone().into(),
-            monitor_addr.clone().into(),
-            monitor_addr.into(),
-            oracle_addr.clone().into(),
-        )));
+        tasks.add(
+            process_manager_ctx
+                .instrumented()
+                .run(process_manager::Actor::new(
+                    db.clone(),
+                    Role::Maker,
+                    projection_actor.clone().into(),
+                    position_metrics_actor.clone().into(),
+                    notifier_actor.into(),
+                    monitor_addr.clone().into(),
+                    monitor_addr.clone().into(),
+                    monitor_addr.clone().into(),
+                    monitor_addr.into(),
+                    oracle_addr.clone().into(),
+                    backup_actor.map(Into::into),
+                )),
+        );
 
         let (collab_settlement_supervisor, libp2p_collab_settlement_addr) =
             supervisor::Actor::new({
                 let executor = executor.clone();
-                move || collab_settlement::maker::Actor::new(executor.clone(), n_payouts)
+                let cfd_actor_addr = cfd_actor_addr.clone();
+                move || {
+                    collab_settlement::maker::Actor::new(
+                        executor.clone(),
+                        n_payouts,
+                        cfd_actor_addr.clone().into(),
+                    )
+                }
             });
         let collab_settlement_supervisor =
             collab_settlement_supervisor.create(None).spawn(&mut tasks);
@@ -145,12 +216,14 @@ where
         let (rollover_supervisor, libp2p_rollover_addr) = supervisor::Actor::new({
             let executor = executor.clone();
             let oracle_addr = oracle_addr.clone();
+            let cfd_actor_addr = cfd_actor_addr.clone();
             move || {
                 rollover::maker::Actor::new(
                     executor.clone(),
                     oracle_pk,
                     oracle_addr.clone().into(),
                     n_payouts,
+                    cfd_actor_addr.clone().into(),
                 )
             }
         });
@@ -164,27 +237,115 @@ where
         });
         let _maker_offer_supervisor = supervisor.create(None).spawn(&mut tasks);
 
-        let cfd_actor_addr = cfd::Actor::new(
-            db.clone(),
-            wallet_addr.clone(),
-            settlement_interval,
-            oracle_pk,
-            projection_actor,
-            process_manager_addr,
-            inc_conn_addr,
-            oracle_addr,
-            time_to_first_position_addr,
-            n_payouts,
-            libp2p_rollover_addr.clone(),
-            libp2p_collab_settlement_addr.clone(),
-            maker_offer_address.clone(),
+        let directory = directory
+            .map(|directory| {
+                let directory_peer_id = directory
+                    .address
+                    .clone()
+                    .extract_peer_id()
+                    .context("Directory address must end with a peer id")?;
+
+                let (publisher_supervisor, publisher_address) = supervisor::Actor::new({
+                    let endpoint_addr = endpoint_addr.clone();
+                    let keypair = identity.libp2p.clone();
+                    let maker_identity = Identity::new(identity.identity_pk);
+                    let announced_multiaddrs = directory.announced_multiaddrs;
+                    move || {
+                        xtra_libp2p_directory::maker::Actor::new(
+                            endpoint_addr.clone(),
+                            keypair.clone(),
+                            directory_peer_id,
+                            maker_identity,
+                            announced_multiaddrs.clone(),
+                        )
+                    }
+                });
+
+                let (dialer_supervisor, dialer_actor) = supervisor::Actor::with_policy(
+                    {
+                        let endpoint_addr = endpoint_addr.clone();
+                        let address = directory.address;
+                        move || {
+                            dialer::Actor::new(endpoint_addr.clone(), vec![address.clone()], vec![])
+                        }
+                    },
+                    always_restart_after(RESTART_INTERVAL),
+                );
+
+                anyhow::Ok((
+                    directory_peer_id,
+                    (publisher_supervisor, publisher_address),
+                    (dialer_supervisor, dialer_actor),
+                ))
+            })
+            .transpose()?;
+
+        let (contract_setup_supervisor, libp2p_contract_setup_addr) = supervisor::Actor::new({
+            let executor = executor.clone();
+            let oracle_addr = oracle_addr.clone();
+            let wallet_addr = wallet_addr.clone();
+            let cfd_actor_addr = cfd_actor_addr.clone();
+            move || {
+                contract_setup::maker::Actor::new(
+                    executor.clone(),
+                    oracle_pk,
+                    oracle_addr.clone().into(),
+                    (wallet_addr.clone().into(), wallet_addr.clone().into()),
+                    (cfd_actor_addr.clone().into(), cfd_actor_addr.clone().into()),
+                    n_payouts,
+                )
+            }
+        });
+        let contract_setup_supervisor = contract_setup_supervisor.create(None).spawn(&mut tasks);
+
+        tasks.add(
+            cfd_actor_ctx.instrumented().run(cfd::Actor::new(
+                db.clone(),
+                wallet_addr.clone(),
+                settlement_interval,
+                oracle_pk,
+                projection_actor,
+                process_manager_addr,
+                inc_conn_addr,
+                oracle_addr,
+                time_to_first_position_addr,
+                n_payouts,
+                libp2p_rollover_addr.clone(),
+                libp2p_collab_settlement_addr.clone(),
+                libp2p_contract_setup_addr.clone(),
+                maker_offer_address.clone(),
+                auto_accept,
+                directory
+                    .as_ref()
+                    .map(|(_, (_, publisher_address), _)| publisher_address.clone().into()),
+            )),
+        );
+
+        let (ping_supervisor, ping_address) = supervisor::Actor::new({
+            let endpoint_addr = endpoint_addr.clone();
+            move || ping::Actor::new(endpoint_addr.clone(), PING_INTERVAL)
+        });
+
+        let identify_listener_actor = xtra_libp2p_identify::listener::Actor::new(
+            endpoint_addr.clone(),
+            version::version().to_string(),
+            identify::features(),
         )
         .create(None)
         .spawn(&mut tasks);
 
-        let (ping_supervisor, ping_address) = supervisor::Actor::new({
+        let (identify_dialer_supervisor, identify_dialer_actor) = supervisor::Actor::new({
             let endpoint_addr = endpoint_addr.clone();
-            move || ping::Actor::new(endpoint_addr.clone(), PING_INTERVAL)
+            move || xtra_libp2p_identify::dialer::Actor::new(endpoint_addr.clone(), vec![])
+        });
+
+        let onion_listener = onion_service.as_ref().map(|onion_service| {
+            let endpoint_addr = endpoint_addr.clone();
+            let onion_address = onion_service.address.clone();
+            supervisor::Actor::with_policy(
+                move || listener::Actor::new(endpoint_addr.clone(), onion_address.clone()),
+                always_restart_after(RESTART_INTERVAL),
+            )
         });
 
         let (listener_supervisor, listener_actor) = supervisor::Actor::with_policy(
@@ -194,8 +355,38 @@ where
 
         let pong_address = pong::Actor::default().create(None).spawn(&mut tasks);
 
+        let mut listen_address_removed_subscribers = vec![listener_actor.into()];
+        if let Some((_, onion_listener_actor)) = &onion_listener {
+            listen_address_removed_subscribers.push(onion_listener_actor.clone().into());
+        }
+
+        let mut connection_established_subscribers = vec![
+            ping_address.clone().into(),
+            maker_offer_address.clone().into(),
+            identify_dialer_actor.clone().into(),
+        ];
+        let mut connection_dropped_subscribers = vec![
+            ping_address.into(),
+            maker_offer_address.into(),
+            identify_dialer_actor.into(),
+        ];
+        if let Some((_, (_, publisher_address), (_, dialer_actor))) = &directory {
+            connection_established_subscribers.push(publisher_address.clone().into());
+            connection_dropped_subscribers.push(dialer_actor.clone().into());
+        }
+
+        let dns_transport = SharedTransport::new(
+            TokioDnsConfig::system(TokioTcpConfig::new())
+                .context("Failed to read system DNS configuration")?,
+        );
         let endpoint = Endpoint::new(
-            Box::new(TokioTcpConfig::new),
+            Box::new(move || {
+                let transport = TorTransport::new(dns_transport.clone());
+                match onion_service.clone() {
+                    Some(onion_service) => transport.with_onion_service(onion_service),
+                    None => transport,
+                }
+            }),
             identity.libp2p,
             ENDPOINT_CONNECTION_TIMEOUT,
             [
@@ -204,23 +395,39 @@ where
                     collab_settlement::PROTOCOL,
                     libp2p_collab_settlement_addr.into(),
                 ),
+                (contract_setup::PROTOCOL, libp2p_contract_setup_addr.into()),
                 (xtra_libp2p_ping::PROTOCOL_NAME, pong_address.clone().into()),
+                (
+                    xtra_libp2p_identify::PROTOCOL_NAME,
+                    identify_listener_actor.clone().into(),
+                ),
             ],
             endpoint::Subscribers::new(
-                vec![
-                    ping_address.clone().into(),
-                    maker_offer_address.clone().into(),
-                ],
-                vec![ping_address.into(), maker_offer_address.into()],
+                connection_established_subscribers,
+                connection_dropped_subscribers,
                 vec![],
-                vec![listener_actor.into()],
+                listen_address_removed_subscribers,
             ),
-        );
-
+        )
+        .with_limits(match &directory {
+            Some((directory_peer_id, _, _)) => reserve_for_directory(limits, *directory_peer_id),
+            None => limits,
+        });
         tasks.add(endpoint_context.run(endpoint));
 
         let listener_supervisor = listener_supervisor.create(None).spawn(&mut tasks);
+        let onion_listener_supervisor = onion_listener.map(|(onion_listener_supervisor, _)| {
+            onion_listener_supervisor.create(None).spawn(&mut tasks)
+        });
         let ping_supervisor = ping_supervisor.create(None).spawn(&mut tasks);
+        let identify_dialer_supervisor = identify_dialer_supervisor.create(None).spawn(&mut tasks);
+        let (directory_publisher_supervisor, directory_dialer_supervisor) = match directory {
+            Some((_, (publisher_supervisor, _), (dialer_supervisor, _))) => (
+                Some(publisher_supervisor.create(None).spawn(&mut tasks)),
+                Some(dialer_supervisor.create(None).spawn(&mut tasks)),
+            ),
+            None => (None, None),
+        };
 
         tasks.add(
             inc_conn_ctx
@@ -235,9 +442,17 @@ where
                 )),
         );
 
-        tasks.add(monitor_ctx.run(monitor_constructor(executor.clone())?));
+        tasks.add(
+            monitor_ctx
+                .instrumented()
+                .run(monitor_constructor(executor.clone())?),
+        );
 
-        tasks.add(oracle_ctx.run(oracle_constructor(executor.clone())));
+        tasks.add(
+            oracle_ctx
+                .instrumented()
+                .run(oracle_constructor(executor.clone())),
+        );
 
         let archive_closed_cfds_actor = archive_closed_cfds::Actor::new(db.clone())
             .create(None)
@@ -259,12 +474,18 @@ where
             executor,
             _tasks: tasks,
             _listener_supervisor: listener_supervisor,
+            _onion_listener_supervisor: onion_listener_supervisor,
             _ping_supervisor: ping_supervisor,
             _rollover_supervisor: rollover_supervisor,
             _collab_settlement_supervisor: collab_settlement_supervisor,
+            _contract_setup_supervisor: contract_setup_supervisor,
             _maker_offer_supervisor,
             _position_metrics_actor: position_metrics_actor,
             _pong_actor: pong_address,
+            _identify_dialer_supervisor: identify_dialer_supervisor,
+            _identify_listener_actor: identify_listener_actor,
+            _directory_dialer_supervisor: directory_dialer_supervisor,
+            _directory_publisher_supervisor: directory_publisher_supervisor,
         })
     }
 
@@ -283,7 +504,16 @@ where
         funding_rate_short: FundingRate,
         opening_fee: OpeningFee,
         leverage_choices: Vec<Leverage>,
+        expiry: Option<Timestamp>,
+        settlement_interval_hours: Option<u32>,
     ) -> Result<()> {
+        let expiry = expiry
+            .map(|expiry| OffsetDateTime::from_unix_timestamp(expiry.seconds()))
+            .transpose()
+            .context("Invalid expiry")?;
+        let settlement_interval =
+            settlement_interval_hours.map(|hours| time::Duration::hours(hours.into()));
+
         self.cfd_actor
             .send(cfd::OfferParams {
                 price_long,
@@ -295,6 +525,8 @@ where
                 funding_rate_short,
                 opening_fee,
                 leverage_choices,
+                expiry,
+                settlement_interval,
             })
             .await??;
 
@@ -367,3 +599,44 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use xtra_libp2p::limits::Violation;
+
+    #[test]
+    fn directory_can_connect_despite_allowlist_and_connection_limit() {
+        let taker = PeerId::random();
+        let directory = PeerId::random();
+        let limits = reserve_for_directory(
+            Limits {
+                allowed_peers: Some(HashSet::from([taker])),
+                max_connections: Some(2),
+                ..Limits::default()
+            },
+            directory,
+        );
+
+        let connected = [PeerId::random(), PeerId::random()];
+
+        assert_eq!(limits.check_connection(&directory, &connected), Ok(()));
+        assert_eq!(
+            limits.check_connection(&taker, &connected),
+            Err(Violation::ConnectionLimit)
+        );
+        assert_eq!(
+            limits.check_connection(&PeerId::random(), []),
+            Err(Violation::DeniedPeer)
+        );
+    }
+
+    #[test]
+    fn directory_does_not_restrict_peers_without_allowlist() {
+        let limits = reserve_for_directory(Limits::default(), PeerId::random());
+
+        assert_eq!(limits.allowed_peers, None);
+        assert_eq!(limits.check_connection(&PeerId::random(), []), Ok(()));
+    }
+}
@@ -0,0 +1,439 @@
+use anyhow::Context;
+use anyhow::Result;
+use model::libp2p::PeerId;
+use model::Cfd;
+use model::Price;
+use model::Usd;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Declarative policy for accepting requests of takers without operator intervention.
+///
+/// The policy is loaded from a TOML file, e.g.
+///
+/// ```toml
+/// [orders]
+/// max_quantity = 1000
+/// known_takers = ["<libp2p peer id of the taker>"]
+///
+/// [rollovers]
+/// min_liquidation_distance_bps = 500
+///
+/// [settlements]
+/// max_price_deviation_bps = 50
+/// ```
+///
+/// Requests of a kind without a section, or not satisfying the policy, are left to the operator.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub orders: Option<OrderPolicy>,
+    pub rollovers: Option<RolloverPolicy>,
+    pub settlements: Option<SettlementPolicy>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OrderPolicy {
+    /// Orders of up to this quantity are accepted.
+    pub max_quantity: Usd,
+    /// Only orders of these takers are accepted.
+    ///
+    /// Takers are identified by their libp2p peer id because, unlike the identity a taker reports
+    /// when taking an order, it is authenticated by the connection.
+    pub known_takers: HashSet<PeerId>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RolloverPolicy {
+    /// Rollovers are accepted unless our quote is closer than this to either liquidation price.
+    pub min_liquidation_distance_bps: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettlementPolicy {
+    /// Settlements are accepted if the proposed price deviates at most this much from our quote.
+    pub max_price_deviation_bps: Decimal,
+}
+
+/// The outcome of evaluating a request against the [`Policy`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// Accept on behalf of the operator
+    Accept { reason: String },
+    /// Leave the decision to the operator
+    Defer { reason: String },
+}
+
+impl Policy {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let policy = toml::from_str::<Self>(&content)
+            .with_context(|| format!("Invalid auto-accept policy in {}", path.display()))?;
+        policy.validate()?;
+
+        Ok(policy)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(rollovers) = &self.rollovers {
+            anyhow::ensure!(
+                !rollovers.min_liquidation_distance_bps.is_sign_negative(),
+                "Minimum distance to liquidation must not be negative"
+            );
+        }
+        if let Some(settlements) = &self.settlements {
+            anyhow::ensure!(
+                !settlements.max_price_deviation_bps.is_sign_negative(),
+                "Maximum deviation of the settlement price must not be negative"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Decide upon an order of the given quantity.
+    ///
+    /// `taker_peer_id` must be the peer id authenticated by the connection the order was received
+    /// on, if any. Orders without one cannot be attributed to a known taker and are deferred.
+    pub fn decide_order(&self, taker_peer_id: Option<PeerId>, quantity: Usd) -> Decision {
+        let policy = match &self.orders {
+            Some(policy) => policy,
+            None => return Decision::defer("No policy for orders"),
+        };
+        let taker_peer_id = match taker_peer_id {
+            Some(taker_peer_id) => taker_peer_id,
+            None => return Decision::defer("Taker is not authenticated by its peer id"),
+        };
+
+        if !policy.known_takers.contains(&taker_peer_id) {
+            return Decision::defer(format!("Taker {taker_peer_id} is not known"));
+        }
+        if quantity > policy.max_quantity {
+            return Decision::defer(format!(
+                "Quantity {quantity} exceeds {}",
+                policy.max_quantity
+            ));
+        }
+
+        Decision::accept(format!(
+            "Quantity {quantity} of known taker {taker_peer_id} is within {}",
+            policy.max_quantity
+        ))
+    }
+
+    /// Decide upon a rollover of the given CFD based on our quote for closing it.
+    pub fn decide_rollover(&self, cfd: &Cfd, quote: Option<Price>) -> Decision {
+        let policy = match &self.rollovers {
+            Some(policy) => policy,
+            None => return Decision::defer("No policy for rollovers"),
+        };
+        let quote = match quote {
+            Some(quote) => quote.into_decimal(),
+            None => return Decision::defer("No quote to assess the distance to liquidation"),
+        };
+
+        let to_long_liquidation = quote - cfd.long_liquidation_price().into_decimal();
+        let to_short_liquidation = cfd.short_liquidation_price().into_decimal() - quote;
+        let distance_bps = to_bps(to_long_liquidation.min(to_short_liquidation), quote);
+
+        if distance_bps < policy.min_liquidation_distance_bps {
+            return Decision::defer(format!(
+                "Quote {quote} is {distance_bps} bps away from liquidation, less than {}",
+                policy.min_liquidation_distance_bps
+            ));
+        }
+
+        Decision::accept(format!(
+            "Quote {quote} is {distance_bps} bps away from liquidation"
+        ))
+    }
+
+    /// Decide upon a settlement at the proposed price based on our quote for closing the CFD.
+    pub fn decide_settlement(&self, proposed: Price, quote: Option<Price>) -> Decision {
+        let policy = match &self.settlements {
+            Some(policy) => policy,
+            None => return Decision::defer("No policy for settlements"),
+        };
+        let quote = match quote {
+            Some(quote) => quote.into_decimal(),
+            None => return Decision::defer("No quote to compare the proposed price with"),
+        };
+
+        let deviation_bps = to_bps((proposed.into_decimal() - quote).abs(), quote);
+
+        if deviation_bps > policy.max_price_deviation_bps {
+            return Decision::defer(format!(
+                "Proposed price {proposed} deviates {deviation_bps} bps from quote {quote}, more than {}",
+                policy.max_price_deviation_bps
+            ));
+        }
+
+        Decision::accept(format!(
+            "Proposed price {proposed} deviates {deviation_bps} bps from quote {quote}"
+        ))
+    }
+}
+
+impl Decision {
+    fn accept(reason: impl Into<String>) -> Self {
+        Decision::Accept {
+            reason: reason.into(),
+        }
+    }
+
+    fn defer(reason: impl Into<String>) -> Self {
+        Decision::Defer {
+            reason: reason.into(),
+        }
+    }
+}
+
+fn to_bps(difference: Decimal, price: Decimal) -> Decimal {
+    (difference / price * Decimal::from(10_000)).round_dp(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::ContractType;
+    use model::FundingRate;
+    use model::Identity;
+    use model::Leverage;
+    use model::OpeningFee;
+    use model::OrderId;
+    use model::Position;
+    use model::Role;
+    use model::TxFeeRate;
+
+    #[test]
+    fn order_up_to_max_quantity_of_known_taker_is_accepted() {
+        let taker = PeerId::random();
+        let policy = order_policy(taker, 1000);
+
+        assert!(matches!(
+            policy.decide_order(Some(taker), usd(1000)),
+            Decision::Accept { .. }
+        ));
+        assert!(matches!(
+            policy.decide_order(Some(taker), usd(1001)),
+            Decision::Defer { .. }
+        ));
+    }
+
+    #[test]
+    fn order_of_unknown_taker_is_deferred() {
+        let policy = order_policy(PeerId::random(), 1000);
+
+        assert!(matches!(
+            policy.decide_order(Some(PeerId::random()), usd(100)),
+            Decision::Defer { .. }
+        ));
+    }
+
+    #[test]
+    fn order_of_peer_spoofing_identity_of_known_taker_is_deferred() {
+        let known_taker = PeerId::random();
+        let policy = order_policy(known_taker, 1000);
+
+        // The spoofer reports the identity of the known taker, but is connected as another peer
+        let spoofed = cfd_with_counterparty(identity(1), PeerId::random());
+
+        assert!(matches!(
+            policy.decide_order(spoofed.counterparty_peer_id(), spoofed.quantity()),
+            Decision::Defer { .. }
+        ));
+    }
+
+    #[test]
+    fn order_without_authenticated_peer_id_is_deferred() {
+        let policy = order_policy(PeerId::random(), 1000);
+
+        assert_eq!(
+            policy.decide_order(None, usd(100)),
+            Decision::defer("Taker is not authenticated by its peer id")
+        );
+    }
+
+    #[test]
+    fn known_takers_are_parsed_from_peer_ids() {
+        let taker = PeerId::random();
+
+        let policy = toml::from_str::<Policy>(&format!(
+            "[orders]\nmax_quantity = 1000\nknown_takers = [\"{taker}\"]\n"
+        ))
+        .unwrap();
+
+        assert!(matches!(
+            policy.decide_order(Some(taker), usd(100)),
+            Decision::Accept { .. }
+        ));
+    }
+
+    #[test]
+    fn requests_without_policy_are_deferred() {
+        let policy = Policy::default();
+
+        assert_eq!(
+            policy.decide_order(Some(PeerId::random()), usd(100)),
+            Decision::defer("No policy for orders")
+        );
+        assert_eq!(
+            policy.decide_rollover(&cfd(), Some(price(25_000))),
+            Decision::defer("No policy for rollovers")
+        );
+        assert_eq!(
+            policy.decide_settlement(price(10_000), Some(price(10_000))),
+            Decision::defer("No policy for settlements")
+        );
+    }
+
+    #[test]
+    fn rollover_is_accepted_at_exactly_min_liquidation_distance() {
+        // The taker is long with leverage 2, hence liquidated at 20000
+        let quote = Some(price(25_000));
+
+        assert!(matches!(
+            rollover_policy(Decimal::from(2000)).decide_rollover(&cfd(), quote),
+            Decision::Accept { .. }
+        ));
+        assert!(matches!(
+            rollover_policy(Decimal::new(200001, 2)).decide_rollover(&cfd(), quote),
+            Decision::Defer { .. }
+        ));
+    }
+
+    #[test]
+    fn rollover_beyond_liquidation_or_without_quote_is_deferred() {
+        let policy = rollover_policy(Decimal::ZERO);
+
+        assert!(matches!(
+            policy.decide_rollover(&cfd(), Some(price(19_000))),
+            Decision::Defer { .. }
+        ));
+        assert!(matches!(
+            policy.decide_rollover(&cfd(), None),
+            Decision::Defer { .. }
+        ));
+    }
+
+    #[test]
+    fn settlement_is_accepted_up_to_max_deviation_in_either_direction() {
+        let policy = settlement_policy(Decimal::from(50));
+        let quote = Some(price(10_000));
+
+        assert!(matches!(
+            policy.decide_settlement(price(10_050), quote),
+            Decision::Accept { .. }
+        ));
+        assert!(matches!(
+            policy.decide_settlement(price(9_950), quote),
+            Decision::Accept { .. }
+        ));
+        assert!(matches!(
+            policy.decide_settlement(Price::new(Decimal::new(1_005_001, 2)).unwrap(), quote),
+            Decision::Defer { .. }
+        ));
+        assert!(matches!(
+            policy.decide_settlement(price(10_000), None),
+            Decision::Defer { .. }
+        ));
+    }
+
+    #[test]
+    fn to_bps_rounds_to_two_decimal_places() {
+        assert_eq!(
+            to_bps(Decimal::from(1), Decimal::from(10_000)),
+            Decimal::ONE
+        );
+        assert_eq!(
+            to_bps(Decimal::from(1), Decimal::from(3)),
+            Decimal::new(333333, 2)
+        );
+        assert_eq!(
+            to_bps(Decimal::from(-5), Decimal::from(100)),
+            Decimal::from(-500)
+        );
+    }
+
+    #[test]
+    fn negative_thresholds_are_rejected() {
+        let policy = Policy {
+            rollovers: Some(RolloverPolicy {
+                min_liquidation_distance_bps: Decimal::from(-1),
+            }),
+            ..Policy::default()
+        };
+
+        assert!(policy.validate().is_err());
+    }
+
+    fn order_policy(known_taker: PeerId, max_quantity: u64) -> Policy {
+        Policy {
+            orders: Some(OrderPolicy {
+                max_quantity: usd(max_quantity),
+                known_takers: HashSet::from([known_taker]),
+            }),
+            ..Policy::default()
+        }
+    }
+
+    fn rollover_policy(min_liquidation_distance_bps: Decimal) -> Policy {
+        Policy {
+            rollovers: Some(RolloverPolicy {
+                min_liquidation_distance_bps,
+            }),
+            ..Policy::default()
+        }
+    }
+
+    fn settlement_policy(max_price_deviation_bps: Decimal) -> Policy {
+        Policy {
+            settlements: Some(SettlementPolicy {
+                max_price_deviation_bps,
+            }),
+            ..Policy::default()
+        }
+    }
+
+    /// A CFD in which we are short at 30000 against a taker with leverage 2.
+    fn cfd() -> Cfd {
+        cfd_with_counterparty(identity(1), PeerId::random())
+    }
+
+    fn cfd_with_counterparty(taker_id: Identity, taker_peer_id: PeerId) -> Cfd {
+        Cfd::new(
+            OrderId::default(),
+            Position::Short,
+            price(30_000),
+            Leverage::TWO,
+            time::Duration::hours(24),
+            Role::Maker,
+            usd(1000),
+            taker_id,
+            Some(taker_peer_id),
+            OpeningFee::default(),
+            FundingRate::default(),
+            TxFeeRate::default(),
+            ContractType::Perpetual,
+        )
+    }
+
+    fn identity(byte: u8) -> Identity {
+        Identity::new(x25519_dalek::PublicKey::from([byte; 32]))
+    }
+
+    fn usd(value: u64) -> Usd {
+        Usd::new(Decimal::from(value))
+    }
+
+    fn price(value: u64) -> Price {
+        Price::new(Decimal::from(value)).unwrap()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::auto_accept;
 use crate::collab_settlement;
 use crate::connection;
 use crate::connection::NoConnection;
@@ -25,7 +26,9 @@ use model::libp2p::PeerId;
 use model::olivia;
 use model::olivia::Announcement;
 use model::olivia::BitMexPriceEventId;
+use model::AutoAcceptAction;
 use model::Cfd;
+use model::ContractType;
 use model::FundingRate;
 use model::Identity;
 use model::Leverage;
@@ -42,10 +45,16 @@ use model::SettlementProposal;
 use model::Timestamp;
 use model::TxFeeRate;
 use model::Usd;
+use model::MAX_SETTLEMENT_INTERVAL;
+use model::MIN_SETTLEMENT_INTERVAL;
 use sqlite_db;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::future::Future;
 use time::Duration;
+use time::OffsetDateTime;
 use tokio_tasks::Tasks;
+use xtra::prelude::MessageChannel;
 use xtra::Actor as _;
 use xtra_productivity::xtra_productivity;
 use xtras::address_map::NotConnected;
@@ -60,6 +69,13 @@ pub struct NewOffers {
     pub params: OfferParams,
 }
 
+/// Funding rates derived from a reference feed, overriding the ones of the offer params.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpdateFundingRates {
+    pub funding_rate_long: FundingRate,
+    pub funding_rate_short: FundingRate,
+}
+
 #[derive(Clone, Copy)]
 pub struct AcceptOrder {
     pub order_id: OrderId,
@@ -93,6 +109,8 @@ pub struct RejectRollover {
 #[derive(Clone, Copy)]
 pub struct TakerConnected {
     pub id: Identity,
+    /// The libp2p peer id the taker announced on its legacy connection, if any.
+    pub peer_id: Option<PeerId>,
 }
 
 #[derive(Clone, Copy)]
@@ -111,45 +129,136 @@ pub struct OfferParams {
     pub funding_rate_short: FundingRate,
     pub opening_fee: OpeningFee,
     pub leverage_choices: Vec<Leverage>,
+    /// Offer fixed-expiry contracts settling at this time instead of perpetual contracts
+    pub expiry: Option<OffsetDateTime>,
+    /// Offer perpetual contracts with this settlement interval instead of the default one
+    pub settlement_interval: Option<Duration>,
 }
 
 impl OfferParams {
     fn pick_oracle_event_id(settlement_interval: Duration) -> BitMexPriceEventId {
-        olivia::next_announcement_after(time::OffsetDateTime::now_utc() + settlement_interval)
+        olivia::next_announcement_after(OffsetDateTime::now_utc() + settlement_interval)
+    }
+
+    /// Ensure that takers will accept fixed-expiry contracts with our expiry.
+    fn validate_expiry(&self, now: OffsetDateTime) -> Result<()> {
+        let expiry = match self.expiry {
+            Some(expiry) => expiry,
+            None => return Ok(()),
+        };
+
+        if (expiry.minute(), expiry.second(), expiry.nanosecond()) != (0, 0, 0) {
+            bail!("Expiry {expiry} is not on the full hour");
+        }
+        if expiry <= now + Duration::HOUR {
+            bail!("Expiry {expiry} is less than an hour from now");
+        }
+        if expiry > now + Order::MAX_EXPIRY {
+            bail!(
+                "Expiry {expiry} is more than {} days from now",
+                Order::MAX_EXPIRY.whole_days()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Ensure that takers will accept perpetual contracts with our settlement interval.
+    fn validate_settlement_interval(&self) -> Result<()> {
+        let settlement_interval = match self.settlement_interval {
+            Some(settlement_interval) => settlement_interval,
+            None => return Ok(()),
+        };
+
+        if settlement_interval.whole_seconds() % Duration::HOUR.whole_seconds() != 0 {
+            bail!("Settlement interval {settlement_interval} is not a whole number of hours");
+        }
+        if settlement_interval < MIN_SETTLEMENT_INTERVAL
+            || settlement_interval > MAX_SETTLEMENT_INTERVAL
+        {
+            bail!(
+                "Settlement interval {settlement_interval} is not within [{MIN_SETTLEMENT_INTERVAL}, {MAX_SETTLEMENT_INTERVAL}]"
+            );
+        }
+
+        Ok(())
+    }
+
+    fn contract_type(&self) -> ContractType {
+        match self.expiry {
+            None => ContractType::Perpetual,
+            Some(_) => ContractType::FixedExpiry,
+        }
+    }
+
+    /// The oracle event and settlement interval of a new order.
+    ///
+    /// Fixed-expiry contracts settle on the event at their expiry, hence their settlement
+    /// interval spans the time until then.
+    fn settlement(&self, settlement_interval: Duration) -> (BitMexPriceEventId, Duration) {
+        match self.expiry {
+            None => (
+                Self::pick_oracle_event_id(settlement_interval),
+                settlement_interval,
+            ),
+            Some(expiry) => {
+                let time_until_expiry = expiry - OffsetDateTime::now_utc();
+                let hours_until_expiry = (time_until_expiry / Duration::HOUR).ceil() as i64;
+
+                (
+                    BitMexPriceEventId::with_20_digits(expiry),
+                    Duration::hours(hours_until_expiry),
+                )
+            }
+        }
+    }
+
+    /// Fixed-expiry contracts don't charge funding.
+    fn funding_rate(&self, funding_rate: FundingRate) -> FundingRate {
+        match self.expiry {
+            None => funding_rate,
+            Some(_) => FundingRate::default(),
+        }
     }
 
     pub fn create_long_order(&self, settlement_interval: Duration) -> Option<Order> {
         self.price_long.map(|price_long| {
+            let (oracle_event_id, settlement_interval) = self.settlement(settlement_interval);
+
             Order::new(
                 Position::Long,
                 price_long,
                 self.min_quantity,
                 self.max_quantity,
                 Origin::Ours,
-                Self::pick_oracle_event_id(settlement_interval),
+                oracle_event_id,
                 settlement_interval,
                 self.tx_fee_rate,
-                self.funding_rate_long,
+                self.funding_rate(self.funding_rate_long),
                 self.opening_fee,
                 self.leverage_choices.clone(),
+                self.contract_type(),
             )
         })
     }
 
     pub fn create_short_order(&self, settlement_interval: Duration) -> Option<Order> {
         self.price_short.map(|price_short| {
+            let (oracle_event_id, settlement_interval) = self.settlement(settlement_interval);
+
             Order::new(
                 Position::Short,
                 price_short,
                 self.min_quantity,
                 self.max_quantity,
                 Origin::Ours,
-                Self::pick_oracle_event_id(settlement_interval),
+                oracle_event_id,
                 settlement_interval,
                 self.tx_fee_rate,
-                self.funding_rate_short,
+                self.funding_rate(self.funding_rate_short),
                 self.opening_fee,
                 self.leverage_choices.clone(),
+                self.contract_type(),
             )
         })
     }
@@ -160,6 +269,8 @@ fn create_maker_offers(offer_params: OfferParams, settlement_interval: Duration)
         long: offer_params.create_long_order(settlement_interval),
         short: offer_params.create_short_order(settlement_interval),
         tx_fee_rate: offer_params.tx_fee_rate,
+        // Rollovers of perpetual contracts are charged at these rates, even if we currently offer
+        // fixed-expiry contracts
         funding_rate_long: offer_params.funding_rate_long,
         funding_rate_short: offer_params.funding_rate_short,
     }
@@ -189,16 +300,30 @@ pub struct Actor<O: 'static, T: 'static, W: 'static> {
     rollover_actors: AddressMap<OrderId, rollover::Actor>,
     takers: xtra::Address<T>,
     current_offers: Option<MakerOffers>,
+    /// The offer params as last set by the operator
+    offer_params: Option<OfferParams>,
+    /// Overrides the funding rates of the offer params if set
+    derived_funding_rates: Option<UpdateFundingRates>,
+    /// Requests of takers are left to the operator unless set
+    auto_accept: Option<auto_accept::Policy>,
     setup_actors: AddressMap<OrderId, contract_setup::Actor>,
     settlement_actors: AddressMap<OrderId, collab_settlement::Actor>,
     oracle: xtra::Address<O>,
     time_to_first_position: xtra::Address<time_to_first_position::Actor>,
     connected_takers: HashSet<Identity>,
+    /// The libp2p peer ids connected takers announced on their legacy connection.
+    ///
+    /// The legacy connection authenticates the identity of the taker, which allows us to check
+    /// the identity takers report when taking an order over libp2p.
+    taker_peer_ids: HashMap<Identity, PeerId>,
     n_payouts: usize,
     tasks: Tasks,
     libp2p_rollover: xtra::Address<daemon::rollover::maker::Actor>,
     libp2p_collab_settlement: xtra::Address<daemon::collab_settlement::maker::Actor>,
+    libp2p_contract_setup: xtra::Address<daemon::contract_setup::maker::Actor>,
     libp2p_offer: xtra::Address<xtra_libp2p_offer::maker::Actor>,
+    /// Keeps the fees in the maker's directory announcement up to date if set
+    directory: Option<MessageChannel<xtra_libp2p_directory::maker::NewFeeSchedule, ()>>,
 }
 
 impl<O, T, W> Actor<O, T, W> {
@@ -216,7 +341,10 @@ impl<O, T, W> Actor<O, T, W> {
         n_payouts: usize,
         libp2p_rollover: xtra::Address<daemon::rollover::maker::Actor>,
         libp2p_collab_settlement: xtra::Address<daemon::collab_settlement::maker::Actor>,
+        libp2p_contract_setup: xtra::Address<daemon::contract_setup::maker::Actor>,
         libp2p_offer: xtra::Address<xtra_libp2p_offer::maker::Actor>,
+        auto_accept: Option<auto_accept::Policy>,
+        directory: Option<MessageChannel<xtra_libp2p_directory::maker::NewFeeSchedule, ()>>,
     ) -> Self {
         Self {
             db: db.clone(),
@@ -229,16 +357,22 @@ impl<O, T, W> Actor<O, T, W> {
             rollover_actors: AddressMap::default(),
             takers,
             current_offers: None,
+            offer_params: None,
+            derived_funding_rates: None,
+            auto_accept,
             setup_actors: AddressMap::default(),
             oracle,
             time_to_first_position,
             n_payouts,
             connected_takers: HashSet::new(),
+            taker_peer_ids: HashMap::new(),
             settlement_actors: AddressMap::default(),
             tasks: Tasks::default(),
             libp2p_rollover,
             libp2p_collab_settlement,
+            libp2p_contract_setup,
             libp2p_offer,
+            directory,
         }
     }
 
@@ -259,7 +393,11 @@ impl<O, T, W> Actor<O, T, W>
 where
     T: xtra::Handler<connection::TakerMessage, Return = Result<(), NoConnection>>,
 {
-    async fn handle_taker_connected(&mut self, taker_id: Identity) -> Result<()> {
+    async fn handle_taker_connected(
+        &mut self,
+        taker_id: Identity,
+        peer_id: Option<PeerId>,
+    ) -> Result<()> {
         self.takers
             .send_async_safe(connection::TakerMessage {
                 taker_id,
@@ -270,6 +408,9 @@ where
         if !self.connected_takers.insert(taker_id) {
             tracing::warn!("Taker already connected: {:?}", &taker_id);
         }
+        if let Some(peer_id) = peer_id {
+            self.taker_peer_ids.insert(taker_id, peer_id);
+        }
         self.update_connected_takers().await?;
         self.time_to_first_position
             .send_async_safe(time_to_first_position::Connected::new(taker_id))
@@ -281,6 +422,7 @@ where
         if !self.connected_takers.remove(&taker_id) {
             tracing::warn!("Removed unknown taker: {:?}", &taker_id);
         }
+        self.taker_peer_ids.remove(&taker_id);
         self.update_connected_takers().await?;
         Ok(())
     }
@@ -292,13 +434,14 @@ where
         + xtra::Handler<oracle::MonitorAttestation, Return = ()>,
     T: xtra::Handler<connection::TakerMessage, Return = Result<(), NoConnection>>
         + xtra::Handler<connection::RegisterRollover, Return = ()>,
-    W: 'static,
+    W: 'static + Send,
 {
     async fn handle_propose_rollover(
         &mut self,
         RolloverProposal { order_id, .. }: RolloverProposal,
         taker_id: Identity,
         version: RolloverVersion,
+        this: xtra::Address<Self>,
     ) -> Result<()> {
         let rollover_actor_addr = rollover::Actor::new(
             order_id,
@@ -317,10 +460,66 @@ where
 
         self.rollover_actors.insert(order_id, rollover_actor_addr);
 
+        self.auto_accept_rollover(order_id, this).await;
+
         Ok(())
     }
 }
 
+impl<O, T, W> Actor<O, T, W>
+where
+    T: xtra::Handler<connection::BroadcastOffers, Return = ()>,
+{
+    /// Record the CFD for an order a taker wants to take.
+    ///
+    /// The orders in our current offers are replicated so that other takers can take the same
+    /// offer.
+    async fn record_taken_order(
+        &mut self,
+        order_to_take: &Order,
+        quantity: Usd,
+        taker_id: Identity,
+        taker_peer_id: Option<PeerId>,
+        leverage: Leverage,
+    ) -> Result<Cfd> {
+        let cfd = Cfd::from_order(
+            order_to_take,
+            quantity,
+            taker_id,
+            taker_peer_id,
+            Role::Maker,
+            leverage,
+        );
+
+        // Replicate the orders in the offers with new ones to allow other takers to use
+        // the same offer
+        if let Some(offers) = &self.current_offers {
+            self.current_offers = Some(offers.replicate());
+        }
+
+        self.takers
+            .send_async_safe(connection::BroadcastOffers(self.current_offers.clone()))
+            .await?;
+
+        self.libp2p_offer
+            .send_async_safe(xtra_libp2p_offer::maker::NewOffers::new(
+                self.current_offers.clone(),
+            ))
+            .await?;
+
+        self.projection
+            .send(projection::Update(self.current_offers.clone()))
+            .await?;
+
+        self.db.insert_cfd(&cfd).await?;
+        self.projection
+            .send(projection::CfdChanged(cfd.id()))
+            .await?;
+
+        Ok(cfd)
+    }
+}
+
 impl<O, T, W> Actor<O, T, W>
 where
     O: xtra::Handler<oracle::GetAnnouncement, Return = Result<Announcement, NoAnnouncement>>
@@ -338,6 +537,7 @@ where
         order_id: OrderId,
         quantity: Usd,
         leverage: Leverage,
+        this: xtra::Address<Self>,
     ) -> Result<()> {
         tracing::debug!(%taker_id, %quantity, %order_id, "Taker wants to take an order");
 
@@ -373,48 +573,19 @@ where
             return Ok(());
         };
 
-        let cfd = Cfd::from_order(
-            &order_to_take,
-            quantity,
-            taker_id,
-            taker_peer_id.map(PeerId::from),
-            Role::Maker,
-            leverage,
-        );
-
-        // 2. Replicate the orders in the offers with new ones to allow other takers to use
-        // the same offer
-        if let Some(offers) = &self.current_offers {
-            self.current_offers = Some(offers.replicate());
-        }
-
-        self.takers
-            .send_async_safe(connection::BroadcastOffers(self.current_offers.clone()))
+        // 2. Record the CFD and replicate the offers
+        let cfd = self
+            .record_taken_order(&order_to_take, quantity, taker_id, taker_peer_id, leverage)
             .await?;
 
-        self.libp2p_offer
-            .send_async_safe(xtra_libp2p_offer::maker::NewOffers::new(
-                self.current_offers.clone(),
-            ))
-            .await?;
-
-        self.projection
-            .send(projection::Update(self.current_offers.clone()))
-            .await?;
-
-        self.db.insert_cfd(&cfd).await?;
-        self.projection
-            .send(projection::CfdChanged(cfd.id()))
-            .await?;
-
-        // 4. Try to get the oracle announcement, if that fails we should exit prior to changing any
+        // 3. Try to get the oracle announcement, if that fails we should exit prior to changing any
         // state
         let announcement = self
             .oracle
             .send(oracle::GetAnnouncement(order_to_take.oracle_event_id))
             .await??;
 
-        // 5. Start up contract setup actor
+        // 4. Start up contract setup actor
         let addr = contract_setup::Actor::new(
             self.db.clone(),
             self.process_manager.clone(),
@@ -434,6 +605,9 @@ where
 
         disconnected.insert(addr);
 
+        // The peer id announced over the legacy connection is not authenticated
+        self.auto_accept_order(order_id, None, this).await;
+
         Ok(())
     }
 }
@@ -445,6 +619,23 @@ impl<O, T, W> Actor<O, T, W> {
 
         tracing::debug!(%order_id, "Maker accepts order");
 
+        // We try to dispatch to libp2p contract setup first
+        match self
+            .libp2p_contract_setup
+            .send(daemon::contract_setup::maker::Accept { order_id })
+            .await
+        {
+            // Return early if dispatch to libp2p contract setup worked
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(error)) => {
+                tracing::debug!("Try fallback to legacy contract setup because unable to handle accept via libp2p: {error:#}");
+            }
+            Err(error) => {
+                // we should never see this given that the libp2p actor is always running
+                tracing::error!("Try fallback to legacy contract setup because unable to dispatch accept to libp2p actor: {error:#}");
+            }
+        }
+
         match self
             .setup_actors
             .send(&order_id, contract_setup::Accepted)
@@ -477,6 +668,23 @@ impl<O, T, W> Actor<O, T, W> {
 
         tracing::debug!(%order_id, "Maker rejects order");
 
+        // We try to dispatch to libp2p contract setup first
+        match self
+            .libp2p_contract_setup
+            .send(daemon::contract_setup::maker::Reject { order_id })
+            .await
+        {
+            // Return early if dispatch to libp2p contract setup worked
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(error)) => {
+                tracing::debug!("Try fallback to legacy contract setup because unable to handle reject via libp2p: {error:#}");
+            }
+            Err(error) => {
+                // we should never see this given that the libp2p actor is always running
+                tracing::error!("Try fallback to legacy contract setup because unable to dispatch reject to libp2p actor: {error:#}");
+            }
+        }
+
         match self
             .setup_actors
             .send(&order_id, contract_setup::Rejected)
@@ -715,6 +923,7 @@ where
         &mut self,
         taker_id: Identity,
         proposal: SettlementProposal,
+        this: xtra::Address<Self>,
     ) -> Result<()> {
         let order_id = proposal.order_id;
 
@@ -736,27 +945,32 @@ where
 
         disconnected.insert(addr);
 
+        self.auto_accept_settlement(proposal, this).await;
+
         Ok(())
     }
 }
 
-#[xtra_productivity]
 impl<O, T, W> Actor<O, T, W>
 where
-    O: xtra::Handler<oracle::GetAnnouncement, Return = Result<Announcement, NoAnnouncement>>
-        + xtra::Handler<oracle::MonitorAttestation, Return = ()>,
-    T: xtra::Handler<connection::ConfirmOrder, Return = Result<()>>
-        + xtra::Handler<connection::TakerMessage, Return = Result<(), NoConnection>>
-        + xtra::Handler<connection::BroadcastOffers, Return = ()>
-        + xtra::Handler<connection::settlement::Response, Return = Result<()>>
-        + xtra::Handler<connection::RegisterRollover, Return = ()>,
-    W: xtra::Handler<wallet::Sign, Return = Result<PartiallySignedTransaction>>
-        + xtra::Handler<wallet::BuildPartyParams, Return = Result<PartyParams>>,
+    T: xtra::Handler<connection::BroadcastOffers, Return = ()>,
 {
-    async fn handle_offer_params(&mut self, msg: OfferParams) -> Result<()> {
+    /// Create offers from the given params and publish them to the UI and all takers.
+    ///
+    /// Derived funding rates take precedence over the ones of the params.
+    async fn publish_offers(&mut self, mut offer_params: OfferParams) -> Result<()> {
+        if let Some(derived) = self.derived_funding_rates {
+            offer_params.funding_rate_long = derived.funding_rate_long;
+            offer_params.funding_rate_short = derived.funding_rate_short;
+        }
+
+        let settlement_interval = offer_params
+            .settlement_interval
+            .unwrap_or(self.settlement_interval);
+
         // 1. Update actor state to current order
         self.current_offers
-            .replace(create_maker_offers(msg, self.settlement_interval));
+            .replace(create_maker_offers(offer_params, settlement_interval));
 
         // 2. Notify UI via feed
         self.projection
@@ -774,11 +988,248 @@ where
             ))
             .await?;
 
+        // 4. Update the fees in the directory announcement
+        if let Some(directory) = &self.directory {
+            let fees = self
+                .current_offers
+                .as_ref()
+                .and_then(xtra_libp2p_directory::FeeSchedule::from_offers);
+            directory
+                .send_async_safe(xtra_libp2p_directory::maker::NewFeeSchedule(fees))
+                .await?;
+        }
+
         Ok(())
     }
+}
+
+impl<O, T, W> Actor<O, T, W>
+where
+    O: Send + 'static,
+    T: Send + 'static,
+    W: Send + 'static,
+{
+    /// The auto-accept policy and the CFD to apply it to, unless auto-accept is disabled.
+    ///
+    /// Only borrows the fields it needs, so that the future does not require the actor to be
+    /// `Sync`.
+    fn auto_accept_policy_and_cfd(
+        &self,
+        order_id: OrderId,
+    ) -> impl Future<Output = Option<(&auto_accept::Policy, Cfd)>> + Send + '_ {
+        let policy = self.auto_accept.as_ref();
+        let db = &self.db;
+
+        async move {
+            let policy = policy?;
+
+            match db.load_open_cfd::<Cfd>(order_id, ()).await {
+                Ok(cfd) => Some((policy, cfd)),
+                Err(e) => {
+                    tracing::warn!(%order_id, "Failed to load CFD to apply auto-accept policy: {e:#}");
+                    None
+                }
+            }
+        }
+    }
+
+    /// Accept a taken order on behalf of the operator if the auto-accept policy allows it.
+    ///
+    /// Takers are only recognised by the peer id authenticated by the connection the order was
+    /// taken on, never by the identity they report.
+    async fn auto_accept_order(
+        &mut self,
+        order_id: OrderId,
+        taker_peer_id: Option<PeerId>,
+        this: xtra::Address<Self>,
+    ) {
+        let (policy, cfd) = match self.auto_accept_policy_and_cfd(order_id).await {
+            Some(policy_and_cfd) => policy_and_cfd,
+            None => return,
+        };
+        let decision = policy.decide_order(taker_peer_id, cfd.quantity());
+
+        self.apply_decision(
+            order_id,
+            AutoAcceptAction::Order,
+            decision,
+            AcceptOrder { order_id },
+            this,
+        )
+        .await;
+    }
+
+    /// Accept a rollover on behalf of the operator if the auto-accept policy allows it.
+    async fn auto_accept_rollover(&mut self, order_id: OrderId, this: xtra::Address<Self>) {
+        let (policy, cfd) = match self.auto_accept_policy_and_cfd(order_id).await {
+            Some(policy_and_cfd) => policy_and_cfd,
+            None => return,
+        };
+        let decision = policy.decide_rollover(&cfd, self.closing_quote(&cfd));
+
+        self.apply_decision(
+            order_id,
+            AutoAcceptAction::Rollover,
+            decision,
+            AcceptRollover { order_id },
+            this,
+        )
+        .await;
+    }
+
+    /// Accept a settlement on behalf of the operator if the auto-accept policy allows it.
+    async fn auto_accept_settlement(
+        &mut self,
+        proposal: SettlementProposal,
+        this: xtra::Address<Self>,
+    ) {
+        let order_id = proposal.order_id;
+
+        let (policy, cfd) = match self.auto_accept_policy_and_cfd(order_id).await {
+            Some(policy_and_cfd) => policy_and_cfd,
+            None => return,
+        };
+        let decision = policy.decide_settlement(proposal.price, self.closing_quote(&cfd));
+
+        self.apply_decision(
+            order_id,
+            AutoAcceptAction::Settlement,
+            decision,
+            AcceptSettlement { order_id },
+            this,
+        )
+        .await;
+    }
+
+    /// Our price for closing the given CFD.
+    ///
+    /// Closing the CFD means taking the position opposite to ours, i.e. the price of our short
+    /// order if we are long in the CFD and vice versa.
+    fn closing_quote(&self, cfd: &Cfd) -> Option<Price> {
+        let offers = self.current_offers.as_ref()?;
+
+        let order = match cfd.position() {
+            Position::Long => offers.short.as_ref(),
+            Position::Short => offers.long.as_ref(),
+        }?;
+
+        Some(order.price)
+    }
+
+    /// Accept on behalf of the operator and record the automated decision in the event log.
+    ///
+    /// The accept goes through our own mailbox because the protocol actor that told us about the
+    /// request might still be waiting for us to return. Hence, we wait for the accept in a task
+    /// and only record the decision once it succeeded, so that the event log does not claim
+    /// accepts that never happened.
+    async fn apply_decision<M>(
+        &mut self,
+        order_id: OrderId,
+        action: AutoAcceptAction,
+        decision: auto_accept::Decision,
+        accept: M,
+        this: xtra::Address<Self>,
+    ) where
+        M: Send + 'static,
+        Self: xtra::Handler<M, Return = Result<()>>,
+    {
+        let reason = match decision {
+            auto_accept::Decision::Accept { reason } => reason,
+            auto_accept::Decision::Defer { reason } => {
+                tracing::info!(%order_id, %action, %reason, "Leaving decision to the operator");
+                return;
+            }
+        };
+
+        tracing::info!(%order_id, %action, %reason, "Accepting automatically");
+
+        let executor = self.executor.clone();
+        self.tasks.add_fallible(
+            async move {
+                this.send(accept)
+                    .await
+                    .context("Actor disconnected")?
+                    .context("Failed to accept")?;
+
+                executor
+                    .execute(order_id, |cfd| Ok(cfd.auto_accept(action, reason)))
+                    .await
+                    .context("Failed to record automated decision")?;
+
+                anyhow::Ok(())
+            },
+            move |e| async move {
+                tracing::warn!(%order_id, %action, "Automated accept failed: {e:#}");
+            },
+        );
+    }
+}
+
+#[xtra_productivity]
+impl<O, T, W> Actor<O, T, W>
+where
+    O: xtra::Handler<oracle::GetAnnouncement, Return = Result<Announcement, NoAnnouncement>>
+        + xtra::Handler<oracle::MonitorAttestation, Return = ()>
+        + xtra::Handler<oracle::SyncAnnouncement, Return = ()>
+        + xtra::Handler<oracle::SettlementIntervalInUse, Return = ()>,
+    T: xtra::Handler<connection::ConfirmOrder, Return = Result<()>>
+        + xtra::Handler<connection::TakerMessage, Return = Result<(), NoConnection>>
+        + xtra::Handler<connection::BroadcastOffers, Return = ()>
+        + xtra::Handler<connection::settlement::Response, Return = Result<()>>
+        + xtra::Handler<connection::RegisterRollover, Return = ()>,
+    W: xtra::Handler<wallet::Sign, Return = Result<PartiallySignedTransaction>>
+        + xtra::Handler<wallet::BuildPartyParams, Return = Result<PartyParams>>,
+{
+    async fn handle_offer_params(&mut self, msg: OfferParams) -> Result<()> {
+        msg.validate_expiry(OffsetDateTime::now_utc())?;
+        msg.validate_settlement_interval()?;
+
+        match msg.expiry {
+            // The expiry lies beyond the announcements the oracle actor syncs on its own
+            Some(expiry) => {
+                self.oracle
+                    .send_async_safe(oracle::SyncAnnouncement(
+                        BitMexPriceEventId::with_20_digits(expiry),
+                    ))
+                    .await?;
+            }
+            // Rolling over perpetual contracts needs announcements up to one settlement interval
+            // into the future
+            None => {
+                let settlement_interval =
+                    msg.settlement_interval.unwrap_or(self.settlement_interval);
+
+                self.oracle
+                    .send_async_safe(oracle::SettlementIntervalInUse(settlement_interval))
+                    .await?;
+            }
+        }
+
+        self.offer_params = Some(msg.clone());
+
+        self.publish_offers(msg).await
+    }
+
+    async fn handle_update_funding_rates(&mut self, msg: UpdateFundingRates) -> Result<()> {
+        if self.derived_funding_rates == Some(msg) {
+            return Ok(());
+        }
+
+        tracing::info!(
+            funding_rate_long = %msg.funding_rate_long,
+            funding_rate_short = %msg.funding_rate_short,
+            "Updating funding rates"
+        );
+        self.derived_funding_rates = Some(msg);
+
+        match self.offer_params.clone() {
+            Some(offer_params) => self.publish_offers(offer_params).await,
+            None => Ok(()),
+        }
+    }
 
     async fn handle(&mut self, msg: TakerConnected) -> Result<()> {
-        self.handle_taker_connected(msg.id).await
+        self.handle_taker_connected(msg.id, msg.peer_id).await
     }
 
     async fn handle(&mut self, msg: TakerDisconnected) -> Result<()> {
@@ -792,7 +1243,10 @@ where
             peer_id,
             msg,
         }: FromTaker,
+        ctx: &mut xtra::Context<Self>,
     ) {
+        let this = ctx.address().expect("we are alive");
+
         match msg {
             wire::TakerToMaker::DeprecatedTakeOrder { order_id, quantity } => {
                 // Old clients do not send over leverage. Hence we default to Leverage::TWO which
@@ -800,7 +1254,7 @@ where
                 let leverage = Leverage::TWO;
 
                 if let Err(e) = self
-                    .handle_take_order(taker_id, peer_id, order_id, quantity, leverage)
+                    .handle_take_order(taker_id, peer_id, order_id, quantity, leverage, this)
                     .await
                 {
                     tracing::error!("Error when handling order take request: {:#}", e)
@@ -812,7 +1266,7 @@ where
                 leverage,
             } => {
                 if let Err(e) = self
-                    .handle_take_order(taker_id, peer_id, order_id, quantity, leverage)
+                    .handle_take_order(taker_id, peer_id, order_id, quantity, leverage, this)
                     .await
                 {
                     tracing::error!("Error when handling order take request: {:#}", e)
@@ -837,6 +1291,7 @@ where
                             maker,
                             price,
                         },
+                        this,
                     )
                     .await
                 {
@@ -861,6 +1316,7 @@ where
                         },
                         taker_id,
                         RolloverVersion::V1,
+                        this,
                     )
                     .await
                 {
@@ -879,6 +1335,7 @@ where
                         },
                         taker_id,
                         RolloverVersion::V2,
+                        this,
                     )
                     .await
                 {
@@ -897,6 +1354,7 @@ where
                         },
                         taker_id,
                         RolloverVersion::V3,
+                        this,
                     )
                     .await
                 {
@@ -918,6 +1376,96 @@ where
     }
 }
 
+#[xtra_productivity(message_impl = false)]
+impl<O, T, W> Actor<O, T, W>
+where
+    O: Send + 'static,
+    T: Send + 'static,
+    W: Send + 'static,
+{
+    async fn handle_order_taken(
+        &mut self,
+        msg: daemon::contract_setup::maker::OrderTaken,
+        ctx: &mut xtra::Context<Self>,
+    ) {
+        let this = ctx.address().expect("we are alive");
+        self.auto_accept_order(msg.order_id, Some(msg.taker_peer_id), this)
+            .await;
+    }
+
+    async fn handle_rollover_proposed(
+        &mut self,
+        msg: daemon::rollover::maker::RolloverProposed,
+        ctx: &mut xtra::Context<Self>,
+    ) {
+        let this = ctx.address().expect("we are alive");
+        self.auto_accept_rollover(msg.order_id, this).await;
+    }
+
+    async fn handle_settlement_proposed(
+        &mut self,
+        msg: daemon::collab_settlement::maker::SettlementProposed,
+        ctx: &mut xtra::Context<Self>,
+    ) {
+        let this = ctx.address().expect("we are alive");
+        self.auto_accept_settlement(msg.proposal, this).await;
+    }
+}
+
+#[xtra_productivity(message_impl = false)]
+impl<O, T, W> Actor<O, T, W>
+where
+    T: xtra::Handler<connection::BroadcastOffers, Return = ()>,
+{
+    async fn handle_order_received(
+        &mut self,
+        msg: daemon::contract_setup::maker::OrderReceived,
+    ) -> Result<BitMexPriceEventId> {
+        let daemon::contract_setup::maker::OrderReceived {
+            order_id,
+            quantity,
+            leverage,
+            taker_id,
+            taker_peer_id,
+        } = msg;
+
+        tracing::debug!(%taker_id, %taker_peer_id, %quantity, %order_id, "Taker wants to take an order");
+
+        // Takers which are not connected via legacy networking cannot be checked. Their reported
+        // identity does not grant them anything though, as the CFD is bound to their peer id.
+        if let Some(announced_peer_id) = self.taker_peer_ids.get(&taker_id) {
+            if *announced_peer_id != taker_peer_id {
+                bail!(
+                    "Taker {taker_id} is connected as peer {announced_peer_id}, not {taker_peer_id}"
+                );
+            }
+        }
+
+        let order_to_take = self
+            .current_offers
+            .as_ref()
+            .and_then(|offers| offers.pick_order_to_take(order_id))
+            .with_context(|| format!("Taker tried to take order with outdated id {order_id}"))?;
+
+        let min = order_to_take.min_quantity;
+        let max = order_to_take.max_quantity;
+        if quantity < min || quantity > max {
+            bail!("Quantity {quantity} not in range [{min}, {max}]");
+        }
+
+        self.record_taken_order(
+            &order_to_take,
+            quantity,
+            taker_id,
+            Some(taker_peer_id),
+            leverage,
+        )
+        .await?;
+
+        Ok(order_to_take.oracle_event_id)
+    }
+}
+
 #[async_trait]
 impl<O: Send + 'static, T: Send + 'static, W: Send + 'static> xtra::Actor for Actor<O, T, W> {
     type Stop = ();
@@ -449,7 +449,10 @@ impl Actor {
 
         let _: Result<(), xtra::Error> = self
             .taker_connected_channel
-            .send_async_safe(cfd::TakerConnected { id: identity })
+            .send_async_safe(cfd::TakerConnected {
+                id: identity,
+                peer_id,
+            })
             .await;
 
         let mut tasks = Tasks::default();
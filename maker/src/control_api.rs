@@ -0,0 +1,74 @@
+use crate::routes::CfdNewOfferParamsRequest;
+use crate::routes::Maker;
+use anyhow::Result;
+use async_trait::async_trait;
+use model::OrderId;
+use serde::Deserialize;
+use shared_bin::control_api::Commands;
+use std::sync::Arc;
+
+/// The commands of the maker's control API, see [`shared_bin::control_api`].
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    SetOfferParams(CfdNewOfferParamsRequest),
+    AcceptOrder { order_id: OrderId },
+    RejectOrder { order_id: OrderId },
+    AcceptSettlement { order_id: OrderId },
+    RejectSettlement { order_id: OrderId },
+    AcceptRollover { order_id: OrderId },
+    RejectRollover { order_id: OrderId },
+    Commit { order_id: OrderId },
+}
+
+#[derive(Clone)]
+pub struct MakerCommands(pub Arc<Maker>);
+
+#[async_trait]
+impl Commands for MakerCommands {
+    type Command = Command;
+
+    fn order_id(command: &Command) -> Option<OrderId> {
+        match command {
+            Command::SetOfferParams(_) => None,
+            Command::AcceptOrder { order_id }
+            | Command::RejectOrder { order_id }
+            | Command::AcceptSettlement { order_id }
+            | Command::RejectSettlement { order_id }
+            | Command::AcceptRollover { order_id }
+            | Command::RejectRollover { order_id }
+            | Command::Commit { order_id } => Some(*order_id),
+        }
+    }
+
+    async fn execute(&self, command: Command) -> Result<()> {
+        let maker = &self.0;
+
+        match command {
+            Command::SetOfferParams(params) => {
+                maker
+                    .set_offer_params(
+                        params.price_long,
+                        params.price_short,
+                        params.min_quantity,
+                        params.max_quantity,
+                        params.tx_fee_rate,
+                        params.daily_funding_rate_long,
+                        params.daily_funding_rate_short,
+                        params.opening_fee,
+                        params.leverage_choices,
+                        params.expiry,
+                        params.settlement_interval_hours,
+                    )
+                    .await
+            }
+            Command::AcceptOrder { order_id } => maker.accept_order(order_id).await,
+            Command::RejectOrder { order_id } => maker.reject_order(order_id).await,
+            Command::AcceptSettlement { order_id } => maker.accept_settlement(order_id).await,
+            Command::RejectSettlement { order_id } => maker.reject_settlement(order_id).await,
+            Command::AcceptRollover { order_id } => maker.accept_rollover(order_id).await,
+            Command::RejectRollover { order_id } => maker.reject_rollover(order_id).await,
+            Command::Commit { order_id } => maker.commit(order_id).await,
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use crate::cfd;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use model::FundingRate;
+use model::SETTLEMENT_INTERVAL;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tasks::Tasks;
+use xtra::message_channel::MessageChannel;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+/// How often we fetch the reference rate unless configured otherwise.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// BitMEX charges funding every 8 hours.
+const BITMEX_FUNDING_INTERVAL_HOURS: u32 = 8;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A source of the reference funding rate our funding rates are derived from.
+#[async_trait]
+pub trait Feed: Send + Sync + 'static {
+    /// Identifies the feed in the logs.
+    fn name(&self) -> String;
+
+    /// The current reference funding rate per hour.
+    ///
+    /// A positive rate means that longs pay shorts.
+    async fn hourly_rate(&self) -> Result<Decimal>;
+}
+
+/// The funding rate of BitMEX's XBTUSD perpetual swap.
+pub struct Bitmex {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Bitmex {
+    pub fn new(network: xtra_bitmex_price_feed::Network) -> Self {
+        let url = format!(
+            "https://{}/api/v1/instrument?symbol=XBTUSD&columns=fundingRate",
+            network.to_url()
+        );
+
+        Self {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Instrument {
+    funding_rate: Option<Decimal>,
+}
+
+#[async_trait]
+impl Feed for Bitmex {
+    fn name(&self) -> String {
+        "BitMEX XBTUSD".to_owned()
+    }
+
+    async fn hourly_rate(&self) -> Result<Decimal> {
+        let instruments = self
+            .client
+            .get(&self.url)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .with_context(|| format!("Failed to GET {}", self.url))?
+            .error_for_status()?
+            .json::<Vec<Instrument>>()
+            .await
+            .context("Failed to deserialize instrument")?;
+
+        let funding_rate = instruments
+            .first()
+            .and_then(|instrument| instrument.funding_rate)
+            .context("No funding rate for XBTUSD")?;
+
+        Ok(funding_rate / Decimal::from(BITMEX_FUNDING_INTERVAL_HOURS))
+    }
+}
+
+/// Reads the hourly rate from a local file, e.g. `0.0001`.
+///
+/// The file is read on every poll, which allows driving the funding rate in tests.
+pub struct File {
+    path: PathBuf,
+}
+
+impl File {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl Feed for File {
+    fn name(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+
+    async fn hourly_rate(&self) -> Result<Decimal> {
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+
+        content
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid hourly rate in {}", self.path.display()))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Params {
+    /// Reference rates are clamped to [-max_hourly_rate, max_hourly_rate].
+    pub max_hourly_rate: Decimal,
+    /// The weight of the latest reference rate in the exponential moving average, within (0, 1].
+    ///
+    /// A weight of 1 disables smoothing.
+    pub smoothing: Decimal,
+}
+
+impl Params {
+    pub fn new(max_hourly_rate: Decimal, smoothing: Decimal) -> Result<Self> {
+        anyhow::ensure!(
+            !max_hourly_rate.is_sign_negative(),
+            "Maximum hourly funding rate must not be negative"
+        );
+        anyhow::ensure!(
+            max_hourly_rate * Decimal::from(SETTLEMENT_INTERVAL.whole_hours()) <= Decimal::ONE,
+            "Maximum hourly funding rate would exceed 100% per settlement interval"
+        );
+        anyhow::ensure!(
+            smoothing > Decimal::ZERO && smoothing <= Decimal::ONE,
+            "Funding rate smoothing must be within (0, 1]"
+        );
+
+        Ok(Self {
+            max_hourly_rate,
+            smoothing,
+        })
+    }
+}
+
+/// Derives funding rates from the reference rates of a [`Feed`].
+///
+/// Funding rates are quoted per [`SETTLEMENT_INTERVAL`], hence the smoothed hourly rate is scaled
+/// accordingly. Both of the maker's positions are charged the same rate.
+pub struct Actor {
+    feed: Arc<dyn Feed>,
+    params: Params,
+    poll_interval: Duration,
+    smoothed_hourly_rate: Option<Decimal>,
+    cfd_actor: MessageChannel<cfd::UpdateFundingRates, Result<()>>,
+    tasks: Tasks,
+}
+
+impl Actor {
+    pub fn new(
+        feed: Arc<dyn Feed>,
+        params: Params,
+        poll_interval: Duration,
+        cfd_actor: MessageChannel<cfd::UpdateFundingRates, Result<()>>,
+    ) -> Self {
+        Self {
+            feed,
+            params,
+            poll_interval,
+            smoothed_hourly_rate: None,
+            cfd_actor,
+            tasks: Tasks::default(),
+        }
+    }
+
+    /// Clamp the reference rate and fold it into the exponential moving average.
+    fn smooth(&mut self, hourly_rate: Decimal) -> Decimal {
+        let max = self.params.max_hourly_rate;
+        let clamped = hourly_rate.max(-max).min(max);
+
+        let smoothed = match self.smoothed_hourly_rate {
+            None => clamped,
+            Some(previous) => previous + self.params.smoothing * (clamped - previous),
+        };
+        self.smoothed_hourly_rate = Some(smoothed);
+
+        smoothed
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Poll;
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: Poll) {
+        let feed = self.feed.name();
+
+        let hourly_rate = match self.feed.hourly_rate().await {
+            Ok(hourly_rate) => hourly_rate,
+            Err(e) => {
+                tracing::warn!(%feed, "Failed to fetch reference funding rate: {e:#}");
+                return;
+            }
+        };
+
+        let smoothed = self.smooth(hourly_rate);
+        tracing::debug!(%feed, %hourly_rate, %smoothed, "Derived hourly funding rate");
+
+        let funding_rate = match FundingRate::new(
+            (smoothed * Decimal::from(SETTLEMENT_INTERVAL.whole_hours())).round_dp(8),
+        ) {
+            Ok(funding_rate) => funding_rate,
+            Err(e) => {
+                tracing::error!("Derived an invalid funding rate: {e:#}");
+                return;
+            }
+        };
+
+        let update = cfd::UpdateFundingRates {
+            funding_rate_long: funding_rate,
+            funding_rate_short: funding_rate,
+        };
+        let result = self
+            .cfd_actor
+            .send(update)
+            .await
+            .context("CFD actor disconnected");
+        if let Err(e) = result.and_then(|result| result) {
+            tracing::warn!("Failed to update funding rates: {e:#}");
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+        self.tasks
+            .add(this.send_interval(self.poll_interval, || Poll));
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xtra::Context;
+
+    #[test]
+    fn params_within_bounds_are_accepted() {
+        assert!(Params::new(rate("0.001"), Decimal::ONE).is_ok());
+        assert!(Params::new(Decimal::ZERO, rate("0.5")).is_ok());
+    }
+
+    #[test]
+    fn negative_max_hourly_rate_is_rejected() {
+        assert!(Params::new(rate("-0.001"), Decimal::ONE).is_err());
+    }
+
+    #[test]
+    fn max_hourly_rate_exceeding_100_percent_per_settlement_interval_is_rejected() {
+        assert_eq!(SETTLEMENT_INTERVAL.whole_hours(), 24);
+
+        assert!(Params::new(rate("0.04"), Decimal::ONE).is_ok());
+        assert!(Params::new(rate("0.05"), Decimal::ONE).is_err());
+    }
+
+    #[test]
+    fn smoothing_outside_of_zero_to_one_is_rejected() {
+        assert!(Params::new(rate("0.001"), Decimal::ZERO).is_err());
+        assert!(Params::new(rate("0.001"), rate("1.1")).is_err());
+    }
+
+    #[test]
+    fn reference_rate_is_clamped_to_max_hourly_rate() {
+        let mut actor = actor(rate("0.001"), Decimal::ONE);
+
+        assert_eq!(actor.smooth(rate("0.01")), rate("0.001"));
+        assert_eq!(actor.smooth(rate("-0.01")), rate("-0.001"));
+        assert_eq!(actor.smooth(rate("0.0005")), rate("0.0005"));
+    }
+
+    #[test]
+    fn first_reference_rate_is_taken_as_is_and_later_ones_are_smoothed() {
+        let mut actor = actor(rate("0.001"), rate("0.5"));
+
+        assert_eq!(actor.smooth(rate("0.0002")), rate("0.0002"));
+        assert_eq!(actor.smooth(rate("0.0004")), rate("0.0003"));
+        assert_eq!(actor.smooth(Decimal::ZERO), rate("0.00015"));
+    }
+
+    #[test]
+    fn smoothing_applies_to_clamped_reference_rate() {
+        let mut actor = actor(rate("0.001"), rate("0.5"));
+
+        actor.smooth(Decimal::ZERO);
+
+        assert_eq!(actor.smooth(rate("0.01")), rate("0.0005"));
+    }
+
+    fn actor(max_hourly_rate: Decimal, smoothing: Decimal) -> Actor {
+        let (cfd_actor, _) = Context::<DummyCfdActor>::new(None);
+
+        Actor::new(
+            Arc::new(File::new(PathBuf::new())),
+            Params::new(max_hourly_rate, smoothing).unwrap(),
+            DEFAULT_POLL_INTERVAL,
+            cfd_actor.into(),
+        )
+    }
+
+    fn rate(rate: &str) -> Decimal {
+        rate.parse().unwrap()
+    }
+
+    struct DummyCfdActor;
+
+    #[async_trait]
+    impl xtra::Actor for DummyCfdActor {
+        type Stop = ();
+
+        async fn stopped(self) -> Self::Stop {}
+    }
+
+    #[xtra_productivity(message_impl = false)]
+    impl DummyCfdActor {
+        fn handle(&mut self, _: cfd::UpdateFundingRates) -> Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -1,20 +1,39 @@
+use anyhow::Result;
 use bdk::bitcoin::util::bip32::ExtendedPrivKey;
 use clap::Parser;
 use clap::Subcommand;
 use daemon::bdk;
 use daemon::bdk::bitcoin;
 use daemon::bdk::bitcoin::Amount;
+use rust_decimal::Decimal;
+use shared_bin::backup::BackupOpts;
 use shared_bin::logger::LevelFilter;
+use shared_bin::margin_health::MarginHealthOpts;
+use shared_bin::notifications::NotificationOpts;
+use shared_bin::pnl_history::PnlHistoryOpts;
+use shared_bin::price_history::PriceHistoryOpts;
+use shared_bin::tls::TlsOpts;
+use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::limits::Limits;
+use xtra_libp2p::limits::RateLimit;
 
 pub use actor_system::ActorSystem;
+pub use actor_system::Directory;
 
 mod actor_system;
+pub mod auto_accept;
 pub mod cfd;
 mod collab_settlement;
 mod connection;
 mod contract_setup;
+pub mod control_api;
+pub mod funding_rate;
 mod future_ext;
 mod metrics;
 mod rollover;
@@ -30,6 +49,13 @@ pub struct Opts {
     #[clap(long, default_value = "127.0.0.1:8001")]
     pub http_address: SocketAddr,
 
+    /// The IP address to listen on for the WebSocket control API, e.g. `127.0.0.1:8002`.
+    ///
+    /// The control API is meant for trading bots and disabled unless specified. Connections are
+    /// not encrypted, so it should only be exposed on localhost.
+    #[clap(long)]
+    pub control_api_address: Option<SocketAddr>,
+
     /// Where to permanently store data, defaults to the current working directory.
     #[clap(long)]
     pub data_dir: Option<PathBuf>,
@@ -38,6 +64,11 @@ pub struct Opts {
     #[clap(short, long)]
     pub json: bool,
 
+    /// Export traces to an OpenTelemetry collector at the given OTLP/gRPC endpoint, e.g.
+    /// `http://localhost:4317`.
+    #[clap(long)]
+    pub otlp_endpoint: Option<String>,
+
     /// If provided will be used for internal wallet instead of a random key. The keys will be
     /// derived according to Bip84
     #[clap(short, long)]
@@ -47,10 +78,154 @@ pub struct Opts {
     #[clap(short, long, default_value = "Debug")]
     pub log_level: LevelFilter,
 
+    /// An onion service to listen on for libp2p connections in addition to the p2p port, e.g.
+    /// `/onion3/<address>:10002`.
+    ///
+    /// Tor has to forward the onion service to the same port on localhost, i.e.
+    /// `HiddenServicePort 10002 127.0.0.1:10002`. This port must differ from the libp2p port.
+    #[clap(long)]
+    pub onion_service: Option<Multiaddr>,
+
+    #[clap(flatten)]
+    pub limits: LimitOpts,
+
+    /// Accept orders, rollovers and settlements according to the policy in the given TOML file.
+    ///
+    /// Requests not covered by the policy are left to the operator.
+    #[clap(long)]
+    pub auto_accept_policy: Option<PathBuf>,
+
+    /// Keep this maker listed in the directory at the given address, e.g.
+    /// `/dns4/directory.example.com/tcp/10500/p2p/<peer-id>`.
+    ///
+    /// Takers discover makers through the signed announcements in the directory.
+    #[clap(long)]
+    pub directory: Option<Multiaddr>,
+
+    /// An address under which takers can reach this maker, announced in the directory.
+    ///
+    /// Can be specified multiple times. Defaults to the onion service, if one is configured.
+    #[clap(long, requires = "directory")]
+    pub announce_multiaddr: Vec<Multiaddr>,
+
+    #[clap(flatten)]
+    pub tls: TlsOpts,
+
+    #[clap(flatten)]
+    pub notifications: NotificationOpts,
+
+    #[clap(flatten)]
+    pub funding_rate: FundingRateOpts,
+
+    #[clap(flatten)]
+    pub price_history: PriceHistoryOpts,
+
+    #[clap(flatten)]
+    pub pnl_history: PnlHistoryOpts,
+
+    #[clap(flatten)]
+    pub margin_health: MarginHealthOpts,
+
+    #[clap(flatten)]
+    pub backup: BackupOpts,
+
     #[clap(subcommand)]
     pub network: Network,
 }
 
+/// Command line options for the limits the maker enforces on the peers connecting to it.
+#[derive(clap::Args, Debug, Clone)]
+pub struct LimitOpts {
+    /// Only accept libp2p connections from the given peer IDs.
+    ///
+    /// Can be specified multiple times. If omitted, all peers that are not denied are accepted.
+    #[clap(long)]
+    pub allow_peer: Vec<PeerId>,
+
+    /// Reject libp2p connections from the given peer ID.
+    ///
+    /// Can be specified multiple times.
+    #[clap(long)]
+    pub deny_peer: Vec<PeerId>,
+
+    /// Upper bound for the number of takers connected at the same time.
+    #[clap(long, default_value = "1000")]
+    pub max_connections: usize,
+
+    /// Upper bound for the number of substreams a taker can have open per protocol.
+    ///
+    /// Takers roll over all their CFDs at the same time, hence this has to be generous.
+    #[clap(long, default_value = "100")]
+    pub max_inbound_substreams_per_protocol: usize,
+
+    /// The number of substreams a taker can open per minute, including a ping every 5 seconds.
+    #[clap(long, default_value = "300")]
+    pub max_inbound_substreams_per_minute: u32,
+}
+
+impl LimitOpts {
+    pub fn limits(&self) -> Limits {
+        Limits {
+            allowed_peers: (!self.allow_peer.is_empty())
+                .then(|| self.allow_peer.iter().copied().collect()),
+            denied_peers: self.deny_peer.iter().copied().collect(),
+            max_connections: Some(self.max_connections),
+            reserved_peers: HashSet::new(),
+            max_inbound_substreams_per_protocol: Some(self.max_inbound_substreams_per_protocol),
+            inbound_substream_rate: Some(RateLimit {
+                max: self.max_inbound_substreams_per_minute,
+                per: Duration::from_secs(60),
+            }),
+        }
+    }
+}
+
+/// Command line options for deriving funding rates from a reference feed.
+///
+/// Unless a feed is configured, the funding rates set with the offer params are charged.
+#[derive(clap::Args, Debug, Clone)]
+pub struct FundingRateOpts {
+    /// Derive funding rates from the funding rate of BitMEX's XBTUSD perpetual swap.
+    #[clap(long, conflicts_with = "funding_rate_file")]
+    pub funding_rate_from_bitmex: bool,
+
+    /// Derive funding rates from the hourly rate in the given file, e.g. `0.0001`.
+    ///
+    /// The file is read on every poll, which is meant for testing.
+    #[clap(long)]
+    pub funding_rate_file: Option<PathBuf>,
+
+    /// Clamp the hourly reference rate to [-max, max].
+    #[clap(long, default_value = "0.0005")]
+    pub max_hourly_funding_rate: Decimal,
+
+    /// The weight of the latest reference rate when smoothing, within (0, 1].
+    ///
+    /// A weight of 1 disables smoothing.
+    #[clap(long, default_value = "0.2")]
+    pub funding_rate_smoothing: Decimal,
+}
+
+impl FundingRateOpts {
+    /// The feed to derive funding rates from, if any.
+    pub fn feed(
+        &self,
+        network: xtra_bitmex_price_feed::Network,
+    ) -> Option<Arc<dyn funding_rate::Feed>> {
+        if self.funding_rate_from_bitmex {
+            return Some(Arc::new(funding_rate::Bitmex::new(network)));
+        }
+
+        self.funding_rate_file
+            .clone()
+            .map(|path| Arc::new(funding_rate::File::new(path)) as _)
+    }
+
+    pub fn params(&self) -> Result<funding_rate::Params> {
+        funding_rate::Params::new(self.max_hourly_funding_rate, self.funding_rate_smoothing)
+    }
+}
+
 #[derive(Parser)]
 pub enum Network {
     /// Run on mainnet.
@@ -60,7 +235,7 @@ pub enum Network {
         electrum: String,
 
         #[clap(subcommand)]
-        withdraw: Option<Withdraw>,
+        command: Option<Command>,
     },
     /// Run on testnet.
     Testnet {
@@ -69,7 +244,7 @@ pub enum Network {
         electrum: String,
 
         #[clap(subcommand)]
-        withdraw: Option<Withdraw>,
+        command: Option<Command>,
     },
     /// Run on signet
     Signet {
@@ -78,12 +253,12 @@ pub enum Network {
         electrum: String,
 
         #[clap(subcommand)]
-        withdraw: Option<Withdraw>,
+        command: Option<Command>,
     },
 }
 
 #[derive(Subcommand)]
-pub enum Withdraw {
+pub enum Command {
     Withdraw {
         /// Optionally specify the amount of Bitcoin to be withdrawn. If not specified the wallet
         /// will be drained. Amount is to be specified with denomination, e.g. "0.1 BTC"
@@ -97,6 +272,18 @@ pub enum Withdraw {
         #[clap(long)]
         address: bdk::bitcoin::Address,
     },
+    /// Replace the database with a backup taken by this daemon.
+    ///
+    /// The backup is only restored if it belongs to the identity derived from the seed and is not
+    /// corrupted. The database it replaces is kept next to it.
+    Restore {
+        /// Path to the backup to restore.
+        #[clap(long)]
+        backup: PathBuf,
+    },
+    /// Check the database for corruption and replay the event log of every CFD to find
+    /// inconsistencies.
+    CheckIntegrity,
 }
 
 impl Network {
@@ -133,11 +320,11 @@ impl Network {
         }
     }
 
-    pub fn withdraw(&self) -> &Option<Withdraw> {
+    pub fn command(&self) -> &Option<Command> {
         match self {
-            Network::Mainnet { withdraw, .. } => withdraw,
-            Network::Testnet { withdraw, .. } => withdraw,
-            Network::Signet { withdraw, .. } => withdraw,
+            Network::Mainnet { command, .. } => command,
+            Network::Testnet { command, .. } => command,
+            Network::Signet { command, .. } => command,
         }
     }
 }
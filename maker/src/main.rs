@@ -2,6 +2,7 @@ use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use clap::StructOpt;
+use daemon::backup;
 use daemon::bdk::FeeRate;
 use daemon::monitor;
 use daemon::oracle;
@@ -12,26 +13,54 @@ use daemon::wallet;
 use daemon::wallet::MAKER_WALLET_ID;
 use daemon::HEARTBEAT_INTERVAL;
 use daemon::N_PAYOUTS;
+use daemon::PRICE_FEED_MAX_BACKOFF;
+use daemon::PRICE_FEED_MAX_RESTARTS;
+use daemon::PRICE_FEED_MIN_BACKOFF;
+use daemon::PRICE_FEED_RESTART_WINDOW;
+use maker::auto_accept;
+use maker::control_api::MakerCommands;
+use maker::funding_rate;
 use maker::routes;
 use maker::ActorSystem;
+use maker::Command;
+use maker::Directory;
 use maker::Opts;
-use maker::Withdraw;
 use model::olivia;
+use model::Identity;
 use model::SETTLEMENT_INTERVAL;
+use rocket_basicauth::TokenStore;
+use shared_bin::api_tokens;
+use shared_bin::api_tokens::SqliteTokenStore;
 use shared_bin::catchers::default_catchers;
+use shared_bin::control_api;
+use shared_bin::escalation;
 use shared_bin::fairings;
+use shared_bin::log_filter;
 use shared_bin::logger;
+use shared_bin::notifications;
+use shared_bin::pnl_history;
+use shared_bin::price_history;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
 use tokio_tasks::Tasks;
 use xtra::Actor;
+use xtras::backoff::Backoff;
 use xtras::supervisor;
-use xtras::supervisor::always_restart;
+use xtras::supervisor::always_restart_with_backoff;
+use xtras::InstrumentExt;
 
 #[rocket::main]
 async fn main() -> Result<()> {
     let opts = Opts::parse();
 
-    logger::init(opts.log_level, opts.json).context("initialize logger")?;
+    let log_filter = logger::init(
+        opts.log_level,
+        opts.json,
+        opts.otlp_endpoint.as_deref(),
+        "maker",
+    )
+    .context("initialize logger")?;
     tracing::info!("Running version: {}", daemon::version::version());
     let settlement_interval_hours = SETTLEMENT_INTERVAL.whole_hours();
 
@@ -65,6 +94,21 @@ async fn main() -> Result<()> {
         None => seed.derive_extended_priv_key(bitcoin_network)?,
     };
 
+    let db_path = data_dir.join("maker.sqlite");
+    let identity = Identity::new(seed.derive_identities().identity_pk);
+
+    match opts.network.command() {
+        Some(Command::Restore {
+            backup: backup_path,
+        }) => {
+            return backup::restore(backup_path, &db_path, identity).await;
+        }
+        Some(Command::CheckIntegrity) => {
+            return shared_bin::backup::check_integrity(db_path).await;
+        }
+        Some(Command::Withdraw { .. }) | None => {}
+    }
+
     let mut tasks = Tasks::default();
 
     let mut wallet_dir = data_dir.clone();
@@ -79,11 +123,11 @@ async fn main() -> Result<()> {
 
     let wallet = wallet.create(None).spawn(&mut tasks);
 
-    if let Some(Withdraw::Withdraw {
+    if let Some(Command::Withdraw {
         amount,
         address,
         fee,
-    }) = opts.network.withdraw()
+    }) = opts.network.command()
     {
         wallet
             .send(wallet::Withdraw {
@@ -110,11 +154,21 @@ async fn main() -> Result<()> {
         .merge(("address", opts.http_address.ip()))
         .merge(("port", opts.http_address.port()))
         .merge(("cli_colors", false));
+    let figment = match opts.tls.config(&data_dir, opts.http_address)? {
+        Some(tls) => figment.merge(("tls", tls)),
+        None => figment,
+    };
 
     let p2p_port = opts.p2p_port;
     let p2p_socket = format!("0.0.0.0:{p2p_port}").parse::<SocketAddr>().unwrap();
 
-    let db = sqlite_db::connect(data_dir.join("maker.sqlite")).await?;
+    let db = sqlite_db::connect(db_path).await?;
+    backup::record_identity(&db, identity).await?;
+
+    let backup_actor = opts
+        .backup
+        .actor(&data_dir, "maker")
+        .map(|actor| actor.create(None).spawn(&mut tasks));
 
     // Create actors
 
@@ -127,6 +181,62 @@ async fn main() -> Result<()> {
     )
     .expect("to parse properly");
 
+    let onion_service = opts
+        .onion_service
+        .clone()
+        .map(daemon::libp2p_utils::create_onion_service)
+        .transpose()?;
+    if let Some(onion_service) = &onion_service {
+        tracing::info!(
+            "Listening on onion service {} forwarded to {}",
+            onion_service.address,
+            onion_service.target
+        );
+    }
+
+    let notifier_actor = opts
+        .notifications
+        .notifier(db.clone())?
+        .create(None)
+        .spawn(&mut tasks);
+
+    let auto_accept_policy = opts
+        .auto_accept_policy
+        .as_deref()
+        .map(auto_accept::Policy::from_file)
+        .transpose()?;
+    if let Some(policy) = &auto_accept_policy {
+        tracing::info!(?policy, "Accepting requests of takers according to policy");
+    }
+
+    let directory = match opts.directory.clone() {
+        Some(address) => {
+            let announced_multiaddrs = if opts.announce_multiaddr.is_empty() {
+                onion_service
+                    .iter()
+                    .map(|onion_service| onion_service.address.clone())
+                    .collect()
+            } else {
+                opts.announce_multiaddr.clone()
+            };
+            if announced_multiaddrs.is_empty() {
+                bail!("Listing in a directory requires --announce-multiaddr or --onion-service");
+            }
+
+            let announced_multiaddrs = announced_multiaddrs
+                .into_iter()
+                .map(|multiaddr| daemon::libp2p_utils::with_peer_id(multiaddr, peer_id.inner()))
+                .collect::<Result<Vec<_>>>()?;
+            tracing::info!(%address, ?announced_multiaddrs, "Listing maker in directory");
+
+            Some(Directory {
+                address,
+                announced_multiaddrs,
+            })
+        }
+        None => None,
+    };
+
     let maker = ActorSystem::new(
         db.clone(),
         wallet.clone(),
@@ -141,29 +251,104 @@ async fn main() -> Result<()> {
         SETTLEMENT_INTERVAL,
         N_PAYOUTS,
         projection_actor.clone(),
+        notifier_actor.clone(),
+        backup_actor,
         identities,
         HEARTBEAT_INTERVAL,
         p2p_socket,
         endpoint_listen,
+        onion_service,
+        opts.limits.limits(),
+        auto_accept_policy,
+        directory,
     )?;
 
+    let _funding_rate_actor = match opts.funding_rate.feed(opts.network.price_feed_network()) {
+        Some(feed) => {
+            tracing::info!(feed = %feed.name(), "Deriving funding rates from reference feed");
+
+            let actor = funding_rate::Actor::new(
+                feed,
+                opts.funding_rate.params()?,
+                funding_rate::DEFAULT_POLL_INTERVAL,
+                maker.cfd_actor.clone().into(),
+            );
+            Some(actor.create(None).spawn(&mut tasks))
+        }
+        None => None,
+    };
+
+    let price_feed_network = opts.network.price_feed_network();
     let (supervisor, price_feed) = supervisor::Actor::with_policy(
-        move || xtra_bitmex_price_feed::Actor::new(opts.network.price_feed_network()),
-        always_restart::<xtra_bitmex_price_feed::Error>(),
+        move || xtra_bitmex_price_feed::Actor::new(price_feed_network),
+        always_restart_with_backoff::<xtra_bitmex_price_feed::Error>(Backoff::new(
+            PRICE_FEED_MIN_BACKOFF,
+            PRICE_FEED_MAX_BACKOFF,
+        ))
+        .with_max_restarts(PRICE_FEED_MAX_RESTARTS, PRICE_FEED_RESTART_WINDOW),
     );
 
-    let _supervisor_address = supervisor.create(None).spawn(&mut tasks);
+    let _supervisor_address = supervisor
+        .escalate_to(escalation::Actor.create(None).spawn(&mut tasks).into())
+        .create(None)
+        .spawn(&mut tasks);
 
     let (proj_actor, projection_feeds) =
         projection::Actor::new(db.clone(), bitcoin_network, price_feed.clone().into());
-    tasks.add(projection_context.run(proj_actor));
+    tasks.add(projection_context.instrumented().run(proj_actor));
+
+    let (margin_health_actor, margin_health_feed) = opts.margin_health.monitor(
+        db.clone(),
+        &projection_feeds,
+        price_feed.clone().into(),
+        notifier_actor.into(),
+        None,
+    );
+    let _margin_health_actor = margin_health_actor.create(None).spawn(&mut tasks);
+
+    let _pnl_history_actor = opts
+        .pnl_history
+        .recorder(db.clone(), &projection_feeds)
+        .create(None)
+        .spawn(&mut tasks);
+
+    let _price_history_actor = opts
+        .price_history
+        .recorder(db.clone(), price_feed.clone().into(), price_feed_network)
+        .create(None)
+        .spawn(&mut tasks);
+
+    let maker = Arc::new(maker);
+    let token_store = Arc::new(SqliteTokenStore::new(db.clone())) as Arc<dyn TokenStore>;
+
+    if let Some(control_api_address) = opts.control_api_address {
+        let listener = TcpListener::bind(control_api_address)
+            .await
+            .with_context(|| format!("Failed to bind control API to {control_api_address}"))?;
+        tracing::info!("Control API listening on ws://{control_api_address}");
+
+        tasks.add(control_api::serve(
+            listener,
+            MakerCommands(maker.clone()),
+            projection_feeds.cfd_feed.clone(),
+            rocket_basicauth::Credentials::new(
+                auth_username,
+                auth_password.clone(),
+                token_store.clone(),
+            ),
+        ));
+    }
 
     let mission_success = rocket::custom(figment)
         .manage(projection_feeds)
+        .manage(margin_health_feed)
         .manage(wallet_feed_receiver)
         .manage(maker)
         .manage(auth_username)
         .manage(auth_password)
+        .manage(db.clone())
+        .manage(log_filter)
+        .manage(token_store)
         .manage(bitcoin_network)
         .mount(
             "/api",
@@ -179,6 +364,12 @@ async fn main() -> Result<()> {
                 routes::get_version,
             ],
         )
+        .mount("/api", api_tokens::routes())
+        .mount("/api", log_filter::routes())
+        .mount("/api", notifications::routes())
+        .mount("/api", pnl_history::routes())
+        .mount("/api", price_history::routes())
+        .mount("/api/v1", routes::v1::routes())
         .register("/api", default_catchers())
         .mount("/", rocket::routes![routes::dist, routes::index])
         .register("/", default_catchers())
@@ -190,6 +381,7 @@ async fn main() -> Result<()> {
     tracing::trace!(?mission_success, "Rocket has landed");
 
     db.close().await;
+    logger::shutdown();
 
     Ok(())
 }
@@ -2,9 +2,11 @@ use crate::actor_system::ActorSystem;
 use anyhow::Result;
 use bdk::sled;
 use daemon::bdk::blockchain::ElectrumBlockchain;
+use daemon::margin_health::MarginHealth;
 use daemon::oracle;
 use daemon::projection::Cfd;
 use daemon::projection::CfdAction;
+use daemon::projection::CfdFeedCursor;
 use daemon::projection::Feeds;
 use daemon::wallet;
 use http_api_problem::HttpApiProblem;
@@ -15,6 +17,7 @@ use model::Leverage;
 use model::OpeningFee;
 use model::OrderId;
 use model::Price;
+use model::Timestamp;
 use model::TxFeeRate;
 use model::Usd;
 use model::WalletInfo;
@@ -25,18 +28,25 @@ use rocket::response::stream::EventStream;
 use rocket::response::Responder;
 use rocket::serde::json::Json;
 use rocket::State;
-use rocket_basicauth::Authenticated;
+use rocket_basicauth::scope;
+use rocket_basicauth::Authorized;
 use rust_embed::RustEmbed;
 use rust_embed_rocket::EmbeddedFileExt;
 use serde::Deserialize;
 use serde::Serialize;
+use shared_bin::cfd_events;
+use shared_bin::LastEventId;
 use shared_bin::ToSseEvent;
 use std::borrow::Cow;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::select;
 use tokio::sync::watch;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+pub mod v1;
+
 pub type Maker = ActorSystem<oracle::Actor, wallet::Actor<ElectrumBlockchain, sled::Tree>>;
 
 #[allow(clippy::too_many_arguments)]
@@ -44,14 +54,18 @@ pub type Maker = ActorSystem<oracle::Actor, wallet::Actor<ElectrumBlockchain, sl
 pub async fn maker_feed(
     rx: &State<Feeds>,
     rx_wallet: &State<watch::Receiver<Option<WalletInfo>>>,
-    _auth: Authenticated,
+    rx_margin_health: &State<watch::Receiver<Vec<MarginHealth>>>,
+    last_event_id: LastEventId,
+    _auth: Authorized<scope::Read>,
 ) -> EventStream![] {
     let rx = rx.inner();
-    let mut rx_cfds = rx.cfds.clone();
+    let mut rx_cfd_feed = rx.cfd_feed.clone();
+    let mut cfd_feed_cursor = CfdFeedCursor::resume(last_event_id.0);
     let mut rx_offers = rx.offers.clone();
     let mut rx_wallet = rx_wallet.inner().clone();
     let mut rx_quote = rx.quote.clone();
     let mut rx_connected_takers = rx.connected_takers.clone();
+    let mut rx_margin_health = rx_margin_health.inner().clone();
 
     EventStream! {
         let wallet_info = rx_wallet.borrow().clone();
@@ -64,14 +78,17 @@ pub async fn maker_feed(
         let quote = rx_quote.borrow().clone();
         yield quote.to_sse_event();
 
-        let cfds = rx_cfds.borrow().clone();
-        if let Some(cfds) = cfds {
-            yield cfds.to_sse_event()
+        let cfd_events = cfd_events(rx_cfd_feed.borrow().as_ref(), &mut cfd_feed_cursor);
+        for event in cfd_events {
+            yield event;
         }
 
         let takers = rx_connected_takers.borrow().clone();
         yield takers.to_sse_event();
 
+        let margin_health = rx_margin_health.borrow().clone();
+        yield margin_health.to_sse_event();
+
         loop{
             select! {
                 Ok(()) = rx_wallet.changed() => {
@@ -87,23 +104,27 @@ pub async fn maker_feed(
                     let takers = rx_connected_takers.borrow().clone();
                     yield takers.to_sse_event();
                 }
-                Ok(()) = rx_cfds.changed() => {
-                    let cfds = rx_cfds.borrow().clone();
-                    if let Some(cfds) = cfds {
-                        yield cfds.to_sse_event()
+                Ok(()) = rx_cfd_feed.changed() => {
+                    let cfd_events = cfd_events(rx_cfd_feed.borrow().as_ref(), &mut cfd_feed_cursor);
+                    for event in cfd_events {
+                        yield event;
                     }
                 }
                 Ok(()) = rx_quote.changed() => {
                     let quote = rx_quote.borrow().clone();
                     yield quote.to_sse_event();
                 }
+                Ok(()) = rx_margin_health.changed() => {
+                    let margin_health = rx_margin_health.borrow().clone();
+                    yield margin_health.to_sse_event();
+                }
             }
         }
     }
 }
 
 /// The maker PUTs this to set the offer params
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct CfdNewOfferParamsRequest {
     pub price_long: Option<Price>,
     pub price_short: Option<Price>,
@@ -116,9 +137,21 @@ pub struct CfdNewOfferParamsRequest {
     pub tx_fee_rate: TxFeeRate,
     // TODO: This is not inline with other parts of the API! We should not expose internal types
     // here. We have to specify sats for here because of that.
+    #[schema(value_type = u64)]
     pub opening_fee: OpeningFee,
     #[serde(default = "empty_leverage")]
     pub leverage_choices: Vec<Leverage>,
+    /// Offer fixed-expiry contracts that settle at this time instead of perpetual contracts
+    ///
+    /// Has to be on the full hour, at most 90 days from now. Fixed-expiry contracts are not
+    /// rolled over and don't charge funding.
+    #[serde(default)]
+    pub expiry: Option<Timestamp>,
+    /// Offer perpetual contracts that settle after this many hours instead of the default 24
+    ///
+    /// Has to be between 6 hours and 7 days.
+    #[serde(default)]
+    pub settlement_interval_hours: Option<u32>,
 }
 
 fn empty_leverage() -> Vec<Leverage> {
@@ -128,8 +161,15 @@ fn empty_leverage() -> Vec<Leverage> {
 #[rocket::put("/offer", data = "<offer_params>")]
 pub async fn put_offer_params(
     offer_params: Json<CfdNewOfferParamsRequest>,
-    maker: &State<Maker>,
-    _auth: Authenticated,
+    maker: &State<Arc<Maker>>,
+    _auth: Authorized<scope::Trade>,
+) -> Result<(), HttpApiProblem> {
+    set_offer_params(maker, &offer_params).await
+}
+
+async fn set_offer_params(
+    maker: &Maker,
+    offer_params: &CfdNewOfferParamsRequest,
 ) -> Result<(), HttpApiProblem> {
     maker
         .set_offer_params(
@@ -142,6 +182,8 @@ pub async fn put_offer_params(
             offer_params.daily_funding_rate_short,
             offer_params.opening_fee,
             offer_params.leverage_choices.clone(),
+            offer_params.expiry,
+            offer_params.settlement_interval_hours,
         )
         .await
         .map_err(|e| {
@@ -157,14 +199,22 @@ pub async fn put_offer_params(
 pub async fn post_cfd_action(
     id: Uuid,
     action: String,
-    maker: &State<Maker>,
-    _auth: Authenticated,
+    maker: &State<Arc<Maker>>,
+    _auth: Authorized<scope::Trade>,
 ) -> Result<(), HttpApiProblem> {
     let id = OrderId::from(id);
     let action = action.parse().map_err(|_| {
         HttpApiProblem::new(StatusCode::BAD_REQUEST).detail(format!("Invalid action: {}", action))
     })?;
 
+    execute_cfd_action(maker, id, action).await
+}
+
+async fn execute_cfd_action(
+    maker: &Maker,
+    id: OrderId,
+    action: CfdAction,
+) -> Result<(), HttpApiProblem> {
     let result = match action {
         CfdAction::AcceptOrder => maker.accept_order(id).await,
         CfdAction::RejectOrder => maker.reject_order(id).await,
@@ -199,22 +249,26 @@ pub fn get_health_check() {}
 struct Asset;
 
 #[rocket::get("/assets/<file..>")]
-pub fn dist<'r>(file: PathBuf, _auth: Authenticated) -> impl Responder<'r, 'static> {
+pub fn dist<'r>(file: PathBuf, _auth: Authorized<scope::Read>) -> impl Responder<'r, 'static> {
     let filename = format!("assets/{}", file.display());
     Asset::get(&filename).into_response(file)
 }
 
 #[rocket::get("/<_paths..>", format = "text/html")]
-pub fn index<'r>(_paths: PathBuf, _auth: Authenticated) -> impl Responder<'r, 'static> {
+pub fn index<'r>(_paths: PathBuf, _auth: Authorized<scope::Read>) -> impl Responder<'r, 'static> {
     let asset = Asset::get("index.html").ok_or(Status::NotFound)?;
     Ok::<(ContentType, Cow<[u8]>), Status>((ContentType::HTML, asset.data))
 }
 
 #[rocket::put("/sync")]
 pub async fn put_sync_wallet(
-    maker: &State<Maker>,
-    _auth: Authenticated,
+    maker: &State<Arc<Maker>>,
+    _auth: Authorized<scope::Trade>,
 ) -> Result<(), HttpApiProblem> {
+    sync_wallet(maker).await
+}
+
+async fn sync_wallet(maker: &Maker) -> Result<(), HttpApiProblem> {
     maker.sync_wallet().await.map_err(|e| {
         HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
             .title("Could not sync wallet")
@@ -227,14 +281,19 @@ pub async fn put_sync_wallet(
 #[rocket::get("/cfds")]
 pub async fn get_cfds<'r>(
     rx: &State<Feeds>,
-    _auth: Authenticated,
+    _auth: Authorized<scope::Read>,
 ) -> Result<Json<Vec<Cfd>>, HttpApiProblem> {
-    let rx = rx.inner();
+    let cfds = cfds(rx.inner())?;
+
+    Ok(Json(cfds))
+}
+
+fn cfds(rx: &Feeds) -> Result<Vec<Cfd>, HttpApiProblem> {
     let rx_cfds = rx.cfds.clone();
     let cfds = rx_cfds.borrow().clone();
 
     match cfds {
-        Some(cfds) => Ok(Json(cfds)),
+        Some(cfds) => Ok(cfds),
         None => Err(HttpApiProblem::new(StatusCode::SERVICE_UNAVAILABLE)
             .title("CFDs not yet available")
             .detail("CFDs are still being loaded from the database. Please retry later.")),
@@ -244,7 +303,7 @@ pub async fn get_cfds<'r>(
 #[rocket::get("/takers")]
 pub async fn get_takers<'r>(
     rx: &State<Feeds>,
-    _auth: Authenticated,
+    _auth: Authorized<scope::Read>,
 ) -> Result<Json<Vec<Identity>>, HttpApiProblem> {
     let rx = rx.inner();
     let rx_connected_takers = rx.connected_takers.clone();
@@ -254,7 +313,7 @@ pub async fn get_takers<'r>(
 }
 
 #[rocket::get("/metrics")]
-pub async fn get_metrics<'r>(_auth: Authenticated) -> Result<String, HttpApiProblem> {
+pub async fn get_metrics<'r>(_auth: Authorized<scope::Read>) -> Result<String, HttpApiProblem> {
     let metrics = prometheus::TextEncoder::new()
         .encode_to_string(&prometheus::gather())
         .map_err(|e| {
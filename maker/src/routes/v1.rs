@@ -0,0 +1,338 @@
+//! Version 1 of the maker's HTTP API, mounted under `/api/v1`.
+//!
+//! The routes are resource-oriented and documented by an OpenAPI document served at
+//! `/api/v1/openapi.json`. The unversioned routes under `/api` remain available for the frontend.
+
+use super::cfds;
+use super::execute_cfd_action;
+use super::set_offer_params;
+use super::sync_wallet;
+use super::CfdNewOfferParamsRequest;
+use super::Maker;
+use daemon::projection::Cfd;
+use daemon::projection::CfdAction;
+use daemon::projection::CfdDetails;
+use daemon::projection::CfdOrder;
+use daemon::projection::CfdState;
+use daemon::projection::Feeds;
+use daemon::projection::LeverageDetails;
+use daemon::projection::MakerOffers;
+use daemon::projection::TxLabel;
+use daemon::projection::TxUrl;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use model::ContractType;
+use model::FundingRate;
+use model::Identity;
+use model::Leverage;
+use model::OrderId;
+use model::Position;
+use model::Price;
+use model::Role;
+use model::Timestamp;
+use model::TradingPair;
+use model::TxFeeRate;
+use model::Usd;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_basicauth::scope;
+use rocket_basicauth::Authorized;
+use serde::Deserialize;
+use shared_bin::openapi;
+use shared_bin::openapi::BasicAuth;
+use shared_bin::WalletInfo;
+use std::sync::Arc;
+use tokio::sync::watch;
+use utoipa::OpenApi;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ItchySats maker API"),
+    paths(
+        get_cfds,
+        get_cfd,
+        post_cfd_action,
+        get_offers,
+        put_offers,
+        get_wallet,
+        post_wallet_sync,
+        get_takers
+    ),
+    components(schemas(
+        Cfd,
+        CfdAction,
+        CfdActionRequest,
+        CfdDetails,
+        CfdNewOfferParamsRequest,
+        CfdOrder,
+        CfdState,
+        ContractType,
+        FundingRate,
+        Identity,
+        Leverage,
+        LeverageDetails,
+        MakerOffers,
+        OrderId,
+        Position,
+        Price,
+        Role,
+        Timestamp,
+        TradingPair,
+        TxFeeRate,
+        TxLabel,
+        TxUrl,
+        Usd,
+        WalletInfo
+    )),
+    modifiers(&BasicAuth),
+    tags(
+        (name = "cfds", description = "CFDs of the maker and the actions that can be taken on them"),
+        (name = "offers", description = "The offers published to takers"),
+        (name = "wallet", description = "The maker's on-chain wallet"),
+        (name = "takers", description = "Takers connected to the maker")
+    )
+)]
+struct ApiDoc;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        get_openapi,
+        get_cfds,
+        get_cfd,
+        post_cfd_action,
+        get_offers,
+        put_offers,
+        get_wallet,
+        post_wallet_sync,
+        get_takers
+    ]
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+pub struct CfdActionRequest {
+    pub action: CfdAction,
+}
+
+#[rocket::get("/openapi.json")]
+fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    openapi::document::<ApiDoc>()
+}
+
+/// All CFDs, the most recently created one first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/cfds",
+    responses(
+        (status = 200, description = "All CFDs", body = [Cfd]),
+        (status = 503, description = "The CFDs are still being loaded from the database")
+    ),
+    security(("basic_auth" = [])),
+    tag = "cfds"
+)]
+#[rocket::get("/cfds")]
+async fn get_cfds(
+    rx: &State<Feeds>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<Vec<Cfd>>, HttpApiProblem> {
+    let cfds = cfds(rx.inner())?;
+
+    Ok(Json(cfds))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cfds/{id}",
+    params(("id" = String, Path, description = "The order ID of the CFD")),
+    responses(
+        (status = 200, description = "The CFD", body = Cfd),
+        (status = 404, description = "There is no CFD with this order ID"),
+        (status = 503, description = "The CFDs are still being loaded from the database")
+    ),
+    security(("basic_auth" = [])),
+    tag = "cfds"
+)]
+#[rocket::get("/cfds/<id>")]
+async fn get_cfd(
+    id: Uuid,
+    rx: &State<Feeds>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<Cfd>, HttpApiProblem> {
+    let id = OrderId::from(id);
+
+    let cfd = cfds(rx.inner())?
+        .into_iter()
+        .find(|cfd| cfd.order_id == id)
+        .ok_or_else(|| {
+            HttpApiProblem::new(StatusCode::NOT_FOUND)
+                .title("CFD not found")
+                .detail(format!("There is no CFD with order ID {id}"))
+        })?;
+
+    Ok(Json(cfd))
+}
+
+/// Act on a CFD, e.g. accept an order or a rollover proposal.
+///
+/// The actions available on a CFD are listed in its `actions`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/cfds/{id}/actions",
+    params(("id" = String, Path, description = "The order ID of the CFD")),
+    request_body = CfdActionRequest,
+    responses(
+        (status = 200, description = "The action was executed"),
+        (status = 400, description = "The maker cannot take this action"),
+        (status = 500, description = "The action failed")
+    ),
+    security(("basic_auth" = [])),
+    tag = "cfds"
+)]
+#[rocket::post("/cfds/<id>/actions", data = "<request>")]
+async fn post_cfd_action(
+    id: Uuid,
+    request: Json<CfdActionRequest>,
+    maker: &State<Arc<Maker>>,
+    _auth: Authorized<scope::Trade>,
+) -> Result<(), HttpApiProblem> {
+    execute_cfd_action(maker, OrderId::from(id), request.action).await
+}
+
+/// The offers currently published to takers.
+#[utoipa::path(
+    get,
+    path = "/api/v1/offers",
+    responses((status = 200, description = "The current offers", body = MakerOffers)),
+    security(("basic_auth" = [])),
+    tag = "offers"
+)]
+#[rocket::get("/offers")]
+async fn get_offers(rx: &State<Feeds>, _auth: Authorized<scope::Read>) -> Json<MakerOffers> {
+    let offers = rx.offers.borrow().clone();
+
+    Json(offers)
+}
+
+/// Publish new offers based on the given parameters.
+#[utoipa::path(
+    put,
+    path = "/api/v1/offers",
+    request_body = CfdNewOfferParamsRequest,
+    responses(
+        (status = 200, description = "The offers were published"),
+        (status = 500, description = "Publishing the offers failed")
+    ),
+    security(("basic_auth" = [])),
+    tag = "offers"
+)]
+#[rocket::put("/offers", data = "<offer_params>")]
+async fn put_offers(
+    offer_params: Json<CfdNewOfferParamsRequest>,
+    maker: &State<Arc<Maker>>,
+    _auth: Authorized<scope::Trade>,
+) -> Result<(), HttpApiProblem> {
+    set_offer_params(maker, &offer_params).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/wallet",
+    responses(
+        (status = 200, description = "Balance and address of the wallet", body = WalletInfo),
+        (status = 503, description = "The wallet has not been synced yet")
+    ),
+    security(("basic_auth" = [])),
+    tag = "wallet"
+)]
+#[rocket::get("/wallet")]
+async fn get_wallet(
+    rx_wallet: &State<watch::Receiver<Option<model::WalletInfo>>>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<WalletInfo>, HttpApiProblem> {
+    let wallet_info = rx_wallet.borrow().as_ref().map(WalletInfo::from);
+
+    let wallet_info = wallet_info.ok_or_else(|| {
+        HttpApiProblem::new(StatusCode::SERVICE_UNAVAILABLE)
+            .title("Wallet not yet available")
+            .detail("The wallet has not been synced yet. Please retry later.")
+    })?;
+
+    Ok(Json(wallet_info))
+}
+
+/// Sync the wallet with the blockchain.
+#[utoipa::path(
+    post,
+    path = "/api/v1/wallet/sync",
+    responses(
+        (status = 200, description = "The wallet was synced"),
+        (status = 500, description = "Syncing the wallet failed")
+    ),
+    security(("basic_auth" = [])),
+    tag = "wallet"
+)]
+#[rocket::post("/wallet/sync")]
+async fn post_wallet_sync(
+    maker: &State<Arc<Maker>>,
+    _auth: Authorized<scope::Trade>,
+) -> Result<(), HttpApiProblem> {
+    sync_wallet(maker).await
+}
+
+/// The identities of all connected takers.
+#[utoipa::path(
+    get,
+    path = "/api/v1/takers",
+    responses((status = 200, description = "The connected takers", body = [Identity])),
+    security(("basic_auth" = [])),
+    tag = "takers"
+)]
+#[rocket::get("/takers")]
+async fn get_takers(rx: &State<Feeds>, _auth: Authorized<scope::Read>) -> Json<Vec<Identity>> {
+    let takers = rx.connected_takers.borrow().clone();
+
+    Json(takers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Status;
+    use shared_bin::openapi::checks;
+
+    #[test]
+    fn openapi_document_builds() {
+        checks::assert_documents::<ApiDoc>(&["/api/v1/cfds", "/api/v1/takers"]);
+    }
+
+    #[tokio::test]
+    async fn serves_openapi_document_without_authentication() {
+        let client = client().await;
+
+        checks::assert_serves_document_without_authentication(&client).await;
+    }
+
+    #[tokio::test]
+    async fn routes_require_authentication() {
+        let client = client().await;
+
+        checks::assert_requires_authentication(&client, "/api/v1/offers").await;
+        checks::assert_requires_authentication(&client, "/api/v1/takers").await;
+    }
+
+    #[tokio::test]
+    async fn routes_respond_to_authenticated_requests() {
+        let client = client().await;
+
+        checks::assert_responds_to_authenticated_requests(&client).await;
+        assert_eq!(
+            checks::get_authenticated(&client, "/api/v1/takers").await,
+            Status::Ok
+        );
+    }
+
+    async fn client() -> rocket::local::asynchronous::Client {
+        checks::client(checks::rocket(routes())).await
+    }
+}
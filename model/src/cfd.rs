@@ -21,7 +21,8 @@ use crate::Timestamp;
 use crate::TradingPair;
 use crate::TxFeeRate;
 use crate::Usd;
-use crate::SETTLEMENT_INTERVAL;
+use crate::MAX_SETTLEMENT_INTERVAL;
+use crate::MIN_SETTLEMENT_INTERVAL;
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Context;
@@ -61,11 +62,12 @@ use std::ops::RangeInclusive;
 use std::str;
 use time::Duration;
 use time::OffsetDateTime;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 pub const CET_TIMELOCK: u32 = 12;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, ToSchema)]
 pub struct OrderId(Uuid);
 
 impl Serialize for OrderId {
@@ -174,6 +176,53 @@ impl MakerOffers {
         (None, self)
     }
 
+    /// Combine the offers of two makers into the best offers from the taker's perspective.
+    ///
+    /// On the maker's long side the taker goes short, hence the higher price is better; on the
+    /// maker's short side the lower price is better. The funding rate of each side is taken
+    /// along with its offer. If both offers are equally good, those of `self` are kept.
+    ///
+    /// The transaction fee rate is taken from the maker whose offers win. If each maker wins one
+    /// side, the higher of their fee rates is reported to not understate the fees of either offer.
+    pub fn best_of(self, other: MakerOffers) -> MakerOffers {
+        let other_long_is_better = match (&self.long, &other.long) {
+            (Some(ours), Some(theirs)) => theirs.price > ours.price,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        let other_short_is_better = match (&self.short, &other.short) {
+            (Some(ours), Some(theirs)) => theirs.price < ours.price,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+
+        let tx_fee_rate = match (other_long_is_better, other_short_is_better) {
+            (true, true) => other.tx_fee_rate,
+            (false, false) => self.tx_fee_rate,
+            _ if other.tx_fee_rate.to_u32() > self.tx_fee_rate.to_u32() => other.tx_fee_rate,
+            _ => self.tx_fee_rate,
+        };
+
+        let (long, funding_rate_long) = if other_long_is_better {
+            (other.long, other.funding_rate_long)
+        } else {
+            (self.long, self.funding_rate_long)
+        };
+        let (short, funding_rate_short) = if other_short_is_better {
+            (other.short, other.funding_rate_short)
+        } else {
+            (self.short, self.funding_rate_short)
+        };
+
+        MakerOffers {
+            long,
+            short,
+            tx_fee_rate,
+            funding_rate_long,
+            funding_rate_short,
+        }
+    }
+
     /// Update the orders after one of them got taken.
     pub fn replicate(&self) -> MakerOffers {
         MakerOffers {
@@ -194,7 +243,7 @@ pub enum Origin {
 }
 
 /// Role in the Cfd
-#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum Role {
     Maker,
     Taker,
@@ -209,6 +258,23 @@ impl From<Origin> for Role {
     }
 }
 
+/// The kind of contract a CFD is based on
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum ContractType {
+    /// A contract without expiry, which is extended by rolling it over and charging funding fees
+    Perpetual,
+    /// A contract that settles at the price attested by the oracle event it was set up with
+    ///
+    /// Fixed-expiry contracts are never rolled over and do not charge funding fees.
+    FixedExpiry,
+}
+
+impl Default for ContractType {
+    fn default() -> Self {
+        ContractType::Perpetual
+    }
+}
+
 /// A concrete order created by a maker for a taker
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Order {
@@ -256,6 +322,10 @@ pub struct Order {
     pub tx_fee_rate: TxFeeRate,
     pub funding_rate: FundingRate,
     pub opening_fee: OpeningFee,
+
+    /// Defaults to a perpetual contract for orders of makers that predate fixed-expiry contracts
+    #[serde(default)]
+    pub contract_type: ContractType,
 }
 
 impl Order {
@@ -272,6 +342,7 @@ impl Order {
         funding_rate: FundingRate,
         opening_fee: OpeningFee,
         leverage_choices: Vec<Leverage>,
+        contract_type: ContractType,
     ) -> Self {
         // allowing deprecated use of field `leverage_taker` here for backwards compatibility.
         #[allow(deprecated)]
@@ -291,6 +362,7 @@ impl Order {
             tx_fee_rate,
             funding_rate,
             opening_fee,
+            contract_type,
         }
     }
 
@@ -308,6 +380,7 @@ impl Order {
             self.funding_rate,
             self.opening_fee,
             self.leverage_choices.clone(),
+            self.contract_type,
         )
     }
 
@@ -317,11 +390,21 @@ impl Order {
     /// consider an order to be outdated.
     const OUTDATED_AFTER_MINS: i64 = 10;
 
+    /// The furthest into the future a fixed-expiry contract may expire
+    pub const MAX_EXPIRY: Duration = Duration::days(90);
+
     /// Defines when we consider the order to be outdated.
     ///
     /// This is used as a safety net to prevent the taker from taking an outdated order.
     pub fn is_safe_to_take(&self, now: OffsetDateTime) -> bool {
-        !self.is_creation_timestamp_outdated(now) && self.is_oracle_event_timestamp_sane(now)
+        if self.is_creation_timestamp_outdated(now) {
+            return false;
+        }
+
+        match self.contract_type {
+            ContractType::Perpetual => self.is_oracle_event_timestamp_sane(now),
+            ContractType::FixedExpiry => self.is_fixed_expiry_sane(now),
+        }
     }
 
     /// Check if the the maker's offer creation timestamp is outdated
@@ -335,16 +418,41 @@ impl Order {
 
     /// Check the oracle event's timestamp for sanity
     ///
-    /// An id within [25h, 23h] from now is considered sane.
+    /// The order's settlement interval has to be a whole number of hours within
+    /// [`MIN_SETTLEMENT_INTERVAL`, `MAX_SETTLEMENT_INTERVAL`]. An id within one hour of the
+    /// settlement interval from now is considered sane, e.g. [23h, 25h] for 24 hours.
     fn is_oracle_event_timestamp_sane(&self, now: OffsetDateTime) -> bool {
+        if !self.is_settlement_interval_sane() {
+            return false;
+        }
+
         let event_id_timestamp = self.oracle_event_id.timestamp();
 
-        let settlement_interval_minus_one_hour = now + SETTLEMENT_INTERVAL - Duration::HOUR;
-        let settlement_interval_plus_one_hour = now + SETTLEMENT_INTERVAL + Duration::HOUR;
+        let settlement_interval_minus_one_hour = now + self.settlement_interval - Duration::HOUR;
+        let settlement_interval_plus_one_hour = now + self.settlement_interval + Duration::HOUR;
 
         event_id_timestamp >= settlement_interval_minus_one_hour
             && event_id_timestamp <= settlement_interval_plus_one_hour
     }
+
+    fn is_settlement_interval_sane(&self) -> bool {
+        self.settlement_interval >= MIN_SETTLEMENT_INTERVAL
+            && self.settlement_interval <= MAX_SETTLEMENT_INTERVAL
+            && self.settlement_interval.whole_seconds() % Duration::HOUR.whole_seconds() == 0
+    }
+
+    /// Check the terms of a fixed-expiry contract for sanity
+    ///
+    /// The oracle event has to be within (1h, `MAX_EXPIRY`] from now and the settlement interval
+    /// has to reach it. Fixed-expiry contracts don't charge any funding.
+    fn is_fixed_expiry_sane(&self, now: OffsetDateTime) -> bool {
+        let event_id_timestamp = self.oracle_event_id.timestamp();
+
+        event_id_timestamp > now + Duration::HOUR
+            && event_id_timestamp <= now + Self::MAX_EXPIRY
+            && now + self.settlement_interval >= event_id_timestamp - Duration::HOUR
+            && self.funding_rate == FundingRate::default()
+    }
 }
 
 /// Proposed collaborative settlement
@@ -373,6 +481,8 @@ pub enum NoRolloverReason {
     InCollaborativeSettlement,
     #[error("Cannot roll over when CFD is already closed")]
     Closed,
+    #[error("Cannot roll over a fixed-expiry contract")]
+    FixedExpiry,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -510,6 +620,32 @@ pub enum EventKind {
         #[serde(with = "hex_transaction")]
         tx: Transaction,
     },
+
+    /// The maker accepted a request of the taker without operator intervention.
+    AutoAccepted {
+        action: AutoAcceptAction,
+        reason: String,
+    },
+}
+
+/// The taker requests a maker can accept automatically.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum AutoAcceptAction {
+    Order,
+    Rollover,
+    Settlement,
+}
+
+impl fmt::Display for AutoAcceptAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AutoAcceptAction::Order => "order",
+            AutoAcceptAction::Rollover => "rollover",
+            AutoAcceptAction::Settlement => "settlement",
+        };
+
+        s.fmt(f)
+    }
 }
 
 impl fmt::Display for EventKind {
@@ -545,6 +681,7 @@ impl fmt::Display for EventKind {
             OracleAttestedPriorCetTimelock { .. } => "OracleAttestedPriorCetTimelock",
             OracleAttestedPostCetTimelock { .. } => "OracleAttestedPostCetTimelock",
             ManualCommit { .. } => "ManualCommit",
+            AutoAccepted { .. } => "AutoAccepted",
         };
 
         s.fmt(f)
@@ -552,6 +689,38 @@ impl fmt::Display for EventKind {
 }
 
 impl EventKind {
+    /// The names of all events, as given by the [`fmt::Display`] implementation.
+    pub const NAMES: &'static [&'static str] = &[
+        "ContractSetupStarted",
+        "ContractSetupCompleted",
+        "ContractSetupFailed",
+        "OfferRejected",
+        "RolloverStarted",
+        "RolloverAccepted",
+        "RolloverRejected",
+        "RolloverCompleted",
+        "RolloverFailed",
+        "CollaborativeSettlementStarted",
+        "CollaborativeSettlementProposalAccepted",
+        "CollaborativeSettlementCompleted",
+        "CollaborativeSettlementRejected",
+        "CollaborativeSettlementFailed",
+        "LockConfirmed",
+        "LockConfirmedAfterFinality",
+        "CommitConfirmed",
+        "CetConfirmed",
+        "RefundConfirmed",
+        "RevokeConfirmed",
+        "CollaborativeSettlementConfirmed",
+        "CetTimelockExpiredPriorOracleAttestation",
+        "CetTimelockExpiredPostOracleAttestation",
+        "RefundTimelockExpired",
+        "OracleAttestedPriorCetTimelock",
+        "OracleAttestedPostCetTimelock",
+        "ManualCommit",
+        "AutoAccepted",
+    ];
+
     pub const CONTRACT_SETUP_STARTED: &'static str = "ContractSetupCompleted";
     pub const CONTRACT_SETUP_COMPLETED_EVENT: &'static str = "ContractSetupCompleted";
     pub const ROLLOVER_COMPLETED_EVENT: &'static str = "RolloverCompleted";
@@ -615,6 +784,7 @@ pub struct Cfd {
     role: Role,
     opening_fee: OpeningFee,
     initial_tx_fee_rate: TxFeeRate,
+    contract_type: ContractType,
     // dynamic (based on events)
     fee_account: FeeAccount,
 
@@ -668,6 +838,7 @@ impl Cfd {
         opening_fee: OpeningFee,
         initial_funding_rate: FundingRate,
         initial_tx_fee_rate: TxFeeRate,
+        contract_type: ContractType,
     ) -> Self {
         let (long_leverage, short_leverage) =
             long_and_short_leverage(taker_leverage, role, position);
@@ -678,7 +849,7 @@ impl Cfd {
             long_leverage,
             short_leverage,
             initial_funding_rate,
-            SETTLEMENT_INTERVAL.whole_hours(),
+            settlement_interval.whole_hours(),
         )
         .expect("values from db to be sane");
 
@@ -697,6 +868,7 @@ impl Cfd {
             initial_funding_rate,
             opening_fee,
             initial_tx_fee_rate,
+            contract_type,
             dlc: None,
             cet: None,
             commit_tx: None,
@@ -745,6 +917,7 @@ impl Cfd {
             order.opening_fee,
             order.funding_rate,
             order.tx_fee_rate,
+            order.contract_type,
         )
     }
 
@@ -788,9 +961,13 @@ impl Cfd {
         &self,
         now: OffsetDateTime,
     ) -> Result<(Txid, BitMexPriceEventId), NoRolloverReason> {
+        if self.contract_type == ContractType::FixedExpiry {
+            return Err(NoRolloverReason::FixedExpiry);
+        }
+
         let expiry_timestamp = self.expiry_timestamp().ok_or(NoRolloverReason::NoDlc)?;
         let time_until_expiry = expiry_timestamp - now;
-        if time_until_expiry > SETTLEMENT_INTERVAL - Duration::HOUR {
+        if time_until_expiry > self.settlement_interval - Duration::HOUR {
             return Err(NoRolloverReason::TooRecent);
         }
 
@@ -802,6 +979,10 @@ impl Cfd {
     }
 
     fn can_rollover(&self) -> Result<(), NoRolloverReason> {
+        if self.contract_type == ContractType::FixedExpiry {
+            return Err(NoRolloverReason::FixedExpiry);
+        }
+
         if self.is_closed() {
             return Err(NoRolloverReason::Closed);
         }
@@ -1492,6 +1673,11 @@ impl Cfd {
         }))
     }
 
+    /// Record that we accepted a request of the taker on behalf of the operator.
+    pub fn auto_accept(&self, action: AutoAcceptAction, reason: String) -> CfdEvent {
+        self.event(EventKind::AutoAccepted { action, reason })
+    }
+
     fn event(&self, event: EventKind) -> CfdEvent {
         CfdEvent::new(self.id, event)
     }
@@ -1530,6 +1716,14 @@ impl Cfd {
         self.initial_price
     }
 
+    pub fn long_liquidation_price(&self) -> Price {
+        calculate_long_liquidation_price(self.long_leverage, self.initial_price)
+    }
+
+    pub fn short_liquidation_price(&self) -> Price {
+        calculate_short_liquidation_price(self.short_leverage, self.initial_price)
+    }
+
     pub fn taker_leverage(&self) -> Leverage {
         match (self.role, self.position) {
             (Role::Taker, Position::Long) | (Role::Maker, Position::Short) => self.long_leverage,
@@ -1569,6 +1763,10 @@ impl Cfd {
         self.opening_fee
     }
 
+    pub fn contract_type(&self) -> ContractType {
+        self.contract_type
+    }
+
     /// Check whether PeerId matches the one the CFD got created with
     pub fn verify_counterparty_peer_id(&self, peer_id: &PeerId) -> Result<()> {
         match self.counterparty_peer_id() {
@@ -1590,8 +1788,8 @@ impl Cfd {
     /// extended by with the next rollover.
     ///
     /// During rollover the time-to-live of the contract is extended
-    /// so that the non-collaborative settlement time is set to ~24
-    /// hours in the future from now.
+    /// so that the non-collaborative settlement time is set to ~one
+    /// settlement interval in the future from now.
     fn hours_to_extend_in_rollover(&self, now: OffsetDateTime) -> Result<u64> {
         let dlc = self.dlc.as_ref().context("Cannot roll over without DLC")?;
         let settlement_time = dlc.settlement_event_id.timestamp();
@@ -1603,10 +1801,11 @@ impl Cfd {
         if !hours_left.is_positive() {
             tracing::warn!("Rolling over a contract that can be settled non-collaboratively");
 
-            return Ok(SETTLEMENT_INTERVAL.whole_hours() as u64);
+            return Ok(self.settlement_interval.whole_hours() as u64);
         }
 
-        let time_to_extend = SETTLEMENT_INTERVAL
+        let time_to_extend = self
+            .settlement_interval
             .checked_sub(hours_left)
             .context("Subtraction overflow")?;
         let hours_to_extend = time_to_extend.whole_hours();
@@ -1614,7 +1813,7 @@ impl Cfd {
         if hours_to_extend.is_negative() {
             bail!(
                 "Cannot rollover if time-to-live of contract is > {} hours",
-                SETTLEMENT_INTERVAL.whole_hours()
+                self.settlement_interval.whole_hours()
             );
         }
 
@@ -1640,7 +1839,7 @@ impl Cfd {
         if !hours_left.is_positive() {
             tracing::warn!("Rolling over a contract that can be settled non-collaboratively");
 
-            return Ok(SETTLEMENT_INTERVAL.whole_hours() as u64);
+            return Ok(self.settlement_interval.whole_hours() as u64);
         }
 
         let to_settlement_time = to_event_id.timestamp();
@@ -1740,6 +1939,9 @@ impl Cfd {
                 // commands
             }
             ManualCommit { tx } => self.commit_tx = Some(tx),
+            AutoAccepted { .. } => {
+                // Only recorded for auditing, the accepted protocol emits its own events
+            }
             RevokeConfirmed => {
                 tracing::error!(order_id = %self.id, "Revoked logic not implemented");
                 // TODO: we should punish the other party instead. For now, we pretend we are in
@@ -2441,6 +2643,7 @@ pub fn calculate_payouts(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::SETTLEMENT_INTERVAL;
     use bdk::bitcoin;
     use bdk::bitcoin::secp256k1::SecretKey;
     use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
@@ -2451,6 +2654,7 @@ mod tests {
     use rand::thread_rng;
     use rust_decimal_macros::dec;
     use std::collections::BTreeMap;
+    use std::num::NonZeroU32;
     use std::str::FromStr;
     use time::ext::NumericalDuration;
     use time::macros::datetime;
@@ -2872,6 +3076,255 @@ mod tests {
         assert_eq!(event, EventKind::OfferRejected);
     }
 
+    #[test]
+    fn event_names_contain_name_of_every_event() {
+        let events = one_event_of_every_kind();
+
+        for event in events.iter() {
+            assert!(EventKind::NAMES.contains(&event.to_string().as_str()));
+        }
+        assert_eq!(
+            EventKind::NAMES.len(),
+            EventKind::NAMES
+                .iter()
+                .collect::<std::collections::HashSet<_>>()
+                .len(),
+            "event names should be unique"
+        );
+        assert_eq!(
+            EventKind::NAMES.len(),
+            events.len(),
+            "every event should be listed"
+        );
+    }
+
+    /// One event of every kind.
+    ///
+    /// The events are matched exhaustively, so adding a kind of event does not compile until it
+    /// is listed here as well.
+    fn one_event_of_every_kind() -> Vec<EventKind> {
+        use EventKind::*;
+
+        let price = Price::new(dec!(10000)).unwrap();
+        let events = vec![
+            ContractSetupStarted,
+            ContractSetupCompleted { dlc: None },
+            ContractSetupFailed,
+            OfferRejected,
+            RolloverStarted,
+            RolloverAccepted,
+            RolloverRejected,
+            RolloverCompleted {
+                dlc: None,
+                funding_fee: FundingFee::new(Amount::ZERO, FundingRate::default()),
+                complete_fee: None,
+            },
+            RolloverFailed,
+            CollaborativeSettlementStarted {
+                proposal: SettlementProposal {
+                    order_id: OrderId::default(),
+                    taker: Amount::ZERO,
+                    maker: Amount::ZERO,
+                    price,
+                },
+            },
+            CollaborativeSettlementProposalAccepted,
+            CollaborativeSettlementCompleted {
+                spend_tx: dummy_transaction(),
+                script: Script::new(),
+                price,
+            },
+            CollaborativeSettlementRejected,
+            CollaborativeSettlementFailed,
+            LockConfirmed,
+            LockConfirmedAfterFinality,
+            CommitConfirmed,
+            CetConfirmed,
+            RefundConfirmed,
+            RevokeConfirmed,
+            CollaborativeSettlementConfirmed,
+            CetTimelockExpiredPriorOracleAttestation,
+            CetTimelockExpiredPostOracleAttestation {
+                cet: dummy_transaction(),
+            },
+            RefundTimelockExpired {
+                refund_tx: dummy_transaction(),
+            },
+            OracleAttestedPriorCetTimelock {
+                timelocked_cet: dummy_transaction(),
+                commit_tx: None,
+                price,
+            },
+            OracleAttestedPostCetTimelock {
+                cet: dummy_transaction(),
+                price,
+            },
+            ManualCommit {
+                tx: dummy_transaction(),
+            },
+            AutoAccepted {
+                action: AutoAcceptAction::Order,
+                reason: String::new(),
+            },
+        ];
+
+        for event in events.iter() {
+            match event {
+                ContractSetupStarted
+                | ContractSetupCompleted { .. }
+                | ContractSetupFailed
+                | OfferRejected
+                | RolloverStarted
+                | RolloverAccepted
+                | RolloverRejected
+                | RolloverCompleted { .. }
+                | RolloverFailed
+                | CollaborativeSettlementStarted { .. }
+                | CollaborativeSettlementProposalAccepted
+                | CollaborativeSettlementCompleted { .. }
+                | CollaborativeSettlementRejected
+                | CollaborativeSettlementFailed
+                | LockConfirmed
+                | LockConfirmedAfterFinality
+                | CommitConfirmed
+                | CetConfirmed
+                | RefundConfirmed
+                | RevokeConfirmed
+                | CollaborativeSettlementConfirmed
+                | CetTimelockExpiredPriorOracleAttestation
+                | CetTimelockExpiredPostOracleAttestation { .. }
+                | RefundTimelockExpired { .. }
+                | OracleAttestedPriorCetTimelock { .. }
+                | OracleAttestedPostCetTimelock { .. }
+                | ManualCommit { .. }
+                | AutoAccepted { .. } => {}
+            }
+        }
+
+        events
+    }
+
+    #[test]
+    fn auto_accepted_event_roundtrips_through_json() {
+        let event = EventKind::AutoAccepted {
+            action: AutoAcceptAction::Rollover,
+            reason: "Liquidation is 2500 bps away".to_owned(),
+        };
+
+        let (name, data) = event.to_json();
+
+        assert_eq!(name, "AutoAccepted");
+        assert_eq!(EventKind::from_json(name, data).unwrap(), event);
+    }
+
+    #[test]
+    fn best_of_maker_offers_picks_best_price_for_taker_on_either_side() {
+        let offers = |long: Decimal, short: Decimal, funding_rate: FundingRate| MakerOffers {
+            long: Some(Order {
+                position_maker: Position::Long,
+                ..Order::dummy_short().with_price(Price::new(long).unwrap())
+            }),
+            short: Some(Order::dummy_short().with_price(Price::new(short).unwrap())),
+            tx_fee_rate: TxFeeRate::default(),
+            funding_rate_long: funding_rate,
+            funding_rate_short: funding_rate,
+        };
+        let funding_rate_a = FundingRate::new(dec!(0.0001)).unwrap();
+        let funding_rate_b = FundingRate::new(dec!(0.0002)).unwrap();
+        let offers_a = offers(dec!(1000), dec!(1010), funding_rate_a);
+        let offers_b = offers(dec!(1005), dec!(1020), funding_rate_b);
+
+        let best = offers_a.clone().best_of(offers_b.clone());
+
+        assert_eq!(best.long, offers_b.long);
+        assert_eq!(best.funding_rate_long, funding_rate_b);
+        assert_eq!(best.short, offers_a.short);
+        assert_eq!(best.funding_rate_short, funding_rate_a);
+    }
+
+    #[test]
+    fn best_of_maker_offers_keeps_offers_of_other_maker_if_side_is_missing() {
+        let offers_a = MakerOffers {
+            long: None,
+            short: Some(Order::dummy_short()),
+            tx_fee_rate: TxFeeRate::default(),
+            funding_rate_long: FundingRate::default(),
+            funding_rate_short: FundingRate::default(),
+        };
+        let offers_b = MakerOffers {
+            long: Some(Order {
+                position_maker: Position::Long,
+                ..Order::dummy_short()
+            }),
+            short: None,
+            ..offers_a.clone()
+        };
+
+        let best = offers_a.clone().best_of(offers_b.clone());
+
+        assert_eq!(best.long, offers_b.long);
+        assert_eq!(best.short, offers_a.short);
+    }
+
+    #[test]
+    fn best_of_maker_offers_takes_fee_rate_of_winning_maker() {
+        let offers_a = MakerOffers {
+            long: Some(Order {
+                position_maker: Position::Long,
+                ..Order::dummy_short().with_price(Price::new(dec!(1000)).unwrap())
+            }),
+            short: Some(Order::dummy_short().with_price(Price::new(dec!(1010)).unwrap())),
+            tx_fee_rate: TxFeeRate::new(NonZeroU32::new(1).unwrap()),
+            funding_rate_long: FundingRate::default(),
+            funding_rate_short: FundingRate::default(),
+        };
+        let offers_b = MakerOffers {
+            long: Some(Order {
+                position_maker: Position::Long,
+                ..Order::dummy_short().with_price(Price::new(dec!(1005)).unwrap())
+            }),
+            short: Some(Order::dummy_short().with_price(Price::new(dec!(1005)).unwrap())),
+            tx_fee_rate: TxFeeRate::new(NonZeroU32::new(2).unwrap()),
+            ..offers_a.clone()
+        };
+
+        let best = offers_a.clone().best_of(offers_b.clone());
+
+        assert_eq!(best.tx_fee_rate, offers_b.tx_fee_rate);
+    }
+
+    #[test]
+    fn best_of_maker_offers_takes_higher_fee_rate_if_makers_win_one_side_each() {
+        let offers_a = MakerOffers {
+            long: Some(Order {
+                position_maker: Position::Long,
+                ..Order::dummy_short().with_price(Price::new(dec!(1000)).unwrap())
+            }),
+            short: Some(Order::dummy_short().with_price(Price::new(dec!(1010)).unwrap())),
+            tx_fee_rate: TxFeeRate::new(NonZeroU32::new(2).unwrap()),
+            funding_rate_long: FundingRate::default(),
+            funding_rate_short: FundingRate::default(),
+        };
+        let offers_b = MakerOffers {
+            long: Some(Order {
+                position_maker: Position::Long,
+                ..Order::dummy_short().with_price(Price::new(dec!(1005)).unwrap())
+            }),
+            short: Some(Order::dummy_short().with_price(Price::new(dec!(1020)).unwrap())),
+            tx_fee_rate: TxFeeRate::new(NonZeroU32::new(1).unwrap()),
+            ..offers_a.clone()
+        };
+
+        assert_eq!(
+            offers_a.clone().best_of(offers_b.clone()).tx_fee_rate,
+            offers_a.tx_fee_rate
+        );
+        assert_eq!(
+            offers_b.best_of(offers_a.clone()).tx_fee_rate,
+            offers_a.tx_fee_rate
+        );
+    }
+
     #[test]
     fn given_cfd_expires_now_then_rollover() {
         // --|----|-------------------------------------------------|--> time
@@ -3654,6 +4107,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn calculate_hours_to_extend_in_rollover_based_on_settlement_interval_of_order() {
+        for now in common_time_boundaries() {
+            let order = Order {
+                settlement_interval: 6.hours(),
+                ..Order::dummy_short()
+            };
+            let taker = Cfd::taker_long_from_order(order, Usd::new(dec!(1000)), Leverage::TWO)
+                .dummy_open(BitMexPriceEventId::with_20_digits(now + 2.hours()));
+
+            assert_eq!(
+                taker.hours_to_extend_in_rollover(now).unwrap(),
+                4,
+                "Failed with now {}",
+                now
+            );
+        }
+    }
+
     #[test]
     fn cannot_rollover_if_time_to_live_is_longer_than_settlement_interval() {
         for now in common_time_boundaries() {
@@ -3889,6 +4361,106 @@ mod tests {
         assert!(!sane, "an oracle event id that is outdated got accepted")
     }
 
+    #[test]
+    fn given_settlement_interval_of_6h_and_oracle_event_id_6h_in_the_future_then_sane_to_take() {
+        let mut order = Order::dummy_short().with_oracle_event_id(
+            BitMexPriceEventId::with_20_digits(datetime!(2021-11-18 16:00:00).assume_utc()),
+        );
+        order.settlement_interval = Duration::hours(6);
+
+        let sane =
+            order.is_oracle_event_timestamp_sane(datetime!(2021-11-18 10:00:00).assume_utc());
+        assert!(sane)
+    }
+
+    #[test]
+    fn given_settlement_interval_of_7_days_and_oracle_event_id_24h_in_the_future_then_not_sane_to_take(
+    ) {
+        let mut order = Order::dummy_short().with_oracle_event_id(
+            BitMexPriceEventId::with_20_digits(datetime!(2021-11-19 10:00:00).assume_utc()),
+        );
+        order.settlement_interval = Duration::days(7);
+
+        let sane =
+            order.is_oracle_event_timestamp_sane(datetime!(2021-11-18 10:00:00).assume_utc());
+        assert!(
+            !sane,
+            "an oracle event id not matching the settlement interval got accepted"
+        )
+    }
+
+    #[test]
+    fn given_settlement_interval_beyond_max_then_not_sane_to_take() {
+        let mut order = Order::dummy_short().with_oracle_event_id(
+            BitMexPriceEventId::with_20_digits(datetime!(2021-11-26 11:00:00).assume_utc()),
+        );
+        order.settlement_interval = MAX_SETTLEMENT_INTERVAL + Duration::HOUR;
+
+        let sane =
+            order.is_oracle_event_timestamp_sane(datetime!(2021-11-18 10:00:00).assume_utc());
+        assert!(!sane, "a settlement interval that is too long got accepted")
+    }
+
+    #[test]
+    fn given_fixed_expiry_within_max_expiry_then_sane_to_take() {
+        let order = Order::dummy_fixed_expiry(datetime!(2021-12-18 10:00:00).assume_utc());
+
+        let sane = order.is_fixed_expiry_sane(datetime!(2021-11-18 10:00:00).assume_utc());
+        assert!(sane)
+    }
+
+    #[test]
+    fn given_fixed_expiry_beyond_max_expiry_then_not_sane_to_take() {
+        let order = Order::dummy_fixed_expiry(datetime!(2022-03-18 10:00:00).assume_utc());
+
+        let sane = order.is_fixed_expiry_sane(datetime!(2021-11-18 10:00:00).assume_utc());
+        assert!(
+            !sane,
+            "an expiry that is too far in the future got accepted"
+        )
+    }
+
+    #[test]
+    fn given_fixed_expiry_with_funding_rate_then_not_sane_to_take() {
+        let order = Order::dummy_fixed_expiry(datetime!(2021-12-18 10:00:00).assume_utc())
+            .with_funding_rate(FundingRate::new(dec!(0.0005)).unwrap());
+
+        let sane = order.is_fixed_expiry_sane(datetime!(2021-11-18 10:00:00).assume_utc());
+        assert!(
+            !sane,
+            "a fixed-expiry contract charging funding got accepted"
+        )
+    }
+
+    #[test]
+    fn given_fixed_expiry_settlement_interval_before_expiry_then_not_sane_to_take() {
+        let mut order = Order::dummy_fixed_expiry(datetime!(2021-12-18 10:00:00).assume_utc());
+        order.settlement_interval = SETTLEMENT_INTERVAL;
+
+        let sane = order.is_fixed_expiry_sane(datetime!(2021-11-18 10:00:00).assume_utc());
+        assert!(
+            !sane,
+            "a settlement interval that ends before the expiry got accepted"
+        )
+    }
+
+    #[test]
+    fn given_fixed_expiry_then_no_auto_rollover() {
+        let expiry = datetime!(2021-11-19 10:00:00).assume_utc();
+        let cfd = Cfd::taker_long_from_order(
+            Order::dummy_fixed_expiry(expiry),
+            Usd::new(dec!(1000)),
+            Leverage::TWO,
+        )
+        .dummy_open(BitMexPriceEventId::with_20_digits(expiry));
+
+        let cannot_roll_over = cfd
+            .can_auto_rollover_taker(datetime!(2021-11-19 10:00:00).assume_utc())
+            .unwrap_err();
+
+        assert_eq!(cannot_roll_over, NoRolloverReason::FixedExpiry)
+    }
+
     impl CfdEvent {
         fn dummy_open(event_id: BitMexPriceEventId) -> Vec<Self> {
             vec![
@@ -4279,9 +4851,20 @@ mod tests {
                 FundingRate::default(),
                 OpeningFee::default(),
                 vec![Leverage::TWO],
+                ContractType::Perpetual,
             )
         }
 
+        /// A fixed-expiry order created 30 days before the given expiry
+        fn dummy_fixed_expiry(expiry: OffsetDateTime) -> Self {
+            Order {
+                oracle_event_id: BitMexPriceEventId::with_20_digits(expiry),
+                settlement_interval: time::Duration::days(30),
+                contract_type: ContractType::FixedExpiry,
+                ..Order::dummy_short()
+            }
+        }
+
         fn with_price(mut self, price: Price) -> Self {
             self.price = price;
             self
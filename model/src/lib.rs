@@ -24,6 +24,7 @@ use std::str;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 use time::OffsetDateTime;
+use utoipa::ToSchema;
 
 mod cfd;
 mod contract_setup;
@@ -38,13 +39,23 @@ pub use contract_setup::SetupParams;
 pub use rollover::RolloverParams;
 pub use rollover::Version as RolloverVersion;
 
-/// The time-to-live of a CFD after it is first created or rolled
+/// The default time-to-live of a CFD after it is first created or rolled
 /// over.
 ///
-/// This variable determines what oracle event ID will be associated
-/// with the non-collaborative settlement of the CFD.
+/// Makers can offer a different settlement interval per order, within
+/// [`MIN_SETTLEMENT_INTERVAL`, `MAX_SETTLEMENT_INTERVAL`]. The settlement interval determines what
+/// oracle event ID will be associated with the non-collaborative settlement of the CFD.
+///
+/// Funding rates are always quoted per `SETTLEMENT_INTERVAL`, independent of the settlement
+/// interval of an order.
 pub const SETTLEMENT_INTERVAL: time::Duration = time::Duration::hours(24);
 
+/// The shortest settlement interval takers accept for perpetual CFDs.
+pub const MIN_SETTLEMENT_INTERVAL: time::Duration = time::Duration::hours(6);
+
+/// The longest settlement interval takers accept for perpetual CFDs.
+pub const MAX_SETTLEMENT_INTERVAL: time::Duration = time::Duration::days(7);
+
 #[derive(thiserror::Error, Debug, Clone, Copy)]
 pub enum Error {
     #[error("Price of zero is not allowed.")]
@@ -54,7 +65,7 @@ pub enum Error {
 }
 
 /// Represents "quantity" or "contract size" in Cfd terms
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize, ToSchema)]
 pub struct Usd(Decimal);
 
 impl Usd {
@@ -89,7 +100,7 @@ impl str::FromStr for Usd {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize, ToSchema)]
 pub struct Price(Decimal);
 
 impl Price {
@@ -157,7 +168,7 @@ impl InversePrice {
     }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
 pub struct Leverage(u8);
 
 impl Leverage {
@@ -447,12 +458,12 @@ impl From<Decimal> for Percent {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ToSchema)]
 pub enum TradingPair {
     BtcUsd,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ToSchema)]
 pub enum Position {
     Long,
     Short,
@@ -468,7 +479,9 @@ impl Position {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+/// Serialized as the hex-encoded public key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, ToSchema)]
+#[schema(value_type = String)]
 pub struct Identity(x25519_dalek::PublicKey);
 
 impl Identity {
@@ -531,7 +544,7 @@ pub struct WalletInfo {
     pub last_updated_at: Timestamp,
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
 pub struct Timestamp(i64);
 
 impl Timestamp {
@@ -561,7 +574,7 @@ impl Timestamp {
 }
 
 /// Funding rate per SETTLEMENT_INTERVAL
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct FundingRate(Decimal);
 
 impl FundingRate {
@@ -888,7 +901,8 @@ impl FeeAccount {
 }
 
 /// Transaction fee in satoshis per vbyte
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+#[schema(value_type = u32)]
 pub struct TxFeeRate(NonZeroU32);
 
 impl TxFeeRate {
@@ -1061,6 +1075,7 @@ pub struct FailedCfd {
     pub counterparty_peer_id: PeerId,
     pub role: Role,
     pub fees: Fees,
+    pub contract_type: ContractType,
     pub kind: FailedKind,
     pub creation_timestamp: Timestamp,
 }
@@ -1112,6 +1127,7 @@ pub struct ClosedCfd {
     pub counterparty_peer_id: PeerId,
     pub role: Role,
     pub fees: Fees,
+    pub contract_type: ContractType,
     pub expiry_timestamp: OffsetDateTime,
     pub lock: Lock,
     pub settlement: Settlement,
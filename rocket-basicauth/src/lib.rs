@@ -1,23 +1,53 @@
 use rocket::http::Header;
 use rocket::http::Status;
+use rocket::mtls::Certificate;
 use rocket::outcome::try_outcome;
 use rocket::request::FromRequest;
 use rocket::request::Outcome;
 use rocket::Request;
 use rocket::State;
+use scope::RequiredScope;
 use std::fmt;
+use std::marker::PhantomData;
 use std::str;
 use std::string::FromUtf8Error;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
 use void::Void;
 
-/// A request guard that can be included in handler definitions to enforce authentication.
+pub use scope::Scope;
+pub use token::generate_secret;
+pub use token::SecretHash;
+pub use token::StoredToken;
+pub use token::TokenStore;
+
+pub mod scope;
+mod token;
+
+/// A request guard that can be included in handler definitions to enforce authentication and
+/// authorization.
+///
+/// Requests authenticated with the configured [`Username`] and [`Password`] are granted all
+/// scopes. Alternatively, requests can be authenticated with an API token from the
+/// [`TokenStore`], in which case the token needs to hold the scope `S`.
+///
+/// If mutual TLS is configured, clients presenting a verified certificate are authenticated as
+/// the API token named after the certificate's common name, without basic auth.
 #[derive(Debug, Clone, Copy)]
-pub struct Authenticated {}
+pub struct Authorized<S> {
+    scope: PhantomData<S>,
+}
 
 #[derive(Debug)]
 pub enum Error {
     UnknownUser(String),
     BadPassword,
+    /// The API token does not hold the scope required by the route.
+    InsufficientScope(Scope),
+    /// Failed to load the API token from the token store.
+    TokenStore(anyhow::Error),
+    /// The TLS client certificate does not specify the name of an API token as common name.
+    NoCommonName,
     /// The contents of the header are not valid base64.
     NotBase64(base64::DecodeError),
     /// The base64-encoded bytes cannot be represented as a UTF8 string.
@@ -47,7 +77,7 @@ impl PartialEq<String> for Username {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Password(String);
 
 impl From<[u8; 32]> for Password {
@@ -62,9 +92,10 @@ impl fmt::Display for Password {
     }
 }
 
+/// Compares in constant time to not leak how much of the password matches.
 impl PartialEq<String> for Password {
     fn eq(&self, other: &String) -> bool {
-        self.0.eq(other)
+        self.0.as_bytes().ct_eq(other.as_bytes()).into()
     }
 }
 
@@ -77,10 +108,23 @@ impl str::FromStr for Password {
 }
 
 #[rocket::async_trait]
-impl<'r> FromRequest<'r> for Authenticated {
+impl<'r, S> FromRequest<'r> for Authorized<S>
+where
+    S: RequiredScope,
+{
     type Error = Error;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if let Outcome::Success(certificate) = req.guard::<Certificate<'r>>().await {
+            let name = match certificate.common_name() {
+                Some(name) => name,
+                None => return Outcome::Failure((Status::Unauthorized, Error::NoCommonName)),
+            };
+            let token = try_outcome!(load_token(req, name).await);
+
+            return authorize(&token);
+        }
+
         let auth_headers = req.headers().get("Authorization").collect::<Vec<_>>();
 
         let (username, password) = match auth_headers.as_slice() {
@@ -101,18 +145,100 @@ impl<'r> FromRequest<'r> for Authenticated {
             .await
             .map_failure(|(status, _)| (status, Error::MissingPassword)));
 
-        if expected_username.inner() != &username {
-            return Outcome::Failure((
-                Status::Unauthorized,
-                Error::UnknownUser(username.to_owned()),
-            ));
+        if expected_username.inner() == &username {
+            if expected_password.inner() != &password {
+                return Outcome::Failure((Status::Unauthorized, Error::BadPassword));
+            }
+
+            return Outcome::Success(Authorized { scope: PhantomData });
         }
 
-        if expected_password.inner() != &password {
+        let token = try_outcome!(load_token(req, &username).await);
+
+        if !token.secret_hash.matches(&password) {
             return Outcome::Failure((Status::Unauthorized, Error::BadPassword));
         }
 
-        Outcome::Success(Authenticated {})
+        authorize(&token)
+    }
+}
+
+async fn load_token(req: &Request<'_>, name: &str) -> Outcome<StoredToken, Error> {
+    let tokens = match req.rocket().state::<Arc<dyn TokenStore>>() {
+        Some(tokens) => tokens,
+        None => {
+            return Outcome::Failure((Status::Unauthorized, Error::UnknownUser(name.to_owned())))
+        }
+    };
+
+    match tokens.load(name).await {
+        Ok(Some(token)) => Outcome::Success(token),
+        Ok(None) => Outcome::Failure((Status::Unauthorized, Error::UnknownUser(name.to_owned()))),
+        Err(e) => Outcome::Failure((Status::InternalServerError, Error::TokenStore(e))),
+    }
+}
+
+fn authorize<S>(token: &StoredToken) -> Outcome<Authorized<S>, Error>
+where
+    S: RequiredScope,
+{
+    if !token.scopes.iter().any(|scope| scope.grants(S::SCOPE)) {
+        return Outcome::Failure((Status::Forbidden, Error::InsufficientScope(S::SCOPE)));
+    }
+
+    Outcome::Success(Authorized { scope: PhantomData })
+}
+
+/// Authenticates clients on connections that are not served by Rocket, e.g. WebSockets.
+///
+/// Accepts the same basic auth credentials as [`Authorized`]. TLS client certificates are not
+/// supported.
+#[derive(Clone)]
+pub struct Credentials {
+    username: Username,
+    password: Password,
+    tokens: Arc<dyn TokenStore>,
+}
+
+impl Credentials {
+    pub fn new(username: Username, password: Password, tokens: Arc<dyn TokenStore>) -> Self {
+        Self {
+            username,
+            password,
+            tokens,
+        }
+    }
+
+    /// Authenticate the value of an `Authorization` header.
+    ///
+    /// Clients authenticating with an API token need to hold the `required` scope.
+    pub async fn authenticate(&self, header: &str, required: Scope) -> Result<(), Error> {
+        let (username, password) = decode_header(header)?;
+
+        if self.username == username {
+            if self.password != password {
+                return Err(Error::BadPassword);
+            }
+
+            return Ok(());
+        }
+
+        let token = self
+            .tokens
+            .load(&username)
+            .await
+            .map_err(Error::TokenStore)?
+            .ok_or(Error::UnknownUser(username))?;
+
+        if !token.secret_hash.matches(&password) {
+            return Err(Error::BadPassword);
+        }
+
+        if !token.scopes.iter().any(|scope| scope.grants(required)) {
+            return Err(Error::InsufficientScope(required));
+        }
+
+        Ok(())
     }
 }
 
@@ -152,6 +278,7 @@ mod tests {
     use rocket::local::blocking::Client;
     use rocket::Build;
     use rocket::Rocket;
+    use std::collections::HashMap;
 
     #[test]
     fn routes_are_password_protected() {
@@ -175,18 +302,125 @@ mod tests {
         assert_eq!(response.status(), Status::Ok);
     }
 
+    #[test]
+    fn correct_password_grants_all_scopes() {
+        let client = Client::tracked(rocket()).unwrap();
+
+        let response = client.get("/admin").header(auth_header()).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn token_grants_access_to_routes_of_its_scope() {
+        let client = Client::tracked(rocket()).unwrap();
+
+        let response = client
+            .get("/protected")
+            .header(token_auth_header("dashboard", "read-secret"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn token_without_required_scope_is_forbidden() {
+        let client = Client::tracked(rocket()).unwrap();
+
+        let response = client
+            .get("/admin")
+            .header(token_auth_header("dashboard", "read-secret"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+
+    #[test]
+    fn token_with_wrong_secret_is_unauthorized() {
+        let client = Client::tracked(rocket()).unwrap();
+
+        let response = client
+            .get("/protected")
+            .header(token_auth_header("dashboard", "wrong-secret"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[rocket::async_test]
+    async fn credentials_accept_password_and_tokens_of_required_scope() {
+        let credentials = Credentials::new(
+            Username("itchysats"),
+            Password::from(*b"Now I'm feelin' so fly like a G6"),
+            token_store(),
+        );
+
+        let password = auth_header();
+        let token = token_auth_header("dashboard", "read-secret");
+        let wrong_secret = token_auth_header("dashboard", "wrong-secret");
+
+        assert!(credentials
+            .authenticate(password.value(), Scope::Admin)
+            .await
+            .is_ok());
+        assert!(credentials
+            .authenticate(token.value(), Scope::Read)
+            .await
+            .is_ok());
+        assert!(matches!(
+            credentials.authenticate(token.value(), Scope::Trade).await,
+            Err(Error::InsufficientScope(Scope::Trade))
+        ));
+        assert!(matches!(
+            credentials
+                .authenticate(wrong_secret.value(), Scope::Read)
+                .await,
+            Err(Error::BadPassword)
+        ));
+    }
+
     #[rocket::get("/protected")]
-    async fn protected(_auth: Authenticated) {}
+    async fn protected(_auth: Authorized<scope::Read>) {}
+
+    #[rocket::get("/admin")]
+    async fn admin(_auth: Authorized<scope::Admin>) {}
+
+    struct InMemoryTokenStore(HashMap<String, StoredToken>);
+
+    #[rocket::async_trait]
+    impl TokenStore for InMemoryTokenStore {
+        async fn load(&self, name: &str) -> anyhow::Result<Option<StoredToken>> {
+            Ok(self.0.get(name).cloned())
+        }
+    }
+
+    fn token_store() -> Arc<dyn TokenStore> {
+        Arc::new(InMemoryTokenStore(HashMap::from([(
+            "dashboard".to_owned(),
+            StoredToken {
+                secret_hash: SecretHash::of("read-secret"),
+                scopes: vec![Scope::Read],
+            },
+        )])))
+    }
 
     /// Constructs a Rocket instance for testing.
     fn rocket() -> Rocket<Build> {
         rocket::build()
             .manage(Username("itchysats"))
             .manage(Password::from(*b"Now I'm feelin' so fly like a G6"))
-            .mount("/", rocket::routes![protected])
+            .manage(token_store())
+            .mount("/", rocket::routes![protected, admin])
             .register("/", rocket::catchers![unauthorized])
     }
 
+    fn token_auth_header(name: &str, secret: &str) -> Header<'static> {
+        Header::new(
+            "Authorization",
+            format!("Basic {}", base64::encode(format!("{name}:{secret}"))),
+        )
+    }
+
     /// Creates an "Authorization" header that matches the password above,
     /// in particular it has been created through:
     /// ```
@@ -0,0 +1,102 @@
+use rocket::serde::Deserialize;
+use rocket::serde::Serialize;
+use std::fmt;
+use std::str;
+
+/// A permission that can be granted to an API token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Scope {
+    /// Read-only access to the feed, metrics and the UI.
+    Read,
+    /// Placing orders and acting on CFDs.
+    Trade,
+    /// Withdrawing funds from the wallet.
+    Withdraw,
+    /// Full access, including the management of API tokens.
+    Admin,
+}
+
+impl Scope {
+    /// Whether holding this scope grants access to routes that require `required`.
+    pub fn grants(&self, required: Scope) -> bool {
+        *self == Scope::Admin || *self == required
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Scope::Read => "read",
+            Scope::Trade => "trade",
+            Scope::Withdraw => "withdraw",
+            Scope::Admin => "admin",
+        };
+
+        s.fmt(f)
+    }
+}
+
+impl str::FromStr for Scope {
+    type Err = UnknownScope;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scope = match s {
+            "read" => Scope::Read,
+            "trade" => Scope::Trade,
+            "withdraw" => Scope::Withdraw,
+            "admin" => Scope::Admin,
+            other => return Err(UnknownScope(other.to_owned())),
+        };
+
+        Ok(scope)
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownScope(pub String);
+
+impl fmt::Display for UnknownScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown scope: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownScope {}
+
+/// The [`Scope`] a route requires, used as type parameter of [`Authorized`](crate::Authorized).
+pub trait RequiredScope: Send + Sync + 'static {
+    const SCOPE: Scope;
+}
+
+/// Requires [`Scope::Read`].
+#[derive(Debug, Clone, Copy)]
+pub enum Read {}
+
+/// Requires [`Scope::Trade`].
+#[derive(Debug, Clone, Copy)]
+pub enum Trade {}
+
+/// Requires [`Scope::Withdraw`].
+#[derive(Debug, Clone, Copy)]
+pub enum Withdraw {}
+
+/// Requires [`Scope::Admin`].
+#[derive(Debug, Clone, Copy)]
+pub enum Admin {}
+
+impl RequiredScope for Read {
+    const SCOPE: Scope = Scope::Read;
+}
+
+impl RequiredScope for Trade {
+    const SCOPE: Scope = Scope::Trade;
+}
+
+impl RequiredScope for Withdraw {
+    const SCOPE: Scope = Scope::Withdraw;
+}
+
+impl RequiredScope for Admin {
+    const SCOPE: Scope = Scope::Admin;
+}
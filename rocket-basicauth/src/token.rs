@@ -0,0 +1,80 @@
+use crate::Scope;
+use sha2::Digest;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+/// A store of API tokens, to be managed by Rocket as `Arc<dyn TokenStore>`.
+///
+/// Clients present a token through basic auth, using the token's name as username and its secret
+/// as password. Only the hash of the secret is ever stored.
+#[rocket::async_trait]
+pub trait TokenStore: Send + Sync + 'static {
+    /// Load the token with the given name, if it exists.
+    async fn load(&self, name: &str) -> anyhow::Result<Option<StoredToken>>;
+}
+
+/// An API token as it is persisted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredToken {
+    pub secret_hash: SecretHash,
+    pub scopes: Vec<Scope>,
+}
+
+/// The SHA256 hash of a token's secret, hex-encoded.
+///
+/// Secrets are random and long enough that a fast hash is sufficient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretHash(String);
+
+impl SecretHash {
+    pub fn of(secret: &str) -> Self {
+        Self(hex::encode(Sha256::digest(secret.as_bytes())))
+    }
+
+    pub fn from_hex(hex: String) -> Self {
+        Self(hex)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether this is the hash of `secret`.
+    ///
+    /// The hashes are compared in constant time to not leak how much of them matches.
+    pub fn matches(&self, secret: &str) -> bool {
+        self.0
+            .as_bytes()
+            .ct_eq(Self::of(secret).0.as_bytes())
+            .into()
+    }
+}
+
+/// Generate a new secret for an API token.
+pub fn generate_secret() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_hash_is_hex_encoded_sha256() {
+        let hash = SecretHash::of("secret");
+
+        assert_eq!(
+            hash.as_str(),
+            "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b"
+        );
+    }
+
+    #[test]
+    fn secret_hash_only_matches_its_secret() {
+        let hash = SecretHash::of("secret");
+
+        assert!(hash.matches("secret"));
+        assert!(!hash.matches("secreT"));
+        assert!(!hash.matches(""));
+    }
+}
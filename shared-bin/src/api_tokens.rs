@@ -0,0 +1,179 @@
+use anyhow::Context;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use model::Timestamp;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_basicauth::scope;
+use rocket_basicauth::Authorized;
+use rocket_basicauth::Scope;
+use rocket_basicauth::SecretHash;
+use rocket_basicauth::StoredToken;
+use rocket_basicauth::TokenStore;
+use rocket_basicauth::Username;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlite_db::api_tokens::ApiToken;
+use time::OffsetDateTime;
+
+/// [`TokenStore`] backed by the daemon's database.
+pub struct SqliteTokenStore(sqlite_db::Connection);
+
+impl SqliteTokenStore {
+    pub fn new(db: sqlite_db::Connection) -> Self {
+        Self(db)
+    }
+}
+
+#[rocket::async_trait]
+impl TokenStore for SqliteTokenStore {
+    async fn load(&self, name: &str) -> anyhow::Result<Option<StoredToken>> {
+        let token = match self.0.load_api_token(name).await? {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+
+        Ok(Some(StoredToken {
+            secret_hash: SecretHash::from_hex(token.secret_hash),
+            scopes: parse_scopes(&token.scopes)?,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateApiTokenRequest {
+    name: String,
+    scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiTokenInfo {
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: Timestamp,
+}
+
+/// A newly created API token.
+///
+/// This is the only time the secret is revealed, it cannot be recovered afterwards.
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedApiToken {
+    name: String,
+    secret: String,
+    scopes: Vec<Scope>,
+}
+
+#[rocket::get("/tokens")]
+pub async fn get_api_tokens(
+    db: &State<sqlite_db::Connection>,
+    _auth: Authorized<scope::Admin>,
+) -> Result<Json<Vec<ApiTokenInfo>>, HttpApiProblem> {
+    let tokens = db
+        .load_api_tokens()
+        .await
+        .and_then(|tokens| {
+            tokens
+                .into_iter()
+                .map(|token| {
+                    Ok(ApiTokenInfo {
+                        scopes: parse_scopes(&token.scopes)?,
+                        name: token.name,
+                        created_at: Timestamp::new(token.created_at.unix_timestamp()),
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Failed to load API tokens")
+                .detail(format!("{e:#}"))
+        })?;
+
+    Ok(Json(tokens))
+}
+
+#[rocket::post("/tokens", data = "<request>")]
+pub async fn post_api_token(
+    request: Json<CreateApiTokenRequest>,
+    db: &State<sqlite_db::Connection>,
+    username: &State<Username>,
+    _auth: Authorized<scope::Admin>,
+) -> Result<Json<CreatedApiToken>, HttpApiProblem> {
+    let CreateApiTokenRequest { name, scopes } = request.into_inner();
+
+    if name.is_empty() || name.contains(':') || username.inner() == &name {
+        return Err(HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Invalid token name")
+            .detail(format!(
+                "Token name must be non-empty, must not contain ':' and must differ from '{}'",
+                username.inner()
+            )));
+    }
+
+    if scopes.is_empty() {
+        return Err(HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Invalid token scopes")
+            .detail("Token must have at least one scope"));
+    }
+
+    let secret = rocket_basicauth::generate_secret();
+    let token = ApiToken {
+        name: name.clone(),
+        secret_hash: SecretHash::of(&secret).as_str().to_owned(),
+        scopes: scopes.iter().map(Scope::to_string).collect(),
+        created_at: OffsetDateTime::now_utc(),
+    };
+
+    db.insert_api_token(&token).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Failed to create API token")
+            .detail(format!("{e:#}"))
+    })?;
+
+    tracing::info!(%name, ?scopes, "Created API token");
+
+    Ok(Json(CreatedApiToken {
+        name,
+        secret,
+        scopes,
+    }))
+}
+
+#[rocket::delete("/tokens/<name>")]
+pub async fn delete_api_token(
+    name: String,
+    db: &State<sqlite_db::Connection>,
+    _auth: Authorized<scope::Admin>,
+) -> Result<(), HttpApiProblem> {
+    let deleted = db.delete_api_token(&name).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Failed to revoke API token")
+            .detail(format!("{e:#}"))
+    })?;
+
+    if !deleted {
+        return Err(HttpApiProblem::new(StatusCode::NOT_FOUND)
+            .title("Unknown API token")
+            .detail(format!("No API token named {name}")));
+    }
+
+    tracing::info!(%name, "Revoked API token");
+
+    Ok(())
+}
+
+/// The routes for managing API tokens, to be mounted under `/api`.
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![get_api_tokens, post_api_token, delete_api_token]
+}
+
+fn parse_scopes(scopes: &[String]) -> anyhow::Result<Vec<Scope>> {
+    scopes
+        .iter()
+        .map(|scope| {
+            scope
+                .parse()
+                .with_context(|| format!("Invalid scope {scope} in database"))
+        })
+        .collect()
+}
@@ -0,0 +1,86 @@
+use anyhow::bail;
+use anyhow::Result;
+use daemon::backup;
+use daemon::integrity;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Command line options for the backups of the database.
+#[derive(clap::Args, Debug, Clone)]
+pub struct BackupOpts {
+    /// Do not back up the database.
+    #[clap(long)]
+    pub no_backups: bool,
+
+    /// Directory to store the backups of the database in.
+    ///
+    /// Defaults to the `backups` directory in the data directory. Backups are most useful on a
+    /// different disk than the data directory.
+    #[clap(long)]
+    pub backup_dir: Option<PathBuf>,
+
+    /// How often to back up the database, in hours.
+    ///
+    /// The database is also backed up whenever a contract setup or rollover completes.
+    #[clap(long, default_value = "24")]
+    pub backup_interval_hours: u64,
+
+    /// How many backups to keep, older ones are deleted.
+    #[clap(long, default_value = "30")]
+    pub backups_to_keep: usize,
+}
+
+impl BackupOpts {
+    /// Build the actor backing up the database `{name}.sqlite` in `data_dir`, unless backups are
+    /// disabled.
+    pub fn actor(&self, data_dir: &Path, name: &str) -> Option<backup::Actor> {
+        if self.no_backups {
+            tracing::warn!("Backups of the database are disabled");
+            return None;
+        }
+
+        let dir = self
+            .backup_dir
+            .clone()
+            .unwrap_or_else(|| data_dir.join("backups"));
+
+        Some(backup::Actor::new(
+            data_dir.join(format!("{name}.sqlite")),
+            dir,
+            name,
+            Duration::from_secs(self.backup_interval_hours * 60 * 60),
+            self.backups_to_keep,
+        ))
+    }
+}
+
+/// Check the database at `path` for corruption and inconsistent event logs, printing every
+/// problem found.
+///
+/// The database is opened read-only, so checking it neither creates nor migrates it. Fails if
+/// there is any problem.
+pub async fn check_integrity(path: PathBuf) -> Result<()> {
+    let db = sqlite_db::open_read_only(&path).await?;
+
+    let problems = db.check_integrity().await?;
+    let inconsistencies = integrity::check_event_logs(&db).await?;
+
+    db.close().await;
+
+    for problem in problems.iter() {
+        println!("{problem}");
+    }
+    for inconsistency in inconsistencies.iter() {
+        println!("{inconsistency}");
+    }
+
+    let total = problems.len() + inconsistencies.len();
+    if total > 0 {
+        bail!("Found {total} problems in the database");
+    }
+
+    println!("No problems found");
+
+    Ok(())
+}
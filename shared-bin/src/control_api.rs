@@ -0,0 +1,483 @@
+//! WebSocket API to control the daemon programmatically, e.g. from a trading bot.
+//!
+//! Clients authenticate during the handshake with the same basic auth credentials as the HTTP
+//! API. API tokens need to hold the `trade` scope.
+//!
+//! Every text message sent by the client is a command, identified by an `id` chosen by the
+//! client:
+//!
+//! ```json
+//! {"id": "1", "command": "commit", "order_id": "..."}
+//! ```
+//!
+//! Once the command was handed to the daemon, it is answered with `{"type": "ack", "id": "1"}`,
+//! otherwise with `{"type": "error", "id": "1", "error": "..."}`.
+//!
+//! Updates are taken from the same feed as the HTTP API. After connecting, the client receives a
+//! snapshot of all CFDs as `{"type": "cfds", "event_id": .., "cfds": [..]}`, followed by
+//! `{"type": "cfd", "event_id": .., "correlation_id": .., "cfd": {..}}` whenever a CFD changes and
+//! `{"type": "cfd_removed", "event_id": .., "order_id": ..}` whenever a CFD is removed. The first
+//! change of a CFD after a command of this connection targeted it carries the `id` of that command
+//! as `correlation_id`, which allows the client to match the events caused by its commands. Should
+//! the client fall too far behind, it receives a new snapshot. The profit of all CFDs at the latest
+//! quote is sent as `{"type": "pnl", "pnl": [..]}` whenever it changes.
+
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use daemon::projection::Cfd;
+use daemon::projection::CfdChange;
+use daemon::projection::CfdFeed;
+use daemon::projection::CfdFeedCursor;
+use daemon::projection::CfdFeedUpdate;
+use daemon::projection::CfdPnl;
+use futures::stream::FuturesUnordered;
+use futures::SinkExt;
+use futures::StreamExt;
+use model::OrderId;
+use rocket_basicauth::Credentials;
+use rocket_basicauth::Scope;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::handshake::server::ErrorResponse;
+use tokio_tungstenite::tungstenite::handshake::server::Request as HandshakeRequest;
+use tokio_tungstenite::tungstenite::handshake::server::Response as HandshakeResponse;
+use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
+use tokio_tungstenite::tungstenite::http::header::WWW_AUTHENTICATE;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// The commands a daemon accepts over the control API.
+#[async_trait]
+pub trait Commands: Clone + Send + Sync + 'static {
+    type Command: DeserializeOwned + Send + 'static;
+
+    /// The CFD the command acts on, if any.
+    fn order_id(command: &Self::Command) -> Option<OrderId>;
+
+    async fn execute(&self, command: Self::Command) -> Result<()>;
+}
+
+/// Serve the control API on the given listener until the daemon shuts down.
+pub async fn serve<C>(
+    listener: TcpListener,
+    commands: C,
+    cfd_feed: watch::Receiver<Option<CfdFeed>>,
+    credentials: Credentials,
+) where
+    C: Commands,
+{
+    let mut connections = FuturesUnordered::new();
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        tracing::warn!("Failed to accept control API connection: {e:#}");
+                        continue;
+                    }
+                };
+
+                let commands = commands.clone();
+                let cfd_feed = cfd_feed.clone();
+                let credentials = credentials.clone();
+                connections.push(async move {
+                    if let Err(e) = handle_connection(stream, peer, commands, cfd_feed, credentials).await {
+                        tracing::warn!(%peer, "Control API connection failed: {e:#}");
+                    }
+                });
+            }
+            Some(()) = connections.next(), if !connections.is_empty() => {}
+        }
+    }
+}
+
+async fn handle_connection<C>(
+    stream: TcpStream,
+    peer: SocketAddr,
+    commands: C,
+    mut cfd_feed: watch::Receiver<Option<CfdFeed>>,
+    credentials: Credentials,
+) -> Result<()>
+where
+    C: Commands,
+{
+    let mut authorization = None;
+    let mut websocket = tokio_tungstenite::accept_hdr_async(
+        stream,
+        |request: &HandshakeRequest, response: HandshakeResponse| {
+            authorization = request
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned);
+
+            match authorization {
+                Some(_) => Ok(response),
+                None => Err(unauthorized()),
+            }
+        },
+    )
+    .await
+    .context("WebSocket handshake failed")?;
+
+    let authorization = authorization.context("No authorization header")?;
+    if let Err(e) = credentials.authenticate(&authorization, Scope::Trade).await {
+        websocket
+            .close(Some(CloseFrame {
+                code: CloseCode::Policy,
+                reason: "Unauthorized".into(),
+            }))
+            .await?;
+        anyhow::bail!("Failed to authenticate: {e:?}");
+    }
+
+    tracing::info!(%peer, "Control API client connected");
+
+    let mut cfd_feed_cursor = CfdFeedCursor::default();
+    let mut correlations = HashMap::<OrderId, String>::new();
+    let mut pending_commands = FuturesUnordered::new();
+
+    let messages = cfd_messages(
+        cfd_feed.borrow().as_ref(),
+        &mut cfd_feed_cursor,
+        &mut correlations,
+    )?;
+    send_all(&mut websocket, messages).await?;
+
+    loop {
+        tokio::select! {
+            message = websocket.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e).context("Failed to receive message"),
+                };
+
+                let request = match serde_json::from_str::<Request<C::Command>>(&text) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        let id = serde_json::from_str::<RequestId>(&text).ok().map(|request| request.id);
+                        let message = ServerMessage::Error {
+                            id: id.as_deref(),
+                            error: format!("Invalid command: {e}"),
+                        };
+                        websocket.send(message.to_message()?).await?;
+                        continue;
+                    }
+                };
+
+                if let Some(order_id) = C::order_id(&request.command) {
+                    correlations.insert(order_id, request.id.clone());
+                }
+
+                let commands = commands.clone();
+                pending_commands.push(async move {
+                    let result = commands.execute(request.command).await;
+                    (request.id, result)
+                });
+            }
+            Some((id, result)) = pending_commands.next(), if !pending_commands.is_empty() => {
+                let message = match result {
+                    Ok(()) => ServerMessage::Ack { id: &id },
+                    Err(e) => {
+                        tracing::debug!(%peer, %id, "Control API command failed: {e:#}");
+                        correlations.retain(|_, correlation_id| *correlation_id != id);
+
+                        ServerMessage::Error {
+                            id: Some(&id),
+                            error: format!("{e:#}"),
+                        }
+                    }
+                };
+                websocket.send(message.to_message()?).await?;
+            }
+            changed = cfd_feed.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+
+                let messages = cfd_messages(
+                    cfd_feed.borrow().as_ref(),
+                    &mut cfd_feed_cursor,
+                    &mut correlations,
+                )?;
+                send_all(&mut websocket, messages).await?;
+            }
+        }
+    }
+
+    tracing::info!(%peer, "Control API client disconnected");
+
+    Ok(())
+}
+
+/// Encode the updates bringing the client up to date with the CFD feed.
+///
+/// `correlations` maps CFDs to the ID of the latest command targeting them. An entry is consumed
+/// by the next change of its CFD.
+fn cfd_messages(
+    feed: Option<&CfdFeed>,
+    cursor: &mut CfdFeedCursor,
+    correlations: &mut HashMap<OrderId, String>,
+) -> Result<Vec<Message>> {
+    let feed = match feed {
+        Some(feed) => feed,
+        None => return Ok(Vec::new()),
+    };
+
+    cursor
+        .advance(feed)
+        .into_iter()
+        .map(|update| cfd_message(update, correlations))
+        .collect()
+}
+
+fn cfd_message(
+    update: CfdFeedUpdate,
+    correlations: &mut HashMap<OrderId, String>,
+) -> Result<Message> {
+    match update {
+        CfdFeedUpdate::Snapshot(feed) => ServerMessage::Cfds {
+            event_id: feed.last_event_id(),
+            cfds: feed.cfds(),
+        }
+        .to_message(),
+        CfdFeedUpdate::Delta(delta) => match &delta.change {
+            CfdChange::Updated(cfd) => {
+                let correlation_id = correlations.remove(&cfd.order_id);
+
+                ServerMessage::Cfd {
+                    event_id: delta.event_id,
+                    correlation_id: correlation_id.as_deref(),
+                    cfd,
+                }
+                .to_message()
+            }
+            CfdChange::Removed(order_id) => {
+                correlations.remove(order_id);
+
+                ServerMessage::CfdRemoved {
+                    event_id: delta.event_id,
+                    order_id: *order_id,
+                }
+                .to_message()
+            }
+        },
+        CfdFeedUpdate::Pnl(pnl) => ServerMessage::Pnl { pnl }.to_message(),
+    }
+}
+
+async fn send_all(
+    websocket: &mut WebSocketStream<TcpStream>,
+    messages: Vec<Message>,
+) -> Result<()> {
+    for message in messages {
+        websocket
+            .send(message)
+            .await
+            .context("Failed to send message")?;
+    }
+
+    Ok(())
+}
+
+fn unauthorized() -> ErrorResponse {
+    let mut response = ErrorResponse::new(None);
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response
+        .headers_mut()
+        .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Basic"));
+
+    response
+}
+
+#[derive(Deserialize)]
+struct Request<C> {
+    id: String,
+    #[serde(flatten)]
+    command: C,
+}
+
+/// Used to report the ID of a request that failed to parse.
+#[derive(Deserialize)]
+struct RequestId {
+    id: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Ack {
+        id: &'a str,
+    },
+    Error {
+        id: Option<&'a str>,
+        error: String,
+    },
+    Cfds {
+        event_id: u64,
+        cfds: &'a [Cfd],
+    },
+    Cfd {
+        event_id: u64,
+        correlation_id: Option<&'a str>,
+        cfd: &'a Cfd,
+    },
+    CfdRemoved {
+        event_id: u64,
+        order_id: OrderId,
+    },
+    Pnl {
+        pnl: &'a [CfdPnl],
+    },
+}
+
+impl ServerMessage<'_> {
+    fn to_message(&self) -> Result<Message> {
+        let json = serde_json::to_string(self).context("Failed to serialize message")?;
+
+        Ok(Message::Text(json))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use daemon::bdk::bitcoin::Amount;
+    use daemon::bdk::bitcoin::Network;
+    use daemon::projection::CfdDelta;
+    use model::ContractType;
+    use model::FundingRate;
+    use model::Leverage;
+    use model::OpeningFee;
+    use model::Position;
+    use model::Price;
+    use model::Role;
+    use model::TxFeeRate;
+    use model::Usd;
+    use rust_decimal::Decimal;
+    use serde_json::json;
+    use serde_json::Value;
+
+    #[test]
+    fn commands_are_answered_with_ack_or_error() {
+        assert_eq!(
+            to_json(ServerMessage::Ack { id: "1" }.to_message().unwrap()),
+            json!({"type": "ack", "id": "1"})
+        );
+        assert_eq!(
+            to_json(
+                ServerMessage::Error {
+                    id: None,
+                    error: "Invalid command".to_owned()
+                }
+                .to_message()
+                .unwrap()
+            ),
+            json!({"type": "error", "id": null, "error": "Invalid command"})
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_contains_all_cfds() {
+        let cfd = cfd().await;
+
+        let message = ServerMessage::Cfds {
+            event_id: 1,
+            cfds: &[cfd.clone()],
+        }
+        .to_message()
+        .unwrap();
+
+        let message = to_json(message);
+        assert_eq!(message["type"], "cfds");
+        assert_eq!(message["event_id"], 1);
+        assert_eq!(message["cfds"][0]["order_id"], json!(cfd.order_id));
+    }
+
+    #[tokio::test]
+    async fn only_first_change_after_command_is_correlated() {
+        let cfd = cfd().await;
+        let mut correlations = HashMap::from([(cfd.order_id, "1".to_owned())]);
+        let updated = CfdDelta {
+            event_id: 1,
+            change: CfdChange::Updated(cfd.clone()),
+        };
+
+        let first =
+            to_json(cfd_message(CfdFeedUpdate::Delta(&updated), &mut correlations).unwrap());
+        let second =
+            to_json(cfd_message(CfdFeedUpdate::Delta(&updated), &mut correlations).unwrap());
+
+        assert_eq!(first["type"], "cfd");
+        assert_eq!(first["correlation_id"], "1");
+        assert_eq!(first["cfd"]["order_id"], json!(cfd.order_id));
+        assert_eq!(second["correlation_id"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn removed_cfd_consumes_correlation() {
+        let cfd = cfd().await;
+        let mut correlations = HashMap::from([(cfd.order_id, "1".to_owned())]);
+        let removed = CfdDelta {
+            event_id: 2,
+            change: CfdChange::Removed(cfd.order_id),
+        };
+
+        let message =
+            to_json(cfd_message(CfdFeedUpdate::Delta(&removed), &mut correlations).unwrap());
+
+        assert_eq!(
+            message,
+            json!({"type": "cfd_removed", "event_id": 2, "order_id": cfd.order_id})
+        );
+        assert!(correlations.is_empty());
+    }
+
+    fn to_json(message: Message) -> Value {
+        match message {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            _ => panic!("Expected text message"),
+        }
+    }
+
+    async fn cfd() -> Cfd {
+        let db = sqlite_db::memory().await.unwrap();
+        let cfd = model::Cfd::new(
+            OrderId::default(),
+            Position::Long,
+            Price::new(Decimal::from(60_000)).unwrap(),
+            Leverage::TWO,
+            time::Duration::hours(24),
+            Role::Taker,
+            Usd::new(Decimal::from(1_000)),
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"
+                .parse()
+                .unwrap(),
+            None,
+            OpeningFee::new(Amount::from_sat(2000)),
+            FundingRate::default(),
+            TxFeeRate::default(),
+            ContractType::Perpetual,
+        );
+        db.insert_cfd(&cfd).await.unwrap();
+
+        db.load_open_cfd::<Cfd>(cfd.id(), Network::Testnet)
+            .await
+            .unwrap()
+    }
+}
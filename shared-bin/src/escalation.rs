@@ -0,0 +1,30 @@
+use crate::logger;
+use async_trait::async_trait;
+use xtras::supervisor::Escalated;
+
+/// Parent of the supervisors of actors the daemon cannot run without.
+///
+/// Once such a supervisor gives up on its actor, we exit the process so that whatever manages the
+/// daemon can restart it instead of carrying on in a degraded state.
+pub struct Actor;
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[async_trait]
+impl xtra::Handler<Escalated> for Actor {
+    type Return = ();
+
+    async fn handle(&mut self, msg: Escalated, _: &mut xtra::Context<Self>) {
+        let Escalated { actor, reason } = msg;
+
+        tracing::error!(%actor, %reason, "Exiting because supervisor gave up on essential actor");
+        logger::shutdown();
+
+        std::process::exit(1);
+    }
+}
@@ -5,8 +5,14 @@ use rocket::fairing::Fairing;
 pub fn log_launch() -> impl Fairing {
     AdHoc::on_liftoff("Log launch", |rocket| {
         Box::pin(async move {
+            let scheme = if rocket.config().tls_enabled() {
+                "https"
+            } else {
+                "http"
+            };
             let http_endpoint = format!(
-                "http://{}:{}",
+                "{}://{}:{}",
+                scheme,
                 rocket.config().address,
                 rocket.config().port
             );
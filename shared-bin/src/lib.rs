@@ -1,6 +1,18 @@
+pub mod api_tokens;
+pub mod backup;
 pub mod catchers;
+pub mod control_api;
+pub mod escalation;
 pub mod fairings;
+pub mod log_filter;
 pub mod logger;
+pub mod margin_health;
+pub mod notifications;
+pub mod openapi;
+pub mod pnl_history;
+pub mod price_history;
+pub mod time_range;
+pub mod tls;
 mod to_sse_event;
 
 pub use crate::to_sse_event::*;
@@ -0,0 +1,58 @@
+use crate::logger::LogFilter;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_basicauth::scope;
+use rocket_basicauth::Authorized;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogDirectives {
+    /// Comma-separated log directives in the format of `RUST_LOG`, e.g.
+    /// `wallet=trace,xtra_libp2p=debug`.
+    directives: String,
+}
+
+#[rocket::get("/log-filter")]
+pub async fn get_log_filter(
+    log_filter: &State<LogFilter>,
+    _auth: Authorized<scope::Admin>,
+) -> Result<Json<LogDirectives>, HttpApiProblem> {
+    let directives = log_filter.directives().map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Failed to read log filter")
+            .detail(format!("{e:#}"))
+    })?;
+
+    Ok(Json(LogDirectives { directives }))
+}
+
+#[rocket::put("/log-filter", data = "<request>")]
+pub async fn put_log_filter(
+    request: Json<LogDirectives>,
+    log_filter: &State<LogFilter>,
+    _auth: Authorized<scope::Admin>,
+) -> Result<Json<LogDirectives>, HttpApiProblem> {
+    log_filter
+        .set_directives(&request.directives)
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::BAD_REQUEST)
+                .title("Failed to change log filter")
+                .detail(format!("{e:#}"))
+        })?;
+
+    let directives = log_filter.directives().map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Failed to read log filter")
+            .detail(format!("{e:#}"))
+    })?;
+
+    Ok(Json(LogDirectives { directives }))
+}
+
+/// The routes for changing the log filter at runtime, to be mounted under `/api`.
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![get_log_filter, put_log_filter]
+}
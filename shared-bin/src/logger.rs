@@ -1,17 +1,105 @@
 use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Result;
+use opentelemetry::sdk::trace::Tracer;
+use opentelemetry::sdk::Resource;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use std::fmt;
+use std::sync::Arc;
 use time::macros::format_description;
 use tracing_subscriber::fmt::time::UtcTime;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
+use xtras::instrumentation::MessageTypeLayer;
 
 pub use tracing_subscriber::filter::LevelFilter;
 
 const RUST_LOG_ENV: &str = "RUST_LOG";
 
+/// Handle to inspect and change the log filter at runtime.
+#[derive(Clone)]
+pub struct LogFilter {
+    level: LevelFilter,
+    reload: Arc<dyn Fn(EnvFilter) -> Result<()> + Send + Sync>,
+    current: Arc<dyn Fn() -> Result<String> + Send + Sync>,
+}
+
+impl LogFilter {
+    fn new<L, S>(level: LevelFilter, handle: reload::Handle<L, S>) -> Self
+    where
+        L: From<EnvFilter> + fmt::Display + Send + Sync + 'static,
+        S: Send + Sync + 'static,
+    {
+        Self {
+            level,
+            reload: Arc::new({
+                let handle = handle.clone();
+                move |filter| handle.reload(filter).context("Logger is gone")
+            }),
+            current: Arc::new(move || {
+                handle
+                    .with_current(|filter| filter.to_string())
+                    .context("Logger is gone")
+            }),
+        }
+    }
+
+    /// The filter of a disabled logger, which cannot be changed.
+    fn disabled() -> Self {
+        Self {
+            level: LevelFilter::OFF,
+            reload: Arc::new(|_| Err(anyhow!("Logging is disabled"))),
+            current: Arc::new(|| Ok(LevelFilter::OFF.to_string())),
+        }
+    }
+
+    /// The directives of the currently active filter.
+    pub fn directives(&self) -> Result<String> {
+        (self.current)()
+    }
+
+    /// Replace the directives that were given in `RUST_LOG` with the given comma-separated
+    /// directives, e.g. `wallet=trace,xtra_libp2p=debug`.
+    ///
+    /// The log level the daemon was started with remains the default for everything not matched
+    /// by a directive. Unlike at startup, invalid directives are rejected rather than ignored.
+    pub fn set_directives(&self, directives: &str) -> Result<()> {
+        let mut filter =
+            base_directives(EnvFilter::new(""))?.add_directive(format!("{}", self.level).parse()?);
+        for directive in directives.split(',').filter(|d| !d.trim().is_empty()) {
+            let directive = directive
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid log directive `{directive}`"))?;
+            filter = filter.add_directive(directive);
+        }
+
+        (self.reload)(filter)?;
+
+        tracing::info!(%directives, "Changed log filter");
+
+        Ok(())
+    }
+}
+
+/// Initialize the logger.
+///
+/// If an `otlp_endpoint` is given, spans are additionally exported to it via OTLP/gRPC, attributed
+/// to `service_name`. Call [`shutdown`] before exiting to flush pending spans.
+///
+/// Returns a handle to change the log filter at runtime.
 #[allow(clippy::print_stdout)] // because the logger is only initialized at the end of this function but we want to print a warning
-pub fn init(level: LevelFilter, json_format: bool) -> Result<()> {
+pub fn init(
+    level: LevelFilter,
+    json_format: bool,
+    otlp_endpoint: Option<&str>,
+    service_name: &'static str,
+) -> Result<LogFilter> {
     if level == LevelFilter::OFF {
-        return Ok(());
+        return Ok(LogFilter::disabled());
     }
 
     let is_terminal = atty::is(atty::Stream::Stderr);
@@ -36,22 +124,77 @@ pub fn init(level: LevelFilter, json_format: bool) -> Result<()> {
         .with_writer(std::io::stderr)
         .with_ansi(is_terminal);
 
-    let result = if json_format {
-        builder.json().with_timer(UtcTime::rfc_3339()).try_init()
+    let tracer = otlp_endpoint
+        .map(|endpoint| otlp_tracer(endpoint, service_name))
+        .transpose()?;
+
+    let (result, log_filter) = if json_format {
+        let builder = builder
+            .json()
+            .with_timer(UtcTime::rfc_3339())
+            .with_filter_reloading();
+        let log_filter = LogFilter::new(level, builder.reload_handle());
+
+        let result = builder
+            .finish()
+            .with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer)))
+            .with(MessageTypeLayer)
+            .try_init();
+
+        (result, log_filter)
     } else {
-        builder
+        let builder = builder
             .compact()
             .with_timer(UtcTime::new(format_description!(
                 "[year]-[month]-[day] [hour]:[minute]:[second]"
             )))
-            .try_init()
+            .with_filter_reloading();
+        let log_filter = LogFilter::new(level, builder.reload_handle());
+
+        let result = builder
+            .finish()
+            .with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer)))
+            .with(MessageTypeLayer)
+            .try_init();
+
+        (result, log_filter)
     };
 
     result.map_err(|e| anyhow!("Failed to init logger: {e}"))?;
 
     tracing::info!("Initialized logger");
 
-    Ok(())
+    if let Some(endpoint) = otlp_endpoint {
+        tracing::info!(%endpoint, "Exporting traces via OTLP");
+    }
+
+    Ok(log_filter)
+}
+
+/// Flush all spans that have not been exported yet.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Install a batching OTLP exporter as the global tracer provider.
+///
+/// Has to be called from within a tokio runtime.
+fn otlp_tracer(endpoint: &str, service_name: &'static str) -> Result<Tracer> {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            opentelemetry::sdk::trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                service_name,
+            )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)
+        .context("Failed to install OTLP exporter")
 }
 
 fn base_directives(env: EnvFilter) -> Result<EnvFilter> {
@@ -79,3 +222,45 @@ fn base_directives(env: EnvFilter) -> Result<EnvFilter> {
         .add_directive("xtra_libp2p=info".parse()?);
     Ok(filter)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::Registry;
+
+    #[test]
+    fn valid_directives_are_applied() {
+        let (_layer, log_filter) = log_filter();
+
+        log_filter
+            .set_directives("wallet=trace, daemon=debug")
+            .unwrap();
+
+        let directives = log_filter.directives().unwrap();
+        assert!(directives.contains("wallet=trace"), "{directives}");
+        assert!(directives.contains("daemon=debug"), "{directives}");
+        assert!(
+            directives.contains("sqlx=warn"),
+            "base directives are kept: {directives}"
+        );
+    }
+
+    #[test]
+    fn invalid_directives_leave_filter_unchanged() {
+        let (_layer, log_filter) = log_filter();
+        log_filter.set_directives("wallet=trace").unwrap();
+        let before = log_filter.directives().unwrap();
+
+        let result = log_filter.set_directives("daemon=debug,wallet=loud");
+
+        assert!(result.is_err());
+        assert_eq!(log_filter.directives().unwrap(), before);
+    }
+
+    /// A filter which is not installed, returned alongside the layer that keeps it alive.
+    fn log_filter() -> (reload::Layer<EnvFilter, Registry>, LogFilter) {
+        let (layer, handle) = reload::Layer::new(EnvFilter::new(LevelFilter::INFO.to_string()));
+
+        (layer, LogFilter::new(LevelFilter::INFO, handle))
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::Result;
+use daemon::margin_health;
+use daemon::margin_health::MarginHealth;
+use daemon::notifier;
+use daemon::projection::Feeds;
+use daemon::taker_cfd;
+use rust_decimal::Decimal;
+use tokio::sync::watch;
+use xtra::message_channel::MessageChannel;
+
+/// Command line options for watching how close CFDs are to their liquidation price.
+#[derive(clap::Args, Debug, Clone)]
+pub struct MarginHealthOpts {
+    /// Alert when the price of a CFD is less than this many percent away from its liquidation
+    /// price.
+    ///
+    /// Alerts are shown in the UI and delivered as `LiquidationThresholdCrossed` notifications.
+    #[clap(long, default_value = "10")]
+    pub liquidation_alert_threshold: Decimal,
+}
+
+impl MarginHealthOpts {
+    /// Build the actor watching the open CFDs of the projection, and the feed it publishes the
+    /// health of the CFDs on.
+    ///
+    /// CFDs crossing the threshold are settled right away if `auto_close` is given.
+    pub fn monitor(
+        &self,
+        db: sqlite_db::Connection,
+        feeds: &Feeds,
+        price_feed: MessageChannel<
+            xtra_bitmex_price_feed::LatestQuote,
+            Option<xtra_bitmex_price_feed::Quote>,
+        >,
+        notifier: MessageChannel<notifier::Notify, ()>,
+        auto_close: Option<MessageChannel<taker_cfd::ProposeSettlement, Result<()>>>,
+    ) -> (margin_health::Actor, watch::Receiver<Vec<MarginHealth>>) {
+        margin_health::Actor::new(
+            db,
+            feeds.cfds.clone(),
+            price_feed,
+            self.liquidation_alert_threshold,
+            notifier,
+            auto_close,
+        )
+    }
+}
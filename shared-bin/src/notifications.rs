@@ -0,0 +1,166 @@
+use anyhow::bail;
+use anyhow::Result;
+use daemon::margin_health;
+use daemon::notifier;
+use daemon::notifier::Sink;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use model::OrderId;
+use model::Timestamp;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_basicauth::scope;
+use rocket_basicauth::Authorized;
+use serde::Serialize;
+use std::sync::Arc;
+use url::Url;
+
+/// How many delivery attempts are returned unless the client asks for a different number.
+const DEFAULT_DELIVERY_LIMIT: u32 = 100;
+
+/// Command line options for notifying operators about CFD lifecycle events.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct NotificationOpts {
+    /// Deliver notifications about CFD lifecycle events to the given URL via HTTP POST.
+    ///
+    /// Can be specified multiple times. Requires `--webhook-secret`.
+    #[clap(long = "webhook-url")]
+    pub webhook_urls: Vec<Url>,
+
+    /// The secret to sign webhooks with.
+    ///
+    /// Every webhook carries the HMAC-SHA256 of its body, keyed with this secret, in the
+    /// `X-ItchySats-Signature` header as `sha256=<hex>`.
+    #[clap(long)]
+    pub webhook_secret: Option<String>,
+
+    /// Only notify about the given event, e.g. `RolloverStarted`.
+    ///
+    /// Can be specified multiple times. Defaults to rollovers, oracle attestations, commits, closed
+    /// CFDs and CFDs close to their liquidation price (`LiquidationThresholdCrossed`). Unknown
+    /// events are rejected on startup.
+    #[clap(long = "notify-event")]
+    pub notify_events: Vec<String>,
+}
+
+impl NotificationOpts {
+    /// Build the notifier delivering to the configured sinks.
+    pub fn notifier(&self, db: sqlite_db::Connection) -> Result<notifier::Actor> {
+        let secret = match (&self.webhook_secret, self.webhook_urls.is_empty()) {
+            (Some(secret), _) => secret.clone(),
+            (None, true) => String::new(),
+            (None, false) => bail!("Webhooks require a secret, set it with --webhook-secret"),
+        };
+
+        let sinks = self
+            .webhook_urls
+            .iter()
+            .map(|url| {
+                tracing::info!(%url, "Delivering notifications via webhook");
+
+                Arc::new(notifier::Webhook::new(url.clone(), secret.clone())) as Arc<dyn Sink>
+            })
+            .collect();
+
+        let events = if self.notify_events.is_empty() {
+            notifier::DEFAULT_EVENTS
+                .iter()
+                .map(|event| event.to_string())
+                .collect()
+        } else {
+            if let Some(unknown) = self
+                .notify_events
+                .iter()
+                .find(|event| !notifier::is_known_event(event))
+            {
+                bail!(
+                    "Unknown event {unknown} for --notify-event, expected the name of a CFD event or {}",
+                    margin_health::LIQUIDATION_THRESHOLD_CROSSED
+                );
+            }
+
+            self.notify_events.iter().cloned().collect()
+        };
+
+        Ok(notifier::Actor::new(db, sinks, events))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NotificationDelivery {
+    order_id: OrderId,
+    event: String,
+    sink: String,
+    attempt: u32,
+    error: Option<String>,
+    attempted_at: Timestamp,
+}
+
+impl From<sqlite_db::notifications::NotificationDelivery> for NotificationDelivery {
+    fn from(delivery: sqlite_db::notifications::NotificationDelivery) -> Self {
+        Self {
+            order_id: delivery.order_id,
+            event: delivery.event,
+            sink: delivery.sink,
+            attempt: delivery.attempt,
+            error: delivery.error,
+            attempted_at: Timestamp::new(delivery.attempted_at.unix_timestamp()),
+        }
+    }
+}
+
+/// The most recent attempts to deliver notifications, including failed ones.
+#[rocket::get("/notifications?<limit>")]
+pub async fn get_notification_deliveries(
+    limit: Option<u32>,
+    db: &State<sqlite_db::Connection>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<Vec<NotificationDelivery>>, HttpApiProblem> {
+    let deliveries = db
+        .load_notification_deliveries(limit.unwrap_or(DEFAULT_DELIVERY_LIMIT))
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Failed to load notification deliveries")
+                .detail(format!("{e:#}"))
+        })?;
+
+    Ok(Json(deliveries.into_iter().map(Into::into).collect()))
+}
+
+/// The routes for inspecting notifications, to be mounted under `/api`.
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![get_notification_deliveries]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unknown_events_are_rejected() {
+        let db = sqlite_db::memory().await.unwrap();
+        let opts = NotificationOpts {
+            notify_events: vec!["RolloverStarted".to_owned(), "RolloverStared".to_owned()],
+            ..NotificationOpts::default()
+        };
+
+        let error = opts.notifier(db).unwrap_err();
+
+        assert!(error.to_string().contains("RolloverStared"));
+    }
+
+    #[tokio::test]
+    async fn known_events_are_accepted() {
+        let db = sqlite_db::memory().await.unwrap();
+        let opts = NotificationOpts {
+            notify_events: vec![
+                "RolloverStarted".to_owned(),
+                margin_health::LIQUIDATION_THRESHOLD_CROSSED.to_owned(),
+            ],
+            ..NotificationOpts::default()
+        };
+
+        assert!(opts.notifier(db).is_ok());
+    }
+}
@@ -0,0 +1,37 @@
+pub mod checks;
+
+use rocket::serde::json::Json;
+use utoipa::openapi::security::Http;
+use utoipa::openapi::security::HttpAuthScheme;
+use utoipa::openapi::security::SecurityScheme;
+use utoipa::openapi::Components;
+use utoipa::openapi::OpenApi;
+use utoipa::Modify;
+
+/// The name of the security scheme protecting the routes of the API.
+///
+/// Routes reference it via `security(("basic_auth" = []))`.
+pub const BASIC_AUTH: &str = "basic_auth";
+
+/// Adds the HTTP basic authentication required by the API to an OpenAPI document.
+pub struct BasicAuth;
+
+impl Modify for BasicAuth {
+    fn modify(&self, openapi: &mut OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Components::new)
+            .add_security_scheme(
+                BASIC_AUTH,
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Basic)),
+            );
+    }
+}
+
+/// Serve the given OpenAPI document as JSON.
+pub fn document<D>() -> Json<OpenApi>
+where
+    D: utoipa::OpenApi,
+{
+    Json(D::openapi())
+}
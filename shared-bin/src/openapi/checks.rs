@@ -0,0 +1,96 @@
+//! Checks shared by the tests of the maker's and taker's versioned APIs.
+//!
+//! Both APIs are protected by [`BasicAuth`](super::BasicAuth) and serve their OpenAPI document
+//! at `/api/v1/openapi.json`, so the checks only differ in the routes they are given.
+
+use daemon::projection::Feeds;
+use daemon::projection::MakerOffers;
+use rocket::http::Header;
+use rocket::http::Status;
+use rocket::local::asynchronous::Client;
+use rocket::Build;
+use rocket::Rocket;
+use rocket::Route;
+use rocket_basicauth::Password;
+use rocket_basicauth::Username;
+use tokio::sync::watch;
+
+/// Basic auth for `user:secret`.
+const AUTHORIZATION: &str = "Basic dXNlcjpzZWNyZXQ=";
+
+/// Mount the given routes under `/api/v1`, accepting `user:secret` and serving empty feeds.
+pub fn rocket(routes: Vec<Route>) -> Rocket<Build> {
+    let feeds = Feeds {
+        quote: watch::channel(None).1,
+        offers: watch::channel(MakerOffers {
+            long: None,
+            short: None,
+        })
+        .1,
+        connected_takers: watch::channel(Vec::new()).1,
+        cfds: watch::channel(None).1,
+        cfd_feed: watch::channel(None).1,
+    };
+
+    rocket::build()
+        .manage(Username("user"))
+        .manage("secret".parse::<Password>().expect("valid password"))
+        .manage(feeds)
+        .mount("/api/v1", routes)
+}
+
+pub async fn client(rocket: Rocket<Build>) -> Client {
+    Client::tracked(rocket).await.expect("valid rocket")
+}
+
+/// Assert that the OpenAPI document builds and documents the given paths and the authentication.
+pub fn assert_documents<D>(paths: &[&str])
+where
+    D: utoipa::OpenApi,
+{
+    let document = D::openapi().to_pretty_json().unwrap();
+
+    for path in paths {
+        assert!(document.contains(path), "{path} is not documented");
+    }
+    assert!(document.contains(super::BASIC_AUTH));
+}
+
+pub async fn assert_serves_document_without_authentication(client: &Client) {
+    let response = client.get("/api/v1/openapi.json").dispatch().await;
+
+    assert_eq!(response.status(), Status::Ok);
+    let document = response
+        .into_json::<rocket::serde::json::Value>()
+        .await
+        .unwrap();
+    assert!(document["paths"]["/api/v1/offers"].is_object());
+}
+
+pub async fn assert_requires_authentication(client: &Client, path: &str) {
+    let response = client.get(path).dispatch().await;
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+/// Assert the responses to the routes backed by [`Feeds`] both daemons serve.
+pub async fn assert_responds_to_authenticated_requests(client: &Client) {
+    assert_eq!(
+        get_authenticated(client, "/api/v1/offers").await,
+        Status::Ok
+    );
+    assert_eq!(
+        get_authenticated(client, "/api/v1/cfds").await,
+        Status::ServiceUnavailable,
+        "CFDs are not loaded yet"
+    );
+}
+
+pub async fn get_authenticated(client: &Client, path: &str) -> Status {
+    client
+        .get(path)
+        .header(Header::new("Authorization", AUTHORIZATION))
+        .dispatch()
+        .await
+        .status()
+}
@@ -0,0 +1,79 @@
+use crate::time_range;
+use daemon::pnl_history;
+use daemon::pnl_history::PnlPoint;
+use daemon::pnl_history::PortfolioPoint;
+use daemon::projection::Feeds;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use model::OrderId;
+use rocket::serde::json::Json;
+use rocket::serde::uuid::Uuid;
+use rocket::State;
+use rocket_basicauth::scope;
+use rocket_basicauth::Authorized;
+
+/// How far back the equity curve goes unless `from` is given.
+const DEFAULT_RANGE: time::Duration = time::Duration::days(30);
+
+/// Command line options for recording the PnL history.
+#[derive(clap::Args, Debug, Clone)]
+pub struct PnlHistoryOpts {
+    /// How many days of PnL snapshots to keep, 0 keeps them forever.
+    #[clap(long, default_value = "90")]
+    pub pnl_history_retention_days: u32,
+}
+
+impl PnlHistoryOpts {
+    /// Build the actor snapshotting the PnL of the open CFDs of the projection.
+    pub fn recorder(&self, db: sqlite_db::Connection, feeds: &Feeds) -> pnl_history::Actor {
+        let retention = (self.pnl_history_retention_days > 0)
+            .then(|| time::Duration::days(self.pnl_history_retention_days.into()));
+
+        pnl_history::Actor::new(db, feeds.cfds.clone(), retention)
+    }
+}
+
+/// The mark-to-market PnL of a CFD over time, oldest first.
+#[rocket::get("/cfds/<id>/pnl")]
+pub async fn get_cfd_pnl(
+    id: Uuid,
+    db: &State<sqlite_db::Connection>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<Vec<PnlPoint>>, HttpApiProblem> {
+    let snapshots = db
+        .load_pnl_snapshots(OrderId::from(id))
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Failed to load PnL snapshots")
+                .detail(format!("{e:#}"))
+        })?;
+
+    Ok(Json(snapshots.into_iter().map(PnlPoint::from).collect()))
+}
+
+/// The equity of all open CFDs over time, oldest first.
+///
+/// `from` and `to` are in seconds since the epoch and default to the last 30 days.
+#[rocket::get("/equity?<from>&<to>")]
+pub async fn get_equity(
+    from: Option<i64>,
+    to: Option<i64>,
+    db: &State<sqlite_db::Connection>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<Vec<PortfolioPoint>>, HttpApiProblem> {
+    let (from, to) = time_range::from_query(from, to, DEFAULT_RANGE)?;
+
+    let curve = db.load_equity_curve(from, to).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Failed to load equity curve")
+            .detail(format!("{e:#}"))
+    })?;
+
+    Ok(Json(curve.into_iter().map(PortfolioPoint::from).collect()))
+}
+
+/// The routes for the PnL history, to be mounted under `/api`.
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![get_cfd_pnl, get_equity]
+}
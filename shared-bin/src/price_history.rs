@@ -0,0 +1,103 @@
+use crate::time_range;
+use daemon::price_history;
+use daemon::price_history::Candle;
+use daemon::price_history::Interval;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_basicauth::scope;
+use rocket_basicauth::Authorized;
+use std::sync::Arc;
+use xtra::message_channel::MessageChannel;
+
+/// The most candles returned at once, bounding the time range that can be requested per interval.
+const MAX_CANDLES: u32 = 10_000;
+
+/// Command line options for recording the price history.
+#[derive(clap::Args, Debug, Clone)]
+pub struct PriceHistoryOpts {
+    /// How many days of quotes to keep in the price history, 0 keeps them forever.
+    #[clap(long, default_value = "90")]
+    pub price_history_retention_days: u32,
+
+    /// Do not fill gaps in the price history from the BitMEX API on startup.
+    #[clap(long)]
+    pub no_price_history_backfill: bool,
+}
+
+impl PriceHistoryOpts {
+    /// Build the actor recording the quotes of the given price feed.
+    pub fn recorder(
+        &self,
+        db: sqlite_db::Connection,
+        price_feed: MessageChannel<
+            xtra_bitmex_price_feed::LatestQuote,
+            Option<xtra_bitmex_price_feed::Quote>,
+        >,
+        network: xtra_bitmex_price_feed::Network,
+    ) -> price_history::Actor {
+        let history: Option<Arc<dyn price_history::History>> = (!self.no_price_history_backfill)
+            .then(|| Arc::new(price_history::Bitmex::new(network)) as _);
+        let retention = (self.price_history_retention_days > 0)
+            .then(|| time::Duration::days(self.price_history_retention_days.into()));
+
+        price_history::Actor::new(db, price_feed, history, retention)
+    }
+}
+
+/// The mid price of BTC/USD as candles of the given interval, oldest first.
+///
+/// `from` and `to` are in seconds since the epoch and default to the last 24 hours, 30 days and
+/// 365 days for candles of one minute, hour and day respectively. Ranges spanning more than
+/// [`MAX_CANDLES`] candles are rejected.
+#[rocket::get("/prices?<interval>&<from>&<to>")]
+pub async fn get_prices(
+    interval: Option<String>,
+    from: Option<i64>,
+    to: Option<i64>,
+    db: &State<sqlite_db::Connection>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<Vec<Candle>>, HttpApiProblem> {
+    let interval = interval
+        .as_deref()
+        .unwrap_or("1h")
+        .parse::<Interval>()
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::BAD_REQUEST)
+                .title("Invalid interval")
+                .detail(format!("{e:#}"))
+        })?;
+
+    let (from, to) = time_range::from_query(from, to, default_range(interval))?;
+
+    let max_range = interval.duration() * MAX_CANDLES;
+    if to - from > max_range {
+        return Err(HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Time range too large")
+            .detail(format!("Candles of {interval} span at most {max_range}")));
+    }
+
+    let candles = price_history::load_candles(db, from, to, interval)
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Failed to load candles")
+                .detail(format!("{e:#}"))
+        })?;
+
+    Ok(Json(candles))
+}
+
+fn default_range(interval: Interval) -> time::Duration {
+    match interval {
+        Interval::OneMinute => time::Duration::days(1),
+        Interval::OneHour => time::Duration::days(30),
+        Interval::OneDay => time::Duration::days(365),
+    }
+}
+
+/// The routes for the price history, to be mounted under `/api`.
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![get_prices]
+}
@@ -0,0 +1,30 @@
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use time::OffsetDateTime;
+
+/// The time range `[from, to)` given by the query parameters of a route.
+///
+/// `from` and `to` are in seconds since the epoch. `to` defaults to now and `from` to
+/// `default_range` before `to`.
+pub fn from_query(
+    from: Option<i64>,
+    to: Option<i64>,
+    default_range: time::Duration,
+) -> Result<(OffsetDateTime, OffsetDateTime), HttpApiProblem> {
+    let to = match to {
+        Some(to) => OffsetDateTime::from_unix_timestamp(to).map_err(invalid_time_range)?,
+        None => OffsetDateTime::now_utc(),
+    };
+    let from = match from {
+        Some(from) => OffsetDateTime::from_unix_timestamp(from).map_err(invalid_time_range)?,
+        None => to - default_range,
+    };
+
+    Ok((from, to))
+}
+
+fn invalid_time_range(e: time::error::ComponentRange) -> HttpApiProblem {
+    HttpApiProblem::new(StatusCode::BAD_REQUEST)
+        .title("Invalid time range")
+        .detail(format!("{e:#}"))
+}
@@ -0,0 +1,148 @@
+use anyhow::Context;
+use anyhow::Result;
+use rcgen::CertificateParams;
+use rcgen::SanType;
+use rocket::config::MutualTls;
+use rocket::config::TlsConfig;
+use std::fs::OpenOptions;
+#[cfg(unix)]
+use std::fs::Permissions;
+use std::io::Write;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// File name of the generated self-signed certificate within the data directory.
+const SELF_SIGNED_CERT: &str = "http_cert.pem";
+
+/// File name of the private key of the generated self-signed certificate within the data
+/// directory.
+const SELF_SIGNED_KEY: &str = "http_key.pem";
+
+/// Command line options for serving the HTTP API over TLS.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct TlsOpts {
+    /// Serve the HTTP API over TLS.
+    ///
+    /// Unless a certificate is provided with `--tls-cert` and `--tls-key`, a self-signed
+    /// certificate is generated into the data directory.
+    #[clap(long)]
+    pub tls: bool,
+
+    /// Path to a PEM file containing the certificate chain to serve the HTTP API with. Implies
+    /// `--tls`.
+    #[clap(long)]
+    pub tls_cert: Option<PathBuf>,
+
+    /// Path to a PEM file containing the private key of the certificate given with `--tls-cert`.
+    #[clap(long)]
+    pub tls_key: Option<PathBuf>,
+
+    /// Path to a PEM file containing CA certificates to verify TLS client certificates with.
+    /// Implies `--tls`.
+    ///
+    /// Clients presenting a certificate signed by one of these CAs are authenticated as the API
+    /// token named after the certificate's common name, without having to provide the token's
+    /// secret. Clients without a certificate can still authenticate with basic auth.
+    #[clap(long)]
+    pub tls_client_ca: Option<PathBuf>,
+}
+
+impl TlsOpts {
+    /// Build the TLS configuration for Rocket, or `None` if TLS is disabled.
+    ///
+    /// If no certificate is configured, a self-signed one is generated into `data_dir` on first
+    /// use and reused afterwards.
+    pub fn config(&self, data_dir: &Path, http_address: SocketAddr) -> Result<Option<TlsConfig>> {
+        let config = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => TlsConfig::from_paths(cert, key),
+            (None, None) if self.tls || self.tls_client_ca.is_some() => {
+                let (cert, key) = self_signed_certificate(data_dir, http_address)?;
+                TlsConfig::from_paths(cert, key)
+            }
+            (None, None) => return Ok(None),
+            _ => anyhow::bail!("Both --tls-cert and --tls-key have to be provided"),
+        };
+
+        let config = match &self.tls_client_ca {
+            Some(ca) => config.with_mutual(MutualTls::from_path(ca)),
+            None => config,
+        };
+
+        Ok(Some(config))
+    }
+}
+
+/// Load the self-signed certificate from the data directory, generating it if it does not exist.
+fn self_signed_certificate(
+    data_dir: &Path,
+    http_address: SocketAddr,
+) -> Result<(PathBuf, PathBuf)> {
+    let cert_path = data_dir.join(SELF_SIGNED_CERT);
+    let key_path = data_dir.join(SELF_SIGNED_KEY);
+
+    if cert_path.exists() && key_path.exists() {
+        tracing::info!(
+            "Using self-signed TLS certificate at {}",
+            cert_path.display()
+        );
+        return Ok((cert_path, key_path));
+    }
+
+    let mut subject_alt_names = vec![
+        SanType::DnsName("localhost".to_owned()),
+        SanType::IpAddress(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        SanType::IpAddress(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+    ];
+    if !http_address.ip().is_unspecified() && !http_address.ip().is_loopback() {
+        subject_alt_names.push(SanType::IpAddress(http_address.ip()));
+    }
+
+    let params = CertificateParams {
+        subject_alt_names,
+        ..CertificateParams::default()
+    };
+
+    let certificate = rcgen::Certificate::from_params(params)
+        .context("Failed to generate self-signed TLS certificate")?;
+
+    std::fs::write(&cert_path, certificate.serialize_pem()?)?;
+    write_private_key(
+        &key_path,
+        certificate.serialize_private_key_pem().as_bytes(),
+    )?;
+
+    tracing::info!(
+        "Generated self-signed TLS certificate at {}",
+        cert_path.display()
+    );
+
+    Ok((cert_path, key_path))
+}
+
+/// Write the private key to `path`, readable only by the current user.
+fn write_private_key(path: &Path, key: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+
+    // `mode` only applies to new files, a key left over from an earlier run keeps its permissions.
+    #[cfg(unix)]
+    file.set_permissions(Permissions::from_mode(0o600))?;
+
+    file.write_all(key)?;
+
+    Ok(())
+}
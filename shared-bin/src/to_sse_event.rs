@@ -2,53 +2,160 @@ use crate::ConnectionCloseReason::MakerVersionOutdated;
 use crate::ConnectionCloseReason::TakerVersionOutdated;
 use daemon::bdk::bitcoin::Amount;
 use daemon::connection;
-use daemon::projection::Cfd;
+use daemon::identify;
+use daemon::margin_health::MarginHealth;
+use daemon::projection::CfdChange;
+use daemon::projection::CfdDelta;
+use daemon::projection::CfdFeed;
+use daemon::projection::CfdFeedCursor;
+use daemon::projection::CfdFeedUpdate;
+use daemon::projection::CfdPnl;
 use daemon::projection::Quote;
 use model::Identity;
 use model::Timestamp;
+use rocket::request::FromRequest;
+use rocket::request::Outcome;
 use rocket::response::stream::Event;
+use rocket::Request;
 use serde::Serialize;
+use utoipa::ToSchema;
+use xtra_libp2p::dialer;
 
 pub trait ToSseEvent {
     fn to_sse_event(&self) -> Event;
 }
 
-impl ToSseEvent for Vec<Cfd> {
+/// All CFDs, to be used if the client cannot be brought up to date with [`CfdDelta`]s.
+impl ToSseEvent for CfdFeed {
     fn to_sse_event(&self) -> Event {
-        Event::json(&self).event("cfds")
+        Event::json(&self.cfds())
+            .event("cfds")
+            .id(self.last_event_id().to_string())
     }
 }
 
+impl ToSseEvent for CfdDelta {
+    fn to_sse_event(&self) -> Event {
+        let event = match &self.change {
+            CfdChange::Updated(cfd) => Event::json(cfd).event("cfd"),
+            CfdChange::Removed(order_id) => {
+                Event::json(&serde_json::json!({ "order_id": order_id })).event("cfd_removed")
+            }
+        };
+
+        event.id(self.event_id.to_string())
+    }
+}
+
+/// The PnL of all CFDs at the latest quote.
+///
+/// Not part of the resumable events, hence sent without an ID.
+impl ToSseEvent for [CfdPnl] {
+    fn to_sse_event(&self) -> Event {
+        Event::json(&self).event("pnl")
+    }
+}
+
+/// The ID of the last event a client received before reconnecting to an event stream.
+///
+/// Taken from the `Last-Event-ID` header, which is sent by browsers when they reconnect an
+/// `EventSource`. Missing or malformed IDs are treated as if the client had not received any
+/// events.
+#[derive(Debug, Clone, Copy)]
+pub struct LastEventId(pub Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let last_event_id = req
+            .headers()
+            .get_one("Last-Event-ID")
+            .and_then(|id| id.trim().parse().ok());
+
+        Outcome::Success(LastEventId(last_event_id))
+    }
+}
+
+/// The events bringing a client of the CFD feed up to date, see [`CfdFeedCursor::advance`].
+pub fn cfd_events(feed: Option<&CfdFeed>, cursor: &mut CfdFeedCursor) -> Vec<Event> {
+    let feed = match feed {
+        Some(feed) => feed,
+        None => return Vec::new(),
+    };
+
+    cursor
+        .advance(feed)
+        .into_iter()
+        .map(|update| match update {
+            CfdFeedUpdate::Snapshot(feed) => feed.to_sse_event(),
+            CfdFeedUpdate::Delta(delta) => delta.to_sse_event(),
+            CfdFeedUpdate::Pnl(pnl) => pnl.to_sse_event(),
+        })
+        .collect()
+}
+
 impl ToSseEvent for Vec<Identity> {
     fn to_sse_event(&self) -> Event {
         Event::json(&self).event("takers")
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct WalletInfo {
     #[serde(with = "daemon::bdk::bitcoin::util::amount::serde::as_btc")]
+    #[schema(value_type = f64)]
     balance: Amount,
     address: String,
     last_updated_at: Timestamp,
 }
 
-impl ToSseEvent for Option<model::WalletInfo> {
-    fn to_sse_event(&self) -> Event {
-        let wallet_info = self.as_ref().map(|wallet_info| WalletInfo {
+impl From<&model::WalletInfo> for WalletInfo {
+    fn from(wallet_info: &model::WalletInfo) -> Self {
+        Self {
             balance: wallet_info.balance,
             address: wallet_info.address.to_string(),
             last_updated_at: wallet_info.last_updated_at,
-        });
+        }
+    }
+}
+
+impl ToSseEvent for Option<model::WalletInfo> {
+    fn to_sse_event(&self) -> Event {
+        let wallet_info = self.as_ref().map(WalletInfo::from);
 
         Event::json(&wallet_info).event("wallet")
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ConnectionStatus {
     online: bool,
     connection_close_reason: Option<ConnectionCloseReason>,
+    addresses: Vec<MakerAddress>,
+}
+
+/// Connection statistics of one of the maker's libp2p addresses.
+#[derive(Debug, Clone, Serialize)]
+pub struct MakerAddress {
+    address: String,
+    connected: bool,
+    consecutive_failures: u32,
+    total_failures: u64,
+    last_error: Option<String>,
+}
+
+impl From<&dialer::AddressStats> for MakerAddress {
+    fn from(stats: &dialer::AddressStats) -> Self {
+        Self {
+            address: stats.address.to_string(),
+            connected: stats.connected,
+            consecutive_failures: stats.consecutive_failures,
+            total_failures: stats.total_failures,
+            last_error: stats.last_error.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -57,12 +164,16 @@ pub enum ConnectionCloseReason {
     TakerVersionOutdated,
 }
 
-impl ToSseEvent for connection::ConnectionStatus {
+impl ToSseEvent for (connection::ConnectionStatus, Vec<dialer::AddressStats>) {
     fn to_sse_event(&self) -> Event {
-        let connected = match self {
+        let (status, address_stats) = self;
+        let addresses = address_stats.iter().map(MakerAddress::from).collect();
+
+        let connected = match status {
             connection::ConnectionStatus::Online => ConnectionStatus {
                 online: true,
                 connection_close_reason: None,
+                addresses,
             },
             connection::ConnectionStatus::Offline { reason } => ConnectionStatus {
                 online: false,
@@ -78,6 +189,7 @@ impl ToSseEvent for connection::ConnectionStatus {
                         }
                     }
                 }),
+                addresses,
             },
         };
 
@@ -85,8 +197,31 @@ impl ToSseEvent for connection::ConnectionStatus {
     }
 }
 
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MakerCompatibility {
+    outdated: Option<ConnectionCloseReason>,
+}
+
+impl ToSseEvent for Option<identify::Compatibility> {
+    fn to_sse_event(&self) -> Event {
+        let outdated = match self {
+            None | Some(identify::Compatibility::Compatible) => None,
+            Some(identify::Compatibility::PeerOutdated) => Some(MakerVersionOutdated),
+            Some(identify::Compatibility::LocalOutdated) => Some(TakerVersionOutdated),
+        };
+
+        Event::json(&MakerCompatibility { outdated }).event("maker_compatibility")
+    }
+}
+
 impl ToSseEvent for Option<Quote> {
     fn to_sse_event(&self) -> Event {
         Event::json(self).event("quote")
     }
 }
+
+impl ToSseEvent for Vec<MarginHealth> {
+    fn to_sse_event(&self) -> Event {
+        Event::json(self).event("margin_health")
+    }
+}
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id integer PRIMARY KEY autoincrement,
+    name text UNIQUE NOT NULL,
+    secret_hash text NOT NULL,
+    scopes text NOT NULL,
+    created_at integer NOT NULL
+);
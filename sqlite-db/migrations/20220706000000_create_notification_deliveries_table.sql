@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS notification_deliveries (
+    id integer PRIMARY KEY autoincrement,
+    order_id text NOT NULL,
+    event text NOT NULL,
+    sink text NOT NULL,
+    attempt integer NOT NULL,
+    error text,
+    attempted_at integer NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS notification_deliveries_order_id ON notification_deliveries (order_id);
@@ -0,0 +1,13 @@
+-- All CFDs created before fixed-expiry contracts were introduced are perpetual contracts.
+ALTER TABLE
+    cfds
+ADD
+    COLUMN contract_type TEXT NOT NULL DEFAULT 'Perpetual';
+ALTER TABLE
+    closed_cfds
+ADD
+    COLUMN contract_type TEXT NOT NULL DEFAULT 'Perpetual';
+ALTER TABLE
+    failed_cfds
+ADD
+    COLUMN contract_type TEXT NOT NULL DEFAULT 'Perpetual';
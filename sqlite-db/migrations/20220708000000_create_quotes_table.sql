@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS quotes (
+    timestamp integer PRIMARY KEY,
+    bid text NOT NULL,
+    ask text NOT NULL
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS pnl_snapshots (
+    id integer PRIMARY KEY autoincrement,
+    order_id text NOT NULL,
+    timestamp integer NOT NULL,
+    profit_sat integer NOT NULL,
+    accumulated_fees_sat integer NOT NULL,
+    margin_sat integer NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS pnl_snapshots_order_id ON pnl_snapshots (order_id);
+CREATE INDEX IF NOT EXISTS pnl_snapshots_timestamp ON pnl_snapshots (timestamp);
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS local_identity (
+    id integer PRIMARY KEY CHECK (id = 0),
+    identity text NOT NULL
+);
@@ -0,0 +1,193 @@
+use crate::Connection;
+use anyhow::Result;
+use time::OffsetDateTime;
+
+/// An API token granting scoped access to the HTTP API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiToken {
+    pub name: String,
+    /// The hex-encoded hash of the token's secret; the secret itself is never stored.
+    pub secret_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: OffsetDateTime,
+}
+
+impl Connection {
+    /// Insert a new API token.
+    ///
+    /// Fails if a token with the same name already exists.
+    pub async fn insert_api_token(&self, token: &ApiToken) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let scopes = token.scopes.join(" ");
+        let created_at = token.created_at.unix_timestamp();
+
+        let query_result = sqlx::query!(
+            r#"
+            INSERT INTO api_tokens
+            (
+                name,
+                secret_hash,
+                scopes,
+                created_at
+            )
+            VALUES ($1, $2, $3, $4)
+            "#,
+            token.name,
+            token.secret_hash,
+            scopes,
+            created_at,
+        )
+        .execute(&mut conn)
+        .await?;
+
+        if query_result.rows_affected() != 1 {
+            anyhow::bail!("failed to insert API token");
+        }
+
+        Ok(())
+    }
+
+    pub async fn load_api_token(&self, name: &str) -> Result<Option<ApiToken>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let row = sqlx::query_as!(
+            ApiTokenRow,
+            r#"
+            SELECT
+                name,
+                secret_hash,
+                scopes,
+                created_at
+            FROM
+                api_tokens
+            WHERE
+                name = $1
+            "#,
+            name,
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        row.map(ApiToken::try_from).transpose()
+    }
+
+    pub async fn load_api_tokens(&self) -> Result<Vec<ApiToken>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let rows = sqlx::query_as!(
+            ApiTokenRow,
+            r#"
+            SELECT
+                name,
+                secret_hash,
+                scopes,
+                created_at
+            FROM
+                api_tokens
+            ORDER BY
+                name
+            "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter().map(ApiToken::try_from).collect()
+    }
+
+    /// Delete the API token with the given name, revoking access for anyone holding it.
+    ///
+    /// Returns whether a token with the given name existed.
+    pub async fn delete_api_token(&self, name: &str) -> Result<bool> {
+        let mut conn = self.inner.acquire().await?;
+
+        let query_result = sqlx::query!(
+            r#"
+            DELETE FROM api_tokens WHERE name = $1
+            "#,
+            name,
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(query_result.rows_affected() == 1)
+    }
+}
+
+struct ApiTokenRow {
+    name: String,
+    secret_hash: String,
+    scopes: String,
+    created_at: i64,
+}
+
+impl TryFrom<ApiTokenRow> for ApiToken {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ApiTokenRow) -> Result<Self> {
+        Ok(ApiToken {
+            name: row.name,
+            secret_hash: row.secret_hash,
+            scopes: row.scopes.split_whitespace().map(str::to_owned).collect(),
+            created_at: OffsetDateTime::from_unix_timestamp(row.created_at)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+
+    #[tokio::test]
+    async fn inserted_api_token_can_be_loaded() {
+        let db = memory().await.unwrap();
+        let token = dummy_api_token("dashboard");
+
+        db.insert_api_token(&token).await.unwrap();
+        let loaded = db.load_api_token("dashboard").await.unwrap();
+
+        assert_eq!(loaded, Some(token));
+    }
+
+    #[tokio::test]
+    async fn cannot_insert_api_token_with_existing_name() {
+        let db = memory().await.unwrap();
+
+        db.insert_api_token(&dummy_api_token("dashboard"))
+            .await
+            .unwrap();
+        let result = db.insert_api_token(&dummy_api_token("dashboard")).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn deleted_api_token_is_gone() {
+        let db = memory().await.unwrap();
+        db.insert_api_token(&dummy_api_token("dashboard"))
+            .await
+            .unwrap();
+        db.insert_api_token(&dummy_api_token("bot")).await.unwrap();
+
+        let deleted = db.delete_api_token("dashboard").await.unwrap();
+        let deleted_again = db.delete_api_token("dashboard").await.unwrap();
+
+        assert!(deleted);
+        assert!(!deleted_again);
+        assert_eq!(
+            db.load_api_tokens().await.unwrap(),
+            vec![dummy_api_token("bot")]
+        );
+    }
+
+    fn dummy_api_token(name: &str) -> ApiToken {
+        ApiToken {
+            name: name.to_owned(),
+            secret_hash: "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b"
+                .to_owned(),
+            scopes: vec!["read".to_owned(), "trade".to_owned()],
+            created_at: OffsetDateTime::from_unix_timestamp(1656979200).unwrap(),
+        }
+    }
+}
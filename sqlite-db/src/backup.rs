@@ -0,0 +1,259 @@
+use crate::Connection;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use libsqlite3_sys as ffi;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::Row;
+use sqlx::SqlitePool;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::path::PathBuf;
+use std::ptr;
+
+/// How long to wait for a lock held by another connection before giving up on a backup step.
+const BUSY_TIMEOUT_MS: c_int = 5_000;
+
+/// Copy the database at `source` to `destination` using SQLite's online backup API.
+///
+/// The database can be in use while it is copied, the copy is a consistent snapshot of it. The
+/// copy is written next to `destination` and only moved into place once complete, so that
+/// `destination` never holds a partial copy.
+///
+/// Blocks until the copy is complete.
+pub fn backup(source: &Path, destination: &Path) -> Result<()> {
+    let partial = partial_path(destination);
+
+    if let Err(e) = copy(source, &partial) {
+        let _ = std::fs::remove_file(&partial);
+
+        return Err(e.context(format!(
+            "Failed to back up {} to {}",
+            source.display(),
+            destination.display()
+        )));
+    }
+
+    std::fs::rename(&partial, destination).with_context(|| {
+        format!(
+            "Failed to move backup {} to {}",
+            partial.display(),
+            destination.display()
+        )
+    })?;
+
+    Ok(())
+}
+
+fn copy(source: &Path, destination: &Path) -> Result<()> {
+    let source = Database::open(source, ffi::SQLITE_OPEN_READONLY)?;
+    let destination = Database::open(
+        destination,
+        ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE,
+    )?;
+
+    let main = CString::new("main").expect("no nul bytes");
+
+    // SAFETY: Both handles are open until the end of this function and the schema names are valid
+    // C strings.
+    let backup =
+        unsafe { ffi::sqlite3_backup_init(destination.0, main.as_ptr(), source.0, main.as_ptr()) };
+    if backup.is_null() {
+        bail!("Failed to start backup: {}", destination.error_message());
+    }
+
+    // Copy all pages in one step, so that concurrent writes cannot restart the backup.
+    //
+    // SAFETY: `backup` was successfully initialised and is finished exactly once below.
+    let step = unsafe { ffi::sqlite3_backup_step(backup, -1) };
+    // SAFETY: See above.
+    let finish = unsafe { ffi::sqlite3_backup_finish(backup) };
+
+    if step != ffi::SQLITE_DONE {
+        bail!("Failed to copy database: {}", destination.error_message());
+    }
+    if finish != ffi::SQLITE_OK {
+        bail!("Failed to finish backup: {}", destination.error_message());
+    }
+
+    Ok(())
+}
+
+/// Where a backup is written to before it is moved to `destination`.
+fn partial_path(destination: &Path) -> PathBuf {
+    let mut partial = destination.as_os_str().to_owned();
+    partial.push(".partial");
+
+    PathBuf::from(partial)
+}
+
+/// A database handle opened through the C API, closed on drop.
+struct Database(*mut ffi::sqlite3);
+
+impl Database {
+    fn open(path: &Path, flags: c_int) -> Result<Self> {
+        let path_str = path
+            .to_str()
+            .with_context(|| format!("Path {} is not valid UTF-8", path.display()))?;
+        let path_str = CString::new(path_str)?;
+
+        let mut handle = ptr::null_mut();
+        // SAFETY: `path_str` is a valid C string and `handle` a valid out pointer. A null VFS
+        // selects the default one.
+        let result =
+            unsafe { ffi::sqlite3_open_v2(path_str.as_ptr(), &mut handle, flags, ptr::null()) };
+
+        // SQLite allocates a handle even if opening fails, it has to be closed either way.
+        let database = Self(handle);
+
+        if result != ffi::SQLITE_OK {
+            bail!(
+                "Failed to open database at {}: {}",
+                path.display(),
+                database.error_message()
+            );
+        }
+
+        // SAFETY: The handle is open.
+        unsafe { ffi::sqlite3_busy_timeout(database.0, BUSY_TIMEOUT_MS) };
+
+        Ok(database)
+    }
+
+    fn error_message(&self) -> String {
+        if self.0.is_null() {
+            return "Out of memory".to_owned();
+        }
+
+        // SAFETY: The handle is open and SQLite returns a valid C string which lives until the
+        // next call on the handle, we copy it right away.
+        unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.0)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        // SAFETY: The handle was returned by `sqlite3_open_v2` and is not used after this.
+        // Closing a null handle is a no-op.
+        unsafe { ffi::sqlite3_close(self.0) };
+    }
+}
+
+/// Open the existing database at `path` without modifying it, e.g. to inspect it.
+///
+/// Unlike [`connect`](crate::connect), this neither creates a missing database nor applies
+/// pending migrations.
+pub async fn open_read_only(path: &Path) -> Result<Connection> {
+    if !path.exists() {
+        bail!("Database {} does not exist", path.display());
+    }
+
+    let pool = SqlitePool::connect_with(SqliteConnectOptions::new().filename(path).read_only(true))
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    Ok(Connection::new(pool))
+}
+
+impl Connection {
+    /// Check the database file for corruption.
+    ///
+    /// Returns a description of every problem found, i.e. an empty list if the database is fine.
+    pub async fn check_integrity(&self) -> Result<Vec<String>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let mut problems = sqlx::query("PRAGMA integrity_check")
+            .fetch_all(&mut conn)
+            .await?
+            .iter()
+            .map(|row| row.try_get::<String, _>(0))
+            .filter(|problem| !matches!(problem, Ok(ok) if ok == "ok"))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let foreign_key_violations = sqlx::query("PRAGMA foreign_key_check")
+            .fetch_all(&mut conn)
+            .await?
+            .iter()
+            .map(|row| {
+                let table = row.try_get::<String, _>("table")?;
+                let parent = row.try_get::<String, _>("parent")?;
+
+                Ok(format!("Row in {table} refers to missing row in {parent}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        problems.extend(foreign_key_violations);
+
+        Ok(problems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect;
+    use crate::quotes::Quote;
+    use rust_decimal_macros::dec;
+    use time::OffsetDateTime;
+
+    #[tokio::test]
+    async fn backup_contains_data_written_before() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("taker.sqlite");
+        let backup_path = dir.path().join("backup.sqlite");
+        let db = connect(path.clone()).await.unwrap();
+        let quote = Quote {
+            timestamp: OffsetDateTime::from_unix_timestamp(1657065600).unwrap(),
+            bid: dec!(20000),
+            ask: dec!(20000.5),
+        };
+        db.insert_quotes(&[quote]).await.unwrap();
+
+        backup(&path, &backup_path).unwrap();
+        db.close().await;
+
+        let backup_db = connect(backup_path.clone()).await.unwrap();
+        assert_eq!(
+            backup_db
+                .load_quotes(quote.timestamp, quote.timestamp + time::Duration::SECOND)
+                .await
+                .unwrap(),
+            vec![quote]
+        );
+        assert!(backup_db.check_integrity().await.unwrap().is_empty());
+        assert!(!partial_path(&backup_path).exists());
+    }
+
+    #[tokio::test]
+    async fn opening_read_only_neither_creates_nor_migrates_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.sqlite");
+
+        assert!(open_read_only(&missing).await.is_err());
+        assert!(!missing.exists());
+
+        let empty = dir.path().join("empty.sqlite");
+        std::fs::File::create(&empty).unwrap();
+
+        let db = open_read_only(&empty).await.unwrap();
+        assert!(db.check_integrity().await.unwrap().is_empty());
+        db.close().await;
+
+        assert_eq!(std::fs::metadata(&empty).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn fails_to_back_up_missing_database() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let result = backup(
+            &dir.path().join("missing.sqlite"),
+            &dir.path().join("backup.sqlite"),
+        );
+
+        assert!(result.is_err());
+    }
+}
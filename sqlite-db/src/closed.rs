@@ -31,6 +31,7 @@ use model::libp2p::PeerId;
 use model::long_and_short_leverage;
 use model::CfdEvent;
 use model::ClosedCfd;
+use model::ContractType;
 use model::Contracts;
 use model::Dlc;
 use model::FeeAccount;
@@ -45,7 +46,6 @@ use model::Price;
 use model::Role;
 use model::Settlement;
 use model::Timestamp;
-use model::SETTLEMENT_INTERVAL;
 use models::Payout;
 use models::Vout;
 use sqlx::pool::PoolConnection;
@@ -127,7 +127,8 @@ impl Connection {
                 fees as "fees: models::Fees",
                 expiry_timestamp,
                 lock_txid as "lock_txid: models::Txid",
-                lock_dlc_vout as "lock_dlc_vout: models::Vout"
+                lock_dlc_vout as "lock_dlc_vout: models::Vout",
+                contract_type as "contract_type: models::ContractType"
             FROM
                 closed_cfds
             WHERE
@@ -170,6 +171,7 @@ impl Connection {
             counterparty_peer_id: cfd.counterparty_peer_id.into(),
             role: cfd.role.into(),
             fees: cfd.fees.into(),
+            contract_type: cfd.contract_type.into(),
             expiry_timestamp,
             lock: Lock {
                 txid: cfd.lock_txid.into(),
@@ -218,6 +220,7 @@ struct ClosedCfdInputAggregate {
     counterparty_network_identity: Identity,
     counterparty_peer_id: Option<PeerId>,
     role: Role,
+    contract_type: ContractType,
     fee_account: FeeAccount,
     initial_funding_fee: FundingFee,
     latest_dlc: Option<Dlc>,
@@ -235,13 +238,14 @@ impl ClosedCfdInputAggregate {
             position,
             initial_price,
             taker_leverage,
-            settlement_interval: _,
+            settlement_interval,
             quantity_usd,
             counterparty_network_identity,
             counterparty_peer_id,
             role,
             opening_fee,
             initial_funding_rate,
+            contract_type,
             ..
         } = cfd;
         let n_contracts = quantity_usd
@@ -259,7 +263,7 @@ impl ClosedCfdInputAggregate {
                 long_leverage,
                 short_leverage,
                 initial_funding_rate,
-                SETTLEMENT_INTERVAL.whole_hours(),
+                settlement_interval.whole_hours(),
             )
             .expect("values from db to be sane")
         };
@@ -273,6 +277,7 @@ impl ClosedCfdInputAggregate {
             counterparty_network_identity,
             counterparty_peer_id,
             role,
+            contract_type,
             fee_account: FeeAccount::new(position, role).add_opening_fee(opening_fee),
             initial_funding_fee,
             latest_dlc: None,
@@ -355,6 +360,7 @@ impl ClosedCfdInputAggregate {
                 self.cet = Some((cet, price));
             }
             ManualCommit { .. } => {}
+            AutoAccepted { .. } => {}
         }
 
         Ok(self)
@@ -474,6 +480,7 @@ impl ClosedCfdInputAggregate {
             counterparty_network_identity,
             counterparty_peer_id,
             role,
+            contract_type,
             fee_account,
             ..
         } = self;
@@ -507,6 +514,7 @@ impl ClosedCfdInputAggregate {
             counterparty_peer_id,
             role,
             fees: Fees::new(fee_account.balance()),
+            contract_type,
             expiry_timestamp: dlc.settlement_event_id.timestamp(),
             lock,
             settlement,
@@ -527,6 +535,7 @@ struct ClosedCfdInput {
     counterparty_peer_id: Option<PeerId>,
     role: Role,
     fees: Fees,
+    contract_type: ContractType,
     expiry_timestamp: OffsetDateTime,
     lock: Lock,
     settlement: Settlement,
@@ -546,6 +555,7 @@ async fn insert_closed_cfd(conn: &mut Transaction<'_, Sqlite>, cfd: ClosedCfdInp
     let position = models::Position::from(cfd.position);
     let counterparty_network_identity = models::Identity::from(cfd.counterparty_network_identity);
     let fees = models::Fees::from(cfd.fees);
+    let contract_type = models::ContractType::from(cfd.contract_type);
     let contracts = models::Contracts::from(cfd.n_contracts);
     let counterparty_peer_id = models::PeerId::from(counterparty_peer_id);
     let lock_txid = models::Txid::from(cfd.lock.txid);
@@ -566,9 +576,10 @@ async fn insert_closed_cfd(conn: &mut Transaction<'_, Sqlite>, cfd: ClosedCfdInp
             fees,
             expiry_timestamp,
             lock_txid,
-            lock_dlc_vout
+            lock_dlc_vout,
+            contract_type
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
         "#,
         id,
         position,
@@ -582,6 +593,7 @@ async fn insert_closed_cfd(conn: &mut Transaction<'_, Sqlite>, cfd: ClosedCfdInp
         expiry_timestamp,
         lock_txid,
         dlc_vout,
+        contract_type,
     )
     .execute(&mut *conn)
     .await?;
@@ -1283,6 +1295,7 @@ mod tests {
             counterparty_peer_id: Some(PeerId::random()),
             role: Role::Maker,
             fees: Fees::new(SignedAmount::ONE_BTC),
+            contract_type: ContractType::Perpetual,
             expiry_timestamp: OffsetDateTime::now_utc(),
             lock: Lock {
                 txid: bdk::bitcoin::Txid::default(),
@@ -1320,6 +1333,7 @@ mod tests {
             OpeningFee::new(Amount::ZERO),
             FundingRate::default(),
             TxFeeRate::default(),
+            ContractType::Perpetual,
         );
 
         let contract_setup_completed =
@@ -103,7 +103,8 @@ impl Connection {
                 counterparty_peer_id as "counterparty_peer_id: models::PeerId",
                 role as "role: models::Role",
                 fees as "fees: models::Fees",
-                kind as "kind: models::FailedKind"
+                kind as "kind: models::FailedKind",
+                contract_type as "contract_type: models::ContractType"
             FROM
                 failed_cfds
             WHERE
@@ -126,6 +127,7 @@ impl Connection {
             counterparty_peer_id: cfd.counterparty_peer_id.into(),
             role: cfd.role.into(),
             fees: cfd.fees.into(),
+            contract_type: cfd.contract_type.into(),
             kind: cfd.kind.into(),
             creation_timestamp,
         };
@@ -207,6 +209,7 @@ async fn insert_failed_cfd(
     let position = models::Position::from(cfd.position);
     let counterparty_network_identity = models::Identity::from(cfd.counterparty_network_identity);
     let counterparty_peer_id = models::PeerId::from(counterparty_peer_id);
+    let contract_type = models::ContractType::from(cfd.contract_type);
 
     let query_result = sqlx::query!(
         r#"
@@ -221,9 +224,10 @@ async fn insert_failed_cfd(
             counterparty_peer_id,
             role,
             fees,
-            kind
+            kind,
+            contract_type
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
         "#,
         id,
         position,
@@ -235,6 +239,7 @@ async fn insert_failed_cfd(
         role,
         fees,
         kind,
+        contract_type,
     )
     .execute(&mut *conn)
     .await?;
@@ -18,6 +18,7 @@ impl crate::CfdAggregate for model::Cfd {
             opening_fee,
             initial_funding_rate,
             initial_tx_fee_rate,
+            contract_type,
         }: crate::Cfd,
     ) -> Self {
         model::Cfd::new(
@@ -33,6 +34,7 @@ impl crate::CfdAggregate for model::Cfd {
             opening_fee,
             initial_funding_rate,
             initial_tx_fee_rate,
+            contract_type,
         )
     }
 
@@ -11,6 +11,7 @@ use futures::Stream;
 use futures::StreamExt;
 use model::libp2p::PeerId;
 use model::CfdEvent;
+use model::ContractType;
 use model::EventKind;
 use model::FundingRate;
 use model::Identity;
@@ -36,15 +37,23 @@ use std::str::FromStr;
 use std::sync::Arc;
 use time::Duration;
 
+pub use crate::backup::backup;
+pub use crate::backup::open_read_only;
 pub use closed::*;
 pub use failed::*;
 use model::EventKind::RolloverCompleted;
 
+pub mod api_tokens;
+mod backup;
 pub mod closed;
 pub mod event_log;
 pub mod failed;
 mod impls;
+mod local_identity;
 mod models;
+pub mod notifications;
+pub mod pnl_snapshots;
+pub mod quotes;
 mod rollover;
 pub mod time_to_first_position;
 
@@ -160,6 +169,7 @@ impl Connection {
         let opening_fee = models::OpeningFee::from(cfd.opening_fee());
         let tx_fee_rate = models::TxFeeRate::from(cfd.initial_tx_fee_rate());
         let counterparty_peer_id = cfd.counterparty_peer_id().map(models::PeerId::from);
+        let contract_type = models::ContractType::from(cfd.contract_type());
 
         let query_result = sqlx::query(
             r#"
@@ -175,8 +185,9 @@ impl Connection {
             role,
             opening_fee,
             initial_funding_rate,
-            initial_tx_fee_rate
-        ) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
+            initial_tx_fee_rate,
+            contract_type
+        ) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
         )
         .bind(&id)
         .bind(&position)
@@ -197,6 +208,7 @@ impl Connection {
         .bind(&opening_fee)
         .bind(&initial_funding_rate)
         .bind(&tx_fee_rate)
+        .bind(&contract_type)
         .execute(&mut conn)
         .await?;
 
@@ -487,6 +499,7 @@ pub struct Cfd {
     pub opening_fee: OpeningFee,
     pub initial_funding_rate: FundingRate,
     pub initial_tx_fee_rate: TxFeeRate,
+    pub contract_type: ContractType,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -542,7 +555,8 @@ async fn load_cfd_row(conn: &mut Transaction<'_, Sqlite>, id: OrderId) -> Result
                 role as "role: models::Role",
                 opening_fee as "opening_fee: models::OpeningFee",
                 initial_funding_rate as "initial_funding_rate: models::FundingRate",
-                initial_tx_fee_rate as "initial_tx_fee_rate: models::TxFeeRate"
+                initial_tx_fee_rate as "initial_tx_fee_rate: models::TxFeeRate",
+                contract_type as "contract_type: models::ContractType"
             from
                 cfds
             where
@@ -577,6 +591,7 @@ async fn load_cfd_row(conn: &mut Transaction<'_, Sqlite>, id: OrderId) -> Result
         opening_fee: cfd_row.opening_fee.into(),
         initial_funding_rate: cfd_row.initial_funding_rate.into(),
         initial_tx_fee_rate: cfd_row.initial_tx_fee_rate.into(),
+        contract_type: cfd_row.contract_type.into(),
     })
 }
 
@@ -907,6 +922,38 @@ mod tests {
         assert_eq!(None, counterparty_peer_id);
     }
 
+    #[tokio::test]
+    async fn given_fixed_expiry_cfd_when_loading_then_contract_type_is_fixed_expiry() {
+        let db = memory().await.unwrap();
+
+        let cfd = Cfd::new(
+            OrderId::default(),
+            Position::Long,
+            Price::new(dec!(60_000)).unwrap(),
+            Leverage::TWO,
+            Duration::days(30),
+            Role::Taker,
+            Usd::new(dec!(1_000)),
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"
+                .parse()
+                .unwrap(),
+            Some(PeerId::random()),
+            OpeningFee::new(Amount::from_sat(2000)),
+            FundingRate::default(),
+            TxFeeRate::default(),
+            ContractType::FixedExpiry,
+        );
+        db.insert_cfd(&cfd).await.unwrap();
+        let mut conn = db.inner.acquire().await.unwrap();
+        let mut db_tx = conn.begin().await.unwrap();
+
+        let super::Cfd { contract_type, .. } = load_cfd_row(&mut db_tx, cfd.id()).await.unwrap();
+
+        db_tx.commit().await.unwrap();
+
+        assert_eq!(ContractType::FixedExpiry, contract_type);
+    }
+
     pub fn dummy_cfd() -> Cfd {
         dummy_taker_with_legacy_identity(
             "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
@@ -929,6 +976,7 @@ mod tests {
             OpeningFee::new(Amount::from_sat(2000)),
             FundingRate::default(),
             TxFeeRate::default(),
+            ContractType::Perpetual,
         )
     }
 
@@ -946,6 +994,7 @@ mod tests {
             OpeningFee::new(Amount::from_sat(2000)),
             FundingRate::default(),
             TxFeeRate::default(),
+            ContractType::Perpetual,
         )
     }
 
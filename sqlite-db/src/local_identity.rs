@@ -0,0 +1,76 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Result;
+use model::Identity;
+
+impl Connection {
+    /// The identity of the daemon the database belongs to, if it was recorded.
+    pub async fn load_local_identity(&self) -> Result<Option<Identity>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let identity = sqlx::query!(
+            r#"
+            SELECT
+                identity AS "identity: models::Identity"
+            FROM
+                local_identity
+            "#
+        )
+        .fetch_optional(&mut conn)
+        .await?
+        .map(|row| row.identity.into());
+
+        Ok(identity)
+    }
+
+    /// Record the identity of the daemon the database belongs to.
+    ///
+    /// Does nothing if an identity was already recorded.
+    pub async fn insert_local_identity(&self, identity: Identity) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let identity = models::Identity::from(identity);
+
+        sqlx::query!(
+            r#"
+            INSERT OR IGNORE INTO local_identity
+            (
+                id,
+                identity
+            )
+            VALUES (0, $1)
+            "#,
+            identity,
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+
+    #[tokio::test]
+    async fn keeps_first_recorded_identity() {
+        let db = memory().await.unwrap();
+        let first =
+            dummy_identity("7e35e34801e766a6a29ecb9e22810ea4e3476c2b37bf75882edf94a68b1d9607");
+        let second =
+            dummy_identity("69a42aa90da8b065b9532b62bff940a3ba07dbbb11d4482c7db83a7e049a9f1e");
+
+        assert_eq!(db.load_local_identity().await.unwrap(), None);
+
+        db.insert_local_identity(first).await.unwrap();
+        db.insert_local_identity(second).await.unwrap();
+
+        assert_eq!(db.load_local_identity().await.unwrap(), Some(first));
+    }
+
+    fn dummy_identity(hex: &str) -> Identity {
+        hex.parse().unwrap()
+    }
+}
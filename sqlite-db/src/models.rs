@@ -130,6 +130,30 @@ impl From<Role> for model::Role {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, sqlx::Type)]
+pub enum ContractType {
+    Perpetual,
+    FixedExpiry,
+}
+
+impl From<model::ContractType> for ContractType {
+    fn from(contract_type: model::ContractType) -> Self {
+        match contract_type {
+            model::ContractType::Perpetual => ContractType::Perpetual,
+            model::ContractType::FixedExpiry => ContractType::FixedExpiry,
+        }
+    }
+}
+
+impl From<ContractType> for model::ContractType {
+    fn from(contract_type: ContractType) -> Self {
+        match contract_type {
+            ContractType::Perpetual => model::ContractType::Perpetual,
+            ContractType::FixedExpiry => model::ContractType::FixedExpiry,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct PublicKey(bitcoin::util::key::PublicKey);
 
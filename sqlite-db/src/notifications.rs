@@ -0,0 +1,143 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Result;
+use model::OrderId;
+use time::OffsetDateTime;
+
+/// A single attempt to deliver a notification about a CFD event to a sink, e.g. a webhook.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationDelivery {
+    pub order_id: OrderId,
+    /// The name of the event the notification was about.
+    pub event: String,
+    /// The name of the sink the notification was delivered to.
+    pub sink: String,
+    /// Starts at 1 and is incremented for every retry.
+    pub attempt: u32,
+    /// Why the attempt failed, `None` if the notification was delivered.
+    pub error: Option<String>,
+    pub attempted_at: OffsetDateTime,
+}
+
+impl Connection {
+    pub async fn insert_notification_delivery(
+        &self,
+        delivery: &NotificationDelivery,
+    ) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let order_id = models::OrderId::from(delivery.order_id);
+        let attempted_at = delivery.attempted_at.unix_timestamp();
+
+        let query_result = sqlx::query!(
+            r#"
+            INSERT INTO notification_deliveries
+            (
+                order_id,
+                event,
+                sink,
+                attempt,
+                error,
+                attempted_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            order_id,
+            delivery.event,
+            delivery.sink,
+            delivery.attempt,
+            delivery.error,
+            attempted_at,
+        )
+        .execute(&mut conn)
+        .await?;
+
+        if query_result.rows_affected() != 1 {
+            anyhow::bail!("failed to insert notification delivery");
+        }
+
+        Ok(())
+    }
+
+    /// Load the latest `limit` delivery attempts, most recent first.
+    pub async fn load_notification_deliveries(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<NotificationDelivery>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                order_id AS "order_id: models::OrderId",
+                event,
+                sink,
+                attempt,
+                error,
+                attempted_at
+            FROM
+                notification_deliveries
+            ORDER BY
+                id DESC
+            LIMIT $1
+            "#,
+            limit,
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(NotificationDelivery {
+                    order_id: row.order_id.into(),
+                    event: row.event,
+                    sink: row.sink,
+                    attempt: u32::try_from(row.attempt)?,
+                    error: row.error,
+                    attempted_at: OffsetDateTime::from_unix_timestamp(row.attempted_at)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+
+    #[tokio::test]
+    async fn loads_latest_notification_deliveries_first() {
+        let db = memory().await.unwrap();
+        let order_id = OrderId::default();
+        let failed = dummy_delivery(order_id, 1, Some("connection refused"));
+        let delivered = dummy_delivery(order_id, 2, None);
+
+        db.insert_notification_delivery(&failed).await.unwrap();
+        db.insert_notification_delivery(&delivered).await.unwrap();
+
+        assert_eq!(
+            db.load_notification_deliveries(10).await.unwrap(),
+            vec![delivered.clone(), failed]
+        );
+        assert_eq!(
+            db.load_notification_deliveries(1).await.unwrap(),
+            vec![delivered]
+        );
+    }
+
+    fn dummy_delivery(
+        order_id: OrderId,
+        attempt: u32,
+        error: Option<&str>,
+    ) -> NotificationDelivery {
+        NotificationDelivery {
+            order_id,
+            event: "RolloverStarted".to_owned(),
+            sink: "webhook https://example.com/hook".to_owned(),
+            attempt,
+            error: error.map(str::to_owned),
+            attempted_at: OffsetDateTime::from_unix_timestamp(1657065600).unwrap(),
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Result;
+use bdk::bitcoin::Amount;
+use bdk::bitcoin::SignedAmount;
+use model::OrderId;
+use time::OffsetDateTime;
+
+/// The mark-to-market state of an open CFD at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PnlSnapshot {
+    pub order_id: OrderId,
+    pub timestamp: OffsetDateTime,
+    /// The unrealised profit at the price of the time, net of `accumulated_fees`.
+    pub profit: SignedAmount,
+    /// The opening fee and funding fees accumulated up to the time.
+    pub accumulated_fees: SignedAmount,
+    pub margin: Amount,
+}
+
+/// The sum of the snapshots of all CFDs taken at the same time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EquityPoint {
+    pub timestamp: OffsetDateTime,
+    pub profit: SignedAmount,
+    pub accumulated_fees: SignedAmount,
+    pub margin: Amount,
+    /// The number of CFDs included in the sums.
+    pub positions: u32,
+}
+
+impl Connection {
+    /// Insert the given snapshots at once.
+    pub async fn insert_pnl_snapshots(&self, snapshots: &[PnlSnapshot]) -> Result<()> {
+        let mut tx = self.inner.begin().await?;
+
+        for snapshot in snapshots {
+            let order_id = models::OrderId::from(snapshot.order_id);
+            let timestamp = snapshot.timestamp.unix_timestamp();
+            let profit = snapshot.profit.as_sat();
+            let accumulated_fees = snapshot.accumulated_fees.as_sat();
+            let margin = snapshot.margin.as_sat() as i64;
+
+            let query_result = sqlx::query!(
+                r#"
+                INSERT INTO pnl_snapshots
+                (
+                    order_id,
+                    timestamp,
+                    profit_sat,
+                    accumulated_fees_sat,
+                    margin_sat
+                )
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                order_id,
+                timestamp,
+                profit,
+                accumulated_fees,
+                margin,
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            if query_result.rows_affected() != 1 {
+                anyhow::bail!("failed to insert PnL snapshot");
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Load all snapshots of the given CFD, oldest first.
+    pub async fn load_pnl_snapshots(&self, order_id: OrderId) -> Result<Vec<PnlSnapshot>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let order_id = models::OrderId::from(order_id);
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                order_id AS "order_id: models::OrderId",
+                timestamp,
+                profit_sat,
+                accumulated_fees_sat,
+                margin_sat
+            FROM
+                pnl_snapshots
+            WHERE
+                order_id = $1
+            ORDER BY
+                timestamp ASC
+            "#,
+            order_id,
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(PnlSnapshot {
+                    order_id: row.order_id.into(),
+                    timestamp: OffsetDateTime::from_unix_timestamp(row.timestamp)?,
+                    profit: SignedAmount::from_sat(row.profit_sat),
+                    accumulated_fees: SignedAmount::from_sat(row.accumulated_fees_sat),
+                    margin: Amount::from_sat(u64::try_from(row.margin_sat)?),
+                })
+            })
+            .collect()
+    }
+
+    /// Load the sums of the snapshots taken in `[from, to)`, oldest first.
+    ///
+    /// There is no point for times at which there were no open CFDs.
+    pub async fn load_equity_curve(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> Result<Vec<EquityPoint>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let from = from.unix_timestamp();
+        let to = to.unix_timestamp();
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                timestamp,
+                SUM(profit_sat) AS "profit_sat!: i64",
+                SUM(accumulated_fees_sat) AS "accumulated_fees_sat!: i64",
+                SUM(margin_sat) AS "margin_sat!: i64",
+                COUNT(*) AS "positions!: i64"
+            FROM
+                pnl_snapshots
+            WHERE
+                timestamp >= $1 AND timestamp < $2
+            GROUP BY
+                timestamp
+            ORDER BY
+                timestamp ASC
+            "#,
+            from,
+            to,
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(EquityPoint {
+                    timestamp: OffsetDateTime::from_unix_timestamp(row.timestamp)?,
+                    profit: SignedAmount::from_sat(row.profit_sat),
+                    accumulated_fees: SignedAmount::from_sat(row.accumulated_fees_sat),
+                    margin: Amount::from_sat(u64::try_from(row.margin_sat)?),
+                    positions: u32::try_from(row.positions)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Delete all snapshots taken before `before`.
+    ///
+    /// Returns the number of deleted snapshots.
+    pub async fn delete_pnl_snapshots_before(&self, before: OffsetDateTime) -> Result<u64> {
+        let mut conn = self.inner.acquire().await?;
+
+        let before = before.unix_timestamp();
+
+        let query_result = sqlx::query!(
+            r#"
+            DELETE FROM pnl_snapshots WHERE timestamp < $1
+            "#,
+            before,
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(query_result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+
+    #[tokio::test]
+    async fn loads_snapshots_of_cfd_oldest_first() {
+        let db = memory().await.unwrap();
+        let order_id = OrderId::default();
+        let later = dummy_snapshot(order_id, 1657066200, 1_000);
+        let earlier = dummy_snapshot(order_id, 1657065900, -500);
+        let other = dummy_snapshot(OrderId::default(), 1657065900, 200);
+
+        db.insert_pnl_snapshots(&[later, earlier, other])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            db.load_pnl_snapshots(order_id).await.unwrap(),
+            vec![earlier, later]
+        );
+    }
+
+    #[tokio::test]
+    async fn sums_snapshots_taken_at_same_time() {
+        let db = memory().await.unwrap();
+        let (first, second) = (OrderId::default(), OrderId::default());
+        db.insert_pnl_snapshots(&[
+            dummy_snapshot(first, 1657065900, -500),
+            dummy_snapshot(second, 1657065900, 200),
+            dummy_snapshot(first, 1657066200, 1_000),
+            dummy_snapshot(first, 1657066500, 1_500),
+        ])
+        .await
+        .unwrap();
+
+        let curve = db
+            .load_equity_curve(at(1657065900), at(1657066500))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            curve,
+            vec![
+                EquityPoint {
+                    timestamp: at(1657065900),
+                    profit: SignedAmount::from_sat(-300),
+                    accumulated_fees: SignedAmount::from_sat(200),
+                    margin: Amount::from_sat(200_000),
+                    positions: 2,
+                },
+                EquityPoint {
+                    timestamp: at(1657066200),
+                    profit: SignedAmount::from_sat(1_000),
+                    accumulated_fees: SignedAmount::from_sat(100),
+                    margin: Amount::from_sat(100_000),
+                    positions: 1,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn deletes_snapshots_before_given_time() {
+        let db = memory().await.unwrap();
+        let order_id = OrderId::default();
+        let old = dummy_snapshot(order_id, 1657065900, -500);
+        let new = dummy_snapshot(order_id, 1657066200, 1_000);
+        db.insert_pnl_snapshots(&[old, new]).await.unwrap();
+
+        let deleted = db
+            .delete_pnl_snapshots_before(at(1657066200))
+            .await
+            .unwrap();
+
+        assert_eq!(deleted, 1);
+        assert_eq!(db.load_pnl_snapshots(order_id).await.unwrap(), vec![new]);
+    }
+
+    fn dummy_snapshot(order_id: OrderId, timestamp: i64, profit: i64) -> PnlSnapshot {
+        PnlSnapshot {
+            order_id,
+            timestamp: at(timestamp),
+            profit: SignedAmount::from_sat(profit),
+            accumulated_fees: SignedAmount::from_sat(100),
+            margin: Amount::from_sat(100_000),
+        }
+    }
+
+    fn at(timestamp: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(timestamp).unwrap()
+    }
+}
@@ -0,0 +1,337 @@
+use crate::Connection;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use time::OffsetDateTime;
+
+/// A bid and ask price of BTC/USD, as published by the price feed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    pub timestamp: OffsetDateTime,
+    pub bid: Decimal,
+    pub ask: Decimal,
+}
+
+/// The open, high, low and close mid price of the quotes within one period.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candle {
+    pub start: OffsetDateTime,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+}
+
+impl Connection {
+    /// Insert the given quotes, skipping the ones we already have a quote for at the same time.
+    ///
+    /// Returns the number of inserted quotes.
+    pub async fn insert_quotes(&self, quotes: &[Quote]) -> Result<u64> {
+        let mut tx = self.inner.begin().await?;
+
+        let mut inserted = 0;
+        for quote in quotes {
+            let timestamp = quote.timestamp.unix_timestamp();
+            let bid = quote.bid.to_string();
+            let ask = quote.ask.to_string();
+
+            let query_result = sqlx::query!(
+                r#"
+                INSERT OR IGNORE INTO quotes
+                (
+                    timestamp,
+                    bid,
+                    ask
+                )
+                VALUES ($1, $2, $3)
+                "#,
+                timestamp,
+                bid,
+                ask,
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            inserted += query_result.rows_affected();
+        }
+
+        tx.commit().await?;
+
+        Ok(inserted)
+    }
+
+    /// Load all quotes in `[from, to)`, oldest first.
+    pub async fn load_quotes(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> Result<Vec<Quote>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let from = from.unix_timestamp();
+        let to = to.unix_timestamp();
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                timestamp AS "timestamp!",
+                bid,
+                ask
+            FROM
+                quotes
+            WHERE
+                timestamp >= $1 AND timestamp < $2
+            ORDER BY
+                timestamp ASC
+            "#,
+            from,
+            to,
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(Quote {
+                    timestamp: OffsetDateTime::from_unix_timestamp(row.timestamp)?,
+                    bid: row.bid.parse()?,
+                    ask: row.ask.parse()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Aggregate the quotes in `[from, to)` into candles of the given period, oldest first.
+    ///
+    /// Periods start at multiples of `period` since the epoch. Periods without any quotes have no
+    /// candle.
+    pub async fn load_candles(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        period: time::Duration,
+    ) -> Result<Vec<Candle>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let from = from.unix_timestamp();
+        let to = to.unix_timestamp();
+        let period = period.whole_seconds();
+
+        let rows = sqlx::query!(
+            r#"
+            WITH periods AS (
+                SELECT
+                    timestamp / $3 * $3 AS start,
+                    MIN(timestamp) AS first,
+                    MAX(timestamp) AS last,
+                    MAX((CAST(bid AS REAL) + CAST(ask AS REAL)) / 2) AS high,
+                    MIN((CAST(bid AS REAL) + CAST(ask AS REAL)) / 2) AS low
+                FROM
+                    quotes
+                WHERE
+                    timestamp >= $1 AND timestamp < $2
+                GROUP BY
+                    start
+            )
+            SELECT
+                periods.start AS "start!: i64",
+                (CAST(first_quote.bid AS REAL) + CAST(first_quote.ask AS REAL)) / 2 AS "open!: f64",
+                periods.high AS "high!: f64",
+                periods.low AS "low!: f64",
+                (CAST(last_quote.bid AS REAL) + CAST(last_quote.ask AS REAL)) / 2 AS "close!: f64"
+            FROM
+                periods
+            JOIN
+                quotes AS first_quote ON first_quote.timestamp = periods.first
+            JOIN
+                quotes AS last_quote ON last_quote.timestamp = periods.last
+            ORDER BY
+                periods.start ASC
+            "#,
+            from,
+            to,
+            period,
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(Candle {
+                    start: OffsetDateTime::from_unix_timestamp(row.start)?,
+                    open: Decimal::try_from(row.open)?,
+                    high: Decimal::try_from(row.high)?,
+                    low: Decimal::try_from(row.low)?,
+                    close: Decimal::try_from(row.close)?,
+                })
+            })
+            .collect()
+    }
+
+    /// The time of the most recent quote, if we have any.
+    pub async fn load_latest_quote_timestamp(&self) -> Result<Option<OffsetDateTime>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let timestamp = sqlx::query_scalar!(
+            r#"
+            SELECT
+                MAX(timestamp) AS "timestamp?: i64"
+            FROM
+                quotes
+            "#
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok(timestamp
+            .map(OffsetDateTime::from_unix_timestamp)
+            .transpose()?)
+    }
+
+    /// Delete all quotes older than `before`.
+    ///
+    /// Returns the number of deleted quotes.
+    pub async fn delete_quotes_before(&self, before: OffsetDateTime) -> Result<u64> {
+        let mut conn = self.inner.acquire().await?;
+
+        let before = before.unix_timestamp();
+
+        let query_result = sqlx::query!(
+            r#"
+            DELETE FROM quotes WHERE timestamp < $1
+            "#,
+            before,
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(query_result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn loads_quotes_in_range_oldest_first() {
+        let db = memory().await.unwrap();
+        let quotes = [
+            dummy_quote(1657065720, dec!(20010)),
+            dummy_quote(1657065600, dec!(20000)),
+            dummy_quote(1657065660, dec!(20005)),
+        ];
+
+        let inserted = db.insert_quotes(&quotes).await.unwrap();
+
+        assert_eq!(inserted, 3);
+        assert_eq!(
+            db.load_quotes(at(1657065600), at(1657065720))
+                .await
+                .unwrap(),
+            vec![quotes[1], quotes[2]]
+        );
+        assert_eq!(
+            db.load_latest_quote_timestamp().await.unwrap(),
+            Some(at(1657065720))
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_quotes_we_already_have() {
+        let db = memory().await.unwrap();
+        let quote = dummy_quote(1657065600, dec!(20000));
+
+        db.insert_quotes(&[quote]).await.unwrap();
+        let inserted = db
+            .insert_quotes(&[dummy_quote(1657065600, dec!(30000))])
+            .await
+            .unwrap();
+
+        assert_eq!(inserted, 0);
+        assert_eq!(
+            db.load_quotes(at(0), at(i64::from(u32::MAX)))
+                .await
+                .unwrap(),
+            vec![quote]
+        );
+    }
+
+    #[tokio::test]
+    async fn deletes_quotes_before_given_time() {
+        let db = memory().await.unwrap();
+        let old = dummy_quote(1657065600, dec!(20000));
+        let new = dummy_quote(1657065660, dec!(20005));
+        db.insert_quotes(&[old, new]).await.unwrap();
+
+        let deleted = db.delete_quotes_before(at(1657065660)).await.unwrap();
+
+        assert_eq!(deleted, 1);
+        assert_eq!(
+            db.load_quotes(at(0), at(i64::from(u32::MAX)))
+                .await
+                .unwrap(),
+            vec![new]
+        );
+    }
+
+    #[tokio::test]
+    async fn aggregates_quotes_in_range_into_candles_of_mid_price() {
+        let db = memory().await.unwrap();
+        db.insert_quotes(&[
+            dummy_quote(1657065540, dec!(30000)),
+            dummy_quote(1657065600, dec!(20000)),
+            dummy_quote(1657065660, dec!(20100)),
+            dummy_quote(1657065720, dec!(19900)),
+            dummy_quote(1657065780, dec!(20050)),
+            dummy_quote(1657069200, dec!(21000)),
+        ])
+        .await
+        .unwrap();
+
+        let candles = db
+            .load_candles(at(1657065600), at(1657072800), time::Duration::HOUR)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            candles,
+            vec![
+                Candle {
+                    start: at(1657065600),
+                    open: dec!(20000.25),
+                    high: dec!(20100.25),
+                    low: dec!(19900.25),
+                    close: dec!(20050.25),
+                },
+                Candle {
+                    start: at(1657069200),
+                    open: dec!(21000.25),
+                    high: dec!(21000.25),
+                    low: dec!(21000.25),
+                    close: dec!(21000.25),
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn no_latest_quote_timestamp_without_quotes() {
+        let db = memory().await.unwrap();
+
+        assert_eq!(db.load_latest_quote_timestamp().await.unwrap(), None);
+    }
+
+    fn dummy_quote(timestamp: i64, bid: Decimal) -> Quote {
+        Quote {
+            timestamp: at(timestamp),
+            bid,
+            ask: bid + dec!(0.5),
+        }
+    }
+
+    fn at(timestamp: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(timestamp).unwrap()
+    }
+}
@@ -17,6 +17,7 @@ mod tests {
     use model::olivia::BitMexPriceEventId;
     use model::Cfd;
     use model::CfdEvent;
+    use model::ContractType;
     use model::EventKind;
     use model::FundingRate;
     use model::Leverage;
@@ -52,6 +53,7 @@ mod tests {
             OpeningFee::new(Amount::from_sat(2000)),
             FundingRate::default(),
             TxFeeRate::default(),
+            ContractType::Perpetual,
         )
     }
 
@@ -45,13 +45,14 @@ import {
     Cfd,
     ConnectionStatus,
     IdentityInfo,
-    intoCfd,
     intoMakerOffer,
     isClosed,
     LeverageDetails,
+    MakerCompatibility,
     MakerOffer,
     WalletInfo,
 } from "./types";
+import useCfds from "./useCfds";
 import { useEventSource } from "./useEventSource";
 import useLatestEvent from "./useLatestEvent";
 
@@ -61,6 +62,8 @@ export interface Offer {
     fundingRateAnnualized?: number;
     fundingRateHourly?: number;
     leverageDetails: LeverageDetails[];
+    // only set for fixed-expiry contracts
+    expiryTimestamp?: number;
 
     // defaulted for display purposes
     minQuantity: number;
@@ -128,6 +131,7 @@ export const App = () => {
                 maxQuantity: offer.max_quantity,
                 lotSize: offer.lot_size,
                 leverageDetails: offer.leverage_details,
+                expiryTimestamp: offer.contract_type === "FixedExpiry" ? offer.expiry_timestamp : undefined,
             };
         }
 
@@ -144,10 +148,13 @@ export const App = () => {
         return Number.parseFloat(Number(n).toFixed(digits));
     }
 
-    const cfdsOrUndefined = useLatestEvent<Cfd[]>(source, "cfds", intoCfd);
-    let cfds = cfdsOrUndefined ? cfdsOrUndefined! : [];
+    const cfds = useCfds(source);
     const connectedToMakerOrUndefined = useLatestEvent<ConnectionStatus>(source, "maker_status");
-    const connectedToMaker = connectedToMakerOrUndefined ? connectedToMakerOrUndefined : { online: false };
+    const makerCompatibility = useLatestEvent<MakerCompatibility>(source, "maker_compatibility");
+    const connectedToMaker: ConnectionStatus = {
+        ...(connectedToMakerOrUndefined ? connectedToMakerOrUndefined : { online: false }),
+        ...(makerCompatibility?.outdated ? { connection_close_reason: makerCompatibility.outdated } : {}),
+    };
 
     dayjs.extend(relativeTime);
     dayjs.extend(utc);
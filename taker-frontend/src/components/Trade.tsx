@@ -33,13 +33,14 @@ import {
     useDisclosure,
     VStack,
 } from "@chakra-ui/react";
+import dayjs from "dayjs";
 import { motion } from "framer-motion";
 import * as React from "react";
 import { useEffect, useState } from "react";
 import { FaWallet } from "react-icons/all";
 import { useNavigate } from "react-router-dom";
 import { Offer } from "../App";
-import { CfdOrderRequestPayload, ConnectionStatus } from "../types";
+import { CfdOrderRequestPayload, ConnectionStatus, unixTimestampToDate } from "../types";
 import usePostRequest from "../usePostRequest";
 import AlertBox from "./AlertBox";
 import BitcoinAmount from "./BitcoinAmount";
@@ -68,6 +69,7 @@ export default function Trade({
         maxQuantity,
         lotSize,
         leverageDetails,
+        expiryTimestamp,
     },
     connectedToMaker,
     walletBalance,
@@ -245,22 +247,42 @@ export default function Trade({
                                     </Td>
                                 </Tr>
 
-                                <Tr id={isLong ? "longPerpetualCost" : "shortPerpetualCost"}>
-                                    <Td>
-                                        <Text>Perpetual Cost</Text>
-                                    </Td>
-                                    <FundingRateTooltip
-                                        fundingRateHourly={fundingRateHourly}
-                                        fundingRateAnnualized={fundingRateAnnualized}
-                                        disabled={!fundingRateHourly}
-                                    >
-                                        <Td isNumeric>
-                                            <Skeleton isLoaded={fundingRateHourly != null}>
-                                                Hourly @ {fundingRateHourly}%
-                                            </Skeleton>
-                                        </Td>
-                                    </FundingRateTooltip>
-                                </Tr>
+                                {expiryTimestamp
+                                    ? (
+                                        <Tr id={isLong ? "longExpiry" : "shortExpiry"}>
+                                            <Td>
+                                                <Text>Expires</Text>
+                                            </Td>
+                                            <Tooltip
+                                                label={`The CFD settles at the price of ${
+                                                    unixTimestampToDate(expiryTimestamp).toLocaleString()
+                                                } and is not rolled over.`}
+                                                hasArrow
+                                            >
+                                                <Td isNumeric>
+                                                    {dayjs.unix(expiryTimestamp).fromNow()}
+                                                </Td>
+                                            </Tooltip>
+                                        </Tr>
+                                    )
+                                    : (
+                                        <Tr id={isLong ? "longPerpetualCost" : "shortPerpetualCost"}>
+                                            <Td>
+                                                <Text>Perpetual Cost</Text>
+                                            </Td>
+                                            <FundingRateTooltip
+                                                fundingRateHourly={fundingRateHourly}
+                                                fundingRateAnnualized={fundingRateAnnualized}
+                                                disabled={!fundingRateHourly}
+                                            >
+                                                <Td isNumeric>
+                                                    <Skeleton isLoaded={fundingRateHourly != null}>
+                                                        Hourly @ {fundingRateHourly}%
+                                                    </Skeleton>
+                                                </Td>
+                                            </FundingRateTooltip>
+                                        </Tr>
+                                    )}
                             </Tbody>
                         </Table>
                     </GridItem>
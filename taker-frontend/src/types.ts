@@ -31,8 +31,13 @@ export interface MakerOffer {
     funding_rate_annualized_percent: number; // e.g. "18.5" (does not include % char)
     funding_rate_hourly_percent: number; // e.g. "0.002345" (does not include % char)
     creation_timestamp: number;
+    contract_type: ContractType;
+    // only set for fixed-expiry contracts
+    expiry_timestamp?: number;
 }
 
+export type ContractType = "Perpetual" | "FixedExpiry";
+
 export interface LeverageDetails {
     leverage: number;
     liquidation_price: number;
@@ -78,6 +83,7 @@ export interface Cfd {
 
     state: State;
     details: CfdDetails;
+    contract_type: ContractType;
     expiry_timestamp?: number;
 
     counterparty: string;
@@ -85,6 +91,19 @@ export interface Cfd {
     accumulated_fees: number;
 }
 
+// The profit of a CFD at the latest quote, sent for all CFDs in `pnl` events.
+export interface CfdPnl {
+    order_id: string;
+    profit_btc?: number;
+    profit_percent?: number;
+    payout?: number;
+}
+
+// Sent as `cfd_removed` event when a CFD is no longer part of the feed.
+export interface CfdRemoved {
+    order_id: string;
+}
+
 export function isClosed(cfd: Cfd): boolean {
     return cfd.state.getGroup() === StateGroupKey.CLOSED;
 }
@@ -291,6 +310,19 @@ export interface WithdrawRequest {
 export interface ConnectionStatus {
     online: boolean;
     connection_close_reason?: ConnectionCloseReason;
+    addresses?: MakerAddress[];
+}
+
+export interface MakerAddress {
+    address: string;
+    connected: boolean;
+    consecutive_failures: number;
+    total_failures: number;
+    last_error?: string;
+}
+
+export interface MakerCompatibility {
+    outdated?: ConnectionCloseReason;
 }
 
 export const enum ConnectionCloseReason {
@@ -0,0 +1,73 @@
+import { useEffect, useState } from "react";
+import { Cfd, CfdPnl, CfdRemoved, intoCfd } from "./types";
+import { EventSourceEvent } from "./useLatestEvent";
+
+// Keeps all CFDs up to date: the feed sends all CFDs in a `cfds` event and afterwards only the CFDs that changed as
+// `cfd` events, the CFDs that were removed as `cfd_removed` events and the profit of all CFDs at the latest quote as
+// `pnl` events.
+export default function useCfds(source: EventSource | null): Cfd[] {
+    const [cfds, setCfds] = useState<Cfd[]>([]);
+
+    useEffect(() => {
+        if (source) {
+            const allCfdsListener = (event: Event) => {
+                setCfds(JSON.parse((event as EventSourceEvent).data, intoCfd));
+            };
+            const cfdListener = (event: Event) => {
+                const cfd: Cfd = JSON.parse((event as EventSourceEvent).data, intoCfd);
+                setCfds((cfds) => upsertCfd(cfds, cfd));
+            };
+            const cfdRemovedListener = (event: Event) => {
+                const removed: CfdRemoved = JSON.parse((event as EventSourceEvent).data);
+                setCfds((cfds) => cfds.filter((cfd) => cfd.order_id !== removed.order_id));
+            };
+            const pnlListener = (event: Event) => {
+                const pnl: CfdPnl[] = JSON.parse((event as EventSourceEvent).data);
+                setCfds((cfds) => applyPnl(cfds, pnl));
+            };
+
+            source.addEventListener("cfds", allCfdsListener);
+            source.addEventListener("cfd", cfdListener);
+            source.addEventListener("cfd_removed", cfdRemovedListener);
+            source.addEventListener("pnl", pnlListener);
+            return () => {
+                source.removeEventListener("cfds", allCfdsListener);
+                source.removeEventListener("cfd", cfdListener);
+                source.removeEventListener("cfd_removed", cfdRemovedListener);
+                source.removeEventListener("pnl", pnlListener);
+            };
+        }
+        return undefined;
+    }, [source]);
+
+    return cfds;
+}
+
+function upsertCfd(cfds: Cfd[], cfd: Cfd): Cfd[] {
+    const index = cfds.findIndex((existing) => existing.order_id === cfd.order_id);
+    if (index === -1) {
+        return [cfd, ...cfds];
+    }
+
+    const updated = [...cfds];
+    updated[index] = cfd;
+    return updated;
+}
+
+function applyPnl(cfds: Cfd[], pnl: CfdPnl[]): Cfd[] {
+    const byOrderId = new Map(pnl.map((entry) => [entry.order_id, entry]));
+
+    return cfds.map((cfd) => {
+        const entry = byOrderId.get(cfd.order_id);
+        if (!entry) {
+            return cfd;
+        }
+
+        return {
+            ...cfd,
+            profit_btc: entry.profit_btc,
+            profit_percent: entry.profit_percent,
+            payout: entry.payout,
+        };
+    });
+}
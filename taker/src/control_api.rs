@@ -0,0 +1,54 @@
+use crate::routes::Taker;
+use anyhow::Result;
+use async_trait::async_trait;
+use model::Leverage;
+use model::OrderId;
+use model::Usd;
+use serde::Deserialize;
+use shared_bin::control_api::Commands;
+use std::sync::Arc;
+
+/// The commands of the taker's control API, see [`shared_bin::control_api`].
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    TakeOffer {
+        order_id: OrderId,
+        quantity: Usd,
+        leverage: Leverage,
+    },
+    ProposeSettlement {
+        order_id: OrderId,
+    },
+    Commit {
+        order_id: OrderId,
+    },
+}
+
+#[derive(Clone)]
+pub struct TakerCommands(pub Arc<Taker>);
+
+#[async_trait]
+impl Commands for TakerCommands {
+    type Command = Command;
+
+    fn order_id(command: &Command) -> Option<OrderId> {
+        match command {
+            Command::TakeOffer { order_id, .. }
+            | Command::ProposeSettlement { order_id }
+            | Command::Commit { order_id } => Some(*order_id),
+        }
+    }
+
+    async fn execute(&self, command: Command) -> Result<()> {
+        match command {
+            Command::TakeOffer {
+                order_id,
+                quantity,
+                leverage,
+            } => self.0.take_offer(order_id, quantity, leverage).await,
+            Command::ProposeSettlement { order_id } => self.0.propose_settlement(order_id).await,
+            Command::Commit { order_id } => self.0.commit(order_id).await,
+        }
+    }
+}
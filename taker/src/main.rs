@@ -1,16 +1,21 @@
+use crate::control_api::TakerCommands;
 use crate::routes::IdentityInfo;
 use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use clap::Parser;
 use clap::Subcommand;
+use daemon::backup;
 use daemon::bdk::bitcoin;
 use daemon::bdk::bitcoin::Address;
 use daemon::bdk::bitcoin::Amount;
 use daemon::bdk::FeeRate;
 use daemon::connection::connect;
+use daemon::libp2p_utils::create_connect_dns_multiaddr;
 use daemon::libp2p_utils::create_connect_tcp_multiaddr;
+use daemon::libp2p_utils::libp2p_port_from_legacy_networking;
 use daemon::libp2p_utils::libp2p_socket_from_legacy_networking;
+use daemon::libp2p_utils::with_peer_id;
 use daemon::monitor;
 use daemon::oracle;
 use daemon::projection;
@@ -22,27 +27,49 @@ use daemon::wallet::TAKER_WALLET_ID;
 use daemon::Environment;
 use daemon::TakerActorSystem;
 use daemon::N_PAYOUTS;
+use itertools::Itertools;
+use libp2p_core::Multiaddr;
 use libp2p_core::PeerId;
 use model::olivia;
 use model::Identity;
 use model::SETTLEMENT_INTERVAL;
 use rocket::fairing::AdHoc;
 use rocket::fairing::Fairing;
+use rocket_basicauth::TokenStore;
+use shared_bin::api_tokens;
+use shared_bin::api_tokens::SqliteTokenStore;
+use shared_bin::backup::BackupOpts;
 use shared_bin::catchers::default_catchers;
+use shared_bin::control_api;
+use shared_bin::escalation;
 use shared_bin::fairings;
+use shared_bin::log_filter;
 use shared_bin::logger;
 use shared_bin::logger::LevelFilter;
+use shared_bin::margin_health::MarginHealthOpts;
+use shared_bin::notifications;
+use shared_bin::notifications::NotificationOpts;
+use shared_bin::pnl_history;
+use shared_bin::pnl_history::PnlHistoryOpts;
+use shared_bin::price_history;
+use shared_bin::price_history::PriceHistoryOpts;
+use shared_bin::tls::TlsOpts;
 use std::env;
+use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::net::TcpListener;
 use tokio_tasks::Tasks;
 use xtra::Actor;
+use xtra_libp2p::multiaddress_ext::MultiaddrExt;
+use xtra_libp2p_directory::Announcement;
+use xtras::InstrumentExt;
 
+mod control_api;
 mod routes;
 
-pub const ANNOUNCEMENT_LOOKAHEAD: time::Duration = time::Duration::hours(24);
-
 const MAINNET_ELECTRUM: &str = "ssl://blockstream.info:700";
 const MAINNET_MAKER: &str = "mainnet.itchysats.network:10000";
 const MAINNET_MAKER_ID: &str = "7e35e34801e766a6a29ecb9e22810ea4e3476c2b37bf75882edf94a68b1d9607";
@@ -73,10 +100,58 @@ struct Opts {
     #[clap(long)]
     maker_peer_id: Option<PeerId>,
 
+    /// Additional libp2p addresses of the maker, e.g. `/dns4/maker.example.com/tcp/10001`.
+    ///
+    /// Can be specified multiple times. The maker's peer id is appended if it is missing. We
+    /// rotate between these and the addresses derived from `--maker` until we are connected.
+    #[clap(long = "maker-multiaddr")]
+    maker_multiaddrs: Vec<Multiaddr>,
+
+    /// Another maker to connect to in addition to the one configured above, as
+    /// `<maker-id>@<multiaddr>`, e.g. `<hex>@/dns4/maker.example.com/tcp/10001/p2p/<peer-id>`.
+    ///
+    /// Can be specified multiple times. The best offers across all makers are shown and each CFD
+    /// is set up with the maker whose offer was taken. Additional makers are only reachable via
+    /// libp2p, hence the multiaddr must include the maker's peer id.
+    #[clap(long = "additional-maker", parse(try_from_str = parse_additional_maker))]
+    additional_makers: Vec<daemon::Maker>,
+
+    /// Discover makers in the directory at the given address, e.g.
+    /// `/dns4/directory.example.com/tcp/10500/p2p/<peer-id>`.
+    ///
+    /// Announcements in the directory are signed by the makers and verified before use.
+    #[clap(long)]
+    directory: Option<Multiaddr>,
+
+    /// Connect to the maker with the given peer id as announced in the directory, in addition to
+    /// the maker configured above.
+    ///
+    /// Can be specified multiple times. Use `--list-makers` to see which makers are listed.
+    #[clap(long, requires = "directory")]
+    directory_maker: Vec<PeerId>,
+
+    /// Print the makers listed in the directory and exit.
+    #[clap(long, requires = "directory")]
+    list_makers: bool,
+
+    /// Dial the maker through Tor using the given SOCKS5 proxy, e.g. `127.0.0.1:9050`.
+    ///
+    /// This is required for reaching the maker under an `/onion3` address. Only applies to
+    /// libp2p connections, the legacy connection to the maker is not affected.
+    #[clap(long)]
+    tor_socks5_proxy: Option<SocketAddr>,
+
     /// The IP address to listen on for the HTTP API.
     #[clap(long, default_value = "127.0.0.1:8000")]
     http_address: SocketAddr,
 
+    /// The IP address to listen on for the WebSocket control API, e.g. `127.0.0.1:8003`.
+    ///
+    /// The control API is meant for trading bots and disabled unless specified. Connections are
+    /// not encrypted, so it should only be exposed on localhost.
+    #[clap(long)]
+    control_api_address: Option<SocketAddr>,
+
     /// Where to permanently store data, defaults to the current working directory.
     #[clap(long)]
     data_dir: Option<PathBuf>,
@@ -85,6 +160,11 @@ struct Opts {
     #[clap(short, long)]
     json: bool,
 
+    /// Export traces to an OpenTelemetry collector at the given OTLP/gRPC endpoint, e.g.
+    /// `http://localhost:4317`.
+    #[clap(long)]
+    otlp_endpoint: Option<String>,
+
     /// Configure the log level, e.g.: one of Error, Warn, Info, Debug, Trace
     #[clap(short, long, default_value = "Debug")]
     log_level: LevelFilter,
@@ -95,6 +175,28 @@ struct Opts {
     #[clap(long)]
     password: Option<rocket_basicauth::Password>,
 
+    #[clap(flatten)]
+    tls: TlsOpts,
+
+    #[clap(flatten)]
+    notifications: NotificationOpts,
+
+    #[clap(flatten)]
+    price_history: PriceHistoryOpts,
+
+    #[clap(flatten)]
+    pnl_history: PnlHistoryOpts,
+
+    #[clap(flatten)]
+    margin_health: MarginHealthOpts,
+
+    #[clap(flatten)]
+    backup: BackupOpts,
+
+    /// Propose to settle CFDs as soon as they cross the liquidation alert threshold.
+    #[clap(long)]
+    auto_close_near_liquidation: bool,
+
     #[clap(subcommand)]
     network: Option<Network>,
 
@@ -106,7 +208,7 @@ impl Opts {
     fn network(&self) -> Network {
         self.network.clone().unwrap_or_else(|| Network::Mainnet {
             electrum: MAINNET_ELECTRUM.to_string(),
-            withdraw: None,
+            command: None,
         })
     }
 
@@ -154,6 +256,62 @@ fn parse_x25519_pubkey(s: &str) -> Result<x25519_dalek::PublicKey> {
     Ok(x25519_dalek::PublicKey::from(bytes))
 }
 
+fn parse_additional_maker(s: &str) -> Result<daemon::Maker> {
+    let (maker_id, multiaddr) = s
+        .split_once('@')
+        .context("Expected maker as <maker-id>@<multiaddr>")?;
+    let identity = Identity::new(parse_x25519_pubkey(maker_id)?);
+    let multiaddr = multiaddr.parse::<Multiaddr>()?;
+
+    anyhow::ensure!(
+        multiaddr.clone().extract_peer_id().is_some(),
+        "Address of maker {identity} does not end with its peer id"
+    );
+
+    Ok(daemon::Maker {
+        identity,
+        multiaddrs: vec![multiaddr],
+    })
+}
+
+/// The makers with the given peer ids as announced in the directory.
+fn directory_makers(
+    announcements: Vec<Announcement>,
+    peer_ids: &[PeerId],
+) -> Result<Vec<daemon::Maker>> {
+    peer_ids
+        .iter()
+        .map(|peer_id| {
+            let announcement = announcements
+                .iter()
+                .find(|announcement| announcement.peer_id.inner() == *peer_id)
+                .with_context(|| format!("Maker {peer_id} is not listed in the directory"))?;
+
+            daemon::Maker::try_from(announcement.clone())
+        })
+        .collect()
+}
+
+fn print_announcement(announcement: &Announcement) {
+    println!("peer id:       {}", announcement.peer_id);
+    println!("maker id:      {}", announcement.identity);
+    println!(
+        "addresses:     {}",
+        itertools::join(announcement.multiaddrs.iter(), ", ")
+    );
+    println!("trading pairs: {:?}", announcement.trading_pairs);
+    match announcement.fees {
+        Some(fees) => println!(
+            "fees:          opening fee {}, funding rate long {}, funding rate short {}",
+            fees.opening_fee.to_inner(),
+            fees.funding_rate_long,
+            fees.funding_rate_short
+        ),
+        None => println!("fees:          no offers"),
+    }
+    println!();
+}
+
 fn parse_umbrel_seed(s: &str) -> Result<[u8; 32]> {
     let mut bytes = [0u8; 32];
     hex::decode_to_slice(s, &mut bytes)?;
@@ -169,7 +327,7 @@ enum Network {
         electrum: String,
 
         #[clap(subcommand)]
-        withdraw: Option<Withdraw>,
+        command: Option<Command>,
     },
     /// Run on testnet
     Testnet {
@@ -178,7 +336,7 @@ enum Network {
         electrum: String,
 
         #[clap(subcommand)]
-        withdraw: Option<Withdraw>,
+        command: Option<Command>,
     },
     /// Run on signet
     Signet {
@@ -187,12 +345,12 @@ enum Network {
         electrum: String,
 
         #[clap(subcommand)]
-        withdraw: Option<Withdraw>,
+        command: Option<Command>,
     },
 }
 
 #[derive(Subcommand, Clone)]
-enum Withdraw {
+enum Command {
     Withdraw {
         /// Optionally specify the amount of Bitcoin to be withdrawn. If not specified the wallet
         /// will be drained. Amount is to be specified with denomination, e.g. "0.1 BTC"
@@ -206,6 +364,18 @@ enum Withdraw {
         #[clap(long)]
         address: Address,
     },
+    /// Replace the database with a backup taken by this daemon.
+    ///
+    /// The backup is only restored if it belongs to the identity derived from the seed and is not
+    /// corrupted. The database it replaces is kept next to it.
+    Restore {
+        /// Path to the backup to restore.
+        #[clap(long)]
+        backup: PathBuf,
+    },
+    /// Check the database for corruption and replay the event log of every CFD to find
+    /// inconsistencies.
+    CheckIntegrity,
 }
 
 impl Network {
@@ -242,11 +412,11 @@ impl Network {
         }
     }
 
-    fn withdraw(&self) -> &Option<Withdraw> {
+    fn command(&self) -> &Option<Command> {
         match self {
-            Network::Mainnet { withdraw, .. } => withdraw,
-            Network::Testnet { withdraw, .. } => withdraw,
-            Network::Signet { withdraw, .. } => withdraw,
+            Network::Mainnet { command, .. } => command,
+            Network::Testnet { command, .. } => command,
+            Network::Signet { command, .. } => command,
         }
     }
 }
@@ -258,7 +428,13 @@ async fn main() -> Result<()> {
     let network = opts.network();
     let (maker_url, maker_id, maker_peer_id) = opts.maker()?;
 
-    logger::init(opts.log_level, opts.json).context("initialize logger")?;
+    let log_filter = logger::init(
+        opts.log_level,
+        opts.json,
+        opts.otlp_endpoint.as_deref(),
+        "taker",
+    )
+    .context("initialize logger")?;
     tracing::info!("Running version: {}", daemon::version::version());
     let settlement_interval_hours = SETTLEMENT_INTERVAL.whole_hours();
 
@@ -266,6 +442,21 @@ async fn main() -> Result<()> {
         "CFDs created with this release will settle after {settlement_interval_hours} hours"
     );
 
+    let announcements = match opts.directory.clone() {
+        Some(directory) => {
+            daemon::directory::fetch_announcements(directory, opts.tor_socks5_proxy).await?
+        }
+        None => Vec::new(),
+    };
+
+    if opts.list_makers {
+        for announcement in announcements.iter() {
+            print_announcement(announcement);
+        }
+
+        return Ok(());
+    }
+
     let data_dir = opts
         .data_dir
         .clone()
@@ -297,6 +488,21 @@ async fn main() -> Result<()> {
         }
     };
 
+    let db_path = data_dir.join("taker.sqlite");
+    let identity = Identity::new(identities.identity_pk);
+
+    match network.command() {
+        Some(Command::Restore {
+            backup: backup_path,
+        }) => {
+            return backup::restore(backup_path, &db_path, identity).await;
+        }
+        Some(Command::CheckIntegrity) => {
+            return shared_bin::backup::check_integrity(db_path).await;
+        }
+        Some(Command::Withdraw { .. }) | None => {}
+    }
+
     let mut tasks = Tasks::default();
 
     let mut wallet_dir = data_dir.clone();
@@ -310,11 +516,11 @@ async fn main() -> Result<()> {
 
     let wallet = wallet.create(None).spawn(&mut tasks);
 
-    if let Some(Withdraw::Withdraw {
+    if let Some(Command::Withdraw {
         amount,
         address,
         fee,
-    }) = network.withdraw()
+    }) = network.command()
     {
         wallet
             .send(wallet::Withdraw {
@@ -334,8 +540,18 @@ async fn main() -> Result<()> {
         .merge(("address", opts.http_address.ip()))
         .merge(("port", opts.http_address.port()))
         .merge(("cli_colors", false));
+    let figment = match opts.tls.config(&data_dir, opts.http_address)? {
+        Some(tls) => figment.merge(("tls", tls)),
+        None => figment,
+    };
+
+    let db = sqlite_db::connect(db_path).await?;
+    backup::record_identity(&db, identity).await?;
 
-    let db = sqlite_db::connect(data_dir.join("taker.sqlite")).await?;
+    let backup_actor = opts
+        .backup
+        .actor(&data_dir, "taker")
+        .map(|actor| actor.create(None).spawn(&mut tasks));
 
     // Create actors
 
@@ -343,14 +559,35 @@ async fn main() -> Result<()> {
 
     let possible_addresses = resolve_maker_addresses(maker_url.as_str()).await?;
 
-    // Assume that the first resolved ipv4 address is good enough for libp2p.
-    let first_maker_address = possible_addresses
-        .iter()
-        .find(|x| x.is_ipv4())
-        .context("Could not resolve maker URL")?;
+    let maker_multiaddrs = maker_multiaddrs(
+        maker_url.as_str(),
+        &possible_addresses,
+        opts.maker_multiaddrs,
+        maker_peer_id,
+    )?;
+    let makers = std::iter::once(daemon::Maker {
+        identity: maker_identity,
+        multiaddrs: maker_multiaddrs,
+    })
+    .chain(opts.additional_makers)
+    .chain(directory_makers(announcements, &opts.directory_maker)?)
+    .collect::<Vec<_>>();
 
-    let maker_libp2p_address = libp2p_socket_from_legacy_networking(first_maker_address);
-    let maker_multiaddr = create_connect_tcp_multiaddr(&maker_libp2p_address, maker_peer_id)?;
+    for maker in makers.iter() {
+        if opts.tor_socks5_proxy.is_none()
+            && maker.multiaddrs.iter().any(|address| address.is_onion())
+        {
+            bail!(
+                "Dialing the onion service of maker {} requires --tor-socks5-proxy",
+                maker.identity
+            );
+        }
+        tracing::info!(
+            "Connecting to maker {} via [{}]",
+            maker.identity,
+            itertools::join(maker.multiaddrs.iter(), ",")
+        );
+    }
 
     let identity_info = IdentityInfo {
         taker_id: hex::encode(identities.identity_pk.to_bytes()),
@@ -362,6 +599,12 @@ async fn main() -> Result<()> {
         Err(_) => Environment::Binary,
     };
 
+    let notifier_actor = opts
+        .notifications
+        .notifier(db.clone())?
+        .create(None)
+        .spawn(&mut tasks);
+
     let price_feed_network = network.price_feed_network();
     let taker = TakerActorSystem::new(
         db.clone(),
@@ -376,11 +619,14 @@ async fn main() -> Result<()> {
             }
         },
         move || xtra_bitmex_price_feed::Actor::new(price_feed_network),
+        Some(escalation::Actor.create(None).spawn(&mut tasks).into()),
         N_PAYOUTS,
         Duration::from_secs(10),
         projection_actor.clone(),
-        Identity::new(maker_id),
-        maker_multiaddr,
+        notifier_actor.clone(),
+        backup_actor,
+        makers,
+        opts.tor_socks5_proxy,
         environment,
     )?;
 
@@ -389,7 +635,33 @@ async fn main() -> Result<()> {
         bitcoin_network,
         taker.price_feed_actor.clone().into(),
     );
-    tasks.add(projection_context.run(proj_actor));
+    tasks.add(projection_context.instrumented().run(proj_actor));
+
+    let (margin_health_actor, margin_health_feed) = opts.margin_health.monitor(
+        db.clone(),
+        &projection_feeds,
+        taker.price_feed_actor.clone().into(),
+        notifier_actor.into(),
+        opts.auto_close_near_liquidation
+            .then(|| taker.cfd_actor.clone().into()),
+    );
+    let _margin_health_actor = margin_health_actor.create(None).spawn(&mut tasks);
+
+    let _pnl_history_actor = opts
+        .pnl_history
+        .recorder(db.clone(), &projection_feeds)
+        .create(None)
+        .spawn(&mut tasks);
+
+    let _price_history_actor = opts
+        .price_history
+        .recorder(
+            db.clone(),
+            taker.price_feed_actor.clone().into(),
+            price_feed_network,
+        )
+        .create(None)
+        .spawn(&mut tasks);
 
     tasks.add(connect(
         taker.maker_online_status_feed_receiver.clone(),
@@ -398,15 +670,43 @@ async fn main() -> Result<()> {
         possible_addresses,
     ));
 
+    let taker = Arc::new(taker);
+    let token_store = Arc::new(SqliteTokenStore::new(db.clone())) as Arc<dyn TokenStore>;
+
+    if let Some(control_api_address) = opts.control_api_address {
+        let listener = TcpListener::bind(control_api_address)
+            .await
+            .with_context(|| format!("Failed to bind control API to {control_api_address}"))?;
+        tracing::info!("Control API listening on ws://{control_api_address}");
+
+        tasks.add(control_api::serve(
+            listener,
+            TakerCommands(taker.clone()),
+            projection_feeds.cfd_feed.clone(),
+            rocket_basicauth::Credentials::new(
+                auth_username,
+                web_password.clone(),
+                token_store.clone(),
+            ),
+        ));
+    }
+
     let mission_success = rocket::custom(figment)
         .manage(projection_feeds)
+        .manage(margin_health_feed)
         .manage(wallet_feed_receiver)
         .manage(identity_info)
         .manage(bitcoin_network)
         .manage(taker.maker_online_status_feed_receiver.clone())
+        .manage(taker.maker_compatibility_feed_receiver.clone())
+        .manage(taker.maker_address_stats_feed_receiver.clone())
+        .manage(taker.makers_online_status_feed_receiver.clone())
         .manage(taker)
         .manage(auth_username)
         .manage(web_password)
+        .manage(db.clone())
+        .manage(log_filter)
+        .manage(token_store)
         .mount(
             "/api",
             rocket::routes![
@@ -420,6 +720,12 @@ async fn main() -> Result<()> {
                 routes::get_version,
             ],
         )
+        .mount("/api", api_tokens::routes())
+        .mount("/api", log_filter::routes())
+        .mount("/api", notifications::routes())
+        .mount("/api", pnl_history::routes())
+        .mount("/api", price_history::routes())
+        .mount("/api/v1", routes::v1::routes())
         .register("/api", default_catchers())
         .mount("/", rocket::routes![routes::dist, routes::index])
         .register("/", default_catchers())
@@ -432,6 +738,7 @@ async fn main() -> Result<()> {
     tracing::trace!(?mission_success, "Rocket has landed");
 
     db.close().await;
+    logger::shutdown();
 
     Ok(())
 }
@@ -449,6 +756,77 @@ async fn resolve_maker_addresses(maker_addr: &str) -> Result<Vec<SocketAddr>> {
     Ok(possible_addresses)
 }
 
+/// Derive the libp2p addresses of the maker from its legacy address.
+///
+/// If the maker is specified by hostname, its `/dns` address comes first so that a change of the
+/// maker's IP is picked up on the next dialing attempt. The addresses the hostname resolved to at
+/// startup and any explicitly configured addresses serve as fallback.
+fn maker_multiaddrs(
+    maker_url: &str,
+    resolved_addresses: &[SocketAddr],
+    configured_multiaddrs: Vec<Multiaddr>,
+    maker_peer_id: PeerId,
+) -> Result<Vec<Multiaddr>> {
+    let dns_multiaddr = match dns_host_and_port(maker_url)? {
+        Some((host, port)) => Some(create_connect_dns_multiaddr(
+            host,
+            libp2p_port_from_legacy_networking(port),
+            maker_peer_id,
+        )?),
+        None => None,
+    };
+
+    let resolved_multiaddrs = resolved_addresses
+        .iter()
+        .map(|address| {
+            create_connect_tcp_multiaddr(
+                &libp2p_socket_from_legacy_networking(address),
+                maker_peer_id,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let configured_multiaddrs = configured_multiaddrs
+        .into_iter()
+        .map(|address| with_peer_id(address, maker_peer_id))
+        .collect::<Result<Vec<_>>>()?;
+
+    let multiaddrs = dns_multiaddr
+        .into_iter()
+        .chain(resolved_multiaddrs)
+        .chain(configured_multiaddrs)
+        .unique()
+        .collect::<Vec<_>>();
+
+    anyhow::ensure!(!multiaddrs.is_empty(), "Could not resolve maker URL");
+
+    Ok(multiaddrs)
+}
+
+/// Split a maker URL into host and port if the host is a DNS name.
+///
+/// IP addresses, including bracketed IPv6 addresses, are not split since they are covered by the
+/// resolved addresses of the maker.
+fn dns_host_and_port(maker_url: &str) -> Result<Option<(&str, u16)>> {
+    if maker_url.parse::<SocketAddr>().is_ok() {
+        return Ok(None);
+    }
+
+    match maker_url.rsplit_once(':') {
+        Some((host, port))
+            if host.parse::<IpAddr>().is_err()
+                && !host.contains(|c: char| matches!(c, ':' | '[' | ']')) =>
+        {
+            let port = port
+                .parse::<u16>()
+                .with_context(|| format!("Invalid port in maker URL {maker_url}"))?;
+
+            Ok(Some((host, port)))
+        }
+        _ => Ok(None),
+    }
+}
+
 /// Attach this fairing to enable loading the UI in the system default browser
 pub fn ui_browser_launch() -> impl Fairing {
     AdHoc::on_liftoff("ui browser launch", move |rocket| {
@@ -464,8 +842,14 @@ pub fn ui_browser_launch() -> impl Fairing {
                 }
             };
 
+            let scheme = if rocket.config().tls_enabled() {
+                "https"
+            } else {
+                "http"
+            };
             let http_endpoint = format!(
-                "http://{}:{}@{}:{}",
+                "{}://{}:{}@{}:{}",
+                scheme,
                 username,
                 password,
                 rocket.config().address,
@@ -486,3 +870,20 @@ pub fn ui_browser_launch() -> impl Fairing {
         })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_dns_names_are_split_into_host_and_port() {
+        assert_eq!(
+            dns_host_and_port("maker.example.com:9999").unwrap(),
+            Some(("maker.example.com", 9999))
+        );
+        assert_eq!(dns_host_and_port("127.0.0.1:9999").unwrap(), None);
+        assert_eq!(dns_host_and_port("[::1]:9999").unwrap(), None);
+        assert_eq!(dns_host_and_port("[2001:db8::1]:9999").unwrap(), None);
+        assert!(dns_host_and_port("maker.example.com:port").is_err());
+    }
+}
@@ -4,9 +4,12 @@ use daemon::bdk::bitcoin::Network;
 use daemon::bdk::blockchain::ElectrumBlockchain;
 use daemon::bdk::sled;
 use daemon::connection::ConnectionStatus;
+use daemon::identify::Compatibility;
+use daemon::margin_health::MarginHealth;
 use daemon::oracle;
 use daemon::projection;
 use daemon::projection::CfdAction;
+use daemon::projection::CfdFeedCursor;
 use daemon::projection::Feeds;
 use daemon::wallet;
 use daemon::TakerActorSystem;
@@ -26,18 +29,26 @@ use rocket::response::Responder;
 use rocket::serde::json::Json;
 use rocket::serde::uuid::Uuid;
 use rocket::State;
-use rocket_basicauth::Authenticated;
+use rocket_basicauth::scope;
+use rocket_basicauth::Authorized;
 use rust_embed::RustEmbed;
 use rust_embed_rocket::EmbeddedFileExt;
 use serde::Deserialize;
 use serde::Serialize;
+use shared_bin::cfd_events;
+use shared_bin::LastEventId;
 use shared_bin::ToSseEvent;
 use std::borrow::Cow;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::select;
 use tokio::sync::watch;
+use utoipa::ToSchema;
+use xtra_libp2p::dialer::AddressStats;
 
-type Taker = TakerActorSystem<
+pub mod v1;
+
+pub(crate) type Taker = TakerActorSystem<
     oracle::Actor,
     wallet::Actor<ElectrumBlockchain, sled::Tree>,
     xtra_bitmex_price_feed::Actor,
@@ -58,15 +69,23 @@ pub async fn feed(
     rx: &State<Feeds>,
     rx_wallet: &State<watch::Receiver<Option<WalletInfo>>>,
     rx_maker_status: &State<watch::Receiver<ConnectionStatus>>,
+    rx_maker_compatibility: &State<watch::Receiver<Option<Compatibility>>>,
+    rx_maker_address_stats: &State<watch::Receiver<Vec<AddressStats>>>,
+    rx_margin_health: &State<watch::Receiver<Vec<MarginHealth>>>,
     identity_info: &State<IdentityInfo>,
-    _auth: Authenticated,
+    last_event_id: LastEventId,
+    _auth: Authorized<scope::Read>,
 ) -> EventStream![] {
     let rx = rx.inner();
-    let mut rx_cfds = rx.cfds.clone();
+    let mut rx_cfd_feed = rx.cfd_feed.clone();
+    let mut cfd_feed_cursor = CfdFeedCursor::resume(last_event_id.0);
     let mut rx_offers = rx.offers.clone();
     let mut rx_quote = rx.quote.clone();
     let mut rx_wallet = rx_wallet.inner().clone();
     let mut rx_maker_status = rx_maker_status.inner().clone();
+    let mut rx_maker_compatibility = rx_maker_compatibility.inner().clone();
+    let mut rx_maker_address_stats = rx_maker_address_stats.inner().clone();
+    let mut rx_margin_health = rx_margin_health.inner().clone();
     let identity = identity_info.inner().clone();
     let mut heartbeat =
         tokio::time::interval(std::time::Duration::from_secs(HEARTBEAT_INTERVAL_SECS));
@@ -76,7 +95,11 @@ pub async fn feed(
         yield wallet_info.to_sse_event();
 
         let maker_status = rx_maker_status.borrow().clone();
-        yield maker_status.to_sse_event();
+        let maker_address_stats = rx_maker_address_stats.borrow().clone();
+        yield (maker_status, maker_address_stats).to_sse_event();
+
+        let maker_compatibility = *rx_maker_compatibility.borrow();
+        yield maker_compatibility.to_sse_event();
 
         yield Event::json(&identity).event("identity");
 
@@ -87,11 +110,14 @@ pub async fn feed(
         let quote = rx_quote.borrow().clone();
         yield quote.to_sse_event();
 
-        let cfds = rx_cfds.borrow().clone();
-        if let Some(cfds) = cfds {
-            yield cfds.to_sse_event()
+        let cfd_events = cfd_events(rx_cfd_feed.borrow().as_ref(), &mut cfd_feed_cursor);
+        for event in cfd_events {
+            yield event;
         }
 
+        let margin_health = rx_margin_health.borrow().clone();
+        yield margin_health.to_sse_event();
+
         loop{
             select! {
                 Ok(()) = rx_wallet.changed() => {
@@ -100,23 +126,37 @@ pub async fn feed(
                 },
                 Ok(()) = rx_maker_status.changed() => {
                     let maker_status = rx_maker_status.borrow().clone();
-                    yield maker_status.to_sse_event();
+                    let maker_address_stats = rx_maker_address_stats.borrow().clone();
+                    yield (maker_status, maker_address_stats).to_sse_event();
+                },
+                Ok(()) = rx_maker_address_stats.changed() => {
+                    let maker_status = rx_maker_status.borrow().clone();
+                    let maker_address_stats = rx_maker_address_stats.borrow().clone();
+                    yield (maker_status, maker_address_stats).to_sse_event();
+                },
+                Ok(()) = rx_maker_compatibility.changed() => {
+                    let maker_compatibility = *rx_maker_compatibility.borrow();
+                    yield maker_compatibility.to_sse_event();
                 },
                 Ok(()) = rx_offers.changed() => {
                     let offers = rx_offers.borrow().clone();
                     yield Event::json(&offers.long).event("long_offer");
                     yield Event::json(&offers.short).event("short_offer");
                 }
-                Ok(()) = rx_cfds.changed() => {
-                    let cfds = rx_cfds.borrow().clone();
-                    if let Some(cfds) = cfds {
-                        yield cfds.to_sse_event()
+                Ok(()) = rx_cfd_feed.changed() => {
+                    let cfd_events = cfd_events(rx_cfd_feed.borrow().as_ref(), &mut cfd_feed_cursor);
+                    for event in cfd_events {
+                        yield event;
                     }
                 }
                 Ok(()) = rx_quote.changed() => {
                     let quote = rx_quote.borrow().clone();
                     yield quote.to_sse_event();
                 }
+                Ok(()) = rx_margin_health.changed() => {
+                    let margin_health = rx_margin_health.borrow().clone();
+                    yield margin_health.to_sse_event();
+                }
                 _ = heartbeat.tick() => {
                     yield Event::json(&Heartbeat::new()).event("heartbeat")
                 }
@@ -140,7 +180,7 @@ impl Heartbeat {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CfdOrderRequest {
     pub order_id: OrderId,
     pub quantity: Usd,
@@ -150,8 +190,15 @@ pub struct CfdOrderRequest {
 #[rocket::post("/cfd/order", data = "<cfd_order_request>")]
 pub async fn post_order_request(
     cfd_order_request: Json<CfdOrderRequest>,
-    taker: &State<Taker>,
-    _auth: Authenticated,
+    taker: &State<Arc<Taker>>,
+    _auth: Authorized<scope::Trade>,
+) -> Result<(), HttpApiProblem> {
+    take_offer(taker, &cfd_order_request).await
+}
+
+async fn take_offer(
+    taker: &Taker,
+    cfd_order_request: &CfdOrderRequest,
 ) -> Result<(), HttpApiProblem> {
     taker
         .take_offer(
@@ -173,14 +220,22 @@ pub async fn post_order_request(
 pub async fn post_cfd_action(
     id: Uuid,
     action: String,
-    taker: &State<Taker>,
-    _auth: Authenticated,
+    taker: &State<Arc<Taker>>,
+    _auth: Authorized<scope::Trade>,
 ) -> Result<(), HttpApiProblem> {
     let id = OrderId::from(id);
     let action = action.parse().map_err(|_| {
         HttpApiProblem::new(StatusCode::BAD_REQUEST).detail(format!("Invalid action: {}", action))
     })?;
 
+    execute_cfd_action(taker, id, action).await
+}
+
+async fn execute_cfd_action(
+    taker: &Taker,
+    id: OrderId,
+    action: CfdAction,
+) -> Result<(), HttpApiProblem> {
     let result = match action {
         CfdAction::AcceptOrder
         | CfdAction::RejectOrder
@@ -233,31 +288,44 @@ pub struct MarginResponse {
 struct Asset;
 
 #[rocket::get("/assets/<file..>")]
-pub fn dist<'r>(file: PathBuf, _auth: Authenticated) -> impl Responder<'r, 'static> {
+pub fn dist<'r>(file: PathBuf, _auth: Authorized<scope::Read>) -> impl Responder<'r, 'static> {
     let filename = format!("assets/{}", file.display());
     Asset::get(&filename).into_response(file)
 }
 
 #[rocket::get("/<_paths..>", format = "text/html")]
-pub fn index<'r>(_paths: PathBuf, _auth: Authenticated) -> impl Responder<'r, 'static> {
+pub fn index<'r>(_paths: PathBuf, _auth: Authorized<scope::Read>) -> impl Responder<'r, 'static> {
     let asset = Asset::get("index.html").ok_or(Status::NotFound)?;
     Ok::<(ContentType, Cow<[u8]>), Status>((ContentType::HTML, asset.data))
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct WithdrawRequest {
+    #[schema(value_type = String)]
     address: bdk::bitcoin::Address,
+    /// The amount to withdraw in BTC, or zero to drain the wallet
     #[serde(with = "bdk::bitcoin::util::amount::serde::as_btc")]
+    #[schema(value_type = f64)]
     amount: Amount,
+    /// The fee rate in sats per vbyte
     fee: f32,
 }
 
 #[rocket::post("/withdraw", data = "<withdraw_request>")]
 pub async fn post_withdraw_request(
     withdraw_request: Json<WithdrawRequest>,
-    taker: &State<Taker>,
+    taker: &State<Arc<Taker>>,
     network: &State<Network>,
-    _auth: Authenticated,
+    _auth: Authorized<scope::Withdraw>,
+) -> Result<String, HttpApiProblem> {
+    withdraw(taker, &withdraw_request, *network.inner()).await
+}
+
+/// Withdraw from the wallet, returning a link to the withdrawal transaction.
+async fn withdraw(
+    taker: &Taker,
+    withdraw_request: &WithdrawRequest,
+    network: Network,
 ) -> Result<String, HttpApiProblem> {
     let amount =
         (withdraw_request.amount != bdk::bitcoin::Amount::ZERO).then(|| withdraw_request.amount);
@@ -275,11 +343,11 @@ pub async fn post_withdraw_request(
                 .detail(format!("{e:#}"))
         })?;
 
-    Ok(projection::to_mempool_url(txid, *network.inner()))
+    Ok(projection::to_mempool_url(txid, network))
 }
 
 #[rocket::get("/metrics")]
-pub async fn get_metrics<'r>(_auth: Authenticated) -> Result<String, HttpApiProblem> {
+pub async fn get_metrics<'r>(_auth: Authorized<scope::Read>) -> Result<String, HttpApiProblem> {
     let metrics = prometheus::TextEncoder::new()
         .encode_to_string(&prometheus::gather())
         .map_err(|e| {
@@ -293,9 +361,13 @@ pub async fn get_metrics<'r>(_auth: Authenticated) -> Result<String, HttpApiProb
 
 #[rocket::put("/sync")]
 pub async fn put_sync_wallet(
-    taker: &State<Taker>,
-    _auth: Authenticated,
+    taker: &State<Arc<Taker>>,
+    _auth: Authorized<scope::Trade>,
 ) -> Result<(), HttpApiProblem> {
+    sync_wallet(taker).await
+}
+
+async fn sync_wallet(taker: &Taker) -> Result<(), HttpApiProblem> {
     taker.sync_wallet().await.map_err(|e| {
         HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
             .title("Could not sync wallet")
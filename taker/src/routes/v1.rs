@@ -0,0 +1,407 @@
+//! Version 1 of the taker's HTTP API, mounted under `/api/v1`.
+//!
+//! The routes are resource-oriented and documented by an OpenAPI document served at
+//! `/api/v1/openapi.json`. The unversioned routes under `/api` remain available for the frontend.
+
+use super::execute_cfd_action;
+use super::sync_wallet;
+use super::take_offer;
+use super::withdraw;
+use super::CfdOrderRequest;
+use super::Taker;
+use super::WithdrawRequest;
+use daemon::bdk::bitcoin::Network;
+use daemon::connection::ConnectionStatus;
+use daemon::projection::Cfd;
+use daemon::projection::CfdAction;
+use daemon::projection::CfdDetails;
+use daemon::projection::CfdOrder;
+use daemon::projection::CfdState;
+use daemon::projection::Feeds;
+use daemon::projection::LeverageDetails;
+use daemon::projection::MakerOffers;
+use daemon::projection::TxLabel;
+use daemon::projection::TxUrl;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use model::libp2p::PeerId;
+use model::ContractType;
+use model::Identity;
+use model::Leverage;
+use model::OrderId;
+use model::Position;
+use model::Price;
+use model::Role;
+use model::Timestamp;
+use model::TradingPair;
+use model::Usd;
+use rocket::serde::json::Json;
+use rocket::serde::uuid::Uuid;
+use rocket::State;
+use rocket_basicauth::scope;
+use rocket_basicauth::Authorized;
+use serde::Deserialize;
+use serde::Serialize;
+use shared_bin::openapi;
+use shared_bin::openapi::BasicAuth;
+use shared_bin::WalletInfo;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::watch;
+use utoipa::OpenApi;
+use utoipa::ToSchema;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ItchySats taker API"),
+    paths(
+        get_cfds,
+        get_cfd,
+        post_cfd,
+        post_cfd_action,
+        get_offers,
+        get_makers,
+        get_wallet,
+        post_wallet_sync,
+        post_withdrawal
+    ),
+    components(schemas(
+        Cfd,
+        CfdAction,
+        CfdActionRequest,
+        CfdDetails,
+        CfdOrder,
+        CfdOrderRequest,
+        CfdState,
+        ContractType,
+        Identity,
+        Leverage,
+        LeverageDetails,
+        MakerOffers,
+        MakerStatus,
+        OrderId,
+        Position,
+        Price,
+        Role,
+        Timestamp,
+        TradingPair,
+        TxLabel,
+        TxUrl,
+        Usd,
+        WalletInfo,
+        Withdrawal,
+        WithdrawRequest
+    )),
+    modifiers(&BasicAuth),
+    tags(
+        (name = "cfds", description = "CFDs of the taker and the actions that can be taken on them"),
+        (name = "offers", description = "The best offers across all makers"),
+        (name = "makers", description = "The makers the taker connects to"),
+        (name = "wallet", description = "The taker's on-chain wallet")
+    )
+)]
+struct ApiDoc;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        get_openapi,
+        get_cfds,
+        get_cfd,
+        post_cfd,
+        post_cfd_action,
+        get_offers,
+        get_makers,
+        get_wallet,
+        post_wallet_sync,
+        post_withdrawal
+    ]
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+pub struct CfdActionRequest {
+    pub action: CfdAction,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MakerStatus {
+    /// libp2p peer id of the maker
+    pub peer_id: String,
+    pub online: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Withdrawal {
+    /// Link to the withdrawal transaction on mempool.space
+    pub url: String,
+}
+
+#[rocket::get("/openapi.json")]
+fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    openapi::document::<ApiDoc>()
+}
+
+/// All CFDs, the most recently created one first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/cfds",
+    responses(
+        (status = 200, description = "All CFDs", body = [Cfd]),
+        (status = 503, description = "The CFDs are still being loaded from the database")
+    ),
+    security(("basic_auth" = [])),
+    tag = "cfds"
+)]
+#[rocket::get("/cfds")]
+async fn get_cfds(
+    rx: &State<Feeds>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<Vec<Cfd>>, HttpApiProblem> {
+    let cfds = cfds(rx.inner())?;
+
+    Ok(Json(cfds))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cfds/{id}",
+    params(("id" = String, Path, description = "The order ID of the CFD")),
+    responses(
+        (status = 200, description = "The CFD", body = Cfd),
+        (status = 404, description = "There is no CFD with this order ID"),
+        (status = 503, description = "The CFDs are still being loaded from the database")
+    ),
+    security(("basic_auth" = [])),
+    tag = "cfds"
+)]
+#[rocket::get("/cfds/<id>")]
+async fn get_cfd(
+    id: Uuid,
+    rx: &State<Feeds>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<Cfd>, HttpApiProblem> {
+    let id = OrderId::from(id);
+
+    let cfd = cfds(rx.inner())?
+        .into_iter()
+        .find(|cfd| cfd.order_id == id)
+        .ok_or_else(|| {
+            HttpApiProblem::new(StatusCode::NOT_FOUND)
+                .title("CFD not found")
+                .detail(format!("There is no CFD with order ID {id}"))
+        })?;
+
+    Ok(Json(cfd))
+}
+
+/// Open a CFD by taking one of the maker's offers.
+#[utoipa::path(
+    post,
+    path = "/api/v1/cfds",
+    request_body = CfdOrderRequest,
+    responses(
+        (status = 200, description = "The order was sent to the maker"),
+        (status = 500, description = "Taking the offer failed")
+    ),
+    security(("basic_auth" = [])),
+    tag = "cfds"
+)]
+#[rocket::post("/cfds", data = "<cfd_order_request>")]
+async fn post_cfd(
+    cfd_order_request: Json<CfdOrderRequest>,
+    taker: &State<Arc<Taker>>,
+    _auth: Authorized<scope::Trade>,
+) -> Result<(), HttpApiProblem> {
+    take_offer(taker, &cfd_order_request).await
+}
+
+/// Act on a CFD, e.g. propose to settle it.
+///
+/// The actions available on a CFD are listed in its `actions`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/cfds/{id}/actions",
+    params(("id" = String, Path, description = "The order ID of the CFD")),
+    request_body = CfdActionRequest,
+    responses(
+        (status = 200, description = "The action was executed"),
+        (status = 400, description = "The taker cannot take this action"),
+        (status = 500, description = "The action failed")
+    ),
+    security(("basic_auth" = [])),
+    tag = "cfds"
+)]
+#[rocket::post("/cfds/<id>/actions", data = "<request>")]
+async fn post_cfd_action(
+    id: Uuid,
+    request: Json<CfdActionRequest>,
+    taker: &State<Arc<Taker>>,
+    _auth: Authorized<scope::Trade>,
+) -> Result<(), HttpApiProblem> {
+    execute_cfd_action(taker, OrderId::from(id), request.action).await
+}
+
+/// The best offers currently published across all makers.
+#[utoipa::path(
+    get,
+    path = "/api/v1/offers",
+    responses((status = 200, description = "The current offers", body = MakerOffers)),
+    security(("basic_auth" = [])),
+    tag = "offers"
+)]
+#[rocket::get("/offers")]
+async fn get_offers(rx: &State<Feeds>, _auth: Authorized<scope::Read>) -> Json<MakerOffers> {
+    let offers = rx.offers.borrow().clone();
+
+    Json(offers)
+}
+
+/// The online status of each maker.
+#[utoipa::path(
+    get,
+    path = "/api/v1/makers",
+    responses((status = 200, description = "The makers", body = [MakerStatus])),
+    security(("basic_auth" = [])),
+    tag = "makers"
+)]
+#[rocket::get("/makers")]
+async fn get_makers(
+    rx_makers: &State<watch::Receiver<HashMap<PeerId, ConnectionStatus>>>,
+    _auth: Authorized<scope::Read>,
+) -> Json<Vec<MakerStatus>> {
+    let mut makers = rx_makers
+        .borrow()
+        .iter()
+        .map(|(peer_id, status)| MakerStatus {
+            peer_id: peer_id.to_string(),
+            online: matches!(status, ConnectionStatus::Online),
+        })
+        .collect::<Vec<_>>();
+    makers.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+
+    Json(makers)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/wallet",
+    responses(
+        (status = 200, description = "Balance and address of the wallet", body = WalletInfo),
+        (status = 503, description = "The wallet has not been synced yet")
+    ),
+    security(("basic_auth" = [])),
+    tag = "wallet"
+)]
+#[rocket::get("/wallet")]
+async fn get_wallet(
+    rx_wallet: &State<watch::Receiver<Option<model::WalletInfo>>>,
+    _auth: Authorized<scope::Read>,
+) -> Result<Json<WalletInfo>, HttpApiProblem> {
+    let wallet_info = rx_wallet.borrow().as_ref().map(WalletInfo::from);
+
+    let wallet_info = wallet_info.ok_or_else(|| {
+        HttpApiProblem::new(StatusCode::SERVICE_UNAVAILABLE)
+            .title("Wallet not yet available")
+            .detail("The wallet has not been synced yet. Please retry later.")
+    })?;
+
+    Ok(Json(wallet_info))
+}
+
+/// Sync the wallet with the blockchain.
+#[utoipa::path(
+    post,
+    path = "/api/v1/wallet/sync",
+    responses(
+        (status = 200, description = "The wallet was synced"),
+        (status = 500, description = "Syncing the wallet failed")
+    ),
+    security(("basic_auth" = [])),
+    tag = "wallet"
+)]
+#[rocket::post("/wallet/sync")]
+async fn post_wallet_sync(
+    taker: &State<Arc<Taker>>,
+    _auth: Authorized<scope::Trade>,
+) -> Result<(), HttpApiProblem> {
+    sync_wallet(taker).await
+}
+
+/// Withdraw funds from the wallet to an external address.
+#[utoipa::path(
+    post,
+    path = "/api/v1/wallet/withdrawals",
+    request_body = WithdrawRequest,
+    responses(
+        (status = 200, description = "The withdrawal transaction was published", body = Withdrawal),
+        (status = 500, description = "The withdrawal failed")
+    ),
+    security(("basic_auth" = [])),
+    tag = "wallet"
+)]
+#[rocket::post("/wallet/withdrawals", data = "<withdraw_request>")]
+async fn post_withdrawal(
+    withdraw_request: Json<WithdrawRequest>,
+    taker: &State<Arc<Taker>>,
+    network: &State<Network>,
+    _auth: Authorized<scope::Withdraw>,
+) -> Result<Json<Withdrawal>, HttpApiProblem> {
+    let url = withdraw(taker, &withdraw_request, *network.inner()).await?;
+
+    Ok(Json(Withdrawal { url }))
+}
+
+fn cfds(rx: &Feeds) -> Result<Vec<Cfd>, HttpApiProblem> {
+    let cfds = rx.cfds.borrow().clone();
+
+    cfds.ok_or_else(|| {
+        HttpApiProblem::new(StatusCode::SERVICE_UNAVAILABLE)
+            .title("CFDs not yet available")
+            .detail("CFDs are still being loaded from the database. Please retry later.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Status;
+    use shared_bin::openapi::checks;
+
+    #[test]
+    fn openapi_document_builds() {
+        checks::assert_documents::<ApiDoc>(&["/api/v1/cfds", "/api/v1/makers"]);
+    }
+
+    #[tokio::test]
+    async fn serves_openapi_document_without_authentication() {
+        let client = client().await;
+
+        checks::assert_serves_document_without_authentication(&client).await;
+    }
+
+    #[tokio::test]
+    async fn routes_require_authentication() {
+        let client = client().await;
+
+        checks::assert_requires_authentication(&client, "/api/v1/offers").await;
+        checks::assert_requires_authentication(&client, "/api/v1/makers").await;
+    }
+
+    #[tokio::test]
+    async fn routes_respond_to_authenticated_requests() {
+        let client = client().await;
+
+        checks::assert_responds_to_authenticated_requests(&client).await;
+        assert_eq!(
+            checks::get_authenticated(&client, "/api/v1/makers").await,
+            Status::Ok
+        );
+    }
+
+    async fn client() -> rocket::local::asynchronous::Client {
+        checks::client(
+            checks::rocket(routes())
+                .manage(watch::channel(HashMap::<PeerId, ConnectionStatus>::new()).1),
+        )
+        .await
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use clap::Parser;
+use libp2p_tcp::TokioTcpConfig;
+use std::time::Duration;
+use tracing::Level;
+use xtra::spawn::TokioGlobalSpawnExt;
+use xtra::Actor as _;
+use xtra_libp2p::endpoint::Subscribers;
+use xtra_libp2p::libp2p::identity::Keypair;
+use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::listener;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p_directory::directory;
+use xtra_libp2p_directory::PROTOCOL_NAME;
+use xtras::supervisor;
+use xtras::supervisor::always_restart;
+
+// Serve a directory of maker announcements on TCP, e.g. as a local stand-in for tests
+
+#[derive(Parser)]
+struct Opts {
+    #[clap(long, default_value = "10500")]
+    port: u16,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let opts = Opts::parse();
+
+    tracing_subscriber::fmt()
+        .with_max_level(Level::DEBUG)
+        .init();
+
+    let id = Keypair::generate_ed25519();
+
+    let peer_id = id.public().to_peer_id();
+    let port = opts.port;
+
+    let multiaddr_str = format!("/ip4/127.0.0.1/tcp/{port}/p2p/{peer_id}");
+    tracing::info!("Publish to and query this directory by using the multiaddr: {multiaddr_str}");
+
+    let directory_addr = directory::Actor::new().create(None).spawn_global();
+
+    let endpoint_addr = Endpoint::new(
+        Box::new(TokioTcpConfig::new),
+        id,
+        Duration::from_secs(30),
+        [(PROTOCOL_NAME, directory_addr.into())],
+        Subscribers::default(),
+    )
+    .create(None)
+    .spawn_global();
+
+    let endpoint_listen = multiaddr_str.parse::<Multiaddr>()?;
+
+    let listener_constructor = move || {
+        let endpoint_listen = endpoint_listen.clone();
+        let endpoint_addr = endpoint_addr.clone();
+        listener::Actor::new(endpoint_addr, endpoint_listen)
+    };
+    let (supervisor, _listener_actor) =
+        supervisor::Actor::with_policy(listener_constructor, always_restart::<listener::Error>());
+    let _listener_supervisor = supervisor.create(None).spawn_global();
+
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
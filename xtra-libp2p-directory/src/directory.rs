@@ -0,0 +1,241 @@
+use crate::protocol;
+use crate::protocol::Request;
+use crate::protocol::Response;
+use crate::Announcement;
+use crate::SignedAnnouncement;
+use async_trait::async_trait;
+use model::Timestamp;
+use std::collections::HashMap;
+use xtra::spawn::TokioGlobalSpawnExt;
+use xtra::Actor as _;
+use xtra::Address;
+use xtra::Context;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::NewInboundSubstream;
+use xtra_productivity::xtra_productivity;
+use xtras::spawner;
+use xtras::spawner::SpawnFallible;
+use xtras::SendAsyncSafe;
+
+/// How far into the future the timestamp of an announcement may lie.
+///
+/// Tolerates small differences between the clocks of maker and directory.
+const MAX_CLOCK_SKEW_SECS: i64 = 5 * 60;
+
+/// The default upper bound for the number of makers listed at the same time.
+///
+/// Anyone can publish an announcement, hence the directory has to bound the memory it spends on
+/// them.
+pub const DEFAULT_MAX_ANNOUNCEMENTS: usize = 1000;
+
+/// An actor implementing the directory side of the protocol.
+///
+/// Keeps the latest valid announcement of every maker that published one and hands them out to
+/// anyone who asks. Announcements are dropped once they expire, so makers that stop re-publishing
+/// disappear from the directory.
+///
+/// Once [`Actor::with_max_announcements`] makers are listed, announcements of makers which are
+/// not listed yet are rejected until other announcements expire.
+///
+/// Note that the [`Announcement::identity`] is not bound to the libp2p key the announcement is
+/// signed with: a maker can claim any identity. Takers have to rely on the peer id instead.
+pub struct Actor {
+    announcements: HashMap<PeerId, (Announcement, SignedAnnouncement)>,
+    max_announcements: usize,
+    spawner: Address<spawner::Actor>,
+}
+
+impl Actor {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let spawner = spawner::Actor::new().create(None).spawn_global();
+
+        Self {
+            announcements: HashMap::default(),
+            max_announcements: DEFAULT_MAX_ANNOUNCEMENTS,
+            spawner,
+        }
+    }
+
+    /// Set the upper bound for the number of makers listed at the same time.
+    pub fn with_max_announcements(mut self, max_announcements: usize) -> Self {
+        self.max_announcements = max_announcements;
+        self
+    }
+
+    fn prune_expired(&mut self, now: Timestamp) {
+        self.announcements
+            .retain(|_, (announcement, _)| !announcement.is_expired(now));
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Private message to publish an announcement received from `peer`.
+struct Publish {
+    peer: PeerId,
+    signed: SignedAnnouncement,
+}
+
+/// Private message to get all announcements which have not yet expired.
+struct Query;
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, msg: Publish) -> Result<(), String> {
+        let Publish { peer, signed } = msg;
+
+        let announcement = signed.verify().map_err(|e| format!("{e:#}"))?;
+
+        if announcement.peer_id.inner() != peer {
+            return Err("Makers can only publish their own announcement".to_string());
+        }
+
+        let now = Timestamp::now();
+        if announcement.is_expired(now) {
+            return Err("Announcement has expired".to_string());
+        }
+        if announcement.timestamp.seconds() > now.seconds() + MAX_CLOCK_SKEW_SECS {
+            return Err("Announcement is from the future".to_string());
+        }
+
+        self.prune_expired(now);
+
+        match self.announcements.get(&peer) {
+            Some((current, _)) if current.timestamp >= announcement.timestamp => {
+                tracing::debug!(%peer, "Ignoring announcement which is not newer than current one");
+                return Ok(());
+            }
+            Some(_) => {}
+            None if self.announcements.len() >= self.max_announcements => {
+                return Err("Directory is full".to_string());
+            }
+            None => {}
+        }
+
+        tracing::debug!(%peer, multiaddrs = ?announcement.multiaddrs, "Listing maker");
+        self.announcements.insert(peer, (announcement, signed));
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, _: Query) -> Vec<SignedAnnouncement> {
+        self.prune_expired(Timestamp::now());
+
+        self.announcements
+            .values()
+            .map(|(_, signed)| signed.clone())
+            .collect()
+    }
+}
+
+#[xtra_productivity(message_impl = false)]
+impl Actor {
+    async fn handle(&mut self, msg: NewInboundSubstream, ctx: &mut Context<Self>) {
+        let NewInboundSubstream { peer, stream } = msg;
+        let this = ctx.address().expect("we are alive");
+
+        let task = async move {
+            protocol::listener(stream, |request| async move {
+                match request {
+                    Request::Publish(signed) => match this.send(Publish { peer, signed }).await {
+                        Ok(Ok(())) => Response::Published,
+                        Ok(Err(reason)) => {
+                            tracing::debug!(%peer, "Rejected announcement: {reason}");
+                            Response::Rejected(reason)
+                        }
+                        Err(_) => Response::Rejected("Directory unavailable".to_string()),
+                    },
+                    Request::Query => match this.send(Query).await {
+                        Ok(announcements) => Response::Announcements(announcements),
+                        Err(_) => Response::Announcements(Vec::new()),
+                    },
+                }
+            })
+            .await?;
+
+            anyhow::Ok(())
+        };
+
+        let err_handler = move |e| async move {
+            tracing::debug!(%peer, "Failed to handle directory request: {e:#}")
+        };
+
+        if let Err(e) = self
+            .spawner
+            .send_async_safe(SpawnFallible::new(task, err_handler))
+            .await
+        {
+            tracing::warn!("Failed to spawn task to handle directory request: {e:#}");
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::dummy_announcement;
+    use xtra_libp2p::libp2p::identity::Keypair;
+
+    #[tokio::test]
+    async fn rejects_new_makers_when_full() {
+        let directory = Actor::new()
+            .with_max_announcements(1)
+            .create(None)
+            .spawn_global();
+
+        let first = Keypair::generate_ed25519();
+        assert_eq!(publish(&directory, &first, Timestamp::now()).await, Ok(()));
+
+        let second = Keypair::generate_ed25519();
+        assert_eq!(
+            publish(&directory, &second, Timestamp::now()).await,
+            Err("Directory is full".to_string())
+        );
+
+        let later = Timestamp::new(Timestamp::now().seconds() + 1);
+        assert_eq!(publish(&directory, &first, later).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn expired_announcements_make_room_on_publish() {
+        let directory = Actor::new()
+            .with_max_announcements(1)
+            .create(None)
+            .spawn_global();
+
+        let ttl = crate::ANNOUNCEMENT_TTL.as_secs() as i64;
+        let almost_expired = Timestamp::new(Timestamp::now().seconds() - ttl + 2);
+        let first = Keypair::generate_ed25519();
+        assert_eq!(publish(&directory, &first, almost_expired).await, Ok(()));
+
+        tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+
+        let second = Keypair::generate_ed25519();
+        assert_eq!(publish(&directory, &second, Timestamp::now()).await, Ok(()));
+    }
+
+    async fn publish(
+        directory: &Address<Actor>,
+        keypair: &Keypair,
+        timestamp: Timestamp,
+    ) -> Result<(), String> {
+        let announcement = Announcement {
+            timestamp,
+            ..dummy_announcement(keypair)
+        };
+
+        directory
+            .send(Publish {
+                peer: keypair.public().to_peer_id(),
+                signed: SignedAnnouncement::new(&announcement, keypair).unwrap(),
+            })
+            .await
+            .unwrap()
+    }
+}
@@ -0,0 +1,305 @@
+use model::libp2p::PeerId;
+use model::FundingRate;
+use model::Identity;
+use model::MakerOffers;
+use model::OpeningFee;
+use model::Timestamp;
+use model::TradingPair;
+use serde::Deserialize;
+use serde::Serialize;
+use std::time::Duration;
+use xtra_libp2p::libp2p::identity::Keypair;
+use xtra_libp2p::libp2p::identity::PublicKey;
+use xtra_libp2p::libp2p::Multiaddr;
+
+pub mod directory;
+pub mod maker;
+mod protocol;
+pub mod taker;
+
+pub const PROTOCOL_NAME: &str = "/itchysats/directory/1.0.0";
+
+/// How long an announcement is listed after it was signed.
+///
+/// Makers re-publish their announcement well within this period.
+pub const ANNOUNCEMENT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// What a maker publishes about itself to a directory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Announcement {
+    pub peer_id: PeerId,
+    /// The identity of the maker for legacy networking, recorded with every CFD.
+    ///
+    /// Unlike the `peer_id`, the identity is not proven by the signature of the announcement; a
+    /// maker can claim any identity.
+    pub identity: Identity,
+    /// The addresses under which the maker can be reached, including its peer id.
+    pub multiaddrs: Vec<Multiaddr>,
+    pub trading_pairs: Vec<TradingPair>,
+    /// The fees of the maker's current offers, unless it has not published any yet.
+    pub fees: Option<FeeSchedule>,
+    /// When the maker signed the announcement.
+    pub timestamp: Timestamp,
+}
+
+/// The fees a maker charges for its offers.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub opening_fee: OpeningFee,
+    pub funding_rate_long: FundingRate,
+    pub funding_rate_short: FundingRate,
+}
+
+impl FeeSchedule {
+    /// The fee schedule of the given offers, if there is at least one order.
+    pub fn from_offers(offers: &MakerOffers) -> Option<Self> {
+        let order = offers.long.as_ref().or(offers.short.as_ref())?;
+
+        Some(Self {
+            opening_fee: order.opening_fee,
+            funding_rate_long: offers.funding_rate_long,
+            funding_rate_short: offers.funding_rate_short,
+        })
+    }
+}
+
+impl Announcement {
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.timestamp.seconds() + ANNOUNCEMENT_TTL.as_secs() as i64 <= now.seconds()
+    }
+}
+
+/// An [`Announcement`] signed with the libp2p identity of the maker.
+///
+/// The announcement is signed in its JSON encoding, which is kept as-is so that verification does
+/// not depend on encoding it again.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedAnnouncement {
+    payload: String,
+    /// The protobuf encoding of the maker's public key.
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl SignedAnnouncement {
+    pub fn new(announcement: &Announcement, keypair: &Keypair) -> anyhow::Result<Self> {
+        let public_key = keypair.public();
+        anyhow::ensure!(
+            public_key.to_peer_id() == announcement.peer_id.inner(),
+            "Announcement has to be signed by the announced peer"
+        );
+
+        let payload = serde_json::to_string(announcement)?;
+        let signature = keypair.sign(payload.as_bytes())?;
+
+        Ok(Self {
+            payload,
+            public_key: public_key.to_protobuf_encoding(),
+            signature,
+        })
+    }
+
+    /// Verify that the announcement was signed by the peer it announces.
+    pub fn verify(&self) -> Result<Announcement, VerificationError> {
+        let public_key = PublicKey::from_protobuf_encoding(&self.public_key)
+            .map_err(|_| VerificationError::InvalidPublicKey)?;
+
+        if !public_key.verify(self.payload.as_bytes(), &self.signature) {
+            return Err(VerificationError::InvalidSignature);
+        }
+
+        let announcement = serde_json::from_str::<Announcement>(&self.payload)?;
+
+        if announcement.peer_id.inner() != public_key.to_peer_id() {
+            return Err(VerificationError::PeerIdMismatch);
+        }
+
+        Ok(announcement)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VerificationError {
+    #[error("Invalid public key")]
+    InvalidPublicKey,
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Failed to decode announcement")]
+    Decode(#[from] serde_json::Error),
+    #[error("Announcement was not signed by the announced peer")]
+    PeerIdMismatch,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Future;
+    use xtra::spawn::TokioGlobalSpawnExt;
+    use xtra::Actor as _;
+    use xtra::Address;
+    use xtra::Context;
+    use xtra_libp2p::endpoint::Subscribers;
+    use xtra_libp2p::libp2p::multiaddr::Protocol;
+    use xtra_libp2p::libp2p::transport::MemoryTransport;
+    use xtra_libp2p::Connect;
+    use xtra_libp2p::Endpoint;
+    use xtra_libp2p::ListenOn;
+
+    #[test]
+    fn signed_announcement_verifies() {
+        let keypair = Keypair::generate_ed25519();
+        let announcement = dummy_announcement(&keypair);
+
+        let signed = SignedAnnouncement::new(&announcement, &keypair).unwrap();
+
+        assert_eq!(signed.verify().unwrap(), announcement);
+    }
+
+    #[test]
+    fn tampered_announcement_does_not_verify() {
+        let keypair = Keypair::generate_ed25519();
+        let mut signed = SignedAnnouncement::new(&dummy_announcement(&keypair), &keypair).unwrap();
+
+        signed.payload = signed.payload.replace("BtcUsd", "BtcEur");
+
+        assert!(matches!(
+            signed.verify(),
+            Err(VerificationError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn cannot_sign_announcement_of_other_peer() {
+        let keypair = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+
+        let result = SignedAnnouncement::new(&dummy_announcement(&other), &keypair);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn announcement_expires_after_ttl() {
+        let announcement = dummy_announcement(&Keypair::generate_ed25519());
+        let signed_at = announcement.timestamp.seconds();
+        let ttl = ANNOUNCEMENT_TTL.as_secs() as i64;
+
+        assert!(!announcement.is_expired(Timestamp::new(signed_at + ttl - 1)));
+        assert!(announcement.is_expired(Timestamp::new(signed_at + ttl)));
+    }
+
+    #[tokio::test]
+    async fn taker_fetches_announcement_published_by_maker() {
+        let _ = tracing_subscriber::fmt()
+            .with_env_filter("xtra_libp2p_directory=trace")
+            .with_test_writer()
+            .try_init();
+
+        let directory_keypair = Keypair::generate_ed25519();
+        let directory_peer_id = directory_keypair.public().to_peer_id();
+        let directory_endpoint = create_directory_endpoint(directory_keypair);
+        directory_endpoint
+            .send(ListenOn(Multiaddr::empty().with(Protocol::Memory(3000))))
+            .await
+            .unwrap();
+        let directory_addr = Multiaddr::empty()
+            .with(Protocol::Memory(3000))
+            .with(Protocol::P2p(directory_peer_id.into()));
+
+        let maker_keypair = Keypair::generate_ed25519();
+        let announcement = dummy_announcement(&maker_keypair);
+        let maker_endpoint = create_endpoint(maker_keypair.clone());
+        maker_endpoint
+            .send(Connect(directory_addr.clone()))
+            .await
+            .unwrap()
+            .unwrap();
+        maker::publish(
+            maker_endpoint,
+            directory_peer_id,
+            SignedAnnouncement::new(&announcement, &maker_keypair).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let taker_endpoint = create_endpoint(Keypair::generate_ed25519());
+        taker_endpoint
+            .send(Connect(directory_addr))
+            .await
+            .unwrap()
+            .unwrap();
+        let announcements = retry_until_some(|| {
+            let taker_endpoint = taker_endpoint.clone();
+            async move {
+                let announcements = taker::fetch(taker_endpoint, directory_peer_id)
+                    .await
+                    .unwrap();
+                (!announcements.is_empty()).then(|| announcements)
+            }
+        })
+        .await;
+
+        assert_eq!(announcements, vec![announcement]);
+    }
+
+    fn create_directory_endpoint(keypair: Keypair) -> Address<Endpoint> {
+        let directory = directory::Actor::new().create(None).spawn_global();
+
+        spawn_endpoint(Endpoint::new(
+            Box::new(MemoryTransport::default),
+            keypair,
+            Duration::from_secs(10),
+            [(PROTOCOL_NAME, directory.into())],
+            Subscribers::default(),
+        ))
+    }
+
+    fn create_endpoint(keypair: Keypair) -> Address<Endpoint> {
+        spawn_endpoint(Endpoint::new(
+            Box::new(MemoryTransport::default),
+            keypair,
+            Duration::from_secs(10),
+            [],
+            Subscribers::default(),
+        ))
+    }
+
+    fn spawn_endpoint(endpoint: Endpoint) -> Address<Endpoint> {
+        let (endpoint_address, endpoint_context) = Context::new(None);
+
+        #[allow(clippy::disallowed_methods)]
+        tokio::spawn(endpoint_context.run(endpoint));
+
+        endpoint_address
+    }
+
+    pub(crate) fn dummy_announcement(keypair: &Keypair) -> Announcement {
+        Announcement {
+            peer_id: keypair.public().to_peer_id().into(),
+            identity: "7e35e34801e766a6a29ecb9e22810ea4e3476c2b37bf75882edf94a68b1d9607"
+                .parse()
+                .unwrap(),
+            multiaddrs: vec!["/dns4/maker.example.com/tcp/10000".parse().unwrap()],
+            trading_pairs: vec![TradingPair::BtcUsd],
+            fees: Some(FeeSchedule {
+                opening_fee: OpeningFee::default(),
+                funding_rate_long: FundingRate::default(),
+                funding_rate_short: FundingRate::default(),
+            }),
+            timestamp: Timestamp::now(),
+        }
+    }
+
+    async fn retry_until_some<F, FUT, T>(mut fut: F) -> T
+    where
+        F: FnMut() -> FUT,
+        FUT: Future<Output = Option<T>>,
+    {
+        loop {
+            match fut().await {
+                Some(t) => return t,
+                None => tokio::time::sleep(Duration::from_millis(200)).await,
+            }
+        }
+    }
+}
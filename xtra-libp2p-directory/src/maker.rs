@@ -0,0 +1,174 @@
+use crate::protocol;
+use crate::protocol::Request;
+use crate::protocol::Response;
+use crate::Announcement;
+use crate::FeeSchedule;
+use crate::SignedAnnouncement;
+use crate::ANNOUNCEMENT_TTL;
+use crate::PROTOCOL_NAME;
+use anyhow::bail;
+use anyhow::Result;
+use async_trait::async_trait;
+use model::Identity;
+use model::Timestamp;
+use model::TradingPair;
+use tokio_tasks::Tasks;
+use xtra::spawn::TokioGlobalSpawnExt;
+use xtra::Actor as _;
+use xtra::Address;
+use xtra::Context;
+use xtra_libp2p::endpoint;
+use xtra_libp2p::libp2p::identity::Keypair;
+use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p::OpenSubstream;
+use xtra_productivity::xtra_productivity;
+use xtras::spawner;
+use xtras::spawner::SpawnFallible;
+use xtras::SendAsyncSafe;
+use xtras::SendInterval;
+
+/// An actor which keeps the maker listed in a directory.
+///
+/// The announcement is re-published periodically so that it does not expire, whenever the
+/// connection to the directory is (re-)established and whenever the fees of the maker change.
+pub struct Actor {
+    endpoint: Address<Endpoint>,
+    keypair: Keypair,
+    directory: PeerId,
+    identity: Identity,
+    multiaddrs: Vec<Multiaddr>,
+    fees: Option<FeeSchedule>,
+    tasks: Tasks,
+    spawner: Address<spawner::Actor>,
+}
+
+impl Actor {
+    pub fn new(
+        endpoint: Address<Endpoint>,
+        keypair: Keypair,
+        directory: PeerId,
+        identity: Identity,
+        multiaddrs: Vec<Multiaddr>,
+    ) -> Self {
+        let spawner = spawner::Actor::new().create(None).spawn_global();
+
+        Self {
+            endpoint,
+            keypair,
+            directory,
+            identity,
+            multiaddrs,
+            fees: None,
+            tasks: Tasks::default(),
+            spawner,
+        }
+    }
+
+    async fn publish_announcement(&self) {
+        let announcement = Announcement {
+            peer_id: self.keypair.public().to_peer_id().into(),
+            identity: self.identity,
+            multiaddrs: self.multiaddrs.clone(),
+            trading_pairs: vec![TradingPair::BtcUsd],
+            fees: self.fees,
+            timestamp: Timestamp::now(),
+        };
+
+        let signed = match SignedAnnouncement::new(&announcement, &self.keypair) {
+            Ok(signed) => signed,
+            Err(e) => {
+                tracing::error!("Failed to sign announcement: {e:#}");
+                return;
+            }
+        };
+
+        let endpoint = self.endpoint.clone();
+        let directory = self.directory;
+
+        let task = async move {
+            tracing::debug!(%directory, "Publishing announcement");
+
+            publish(endpoint, directory, signed).await
+        };
+
+        let err_handler = move |e| async move {
+            tracing::warn!(%directory, "Failed to publish announcement: {e:#}")
+        };
+
+        if let Err(e) = self
+            .spawner
+            .send_async_safe(SpawnFallible::new(task, err_handler))
+            .await
+        {
+            tracing::warn!("Failed to spawn task to publish announcement: {e:#}");
+        };
+    }
+}
+
+/// Publish a signed announcement to the directory identified by `directory`.
+///
+/// Requires an established connection to the directory.
+pub async fn publish(
+    endpoint: Address<Endpoint>,
+    directory: PeerId,
+    signed: SignedAnnouncement,
+) -> Result<()> {
+    let stream = endpoint
+        .send(OpenSubstream::single_protocol(directory, PROTOCOL_NAME))
+        .await??;
+
+    match protocol::dialer(stream, Request::Publish(signed)).await? {
+        Response::Published => Ok(()),
+        Response::Rejected(reason) => bail!("Directory rejected announcement: {reason}"),
+        Response::Announcements(_) => bail!("Unexpected response from directory"),
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut Context<Self>) {
+        let this = ctx.address().expect("we just started");
+
+        self.tasks
+            .add(this.send_interval(ANNOUNCEMENT_TTL / 4, || Publish));
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Private message to publish the announcement to the directory.
+struct Publish;
+
+/// Inform the `directory::maker::Actor` about the fees of the maker's latest offers.
+///
+/// The updated announcement is published right away.
+pub struct NewFeeSchedule(pub Option<FeeSchedule>);
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: Publish) {
+        self.publish_announcement().await;
+    }
+
+    async fn handle(&mut self, msg: NewFeeSchedule) {
+        if self.fees == msg.0 {
+            return;
+        }
+
+        self.fees = msg.0;
+        self.publish_announcement().await;
+    }
+}
+
+#[xtra_productivity(message_impl = false)]
+impl Actor {
+    async fn handle_connection_established(&mut self, msg: endpoint::ConnectionEstablished) {
+        if msg.peer == self.directory {
+            self.publish_announcement().await;
+        }
+    }
+}
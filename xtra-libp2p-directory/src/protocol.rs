@@ -0,0 +1,61 @@
+use crate::SignedAnnouncement;
+use asynchronous_codec::Framed;
+use asynchronous_codec::JsonCodec;
+use asynchronous_codec::JsonCodecError;
+use futures::AsyncRead;
+use futures::AsyncWrite;
+use futures::Future;
+use futures::SinkExt;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Request {
+    Publish(SignedAnnouncement),
+    Query,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Response {
+    Published,
+    Rejected(String),
+    Announcements(Vec<SignedAnnouncement>),
+}
+
+/// Send a request to the directory and wait for its response.
+pub(crate) async fn dialer<S>(stream: S, request: Request) -> Result<Response, ReceiveError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut framed = Framed::new(stream, JsonCodec::<Request, Response>::new());
+
+    framed.send(request).await?;
+    let response = framed.next().await.ok_or(ReceiveError::Terminated)??;
+
+    Ok(response)
+}
+
+/// Receive a request from a peer and answer it with the response computed by `handler`.
+pub(crate) async fn listener<S, F, FUT>(stream: S, handler: F) -> Result<(), ReceiveError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: FnOnce(Request) -> FUT,
+    FUT: Future<Output = Response>,
+{
+    let mut framed = Framed::new(stream, JsonCodec::<Response, Request>::new());
+
+    let request = framed.next().await.ok_or(ReceiveError::Terminated)??;
+    let response = handler(request).await;
+    framed.send(response).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ReceiveError {
+    #[error("The stream has terminated.")]
+    Terminated,
+    #[error("Failed to encode or decode directory message.")]
+    Codec(#[from] JsonCodecError),
+}
@@ -0,0 +1,49 @@
+use crate::protocol;
+use crate::protocol::Request;
+use crate::protocol::Response;
+use crate::Announcement;
+use crate::PROTOCOL_NAME;
+use anyhow::bail;
+use anyhow::Result;
+use model::Timestamp;
+use xtra::Address;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p::OpenSubstream;
+
+/// Fetch the announcements of all makers listed in the directory identified by `directory`.
+///
+/// Every announcement is verified independently of the directory. Announcements which fail
+/// verification or have expired are dropped.
+///
+/// Requires an established connection to the directory.
+pub async fn fetch(endpoint: Address<Endpoint>, directory: PeerId) -> Result<Vec<Announcement>> {
+    let stream = endpoint
+        .send(OpenSubstream::single_protocol(directory, PROTOCOL_NAME))
+        .await??;
+
+    let signed_announcements = match protocol::dialer(stream, Request::Query).await? {
+        Response::Announcements(announcements) => announcements,
+        Response::Published | Response::Rejected(_) => {
+            bail!("Unexpected response from directory")
+        }
+    };
+
+    let now = Timestamp::now();
+    let announcements = signed_announcements
+        .into_iter()
+        .filter_map(|signed| match signed.verify() {
+            Ok(announcement) if announcement.is_expired(now) => {
+                tracing::debug!(peer_id = %announcement.peer_id, "Ignoring expired announcement");
+                None
+            }
+            Ok(announcement) => Some(announcement),
+            Err(e) => {
+                tracing::warn!(%directory, "Ignoring invalid announcement: {e:#}");
+                None
+            }
+        })
+        .collect();
+
+    Ok(announcements)
+}
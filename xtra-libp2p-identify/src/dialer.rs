@@ -0,0 +1,143 @@
+use crate::protocol;
+use crate::PeerInfo;
+use crate::PROTOCOL_NAME;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use xtra::message_channel::MessageChannel;
+use xtra::spawn::TokioGlobalSpawnExt;
+use xtra::Actor as _;
+use xtra::Address;
+use xtra::Context;
+use xtra_libp2p::endpoint;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p::OpenSubstream;
+use xtra_productivity::xtra_productivity;
+use xtras::spawner;
+use xtras::spawner::SpawnFallible;
+use xtras::SendAsyncSafe;
+
+/// The dialing end of the identify protocol.
+///
+/// Whenever a new connection is established, this actor opens an identify substream to the peer
+/// and records the [`PeerInfo`] it receives. Subscribers are notified with a [`PeerInfoReceived`]
+/// message, allowing them to react to incompatible peers before any other protocol is attempted.
+pub struct Actor {
+    endpoint: Address<Endpoint>,
+    spawner: Address<spawner::Actor>,
+    peer_infos: HashMap<PeerId, PeerInfo>,
+    subscribers: Vec<MessageChannel<PeerInfoReceived, ()>>,
+}
+
+impl Actor {
+    pub fn new(
+        endpoint: Address<Endpoint>,
+        subscribers: Vec<MessageChannel<PeerInfoReceived, ()>>,
+    ) -> Self {
+        let spawner = spawner::Actor::new().create(None).spawn_global();
+
+        Self {
+            endpoint,
+            spawner,
+            peer_infos: HashMap::default(),
+            subscribers,
+        }
+    }
+}
+
+/// Notifies subscribers about the [`PeerInfo`] of a newly connected peer.
+#[derive(Clone, Debug)]
+pub struct PeerInfoReceived {
+    pub peer: PeerId,
+    pub info: PeerInfo,
+}
+
+/// Retrieve the [`PeerInfo`] of a connected peer, if we have already received it.
+#[derive(Clone, Copy, Debug)]
+pub struct GetPeerInfo(pub PeerId);
+
+/// Private message to record the info a peer sent us.
+struct RecordPeerInfo {
+    peer: PeerId,
+    info: PeerInfo,
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, msg: RecordPeerInfo) {
+        let RecordPeerInfo { peer, info } = msg;
+
+        tracing::info!(
+            %peer,
+            daemon_version = %info.daemon_version,
+            protocols = ?info.protocols,
+            features = ?info.features,
+            "Identified peer"
+        );
+
+        self.peer_infos.insert(peer, info.clone());
+
+        for subscriber in &self.subscribers {
+            if let Err(e) = subscriber
+                .send_async_safe(PeerInfoReceived {
+                    peer,
+                    info: info.clone(),
+                })
+                .await
+            {
+                tracing::warn!("Unable to reach subscriber: {e:#}");
+            }
+        }
+    }
+
+    async fn handle(&mut self, GetPeerInfo(peer): GetPeerInfo) -> Option<PeerInfo> {
+        self.peer_infos.get(&peer).cloned()
+    }
+}
+
+#[xtra_productivity(message_impl = false)]
+impl Actor {
+    async fn handle_connection_established(
+        &mut self,
+        msg: endpoint::ConnectionEstablished,
+        ctx: &mut Context<Self>,
+    ) {
+        let peer = msg.peer;
+        let endpoint = self.endpoint.clone();
+        let this = ctx.address().expect("we are alive");
+
+        let task = async move {
+            let stream = endpoint
+                .send(OpenSubstream::single_protocol(peer, PROTOCOL_NAME))
+                .await??;
+            let info = protocol::recv(stream).await?;
+
+            this.send_async_safe(RecordPeerInfo { peer, info }).await?;
+
+            anyhow::Ok(())
+        };
+
+        let err_handler = move |e| async move {
+            tracing::debug!(%peer, "Outbound identify protocol failed: {e:#}")
+        };
+
+        if let Err(e) = self
+            .spawner
+            .send_async_safe(SpawnFallible::new(task, err_handler))
+            .await
+        {
+            tracing::warn!("Failed to spawn identify task: {e:#}");
+        };
+    }
+
+    async fn handle_connection_dropped(&mut self, msg: endpoint::ConnectionDropped) {
+        self.peer_infos.remove(&msg.peer);
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
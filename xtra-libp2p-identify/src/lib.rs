@@ -0,0 +1,151 @@
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashSet;
+
+pub mod dialer;
+pub mod listener;
+mod protocol;
+
+pub const PROTOCOL_NAME: &str = "/itchysats/identify/1.0.0";
+
+/// Information a peer advertises about itself upon connecting.
+///
+/// Knowing the protocols and features of the other party upfront allows us to detect
+/// incompatibilities before a protocol fails half-way through.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    /// The version of the software the peer is running.
+    pub daemon_version: String,
+    /// The protocols the peer accepts inbound substreams for.
+    pub protocols: HashSet<String>,
+    /// Optional capabilities of the peer that are not expressed through protocol names.
+    pub features: HashSet<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Future;
+    use futures::FutureExt;
+    use std::time::Duration;
+    use xtra::spawn::TokioGlobalSpawnExt;
+    use xtra::Actor as _;
+    use xtra::Address;
+    use xtra::Context;
+    use xtra_libp2p::endpoint::Subscribers;
+    use xtra_libp2p::libp2p::identity::Keypair;
+    use xtra_libp2p::libp2p::multiaddr::Protocol;
+    use xtra_libp2p::libp2p::transport::MemoryTransport;
+    use xtra_libp2p::libp2p::Multiaddr;
+    use xtra_libp2p::libp2p::PeerId;
+    use xtra_libp2p::Connect;
+    use xtra_libp2p::Endpoint;
+    use xtra_libp2p::ListenOn;
+
+    #[tokio::test]
+    async fn peers_identify_each_other_on_connect() {
+        tracing_subscriber::fmt()
+            .with_env_filter("xtra_libp2p_identify=trace")
+            .with_test_writer()
+            .init();
+
+        let (alice_peer_id, alice_dialer, alice_endpoint) = create_endpoint_with_identify("0.1.0");
+        let (bob_peer_id, bob_dialer, bob_endpoint) = create_endpoint_with_identify("0.2.0");
+
+        alice_endpoint
+            .send(ListenOn(Multiaddr::empty().with(Protocol::Memory(2000))))
+            .await
+            .unwrap();
+        bob_endpoint
+            .send(Connect(
+                Multiaddr::empty()
+                    .with(Protocol::Memory(2000))
+                    .with(Protocol::P2p(alice_peer_id.into())),
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let bob_info = retry_until_some(|| {
+            let alice_dialer = alice_dialer.clone();
+            async move {
+                alice_dialer
+                    .send(dialer::GetPeerInfo(bob_peer_id))
+                    .map(|res| res.unwrap())
+                    .await
+            }
+        })
+        .await;
+        let alice_info = retry_until_some(|| {
+            let bob_dialer = bob_dialer.clone();
+            async move {
+                bob_dialer
+                    .send(dialer::GetPeerInfo(alice_peer_id))
+                    .map(|res| res.unwrap())
+                    .await
+            }
+        })
+        .await;
+
+        assert_eq!(alice_info.daemon_version, "0.1.0");
+        assert_eq!(bob_info.daemon_version, "0.2.0");
+        assert_eq!(
+            alice_info.protocols,
+            HashSet::from([PROTOCOL_NAME.to_string()])
+        );
+        assert_eq!(
+            alice_info.features,
+            HashSet::from(["test-feature".to_string()])
+        );
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn create_endpoint_with_identify(
+        daemon_version: &str,
+    ) -> (PeerId, Address<dialer::Actor>, Address<Endpoint>) {
+        let (endpoint_address, endpoint_context) = Context::new(None);
+
+        let id = Keypair::generate_ed25519();
+        let dialer_address = dialer::Actor::new(endpoint_address.clone(), vec![])
+            .create(None)
+            .spawn_global();
+        let listener_address = listener::Actor::new(
+            endpoint_address.clone(),
+            daemon_version.to_string(),
+            HashSet::from(["test-feature".to_string()]),
+        )
+        .create(None)
+        .spawn_global();
+
+        let endpoint = Endpoint::new(
+            Box::new(MemoryTransport::default),
+            id.clone(),
+            Duration::from_secs(10),
+            [(PROTOCOL_NAME, listener_address.into())],
+            Subscribers::new(
+                vec![dialer_address.clone().into()],
+                vec![dialer_address.clone().into()],
+                vec![],
+                vec![],
+            ),
+        );
+
+        #[allow(clippy::disallowed_methods)]
+        tokio::spawn(endpoint_context.run(endpoint));
+
+        (id.public().to_peer_id(), dialer_address, endpoint_address)
+    }
+
+    async fn retry_until_some<F, FUT, T>(mut fut: F) -> T
+    where
+        F: FnMut() -> FUT,
+        FUT: Future<Output = Option<T>>,
+    {
+        loop {
+            match fut().await {
+                Some(t) => return t,
+                None => tokio::time::sleep(Duration::from_millis(200)).await,
+            }
+        }
+    }
+}
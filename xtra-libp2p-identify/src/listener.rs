@@ -0,0 +1,95 @@
+use crate::protocol;
+use crate::PeerInfo;
+use async_trait::async_trait;
+use std::collections::HashSet;
+use xtra::spawn::TokioGlobalSpawnExt;
+use xtra::Actor as _;
+use xtra::Address;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p::GetConnectionStats;
+use xtra_libp2p::NewInboundSubstream;
+use xtra_productivity::xtra_productivity;
+use xtras::spawner;
+use xtras::spawner::SpawnFallible;
+use xtras::SendAsyncSafe;
+
+/// The listening end of the identify protocol.
+///
+/// Answers every inbound substream with our [`PeerInfo`]. The advertised protocols are taken from
+/// the [`Endpoint`] at the time of the request, so they always reflect the handlers the endpoint
+/// was actually constructed with.
+pub struct Actor {
+    endpoint: Address<Endpoint>,
+    daemon_version: String,
+    features: HashSet<String>,
+    spawner: Address<spawner::Actor>,
+}
+
+impl Actor {
+    pub fn new(
+        endpoint: Address<Endpoint>,
+        daemon_version: String,
+        features: HashSet<String>,
+    ) -> Self {
+        let spawner = spawner::Actor::new().create(None).spawn_global();
+
+        Self {
+            endpoint,
+            daemon_version,
+            features,
+            spawner,
+        }
+    }
+}
+
+#[xtra_productivity(message_impl = false)]
+impl Actor {
+    async fn handle(&mut self, msg: NewInboundSubstream) {
+        let NewInboundSubstream { peer, stream } = msg;
+
+        let endpoint = self.endpoint.clone();
+        let daemon_version = self.daemon_version.clone();
+        let features = self.features.clone();
+
+        let task = async move {
+            let protocols = endpoint
+                .send(GetConnectionStats)
+                .await?
+                .inbound_protocols
+                .into_iter()
+                .map(|protocol| protocol.to_string())
+                .collect();
+
+            let info = PeerInfo {
+                daemon_version,
+                protocols,
+                features,
+            };
+
+            tracing::trace!(%peer, ?info, "Sending peer info");
+
+            protocol::send(stream, info).await?;
+
+            anyhow::Ok(())
+        };
+
+        let err_handler = move |e| async move {
+            tracing::debug!(%peer, "Inbound identify protocol failed: {e:#}")
+        };
+
+        if let Err(e) = self
+            .spawner
+            .send_async_safe(SpawnFallible::new(task, err_handler))
+            .await
+        {
+            tracing::warn!("Failed to spawn identify task: {e:#}");
+        };
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
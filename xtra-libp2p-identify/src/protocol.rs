@@ -0,0 +1,62 @@
+use crate::PeerInfo;
+use asynchronous_codec::FramedRead;
+use asynchronous_codec::FramedWrite;
+use asynchronous_codec::JsonCodec;
+use asynchronous_codec::JsonCodecError;
+use futures::AsyncReadExt;
+use futures::AsyncWriteExt;
+use futures::SinkExt;
+use futures::StreamExt;
+
+pub(crate) async fn send<S>(sink: S, info: PeerInfo) -> Result<(), JsonCodecError>
+where
+    S: AsyncWriteExt + Unpin,
+{
+    let mut framed = FramedWrite::new(sink, JsonCodec::<PeerInfo, ()>::new());
+
+    framed.send(info).await?;
+
+    Ok(())
+}
+
+pub(crate) async fn recv<S>(stream: S) -> Result<PeerInfo, ReceiveError>
+where
+    S: AsyncReadExt + Unpin,
+{
+    let mut framed = FramedRead::new(stream, JsonCodec::<(), PeerInfo>::new());
+
+    let info = framed.next().await.ok_or(ReceiveError::Terminated)??;
+
+    Ok(info)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ReceiveError {
+    #[error("The stream has terminated.")]
+    Terminated,
+    #[error("Failed to decode peer info.")]
+    Decode(#[from] JsonCodecError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sluice::pipe::pipe;
+    use std::collections::HashSet;
+
+    #[tokio::test]
+    async fn can_execute_protocol() {
+        let (stream, sink) = pipe();
+
+        let info = PeerInfo {
+            daemon_version: "0.4.22".to_string(),
+            protocols: HashSet::from(["/ipfs/ping/1.0.0".to_string()]),
+            features: HashSet::default(),
+        };
+
+        let (send_res, recv_res) = tokio::join!(send(sink, info.clone()), recv(stream));
+
+        assert!(send_res.is_ok());
+        assert_eq!(recv_res.unwrap(), info)
+    }
+}
@@ -12,6 +12,7 @@ mod tests {
     use futures::Future;
     use futures::FutureExt;
     use model::olivia::BitMexPriceEventId;
+    use model::ContractType;
     use model::FundingRate;
     use model::Leverage;
     use model::MakerOffers;
@@ -171,7 +172,7 @@ mod tests {
     #[xtra_productivity(message_impl = false)]
     impl OffersReceiver {
         async fn handle(&mut self, msg: LatestMakerOffers) {
-            self.latest_offers = msg.0;
+            self.latest_offers = msg.offers;
         }
     }
 
@@ -220,6 +221,7 @@ mod tests {
             FundingRate::default(),
             OpeningFee::default(),
             vec![Leverage::TWO],
+            ContractType::Perpetual,
         )
     }
 }
@@ -4,6 +4,7 @@ use model::MakerOffers;
 use xtra::prelude::MessageChannel;
 use xtra::spawn::TokioGlobalSpawnExt;
 use xtra::Actor as _;
+use xtra_libp2p::libp2p::PeerId;
 use xtra_libp2p::NewInboundSubstream;
 use xtra_productivity::xtra_productivity;
 use xtras::spawner;
@@ -37,7 +38,9 @@ impl Actor {
 
             tracing::debug!(%peer, ?offers, "Received offers");
 
-            maker_offers.send(LatestMakerOffers(offers)).await?;
+            maker_offers
+                .send(LatestMakerOffers { peer, offers })
+                .await?;
 
             anyhow::Ok(())
         };
@@ -55,9 +58,12 @@ impl Actor {
     }
 }
 
-/// Message used to inform other actors about the maker's latest
-/// offers.
-pub struct LatestMakerOffers(pub Option<MakerOffers>);
+/// Message used to inform other actors about the latest offers of
+/// the maker identified by `peer`.
+pub struct LatestMakerOffers {
+    pub peer: PeerId,
+    pub offers: Option<MakerOffers>,
+}
 
 #[async_trait]
 impl xtra::Actor for Actor {
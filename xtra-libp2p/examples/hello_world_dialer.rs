@@ -49,7 +49,7 @@ async fn main() -> Result<()> {
     let dialer_constructor = {
         let connect_addr = opts.multiaddr.clone();
         let endpoint_addr = endpoint_addr.clone();
-        move || dialer::Actor::new(endpoint_addr.clone(), connect_addr.clone())
+        move || dialer::Actor::new(endpoint_addr.clone(), vec![connect_addr.clone()], vec![])
     };
 
     let (supervisor, _dialer_actor) =
@@ -9,38 +9,89 @@ use async_trait::async_trait;
 use libp2p_core::Multiaddr;
 use libp2p_core::PeerId;
 use std::time::Duration;
+use tokio_tasks::Tasks;
+use xtra::message_channel::MessageChannel;
 use xtra::Address;
 use xtra_productivity::xtra_productivity;
+use xtras::backoff::Backoff;
 use xtras::SendAsyncSafe;
 
-/// If we're not connected by this time, stop the actor.
+/// If we're not connected to an address by this time, we move on to the next one.
 pub const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// How often we check whether a connection got established while dialing.
+const CONNECTION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Delay before redialing after every address failed once.
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound for the delay between two rounds of dialing all addresses.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
 /// xtra actor that takes care of dialing (connecting) to an Endpoint.
 ///
-/// Polls Endpoint at startup to check whether connection got established correctly, and
-/// then listens for ConnectionDropped message to stop itself.
-/// Should be used in conjunction with supervisor maintaining resilient connection.
+/// The listener can be reachable under several addresses, all of which must share the same
+/// `/p2p` suffix. Addresses are dialed one after the other until a connection is established.
+/// Once every address failed, the next round of dialing is delayed by a jittered, exponentially
+/// increasing backoff which is reset as soon as we are connected again.
+///
+/// If the connection is dropped, the actor redials starting from the address that worked last.
+/// Subscribers are notified about the [`AddressStats`] of all addresses whenever they change.
 pub struct Actor {
     endpoint: Address<Endpoint>,
-    connect_address: Multiaddr,
+    addresses: Vec<AddressStats>,
     listener_peer_id: Option<PeerId>,
+    next_address: usize,
+    failed_in_round: usize,
+    /// Number of rounds in which every address failed since we were last connected.
+    failed_rounds: u32,
+    backoff: Backoff,
+    subscribers: Vec<MessageChannel<LatestAddressStats, ()>>,
+    tasks: Tasks,
     stop_reason: Option<Error>,
 }
 
+/// Connection statistics of a single address of the listener.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressStats {
+    pub address: Multiaddr,
+    pub connected: bool,
+    /// Number of failed dialing attempts since the last successful connection.
+    pub consecutive_failures: u32,
+    pub total_failures: u64,
+    pub last_error: Option<String>,
+}
+
+/// Notifies subscribers about the [`AddressStats`] of all addresses of the listener.
+#[derive(Clone, Debug)]
+pub struct LatestAddressStats(pub Vec<AddressStats>);
+
 impl Actor {
-    pub fn new(endpoint: Address<Endpoint>, connect_address: Multiaddr) -> Self {
+    pub fn new(
+        endpoint: Address<Endpoint>,
+        connect_addresses: Vec<Multiaddr>,
+        subscribers: Vec<MessageChannel<LatestAddressStats, ()>>,
+    ) -> Self {
         Self {
             endpoint,
-            connect_address,
+            addresses: connect_addresses
+                .into_iter()
+                .map(AddressStats::new)
+                .collect(),
             listener_peer_id: None,
+            next_address: 0,
+            failed_in_round: 0,
+            failed_rounds: 0,
+            backoff: Backoff::new(INITIAL_BACKOFF, MAX_BACKOFF),
+            subscribers,
+            tasks: Tasks::default(),
             stop_reason: None,
         }
     }
 
-    async fn connect(&self) -> Result<(), Error> {
+    async fn connect(&self, address: Multiaddr) -> Result<(), Error> {
         self.endpoint
-            .send(Connect(self.connect_address.clone()))
+            .send(Connect(address))
             .await
             .map_err(|_| Error::NoEndpoint)?
             .map_err(|e| Error::Failed { source: anyhow!(e) })
@@ -51,6 +102,44 @@ impl Actor {
         self.stop_reason = Some(e);
         ctx.stop_self();
     }
+
+    /// Extract the peer ID that all addresses must agree on.
+    fn listener_peer_id(&self) -> Result<PeerId, Error> {
+        let mut peer_ids = self
+            .addresses
+            .iter()
+            .map(|stats| stats.address.clone().extract_peer_id());
+
+        let peer_id = peer_ids
+            .next()
+            .ok_or(Error::NoAddresses)?
+            .ok_or(Error::InvalidPeerId)?;
+
+        if peer_ids.any(|other| other != Some(peer_id)) {
+            return Err(Error::InvalidPeerId);
+        }
+
+        Ok(peer_id)
+    }
+
+    fn schedule_dial(&mut self, delay: Duration, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("self to be alive");
+
+        self.tasks.add(async move {
+            tokio::time::sleep(delay).await;
+            let _ = this.send_async_safe(Dial).await;
+        });
+    }
+
+    async fn notify_subscribers(&self) {
+        let stats = LatestAddressStats(self.addresses.clone());
+
+        for subscriber in &self.subscribers {
+            if subscriber.send(stats.clone()).await.is_err() {
+                tracing::warn!("Unable to notify subscriber about address stats");
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -59,18 +148,16 @@ impl xtra::Actor for Actor {
 
     async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
         tracing::debug!("Starting dialer actor");
-        match self
-            .connect_address
-            .clone()
-            .extract_peer_id()
-            .ok_or(Error::InvalidPeerId)
-        {
+        match self.listener_peer_id() {
             Ok(peer_id) => self.listener_peer_id = Some(peer_id),
             Err(e) => {
                 self.stop_with_error(e, ctx);
+                return;
             }
         }
 
+        self.notify_subscribers().await;
+
         let this = ctx.address().expect("self to be alive");
         this.send_async_safe(Dial).await.expect("self to be alive");
     }
@@ -95,43 +182,115 @@ impl Actor {
             .contains(&self.peer_id()))
     }
 
-    async fn dial(&self) -> Result<()> {
-        if self.is_connection_established().await? {
-            tracing::info!("Connection is already established, no need to connect");
-            return Ok(());
-        }
+    async fn dial(&self, address: Multiaddr) -> Result<()> {
+        self.connect(address).await?;
 
-        if let Err(e) = self.connect().await {
-            tracing::warn!("Failed to request connection from endpoint: {e:#}");
-        }
+        // Give the connection enough time to be established before moving on to the next address
+        let mut waited = Duration::ZERO;
+        while waited < CONNECTION_TIMEOUT {
+            tokio::time::sleep(CONNECTION_POLL_INTERVAL).await;
+            waited += CONNECTION_POLL_INTERVAL;
 
-        // Only check the connection again after it had enough time to be established
-        tokio::time::sleep(CONNECTION_TIMEOUT).await;
+            if self.is_connection_established().await? {
+                return Ok(());
+            }
+        }
 
-        anyhow::ensure!(
-            self.is_connection_established().await?,
-            "No connection after dialing attempt",
-        );
-        Ok(())
+        anyhow::bail!("No connection after dialing attempt")
     }
 }
 
 #[xtra_productivity]
 impl Actor {
     async fn handle(&mut self, _msg: Dial, ctx: &mut xtra::Context<Self>) {
-        if let Err(e) = self.dial().await {
-            self.stop_with_error(Error::Failed { source: e }, ctx);
+        match self.is_connection_established().await {
+            Ok(true) => {
+                tracing::info!("Connection is already established, no need to connect");
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                self.stop_with_error(Error::Failed { source: e }, ctx);
+                return;
+            }
+        }
+
+        let index = self.next_address;
+        let address = self.addresses[index].address.clone();
+
+        match self.dial(address.clone()).await {
+            Ok(()) => {
+                tracing::info!(%address, "Connection established");
+
+                self.addresses[index].record_success();
+                self.failed_in_round = 0;
+                self.failed_rounds = 0;
+            }
+            Err(e) => {
+                tracing::warn!(%address, "Failed to connect: {e:#}");
+
+                self.addresses[index].record_failure(&e);
+                self.next_address = (index + 1) % self.addresses.len();
+                self.failed_in_round += 1;
+
+                let delay = if self.failed_in_round < self.addresses.len() {
+                    Duration::ZERO
+                } else {
+                    self.failed_in_round = 0;
+                    let delay = self.backoff.delay(self.failed_rounds);
+                    self.failed_rounds = self.failed_rounds.saturating_add(1);
+
+                    delay
+                };
+
+                tracing::debug!("Dialing next address in {}ms", delay.as_millis());
+                self.schedule_dial(delay, ctx);
+            }
         }
+
+        self.notify_subscribers().await;
     }
 }
 
 #[xtra_productivity(message_impl = false)]
 impl Actor {
     async fn handle(&mut self, msg: endpoint::ConnectionDropped, ctx: &mut xtra::Context<Self>) {
-        if msg.peer == self.peer_id() {
-            tracing::debug!("Dialer noticed connection got dropped");
-            self.stop_with_error(Error::ConnectionDropped, ctx);
+        if msg.peer != self.peer_id() {
+            return;
         }
+
+        tracing::debug!("Dialer noticed connection got dropped, redialing");
+
+        for stats in self.addresses.iter_mut() {
+            stats.connected = false;
+        }
+        self.notify_subscribers().await;
+
+        self.schedule_dial(Duration::ZERO, ctx);
+    }
+}
+
+impl AddressStats {
+    fn new(address: Multiaddr) -> Self {
+        Self {
+            address,
+            connected: false,
+            consecutive_failures: 0,
+            total_failures: 0,
+            last_error: None,
+        }
+    }
+
+    fn record_success(&mut self) {
+        self.connected = true;
+        self.consecutive_failures = 0;
+    }
+
+    fn record_failure(&mut self, error: &anyhow::Error) {
+        self.connected = false;
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.total_failures = self.total_failures.saturating_add(1);
+        self.last_error = Some(format!("{error:#}"));
     }
 }
 
@@ -144,8 +303,8 @@ pub enum Error {
     },
     #[error("Endpoint actor is disconnected")]
     NoEndpoint,
-    #[error("Connection dropped from endpoint")]
-    ConnectionDropped,
+    #[error("No addresses to dial")]
+    NoAddresses,
     #[error("Invalid Peer Id")]
     InvalidPeerId,
     #[error("Stop reason was not specified")]
@@ -1,3 +1,7 @@
+use crate::limits::Allowance;
+use crate::limits::Limits;
+use crate::limits::Violation;
+use crate::limits::Violations;
 use crate::multiaddress_ext::MultiaddrExt as _;
 use crate::upgrade;
 use crate::Connection;
@@ -48,6 +52,10 @@ use xtras::SendAsyncSafe;
 /// connection. Any incoming substream will - assuming the protocol is supported by the endpoint -
 /// trigger a [`NewInboundSubstream`] message to the actor provided in the constructor.
 /// Opening a new substream can be achieved by sending the [`OpenSubstream`] message.
+///
+/// To protect against misbehaving peers, [`Limits`] can be configured via
+/// [`Endpoint::with_limits`]. Connections and inbound substreams that violate these limits are
+/// rejected and the violations are reported through [`GetConnectionStats`].
 pub struct Endpoint {
     transport_fn: Box<dyn Fn() -> Boxed<Connection> + Send + 'static>,
     tasks: Tasks,
@@ -57,8 +65,19 @@ pub struct Endpoint {
     inflight_connections: HashSet<PeerId>,
     connection_timeout: Duration,
     subscribers: Subscribers,
+    limits: Limits,
+    /// The allowances of connected peers and of peers which have not used up their allowance
+    /// long enough ago for it to be replenished.
+    allowances: HashMap<PeerId, Allowance>,
+    limit_violations: HashMap<PeerId, Violations>,
 }
 
+/// Upper bound for the number of peers we track limit violations for.
+///
+/// Peer IDs are cheap to generate, hence we must not allow an attacker to grow this map
+/// indefinitely. Violations of additional peers are only recorded in the metrics.
+const MAX_TRACKED_VIOLATING_PEERS: usize = 1000;
+
 /// Open a substream to the provided peer.
 ///
 /// Fails if we are not connected to the peer or the peer does not support any of the requested
@@ -139,6 +158,10 @@ pub struct GetConnectionStats;
 pub struct ConnectionStats {
     pub connected_peers: HashSet<PeerId>,
     pub listen_addresses: HashSet<Multiaddr>,
+    /// The protocols for which we accept inbound substreams.
+    pub inbound_protocols: HashSet<&'static str>,
+    /// The number of times peers violated the [`Limits`] of the endpoint.
+    pub limit_violations: HashMap<PeerId, Violations>,
 }
 
 /// Notifies an actor of a new, inbound substream from the given peer.
@@ -246,7 +269,49 @@ impl Endpoint {
             inflight_connections: HashSet::default(),
             connection_timeout,
             subscribers,
+            limits: Limits::default(),
+            allowances: HashMap::default(),
+            limit_violations: HashMap::default(),
+        }
+    }
+
+    /// Enforce the given [`Limits`] on all peers.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The allowance of `peer`, shared with its previous connections.
+    ///
+    /// Allowances are only forgotten once they are replenished, so that peers cannot reset them by
+    /// reconnecting.
+    fn allowance(&mut self, peer: PeerId) -> Allowance {
+        let controls = &self.controls;
+        self.allowances
+            .retain(|peer, allowance| controls.contains_key(peer) || !allowance.is_replenished());
+
+        let limits = &self.limits;
+        let protocols = self.inbound_substream_channels.keys().copied();
+        self.allowances
+            .entry(peer)
+            .or_insert_with(|| Allowance::new(limits, protocols))
+            .clone()
+    }
+
+    fn record_limit_violation(&mut self, peer: PeerId, violation: Violation) {
+        tracing::warn!(%peer, "Limit violated: {violation}");
+        violation.record_metric();
+
+        if self.limit_violations.len() >= MAX_TRACKED_VIOLATING_PEERS
+            && !self.limit_violations.contains_key(&peer)
+        {
+            return;
         }
+
+        self.limit_violations
+            .entry(peer)
+            .or_default()
+            .record(violation);
     }
 
     async fn drop_connection(&mut self, peer: &PeerId) {
@@ -296,6 +361,15 @@ impl Endpoint {
         self.inflight_connections.remove(&msg.peer);
         let this = ctx.address().expect("we are alive");
 
+        if let Err(violation) = self
+            .limits
+            .check_connection(&msg.peer, self.controls.keys())
+        {
+            // Dropping the connection's control and worker closes the connection.
+            self.record_limit_violation(msg.peer, violation);
+            return;
+        }
+
         let NewConnection {
             peer,
             control,
@@ -307,11 +381,13 @@ impl Endpoint {
         tasks.add(worker);
         tasks.add_fallible(
             {
+                let this = this.clone();
                 let inbound_substream_channels = self
                     .inbound_substream_channels
                     .iter()
                     .map(|(proto, channel)| (proto.to_owned(), channel.clone()))
                     .collect::<HashMap<_, _>>();
+                let allowance = self.allowance(peer);
 
                 async move {
                     loop {
@@ -333,8 +409,21 @@ impl Endpoint {
                             .get(&protocol)
                             .expect("Cannot negotiate a protocol that we don't support");
 
-                        let stream =
+                        let permit = match allowance.try_acquire(protocol) {
+                            Ok(permit) => permit,
+                            Err(violation) => {
+                                let _ = this
+                                    .send_async_safe(LimitViolated { peer, violation })
+                                    .await;
+                                continue;
+                            }
+                        };
+
+                        let mut stream =
                             Substream::new(stream, protocol, libp2p_core::Endpoint::Listener);
+                        if let Some(permit) = permit {
+                            stream = stream.with_permit(permit);
+                        }
 
                         let _ = channel
                             .send_async_safe(NewInboundSubstream { peer, stream })
@@ -377,9 +466,15 @@ impl Endpoint {
         ConnectionStats {
             connected_peers: self.controls.keys().copied().collect(),
             listen_addresses: self.listen_addresses.clone(),
+            inbound_protocols: self.inbound_substream_channels.keys().copied().collect(),
+            limit_violations: self.limit_violations.clone(),
         }
     }
 
+    async fn handle(&mut self, msg: LimitViolated) {
+        self.record_limit_violation(msg.peer, msg.violation);
+    }
+
     async fn handle(&mut self, msg: Connect, ctx: &mut xtra::Context<Self>) -> Result<(), Error> {
         let this = ctx.address().expect("we are alive");
 
@@ -634,6 +729,12 @@ struct ExistingConnectionFailed {
     error: anyhow::Error,
 }
 
+#[derive(Debug)]
+struct LimitViolated {
+    peer: PeerId,
+    violation: Violation,
+}
+
 struct NewListenAddress {
     listen_address: Multiaddr,
 }
@@ -20,9 +20,12 @@ use libp2p_core::PeerId;
 
 pub mod dialer;
 pub mod endpoint;
+pub mod limits;
 pub mod listener;
 pub mod multiaddress_ext;
+pub mod shared;
 mod substream;
+pub mod tor;
 mod upgrade;
 mod verify_peer_id;
 
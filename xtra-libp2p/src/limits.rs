@@ -0,0 +1,441 @@
+use conquer_once::Lazy;
+use libp2p_core::PeerId;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+
+/// Limits an [`Endpoint`](crate::Endpoint) enforces on its peers.
+///
+/// The default does not impose any limits.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    /// If set, only these peers are allowed to connect.
+    pub allowed_peers: Option<HashSet<PeerId>>,
+    /// Peers that are never allowed to connect.
+    pub denied_peers: HashSet<PeerId>,
+    /// The maximum number of peers we can be connected to at the same time.
+    ///
+    /// Note that the endpoint keeps only a single connection per peer; a new connection of an
+    /// already connected peer replaces the previous one.
+    pub max_connections: Option<usize>,
+    /// Peers which neither count towards nor are refused by `max_connections`.
+    ///
+    /// Useful for peers we depend on, e.g. a directory we dial ourselves, which must not lose
+    /// their connection because other peers used up all the slots. Reserved peers still have to
+    /// pass `allowed_peers` and `denied_peers`.
+    pub reserved_peers: HashSet<PeerId>,
+    /// The maximum number of inbound substreams a single peer can have open per protocol.
+    pub max_inbound_substreams_per_protocol: Option<usize>,
+    /// The maximum rate at which a single peer can open inbound substreams, across all protocols.
+    pub inbound_substream_rate: Option<RateLimit>,
+}
+
+/// Allow up to `max` events within `per`.
+///
+/// The allowance is replenished continuously, i.e. a peer that used up its allowance can open
+/// another substream after `per / max` has passed.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    pub max: u32,
+    pub per: Duration,
+}
+
+impl Limits {
+    /// Check whether `peer` may connect given the peers we are `connected` to.
+    pub fn check_connection<'a>(
+        &self,
+        peer: &PeerId,
+        connected: impl IntoIterator<Item = &'a PeerId>,
+    ) -> Result<(), Violation> {
+        let allowed = self
+            .allowed_peers
+            .as_ref()
+            .map_or(true, |allowed| allowed.contains(peer));
+
+        if !allowed || self.denied_peers.contains(peer) {
+            return Err(Violation::DeniedPeer);
+        }
+
+        let max = match self.max_connections {
+            Some(max) if !self.reserved_peers.contains(peer) => max,
+            _ => return Ok(()),
+        };
+
+        let mut already_connected = false;
+        let mut num_connections = 0;
+        for connected in connected {
+            already_connected |= connected == peer;
+
+            if !self.reserved_peers.contains(connected) {
+                num_connections += 1;
+            }
+        }
+
+        if !already_connected && num_connections >= max {
+            return Err(Violation::ConnectionLimit);
+        }
+
+        Ok(())
+    }
+}
+
+/// A breach of the [`Limits`] by a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The peer is not allowed to connect.
+    DeniedPeer,
+    /// The peer tried to connect while we were at the maximum number of connections.
+    ConnectionLimit,
+    /// The peer tried to exceed the maximum number of inbound substreams for a protocol.
+    SubstreamLimit { protocol: &'static str },
+    /// The peer opened inbound substreams too fast.
+    RateLimit { protocol: &'static str },
+}
+
+impl Violation {
+    fn label(&self) -> &'static str {
+        match self {
+            Violation::DeniedPeer => "denied_peer",
+            Violation::ConnectionLimit => "connection_limit",
+            Violation::SubstreamLimit { .. } => "substream_limit",
+            Violation::RateLimit { .. } => "rate_limit",
+        }
+    }
+
+    fn protocol(&self) -> &'static str {
+        match self {
+            Violation::DeniedPeer | Violation::ConnectionLimit => "",
+            Violation::SubstreamLimit { protocol } | Violation::RateLimit { protocol } => protocol,
+        }
+    }
+
+    pub(crate) fn record_metric(&self) {
+        LIMIT_VIOLATIONS_COUNTER
+            .with(&HashMap::from([
+                (VIOLATION_LABEL, self.label()),
+                (PROTOCOL_LABEL, self.protocol()),
+            ]))
+            .inc();
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::DeniedPeer => write!(f, "Peer is not allowed to connect"),
+            Violation::ConnectionLimit => write!(f, "Maximum number of connections reached"),
+            Violation::SubstreamLimit { protocol } => {
+                write!(f, "Maximum number of substreams for {protocol} reached")
+            }
+            Violation::RateLimit { protocol } => {
+                write!(f, "Substream for {protocol} exceeds rate limit")
+            }
+        }
+    }
+}
+
+/// The number of times a peer violated the [`Limits`], segregated by kind of violation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Violations {
+    pub denied_peer: u64,
+    pub connection_limit: u64,
+    pub substream_limit: u64,
+    pub rate_limit: u64,
+}
+
+impl Violations {
+    pub(crate) fn record(&mut self, violation: Violation) {
+        let counter = match violation {
+            Violation::DeniedPeer => &mut self.denied_peer,
+            Violation::ConnectionLimit => &mut self.connection_limit,
+            Violation::SubstreamLimit { .. } => &mut self.substream_limit,
+            Violation::RateLimit { .. } => &mut self.rate_limit,
+        };
+
+        *counter = counter.saturating_add(1);
+    }
+}
+
+/// The inbound substreams a single peer may still open.
+///
+/// Shared by all connections of the peer, so that reconnecting does not reset the allowance.
+#[derive(Clone, Debug)]
+pub(crate) struct Allowance {
+    max_substreams: usize,
+    substreams: HashMap<&'static str, Arc<Semaphore>>,
+    rate: Option<Arc<Mutex<TokenBucket>>>,
+}
+
+impl Allowance {
+    pub(crate) fn new(limits: &Limits, protocols: impl IntoIterator<Item = &'static str>) -> Self {
+        let max_substreams = limits
+            .max_inbound_substreams_per_protocol
+            .unwrap_or_default();
+        let substreams = match limits.max_inbound_substreams_per_protocol {
+            Some(max) => protocols
+                .into_iter()
+                .map(|protocol| (protocol, Arc::new(Semaphore::new(max))))
+                .collect(),
+            None => HashMap::default(),
+        };
+
+        Self {
+            max_substreams,
+            substreams,
+            rate: limits
+                .inbound_substream_rate
+                .map(|limit| Arc::new(Mutex::new(TokenBucket::new(limit)))),
+        }
+    }
+
+    /// Take the allowance for an inbound substream for `protocol`.
+    ///
+    /// Returns the permit to hold for as long as the substream is open, if the number of
+    /// substreams is limited.
+    pub(crate) fn try_acquire(
+        &self,
+        protocol: &'static str,
+    ) -> Result<Option<OwnedSemaphorePermit>, Violation> {
+        if let Some(rate) = self.rate.as_ref() {
+            if !rate.lock().expect("not poisoned").try_acquire() {
+                return Err(Violation::RateLimit { protocol });
+            }
+        }
+
+        match self.substreams.get(protocol) {
+            Some(semaphore) => match semaphore.clone().try_acquire_owned() {
+                Ok(permit) => Ok(Some(permit)),
+                Err(_) => Err(Violation::SubstreamLimit { protocol }),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Whether the allowance is back to what a new peer gets, i.e. it can be forgotten.
+    pub(crate) fn is_replenished(&self) -> bool {
+        let substreams_closed = self
+            .substreams
+            .values()
+            .all(|semaphore| semaphore.available_permits() == self.max_substreams);
+        let rate_replenished = self.rate.as_ref().map_or(true, |rate| {
+            rate.lock()
+                .expect("not poisoned")
+                .is_full_at(Instant::now())
+        });
+
+        substreams_closed && rate_replenished
+    }
+}
+
+/// Token bucket enforcing a [`RateLimit`].
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    tokens_per_second: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        let capacity = f64::from(limit.max);
+
+        Self {
+            capacity,
+            tokens: capacity,
+            tokens_per_second: capacity / limit.per.as_secs_f64(),
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub(crate) fn try_acquire(&mut self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&mut self, now: Instant) -> bool {
+        self.refill(now);
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+
+        self.tokens -= 1.0;
+        true
+    }
+
+    fn is_full_at(&mut self, now: Instant) -> bool {
+        self.refill(now);
+
+        self.tokens >= self.capacity
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.tokens_per_second).min(self.capacity);
+        self.last_refill = now;
+    }
+}
+
+const VIOLATION_LABEL: &str = "violation";
+const PROTOCOL_LABEL: &str = "protocol";
+
+static LIMIT_VIOLATIONS_COUNTER: Lazy<prometheus::IntCounterVec> = Lazy::new(|| {
+    prometheus::register_int_counter_vec!(
+        "libp2p_limit_violations_total",
+        "The number of times peers violated the limits of the endpoint.",
+        &[VIOLATION_LABEL, PROTOCOL_LABEL]
+    )
+    .unwrap()
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denied_peer_cannot_connect() {
+        let peer = PeerId::random();
+        let limits = Limits {
+            denied_peers: HashSet::from([peer]),
+            ..Limits::default()
+        };
+
+        assert_eq!(
+            limits.check_connection(&peer, []),
+            Err(Violation::DeniedPeer)
+        );
+        assert_eq!(limits.check_connection(&PeerId::random(), []), Ok(()));
+    }
+
+    #[test]
+    fn only_allowed_peers_can_connect() {
+        let peer = PeerId::random();
+        let limits = Limits {
+            allowed_peers: Some(HashSet::from([peer])),
+            ..Limits::default()
+        };
+
+        assert_eq!(limits.check_connection(&peer, []), Ok(()));
+        assert_eq!(
+            limits.check_connection(&PeerId::random(), []),
+            Err(Violation::DeniedPeer)
+        );
+    }
+
+    #[test]
+    fn connected_peer_can_reconnect_at_connection_limit() {
+        let limits = Limits {
+            max_connections: Some(2),
+            ..Limits::default()
+        };
+        let connected = [PeerId::random(), PeerId::random()];
+
+        assert_eq!(
+            limits.check_connection(&PeerId::random(), &connected),
+            Err(Violation::ConnectionLimit)
+        );
+        assert_eq!(limits.check_connection(&connected[0], &connected), Ok(()));
+    }
+
+    #[test]
+    fn reserved_peer_is_exempt_from_connection_limit() {
+        let reserved = PeerId::random();
+        let limits = Limits {
+            max_connections: Some(1),
+            reserved_peers: HashSet::from([reserved]),
+            ..Limits::default()
+        };
+
+        let other = PeerId::random();
+        assert_eq!(limits.check_connection(&reserved, [&other]), Ok(()));
+        assert_eq!(limits.check_connection(&other, [&reserved]), Ok(()));
+        assert_eq!(
+            limits.check_connection(&PeerId::random(), [&reserved, &other]),
+            Err(Violation::ConnectionLimit)
+        );
+    }
+
+    #[test]
+    fn reserved_peer_is_still_subject_to_allowed_peers() {
+        let reserved = PeerId::random();
+        let limits = Limits {
+            allowed_peers: Some(HashSet::from([PeerId::random()])),
+            reserved_peers: HashSet::from([reserved]),
+            ..Limits::default()
+        };
+
+        assert_eq!(
+            limits.check_connection(&reserved, []),
+            Err(Violation::DeniedPeer)
+        );
+    }
+
+    #[test]
+    fn token_bucket_allows_burst_and_refills_over_time() {
+        let mut bucket = TokenBucket::new(RateLimit {
+            max: 2,
+            per: Duration::from_secs(10),
+        });
+        let start = bucket.last_refill;
+
+        assert!(bucket.try_acquire_at(start));
+        assert!(bucket.try_acquire_at(start));
+        assert!(!bucket.try_acquire_at(start));
+
+        assert!(!bucket.try_acquire_at(start + Duration::from_secs(4)));
+        assert!(bucket.try_acquire_at(start + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn token_bucket_is_full_once_replenished() {
+        let mut bucket = TokenBucket::new(RateLimit {
+            max: 2,
+            per: Duration::from_secs(10),
+        });
+        let start = bucket.last_refill;
+
+        assert!(bucket.is_full_at(start));
+        assert!(bucket.try_acquire_at(start));
+        assert!(!bucket.is_full_at(start + Duration::from_secs(4)));
+        assert!(bucket.is_full_at(start + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn allowance_is_shared_between_clones() {
+        let limits = Limits {
+            max_inbound_substreams_per_protocol: Some(1),
+            inbound_substream_rate: Some(RateLimit {
+                max: 2,
+                per: Duration::from_secs(60),
+            }),
+            ..Limits::default()
+        };
+        let allowance = Allowance::new(&limits, ["/foo/1.0.0"]);
+        let reconnected = allowance.clone();
+
+        let permit = allowance.try_acquire("/foo/1.0.0").unwrap();
+        assert!(permit.is_some());
+        assert_eq!(
+            reconnected.try_acquire("/foo/1.0.0").unwrap_err(),
+            Violation::SubstreamLimit {
+                protocol: "/foo/1.0.0"
+            }
+        );
+        assert!(!reconnected.is_replenished());
+
+        drop(permit);
+        assert_eq!(
+            reconnected.try_acquire("/foo/1.0.0").unwrap_err(),
+            Violation::RateLimit {
+                protocol: "/foo/1.0.0"
+            }
+        );
+    }
+}
@@ -4,6 +4,16 @@ use libp2p_core::PeerId;
 
 pub trait MultiaddrExt {
     fn extract_peer_id(self) -> Option<PeerId>;
+
+    /// Whether this is the address of an onion service, e.g. `/onion3/<address>:<port>`.
+    fn is_onion(&self) -> bool;
+
+    /// The host and port a SOCKS5 proxy has to connect to in order to reach this address.
+    ///
+    /// Supports `/onion3` addresses as well as `/dns`, `/dns4`, `/dns6`, `/ip4` and `/ip6`
+    /// addresses followed by `/tcp`. A trailing `/p2p` segment is ignored. Hostnames are passed
+    /// on to the proxy to prevent leaking DNS requests.
+    fn socks5_target(&self) -> Option<(String, u16)>;
 }
 
 impl MultiaddrExt for Multiaddr {
@@ -15,4 +25,84 @@ impl MultiaddrExt for Multiaddr {
 
         Some(peer_id)
     }
+
+    fn is_onion(&self) -> bool {
+        matches!(self.iter().next(), Some(Protocol::Onion3(_)))
+    }
+
+    fn socks5_target(&self) -> Option<(String, u16)> {
+        let mut protocols = self.iter();
+
+        let target = match protocols.next()? {
+            Protocol::Onion3(address) => {
+                let host = data_encoding::BASE32.encode(address.hash()).to_lowercase();
+
+                (format!("{host}.onion"), address.port())
+            }
+            host => {
+                let host = match host {
+                    Protocol::Dns(host) | Protocol::Dns4(host) | Protocol::Dns6(host) => {
+                        host.to_string()
+                    }
+                    Protocol::Ip4(ip) => ip.to_string(),
+                    Protocol::Ip6(ip) => ip.to_string(),
+                    _ => return None,
+                };
+                let port = match protocols.next()? {
+                    Protocol::Tcp(port) => port,
+                    _ => return None,
+                };
+
+                (host, port)
+            }
+        };
+
+        match protocols.next() {
+            None | Some(Protocol::P2p(_)) => Some(target),
+            Some(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onion3_address_is_passed_to_proxy_as_hostname() {
+        let address = "/onion3/vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd:10001/p2p/12D3KooWEsK2X8Tp24XtyWh7DM65VfwXtNH2cmfs2JsWmkmwKbV1"
+            .parse::<Multiaddr>()
+            .unwrap();
+
+        assert!(address.is_onion());
+        assert_eq!(
+            address.socks5_target(),
+            Some((
+                "vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd.onion".to_string(),
+                10001
+            ))
+        );
+    }
+
+    #[test]
+    fn dns_and_ip_addresses_are_passed_to_proxy() {
+        let dns = "/dns4/maker.example.com/tcp/10001"
+            .parse::<Multiaddr>()
+            .unwrap();
+        let ip6 = "/ip6/::1/tcp/10001".parse::<Multiaddr>().unwrap();
+
+        assert!(!dns.is_onion());
+        assert_eq!(
+            dns.socks5_target(),
+            Some(("maker.example.com".to_string(), 10001))
+        );
+        assert_eq!(ip6.socks5_target(), Some(("::1".to_string(), 10001)));
+    }
+
+    #[test]
+    fn non_tcp_addresses_are_not_supported() {
+        let address = "/memory/10000".parse::<Multiaddr>().unwrap();
+
+        assert_eq!(address.socks5_target(), None);
+    }
 }
@@ -0,0 +1,76 @@
+use libp2p_core::transport::TransportError;
+use libp2p_core::Multiaddr;
+use libp2p_core::Transport;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+/// A [`Transport`] wrapper which can be cloned without cloning the inner transport.
+///
+/// All clones share the same inner transport. This allows constructing a fallible transport
+/// (e.g. one reading the system DNS configuration) once up front, while still handing out a fresh
+/// transport to every dial and listen of the [`Endpoint`](crate::Endpoint).
+pub struct SharedTransport<TInner> {
+    inner: Arc<Mutex<TInner>>,
+}
+
+impl<TInner> SharedTransport<TInner> {
+    pub fn new(inner: TInner) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, TInner> {
+        self.inner
+            .lock()
+            .expect("no panics while holding the transport lock")
+    }
+}
+
+impl<TInner> Clone for SharedTransport<TInner> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<TInner> Transport for SharedTransport<TInner>
+where
+    TInner: Transport,
+{
+    type Output = TInner::Output;
+    type Error = TInner::Error;
+    type Listener = TInner::Listener;
+    type ListenerUpgrade = TInner::ListenerUpgrade;
+    type Dial = TInner::Dial;
+
+    fn listen_on(&mut self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        self.inner().listen_on(addr)
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        self.inner().dial(addr)
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        self.inner().dial_as_listener(addr)
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner().address_translation(listen, observed)
+    }
+}
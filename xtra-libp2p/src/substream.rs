@@ -13,6 +13,7 @@ use std::io::IoSliceMut;
 use std::pin::Pin;
 use std::task::Context;
 use std::task::Poll;
+use tokio::sync::OwnedSemaphorePermit;
 
 /// A substream is an isolated channel within another connection.
 ///
@@ -44,6 +45,11 @@ pub struct Substream {
 
     /// The prometheus counter for the number of bytes written.
     written_counter: IntCounter,
+
+    /// Permit counting this substream towards the limit of concurrent substreams of a peer.
+    ///
+    /// The permit is released once the substream is dropped.
+    _permit: Option<OwnedSemaphorePermit>,
 }
 
 impl Substream {
@@ -63,8 +69,14 @@ impl Substream {
             _timer: SUBSTREAM_DURATION_HISTOGRAM.with(&labels).start_timer(),
             read_counter: SUBSTREAM_BYTES_READ_COUNTER.with(&labels),
             written_counter: SUBSTREAM_BYTES_WRITTEN_COUNTER.with(&labels),
+            _permit: None,
         }
     }
+
+    pub(crate) fn with_permit(mut self, permit: OwnedSemaphorePermit) -> Self {
+        self._permit = Some(permit);
+        self
+    }
 }
 
 impl AsyncRead for Substream {
@@ -0,0 +1,240 @@
+use crate::multiaddress_ext::MultiaddrExt;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::FutureExt;
+use futures::StreamExt;
+use futures::TryFutureExt;
+use futures::TryStreamExt;
+use libp2p_core::either::EitherOutput;
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::transport::ListenerEvent;
+use libp2p_core::transport::TransportError;
+use libp2p_core::Multiaddr;
+use libp2p_core::Transport;
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+use tokio_socks::tcp::Socks5Stream;
+use tokio_util::compat::Compat;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+/// A [`Transport`] wrapper adding support for Tor.
+///
+/// If configured with a SOCKS5 proxy (i.e. Tor's `SocksPort`), _all_ dials are tunnelled through
+/// the proxy. This includes `/onion3` addresses as well as `/dns` and `/ip` addresses followed by
+/// `/tcp`, which keeps our IP hidden from the dialed peer. Hostnames are resolved by the proxy.
+/// Without a proxy, dials are passed on to the inner transport.
+///
+/// Tor publishes onion services on its own and forwards incoming connections to a local address
+/// (`HiddenServicePort` in `torrc`). If configured with an [`OnionService`], listening on its
+/// address makes the inner transport listen on the local address instead. Listening on any other
+/// address is passed on to the inner transport.
+#[derive(Clone)]
+pub struct TorTransport<TInner> {
+    inner: TInner,
+    socks_proxy: Option<SocketAddr>,
+    onion_service: Option<OnionService>,
+}
+
+/// An onion service that Tor forwards to a local address.
+#[derive(Clone, Debug)]
+pub struct OnionService {
+    /// The public address of the onion service, e.g. `/onion3/<address>:<port>`.
+    pub address: Multiaddr,
+    /// The local address Tor forwards connections to, e.g. `/ip4/127.0.0.1/tcp/<port>`.
+    pub target: Multiaddr,
+}
+
+impl<TInner> TorTransport<TInner> {
+    pub fn new(inner: TInner) -> Self {
+        Self {
+            inner,
+            socks_proxy: None,
+            onion_service: None,
+        }
+    }
+
+    /// Dial all addresses through the given SOCKS5 proxy.
+    pub fn with_socks_proxy(mut self, socks_proxy: SocketAddr) -> Self {
+        self.socks_proxy = Some(socks_proxy);
+        self
+    }
+
+    /// Listen on the given onion service.
+    pub fn with_onion_service(mut self, onion_service: OnionService) -> Self {
+        self.onion_service = Some(onion_service);
+        self
+    }
+}
+
+impl<TInner> Transport for TorTransport<TInner>
+where
+    TInner: Transport + 'static,
+    TInner::Dial: Send + 'static,
+    TInner::Listener: Send + 'static,
+    TInner::ListenerUpgrade: Send + 'static,
+    TInner::Output: 'static,
+    TInner::Error: 'static,
+{
+    type Output = EitherOutput<Compat<Socks5Stream<TcpStream>>, TInner::Output>;
+    type Error = Error<TInner::Error>;
+    #[allow(clippy::type_complexity)]
+    type Listener =
+        BoxStream<'static, Result<ListenerEvent<Self::ListenerUpgrade, Self::Error>, Self::Error>>;
+    type ListenerUpgrade = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(&mut self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        let addr = match &self.onion_service {
+            Some(onion_service) if without_peer_id(addr.clone()) == onion_service.address => {
+                onion_service.target.clone()
+            }
+            _ if addr.is_onion() => return Err(TransportError::MultiaddrNotSupported(addr)),
+            _ => addr,
+        };
+
+        let listener = self
+            .inner
+            .listen_on(addr)
+            .map_err(|e| e.map(Error::Inner))?
+            .map_err(Error::Inner)
+            .map_ok(|e| {
+                e.map(|u| u.map_ok(EitherOutput::Second).map_err(Error::Inner).boxed())
+                    .map_err(Error::Inner)
+            })
+            .boxed();
+
+        Ok(listener)
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        let socks_proxy = match self.socks_proxy {
+            Some(socks_proxy) => socks_proxy,
+            None if addr.is_onion() => return Err(TransportError::MultiaddrNotSupported(addr)),
+            None => {
+                let dial = self
+                    .inner
+                    .dial(addr)
+                    .map_err(|e| e.map(Error::Inner))?
+                    .map_ok(EitherOutput::Second)
+                    .map_err(Error::Inner)
+                    .boxed();
+
+                return Ok(dial);
+            }
+        };
+
+        let target = addr
+            .socks5_target()
+            .ok_or(TransportError::MultiaddrNotSupported(addr))?;
+
+        let dial = Socks5Stream::connect(socks_proxy, target)
+            .map_ok(|stream| EitherOutput::First(stream.compat()))
+            .map_err(Error::Socks5)
+            .boxed();
+
+        Ok(dial)
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        // Hole punching does not apply to connections through a proxy.
+        self.dial(addr)
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        if self.socks_proxy.is_some() {
+            // The observed address is the one of the proxy, which is of no use to anybody.
+            return None;
+        }
+
+        self.inner.address_translation(listen, observed)
+    }
+}
+
+fn without_peer_id(mut addr: Multiaddr) -> Multiaddr {
+    if let Some(Protocol::P2p(_)) = addr.iter().last() {
+        addr.pop();
+    }
+
+    addr
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error<T> {
+    #[error("Failed to connect through SOCKS5 proxy")]
+    Socks5(#[from] tokio_socks::Error),
+    #[error("Inner transport failed")]
+    Inner(#[source] T),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::transport::MemoryTransport;
+
+    #[test]
+    fn cannot_dial_onion_without_proxy() {
+        let mut transport = TorTransport::new(MemoryTransport::default());
+
+        let result = transport.dial(onion_address());
+
+        assert!(matches!(
+            result,
+            Err(TransportError::MultiaddrNotSupported(_))
+        ))
+    }
+
+    #[test]
+    fn cannot_dial_unsupported_address_through_proxy() {
+        let mut transport = TorTransport::new(MemoryTransport::default())
+            .with_socks_proxy("127.0.0.1:9050".parse().unwrap());
+
+        let result = transport.dial("/memory/10000".parse().unwrap());
+
+        assert!(matches!(
+            result,
+            Err(TransportError::MultiaddrNotSupported(_))
+        ))
+    }
+
+    #[test]
+    fn listening_on_onion_service_listens_on_target() {
+        let port = rand::random::<u64>();
+        let mut transport =
+            TorTransport::new(MemoryTransport::default()).with_onion_service(OnionService {
+                address: onion_address(),
+                target: format!("/memory/{port}").parse().unwrap(),
+            });
+
+        assert!(transport.listen_on(onion_address()).is_ok());
+    }
+
+    #[test]
+    fn cannot_listen_on_unknown_onion_service() {
+        let mut transport = TorTransport::new(MemoryTransport::default());
+
+        let result = transport.listen_on(onion_address());
+
+        assert!(matches!(
+            result,
+            Err(TransportError::MultiaddrNotSupported(_))
+        ))
+    }
+
+    fn onion_address() -> Multiaddr {
+        "/onion3/vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd:10001"
+            .parse()
+            .unwrap()
+    }
+}
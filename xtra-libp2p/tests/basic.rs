@@ -13,12 +13,16 @@ use xtra::message_channel::MessageChannel;
 use xtra::spawn::TokioGlobalSpawnExt;
 use xtra::Actor;
 use xtra::Address;
+use xtra_libp2p::dialer;
 use xtra_libp2p::endpoint;
 use xtra_libp2p::endpoint::Subscribers;
 use xtra_libp2p::libp2p::identity::Keypair;
 use xtra_libp2p::libp2p::transport::MemoryTransport;
 use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::limits::Limits;
+use xtra_libp2p::limits::Violations;
 use xtra_libp2p::Connect;
+use xtra_libp2p::ConnectionStats;
 use xtra_libp2p::Disconnect;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p::GetConnectionStats;
@@ -198,6 +202,28 @@ async fn listen_address_is_reflected_in_stats() {
     );
 }
 
+#[tokio::test]
+async fn inbound_protocols_are_reflected_in_stats() {
+    let alice_hello_world_handler = HelloWorld::default().create(None).spawn_global();
+    let (alice, bob, _) = alice_and_bob(
+        [(
+            "/hello-world/1.0.0",
+            alice_hello_world_handler.clone().into(),
+        )],
+        [],
+    )
+    .await;
+
+    let alice_stats = alice.endpoint.send(GetConnectionStats).await.unwrap();
+    let bob_stats = bob.endpoint.send(GetConnectionStats).await.unwrap();
+
+    assert_eq!(
+        alice_stats.inbound_protocols,
+        HashSet::from(["/hello-world/1.0.0"])
+    );
+    assert!(bob_stats.inbound_protocols.is_empty());
+}
+
 #[tokio::test]
 async fn cannot_open_substream_for_unhandled_protocol() {
     let (alice, bob, _) = alice_and_bob([], []).await;
@@ -304,6 +330,165 @@ async fn falls_back_to_next_protocol_if_unsupported() {
     assert_eq!(actual_protocol, "/hello-world/1.0.0");
 }
 
+#[tokio::test]
+async fn dialer_rotates_to_next_address_if_first_is_unreachable() {
+    let alice = make_node([]);
+    let bob = make_node([]);
+
+    let port = rand::random::<u16>();
+    let alice_listen = format!("/memory/{port}").parse::<Multiaddr>().unwrap();
+    alice.endpoint.send(ListenOn(alice_listen)).await.unwrap();
+
+    let alice_peer_id = alice.peer_id;
+    let unreachable = format!("/memory/{}/p2p/{alice_peer_id}", port.wrapping_add(1))
+        .parse()
+        .unwrap();
+    let reachable = format!("/memory/{port}/p2p/{alice_peer_id}")
+        .parse()
+        .unwrap();
+
+    let _dialer = dialer::Actor::new(bob.endpoint.clone(), vec![unreachable, reachable], vec![])
+        .create(None)
+        .spawn_global();
+
+    let connected = async {
+        loop {
+            let bob_stats = bob.endpoint.send(GetConnectionStats).await.unwrap();
+            if bob_stats.connected_peers.contains(&alice_peer_id) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    };
+
+    tokio::time::timeout(dialer::CONNECTION_TIMEOUT * 3, connected)
+        .await
+        .expect("Bob to connect to Alice via second address");
+}
+
+#[tokio::test]
+async fn denied_peer_is_disconnected() {
+    let bob = make_node([]);
+    let alice = make_node_with_limits(
+        [],
+        Limits {
+            denied_peers: HashSet::from([bob.peer_id]),
+            ..Limits::default()
+        },
+    );
+
+    let port = rand::random::<u16>();
+    let alice_listen = format!("/memory/{port}").parse::<Multiaddr>().unwrap();
+    alice.endpoint.send(ListenOn(alice_listen)).await.unwrap();
+
+    let alice_peer_id = alice.peer_id;
+    bob.endpoint
+        .send(Connect(
+            format!("/memory/{port}/p2p/{alice_peer_id}")
+                .parse()
+                .unwrap(),
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let alice_stats = wait_until(&alice.endpoint, |stats| {
+        stats.limit_violations.contains_key(&bob.peer_id)
+    })
+    .await;
+
+    assert!(alice_stats.connected_peers.is_empty());
+    assert_eq!(
+        alice_stats.limit_violations[&bob.peer_id],
+        Violations {
+            denied_peer: 1,
+            ..Violations::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn substreams_exceeding_limit_are_rejected() {
+    let alice_hello_world_handler = HelloWorld::default().create(None).spawn_global();
+    let alice = make_node_with_limits(
+        [(
+            "/hello-world/1.0.0",
+            alice_hello_world_handler.clone().into(),
+        )],
+        Limits {
+            max_inbound_substreams_per_protocol: Some(1),
+            ..Limits::default()
+        },
+    );
+    let bob = make_node([]);
+
+    let port = rand::random::<u16>();
+    let alice_listen = format!("/memory/{port}").parse::<Multiaddr>().unwrap();
+    alice.endpoint.send(ListenOn(alice_listen)).await.unwrap();
+
+    let alice_peer_id = alice.peer_id;
+    bob.endpoint
+        .send(Connect(
+            format!("/memory/{port}/p2p/{alice_peer_id}")
+                .parse()
+                .unwrap(),
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Alice keeps the first substream open until Bob sends his name.
+    let _first = bob
+        .endpoint
+        .send(OpenSubstream::single_protocol(
+            alice_peer_id,
+            "/hello-world/1.0.0",
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    let _second = bob
+        .endpoint
+        .send(OpenSubstream::single_protocol(
+            alice_peer_id,
+            "/hello-world/1.0.0",
+        ))
+        .await
+        .unwrap();
+
+    let alice_stats = wait_until(&alice.endpoint, |stats| {
+        stats.limit_violations.contains_key(&bob.peer_id)
+    })
+    .await;
+
+    assert_eq!(
+        alice_stats.limit_violations[&bob.peer_id],
+        Violations {
+            substream_limit: 1,
+            ..Violations::default()
+        }
+    );
+}
+
+async fn wait_until(
+    endpoint: &Address<Endpoint>,
+    condition: impl Fn(&ConnectionStats) -> bool,
+) -> ConnectionStats {
+    let poll = async {
+        loop {
+            let stats = endpoint.send(GetConnectionStats).await.unwrap();
+            if condition(&stats) {
+                return stats;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    };
+
+    tokio::time::timeout(Duration::from_secs(10), poll)
+        .await
+        .expect("condition to be met in time")
+}
+
 async fn alice_and_bob<const AN: usize, const BN: usize>(
     alice_inbound_substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); AN],
     bob_inbound_substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); BN],
@@ -345,6 +530,13 @@ struct Node {
 
 fn make_node<const N: usize>(
     substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); N],
+) -> Node {
+    make_node_with_limits(substream_handlers, Limits::default())
+}
+
+fn make_node_with_limits<const N: usize>(
+    substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); N],
+    limits: Limits,
 ) -> Node {
     let id = Keypair::generate_ed25519();
     let peer_id = id.public().to_peer_id();
@@ -365,6 +557,7 @@ fn make_node<const N: usize>(
             vec![subscriber_stats.clone().into()],
         ),
     )
+    .with_limits(limits)
     .create(None)
     .spawn_global();
 
@@ -0,0 +1,208 @@
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::Multiaddr;
+use libp2p_tcp::TokioTcpConfig;
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use xtra::spawn::TokioGlobalSpawnExt;
+use xtra::Actor;
+use xtra::Address;
+use xtra_libp2p::endpoint::Subscribers;
+use xtra_libp2p::libp2p::identity::Keypair;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::tor::OnionService;
+use xtra_libp2p::tor::TorTransport;
+use xtra_libp2p::Connect;
+use xtra_libp2p::ConnectionStats;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p::GetConnectionStats;
+use xtra_libp2p::ListenOn;
+
+const ONION_HOST: &str = "vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd";
+
+#[tokio::test]
+async fn connect_to_onion_service_through_socks5_proxy() {
+    let onion_service_target = free_local_socket().await;
+    let onion_service = OnionService {
+        address: format!("/onion3/{ONION_HOST}:10001").parse().unwrap(),
+        target: Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::LOCALHOST))
+            .with(Protocol::Tcp(onion_service_target.port())),
+    };
+
+    let socks_proxy = spawn_socks5_proxy(HashMap::from([(
+        format!("{ONION_HOST}.onion"),
+        onion_service_target,
+    )]))
+    .await;
+
+    let (alice_peer_id, alice) = make_endpoint({
+        let onion_service = onion_service.clone();
+        move || TorTransport::new(TokioTcpConfig::new()).with_onion_service(onion_service.clone())
+    });
+    let (_, bob) = make_endpoint(move || {
+        TorTransport::new(TokioTcpConfig::new()).with_socks_proxy(socks_proxy)
+    });
+
+    alice
+        .send(ListenOn(onion_service.address.clone()))
+        .await
+        .unwrap();
+    wait_until(&alice, "Alice listens on onion service", |stats| {
+        stats.listen_addresses.contains(&onion_service.address)
+    })
+    .await;
+
+    bob.send(Connect(
+        onion_service
+            .address
+            .clone()
+            .with(Protocol::P2p(alice_peer_id.into())),
+    ))
+    .await
+    .unwrap()
+    .unwrap();
+
+    wait_until(&bob, "Bob connects to Alice through proxy", |stats| {
+        stats.connected_peers.contains(&alice_peer_id)
+    })
+    .await;
+}
+
+fn make_endpoint<T>(transport: impl Fn() -> T + Send + 'static) -> (PeerId, Address<Endpoint>)
+where
+    T: libp2p_core::Transport + Send + Sync + 'static,
+    T::Output: futures::AsyncRead + futures::AsyncWrite + Unpin + Send + 'static,
+    T::Error: Send + Sync,
+    T::Listener: Send + 'static,
+    T::Dial: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+{
+    let id = Keypair::generate_ed25519();
+    let peer_id = id.public().to_peer_id();
+
+    let endpoint = Endpoint::new(
+        Box::new(transport),
+        id,
+        Duration::from_secs(20),
+        [],
+        Subscribers::default(),
+    )
+    .create(None)
+    .spawn_global();
+
+    (peer_id, endpoint)
+}
+
+async fn wait_until(
+    endpoint: &Address<Endpoint>,
+    description: &str,
+    condition: impl Fn(&ConnectionStats) -> bool,
+) {
+    let poll = async {
+        while !condition(&endpoint.send(GetConnectionStats).await.unwrap()) {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    };
+
+    tokio::time::timeout(Duration::from_secs(10), poll)
+        .await
+        .unwrap_or_else(|_| panic!("Timed out waiting for: {description}"));
+}
+
+async fn free_local_socket() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+/// Spawns a minimal SOCKS5 proxy standing in for Tor.
+///
+/// Only unauthenticated `CONNECT` requests are supported. Onion hostnames are looked up in the
+/// given map, mimicking Tor forwarding connections to the local target of an onion service.
+async fn spawn_socks5_proxy(onion_services: HashMap<String, SocketAddr>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let onion_services = onion_services.clone();
+
+            tokio::spawn(async move {
+                if let Err(e) = handle_socks5_connection(stream, &onion_services).await {
+                    tracing::warn!("SOCKS5 connection failed: {e:#}");
+                }
+            });
+        }
+    });
+
+    address
+}
+
+async fn handle_socks5_connection(
+    mut stream: TcpStream,
+    onion_services: &HashMap<String, SocketAddr>,
+) -> Result<()> {
+    // Greeting: VER | NMETHODS | METHODS
+    let mut greeting = [0u8; 2];
+    stream.read_exact(&mut greeting).await?;
+    anyhow::ensure!(greeting[0] == 5, "Unsupported SOCKS version");
+    let mut methods = vec![0u8; greeting[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    anyhow::ensure!(methods.contains(&0), "Client requires authentication");
+    stream.write_all(&[5, 0]).await?;
+
+    // Request: VER | CMD | RSV | ATYP | DST.ADDR | DST.PORT
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    anyhow::ensure!(request[1] == 1, "Only CONNECT is supported");
+
+    let target = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            stream.read_exact(&mut ip).await?;
+            SocketAddr::from((Ipv4Addr::from(ip), stream.read_u16().await?))
+        }
+        3 => {
+            let mut host = vec![0u8; stream.read_u8().await? as usize];
+            stream.read_exact(&mut host).await?;
+            let host = String::from_utf8(host)?;
+            let port = stream.read_u16().await?;
+
+            match onion_services.get(&host) {
+                Some(target) => *target,
+                None => tokio::net::lookup_host((host.as_str(), port))
+                    .await?
+                    .next()
+                    .with_context(|| format!("Unable to resolve {host}"))?,
+            }
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            stream.read_exact(&mut ip).await?;
+            SocketAddr::from((Ipv6Addr::from(ip), stream.read_u16().await?))
+        }
+        other => bail!("Unsupported address type {other}"),
+    };
+
+    let mut upstream = TcpStream::connect(target).await?;
+
+    // Reply: VER | REP | RSV | ATYP | BND.ADDR | BND.PORT
+    stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+
+    tokio::io::copy_bidirectional(&mut stream, &mut upstream).await?;
+
+    Ok(())
+}
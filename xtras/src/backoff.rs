@@ -0,0 +1,71 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Exponential backoff with "equal jitter".
+///
+/// The n-th consecutive attempt is delayed by `initial * 2^n`, capped at `max`. Half of each delay
+/// is fixed while the other half is randomised. This keeps the delay growing exponentially but
+/// prevents several parties from retrying in lockstep, e.g. takers redialing a restarted maker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max: max.max(initial),
+        }
+    }
+
+    /// The upper bound of any delay.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// The delay before the given attempt, counting from `0`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        let delay = self.initial.saturating_mul(factor).min(self.max);
+
+        let half = delay / 2;
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially_with_jitter_up_to_max() {
+        let backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(10));
+
+        for (attempt, expected) in [(0, 1), (1, 2), (2, 4), (3, 8), (4, 10), (20, 10)] {
+            let expected = Duration::from_secs(expected);
+            let delay = backoff.delay(attempt);
+
+            assert!(
+                delay >= expected / 2 && delay <= expected,
+                "delay {delay:?} of attempt {attempt} should be between {:?} and {expected:?}",
+                expected / 2
+            );
+        }
+    }
+
+    #[test]
+    fn backoff_does_not_overflow() {
+        let backoff = Backoff::new(Duration::from_secs(u64::MAX), Duration::from_secs(u64::MAX));
+
+        assert!(backoff.delay(u32::MAX) <= Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn max_is_never_below_initial() {
+        let backoff = Backoff::new(Duration::from_secs(10), Duration::from_secs(1));
+
+        assert_eq!(backoff.max(), Duration::from_secs(10));
+    }
+}
@@ -1,54 +1,24 @@
-use std::ops::ControlFlow;
+use crate::instrumentation::InstrumentExt;
+use crate::instrumentation::InstrumentedContext;
 use std::time::Duration;
-use tokio::time::timeout;
 use xtra::Actor;
 use xtra::Context;
 
-/// Extension trait which converts a `Context` into a [`TimeoutManager`], which will run the actor
-/// with a given handler timeout duration.
+/// Extension trait which converts a `Context` into an [`InstrumentedContext`], which will run the
+/// actor with a given handler timeout duration.
+///
+/// The actor is instrumented the same way as with [`InstrumentExt`].
 pub trait HandlerTimeoutExt<A> {
-    /// Wrap the given context in a [`TimeoutManager`]
-    fn with_handler_timeout(self, timeout: Duration) -> TimeoutManager<A>;
+    /// Wrap the given context in an [`InstrumentedContext`] aborting handlers after `timeout`
+    fn with_handler_timeout(self, timeout: Duration) -> InstrumentedContext<A>;
 }
 
-impl<A> HandlerTimeoutExt<A> for Context<A> {
-    fn with_handler_timeout(self, timeout: Duration) -> TimeoutManager<A> {
-        TimeoutManager { ctx: self, timeout }
-    }
-}
-
-pub struct TimeoutManager<A> {
-    ctx: Context<A>,
-    timeout: Duration,
-}
-
-impl<A> TimeoutManager<A>
+impl<A> HandlerTimeoutExt<A> for Context<A>
 where
     A: Actor,
 {
-    /// Run the actor with the previously specified handler timeout duration. See
-    /// [`HandlerTimeoutExt`] for more.
-    pub async fn run(mut self, mut actor: A) -> A::Stop {
-        actor.started(&mut self.ctx).await;
-
-        if !self.ctx.running {
-            return actor.stopped().await;
-        }
-
-        loop {
-            let msg = self.ctx.next_message().await;
-
-            match timeout(self.timeout, self.ctx.tick(msg, &mut actor)).await {
-                Ok(ControlFlow::Continue(())) => (),
-                Ok(ControlFlow::Break(())) => break actor.stopped().await,
-                Err(_elapsed) => {
-                    tracing::warn!(
-                        timeout_seconds = self.timeout.as_secs(),
-                        "Handler execution timed out"
-                    );
-                }
-            }
-        }
+    fn with_handler_timeout(self, timeout: Duration) -> InstrumentedContext<A> {
+        self.instrumented().with_handler_timeout(timeout)
     }
 }
 
@@ -83,6 +53,15 @@ mod test {
         }
     }
 
+    #[async_trait::async_trait]
+    impl Handler<Duration> for MyActor {
+        type Return = ();
+
+        async fn handle(&mut self, duration: Duration, _ctx: &mut Context<Self>) {
+            tokio::time::sleep(duration).await
+        }
+    }
+
     #[tokio::test]
     async fn started_is_called() {
         let (addr, ctx) = Context::new(None);
@@ -93,4 +72,24 @@ mod test {
         drop(addr);
         assert!(fut.await);
     }
+
+    #[tokio::test]
+    async fn actor_keeps_running_after_handler_times_out() {
+        let (addr, ctx) = Context::new(None);
+
+        #[allow(clippy::disallowed_methods)]
+        let task = tokio::spawn(
+            ctx.with_handler_timeout(Duration::from_millis(10))
+                .run(MyActor { started: false }),
+        );
+
+        let _ = addr.send(Duration::from_secs(60)).split_receiver().await;
+        tokio::time::timeout(Duration::from_secs(1), addr.send(()))
+            .await
+            .expect("handler to time out")
+            .unwrap();
+
+        drop(addr);
+        assert!(task.await.unwrap());
+    }
 }
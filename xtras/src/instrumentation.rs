@@ -0,0 +1,447 @@
+use crate::ActorName;
+use conquer_once::Lazy;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio::time::Instant;
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::span::Attributes;
+use tracing::span::Id;
+use tracing::Instrument;
+use tracing::Span;
+use tracing::Subscriber;
+use tracing_subscriber::layer;
+use tracing_subscriber::registry::LookupSpan;
+use xtra::Actor;
+use xtra::Context;
+
+/// Handlers taking longer than this are reported as slow unless configured otherwise.
+pub const DEFAULT_SLOW_HANDLER_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Extension trait which converts a `Context` into an [`InstrumentedContext`], which will run the
+/// actor while recording metrics about its mailbox and handlers.
+///
+/// The metrics are exported to Prometheus, labelled with the [`ActorName`] of the actor:
+///
+/// - `xtra_mailbox_depth`: the number of messages waiting in the mailbox,
+/// - `xtra_handler_duration_seconds`: how long handling a message took,
+/// - `xtra_slow_handlers_total`: how many handlers exceeded the slow-handler threshold.
+///
+/// The handler metrics are additionally labelled with the type of the message. xtra only reveals
+/// the message type in the spans of its `instrumentation` feature, hence it is only known if
+/// [`MessageTypeLayer`] is installed and those spans are enabled, e.g. with an `xtra=debug`
+/// directive. Otherwise the message is labelled as `unknown`.
+///
+/// Every message is handled within a `handle_message` span carrying the actor name.
+pub trait InstrumentExt<A> {
+    /// Wrap the given context in an [`InstrumentedContext`]
+    fn instrumented(self) -> InstrumentedContext<A>;
+}
+
+impl<A> InstrumentExt<A> for Context<A>
+where
+    A: Actor,
+{
+    fn instrumented(self) -> InstrumentedContext<A> {
+        InstrumentedContext {
+            ctx: self,
+            instrumentation: Instrumentation::new::<A>(DEFAULT_SLOW_HANDLER_THRESHOLD),
+            handler_timeout: None,
+        }
+    }
+}
+
+pub struct InstrumentedContext<A> {
+    ctx: Context<A>,
+    instrumentation: Instrumentation,
+    handler_timeout: Option<Duration>,
+}
+
+impl<A> InstrumentedContext<A>
+where
+    A: Actor,
+{
+    /// Warn about handlers taking longer than `threshold`.
+    pub fn with_slow_handler_threshold(self, threshold: Duration) -> Self {
+        Self {
+            instrumentation: Instrumentation::new::<A>(threshold),
+            ..self
+        }
+    }
+
+    /// Abort handlers taking longer than `timeout`.
+    pub fn with_handler_timeout(self, timeout: Duration) -> Self {
+        Self {
+            handler_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Run the actor, recording metrics about its mailbox and handlers. See [`InstrumentExt`] for
+    /// more.
+    pub async fn run(mut self, mut actor: A) -> A::Stop {
+        actor.started(&mut self.ctx).await;
+
+        if !self.ctx.running {
+            return actor.stopped().await;
+        }
+
+        loop {
+            let msg = self.ctx.next_message().await;
+            self.instrumentation.record_mailbox_depth(&self.ctx);
+
+            let started_at = Instant::now();
+            let mut tick = CaptureMessageType::new(self.ctx.tick(msg, &mut actor));
+            let span = self.instrumentation.span();
+            let flow = match self.handler_timeout {
+                Some(handler_timeout) => timeout(handler_timeout, &mut tick)
+                    .instrument(span)
+                    .await
+                    .ok(),
+                None => Some((&mut tick).instrument(span).await),
+            };
+            self.instrumentation
+                .record_handler(tick.into_message().as_deref(), started_at.elapsed());
+
+            match flow {
+                Some(ControlFlow::Continue(())) => {}
+                Some(ControlFlow::Break(())) => break actor.stopped().await,
+                None => {
+                    tracing::warn!(
+                        timeout_seconds = self.handler_timeout.unwrap_or_default().as_secs(),
+                        "Handler execution timed out"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Records metrics about the mailbox and handlers of an actor.
+struct Instrumentation {
+    actor: String,
+    slow_handler_threshold: Duration,
+}
+
+impl Instrumentation {
+    fn new<A>(slow_handler_threshold: Duration) -> Self
+    where
+        A: Actor,
+    {
+        Self {
+            actor: A::name(),
+            slow_handler_threshold,
+        }
+    }
+
+    fn span(&self) -> Span {
+        tracing::debug_span!("handle_message", actor = %self.actor)
+    }
+
+    fn record_mailbox_depth<A>(&self, ctx: &Context<A>)
+    where
+        A: Actor,
+    {
+        let depth = ctx
+            .address()
+            .map(|address| address.len())
+            .unwrap_or_default();
+
+        MAILBOX_DEPTH_GAUGE
+            .with(&HashMap::from([(ACTOR_LABEL, self.actor.as_str())]))
+            .set(depth as i64);
+    }
+
+    fn record_handler(&self, message: Option<&str>, elapsed: Duration) {
+        let message = message.unwrap_or(UNKNOWN_MESSAGE);
+        let labels = HashMap::from([(ACTOR_LABEL, self.actor.as_str()), (MESSAGE_LABEL, message)]);
+
+        HANDLER_DURATION_HISTOGRAM
+            .with(&labels)
+            .observe(elapsed.as_secs_f64());
+
+        if elapsed >= self.slow_handler_threshold {
+            SLOW_HANDLERS_COUNTER.with(&labels).inc();
+
+            tracing::warn!(
+                actor = %self.actor,
+                %message,
+                elapsed_ms = elapsed.as_millis() as u64,
+                threshold_ms = self.slow_handler_threshold.as_millis() as u64,
+                "Slow handler"
+            );
+        }
+    }
+}
+
+/// Tells the instrumented actors which type of message they are handling.
+///
+/// The type is taken from the spans emitted by xtra's `instrumentation` feature: every handler
+/// runs within an `xtra_message_handler` span, the parent of which carries the message type.
+pub struct MessageTypeLayer;
+
+impl<S> tracing_subscriber::Layer<S> for MessageTypeLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: layer::Context<'_, S>) {
+        if attrs.metadata().name() != XTRA_REQUEST_SPAN {
+            return;
+        }
+
+        let mut visitor = MessageTypeVisitor(None);
+        attrs.record(&mut visitor);
+
+        if let (Some(message_type), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(message_type);
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: layer::Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) if span.name() == XTRA_HANDLER_SPAN => span,
+            _ => return,
+        };
+        let parent = match span.parent() {
+            Some(parent) => parent,
+            None => return,
+        };
+
+        if let Some(message_type) = parent.extensions().get::<MessageType>() {
+            HANDLED_MESSAGE_TYPE.with(|handled| {
+                if let Some(handled) = handled.borrow_mut().as_mut() {
+                    handled.get_or_insert_with(|| message_type.0.clone());
+                }
+            });
+        }
+    }
+}
+
+struct MessageType(String);
+
+struct MessageTypeVisitor(Option<MessageType>);
+
+impl Visit for MessageTypeVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == XTRA_MESSAGE_TYPE_FIELD {
+            self.0 = Some(MessageType(value.to_owned()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == XTRA_MESSAGE_TYPE_FIELD {
+            self.0 = Some(MessageType(format!("{value:?}")));
+        }
+    }
+}
+
+thread_local! {
+    /// The message type reported by [`MessageTypeLayer`] while a [`CaptureMessageType`] future is
+    /// polled on this thread.
+    static HANDLED_MESSAGE_TYPE: RefCell<Option<Option<String>>> = RefCell::new(None);
+}
+
+/// Captures the type of the message handled by the wrapped `tick` future.
+struct CaptureMessageType<F> {
+    tick: Pin<Box<F>>,
+    message: Option<String>,
+}
+
+impl<F> CaptureMessageType<F> {
+    fn new(tick: F) -> Self {
+        Self {
+            tick: Box::pin(tick),
+            message: None,
+        }
+    }
+
+    fn into_message(self) -> Option<String> {
+        self.message
+    }
+}
+
+impl<F> Future for CaptureMessageType<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let previous =
+            HANDLED_MESSAGE_TYPE.with(|handled| handled.replace(Some(this.message.take())));
+        let poll = this.tick.as_mut().poll(cx);
+        this.message = HANDLED_MESSAGE_TYPE
+            .with(|handled| handled.replace(previous))
+            .flatten();
+
+        poll
+    }
+}
+
+const ACTOR_LABEL: &str = "actor";
+const MESSAGE_LABEL: &str = "message";
+const UNKNOWN_MESSAGE: &str = "unknown";
+
+const XTRA_REQUEST_SPAN: &str = "xtra_actor_request";
+const XTRA_HANDLER_SPAN: &str = "xtra_message_handler";
+const XTRA_MESSAGE_TYPE_FIELD: &str = "message_type";
+
+static MAILBOX_DEPTH_GAUGE: Lazy<prometheus::IntGaugeVec> = Lazy::new(|| {
+    prometheus::register_int_gauge_vec!(
+        "xtra_mailbox_depth",
+        "The number of messages waiting in the mailbox of an actor.",
+        &[ACTOR_LABEL]
+    )
+    .unwrap()
+});
+
+static HANDLER_DURATION_HISTOGRAM: Lazy<prometheus::HistogramVec> = Lazy::new(|| {
+    prometheus::register_histogram_vec!(
+        "xtra_handler_duration_seconds",
+        "The time it took an actor to handle a message.",
+        &[ACTOR_LABEL, MESSAGE_LABEL],
+        prometheus::exponential_buckets(0.001, 4.0, 9).unwrap()
+    )
+    .unwrap()
+});
+
+static SLOW_HANDLERS_COUNTER: Lazy<prometheus::IntCounterVec> = Lazy::new(|| {
+    prometheus::register_int_counter_vec!(
+        "xtra_slow_handlers_total",
+        "The number of handlers that took longer than the slow-handler threshold.",
+        &[ACTOR_LABEL, MESSAGE_LABEL]
+    )
+    .unwrap()
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use xtra_productivity::xtra_productivity;
+
+    #[tokio::test]
+    async fn records_handler_durations_of_instrumented_actor() {
+        let _guard = tracing_subscriber::registry()
+            .with(MessageTypeLayer)
+            .set_default();
+        let (address, ctx) = Context::new(None);
+
+        #[allow(clippy::disallowed_methods)]
+        let task = tokio::spawn(ctx.instrumented().run(Sleeper));
+
+        address
+            .send(Sleep(Duration::from_millis(10)))
+            .await
+            .unwrap();
+        address.send(Sleep(Duration::ZERO)).await.unwrap();
+        drop(address);
+        task.await.unwrap();
+
+        let actor = Sleeper::name();
+        let histogram = HANDLER_DURATION_HISTOGRAM.with(&HashMap::from([
+            (ACTOR_LABEL, actor.as_str()),
+            (MESSAGE_LABEL, std::any::type_name::<Sleep>()),
+        ]));
+
+        assert_eq!(histogram.get_sample_count(), 2);
+        assert!(histogram.get_sample_sum() >= 0.01);
+    }
+
+    #[tokio::test]
+    async fn counts_slow_handlers() {
+        let (address, ctx) = Context::new(None);
+
+        #[allow(clippy::disallowed_methods)]
+        let task = tokio::spawn(
+            ctx.instrumented()
+                .with_slow_handler_threshold(Duration::from_millis(5))
+                .run(SlowSleeper),
+        );
+
+        address
+            .send(Sleep(Duration::from_millis(10)))
+            .await
+            .unwrap();
+        address.send(Sleep(Duration::ZERO)).await.unwrap();
+        drop(address);
+        task.await.unwrap();
+
+        let actor = SlowSleeper::name();
+        let slow_handlers = SLOW_HANDLERS_COUNTER
+            .with(&HashMap::from([
+                (ACTOR_LABEL, actor.as_str()),
+                (MESSAGE_LABEL, UNKNOWN_MESSAGE),
+            ]))
+            .get();
+
+        assert_eq!(slow_handlers, 1);
+    }
+
+    #[tokio::test]
+    async fn captures_message_type_of_xtra_handler_span() {
+        let _guard = tracing_subscriber::registry()
+            .with(MessageTypeLayer)
+            .set_default();
+        let request = tracing::debug_span!("xtra_actor_request", message_type = "Sleep");
+        let handler = tracing::debug_span!(parent: &request, "xtra_message_handler");
+        let other = tracing::debug_span!(parent: &request, "other");
+
+        let mut tick = CaptureMessageType::new(async {
+            let _entered = other.enter();
+        });
+        (&mut tick).await;
+        assert_eq!(tick.into_message(), None);
+
+        let mut tick = CaptureMessageType::new(async {
+            let _entered = handler.enter();
+        });
+        (&mut tick).await;
+        assert_eq!(tick.into_message().as_deref(), Some("Sleep"));
+    }
+
+    /// Two actor types so the tests don't share metrics.
+    struct Sleeper;
+    struct SlowSleeper;
+
+    struct Sleep(Duration);
+
+    #[async_trait]
+    impl Actor for Sleeper {
+        type Stop = ();
+
+        async fn stopped(self) -> Self::Stop {}
+    }
+
+    #[async_trait]
+    impl Actor for SlowSleeper {
+        type Stop = ();
+
+        async fn stopped(self) -> Self::Stop {}
+    }
+
+    #[xtra_productivity]
+    impl Sleeper {
+        async fn handle(&mut self, msg: Sleep) {
+            tokio::time::sleep(msg.0).await
+        }
+    }
+
+    #[xtra_productivity(message_impl = false)]
+    impl SlowSleeper {
+        async fn handle(&mut self, msg: Sleep) {
+            tokio::time::sleep(msg.0).await
+        }
+    }
+}
@@ -1,6 +1,8 @@
 mod actor_name;
 pub mod address_map;
+pub mod backoff;
 pub mod handler_timeout;
+pub mod instrumentation;
 mod send_async_safe;
 mod send_interval;
 pub mod spawner;
@@ -9,5 +11,6 @@ pub mod supervisor;
 pub use actor_name::ActorName;
 pub use address_map::AddressMap;
 pub use handler_timeout::HandlerTimeoutExt;
+pub use instrumentation::InstrumentExt;
 pub use send_async_safe::SendAsyncSafe;
 pub use send_interval::SendInterval;
@@ -1,14 +1,19 @@
+use crate::backoff::Backoff;
 use crate::ActorName;
+use crate::SendAsyncSafe;
 use async_trait::async_trait;
-use futures::Future;
+use conquer_once::Lazy;
 use futures::FutureExt;
 use std::any::Any;
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt;
 use std::panic::AssertUnwindSafe;
-use std::pin::Pin;
 use std::time::Duration;
+use tokio::time::Instant;
 use tokio_tasks::Tasks;
+use xtra::message_channel::MessageChannel;
 use xtra::Address;
 use xtra::Context;
 use xtra_productivity::xtra_productivity;
@@ -19,40 +24,114 @@ pub struct Actor<T, R> {
     context: Context<T>,
     ctor: Box<dyn Fn() -> T + Send + 'static>,
     tasks: Tasks,
-    restart_policy: AsyncClosure<R>,
+    restart_policy: RestartPolicy<R>,
     _actor: Address<T>, // kept around to ensure that the supervised actor stays alive
     metrics: Metrics,
+    /// When the currently running instance of the actor was spawned.
+    spawned_at: Instant,
+    /// How many restarts with [`Restart::WithBackoff`] happened since the actor last ran stable.
+    backoff_attempts: u32,
+    /// When the actor was restarted within the window of [`RestartPolicy::with_max_restarts`].
+    recent_restarts: VecDeque<Instant>,
+    escalate_to: Option<MessageChannel<Escalated, ()>>,
 }
 
-type AsyncClosure<R> = Box<
-    dyn for<'a> FnMut(&'a R) -> Pin<Box<dyn Future<Output = bool> + 'a + Send + Sync>>
-        + Send
-        + Sync,
->;
+/// Decides whether and when the supervisor restarts the actor after it stopped.
+///
+/// The strategy is picked per stop reason, which allows for example to retry transient errors
+/// with a backoff but to give up on fatal ones:
+///
+/// ```ignore
+/// RestartPolicy::new(|error: &Error| match error {
+///     Error::Fatal => Restart::Never,
+///     _ => Restart::WithBackoff(Backoff::new(Duration::from_secs(1), Duration::from_secs(60))),
+/// })
+/// .with_max_restarts(10, Duration::from_secs(600))
+/// ```
+pub struct RestartPolicy<R> {
+    strategy: Box<dyn Fn(&R) -> Restart + Send + Sync>,
+    max_restarts: Option<MaxRestarts>,
+}
+
+impl<R> RestartPolicy<R> {
+    pub fn new(strategy: impl Fn(&R) -> Restart + Send + Sync + 'static) -> Self {
+        Self {
+            strategy: Box::new(strategy),
+            max_restarts: None,
+        }
+    }
+
+    /// Escalate instead of restarting if the actor was already restarted `restarts` times
+    /// within `window`.
+    ///
+    /// Escalating stops the supervisor and notifies the parent configured with
+    /// [`Actor::escalate_to`]. Panics count towards this limit as well.
+    pub fn with_max_restarts(self, restarts: usize, window: Duration) -> Self {
+        Self {
+            max_restarts: Some(MaxRestarts { restarts, window }),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MaxRestarts {
+    restarts: usize,
+    window: Duration,
+}
 
-/// Closure that configures the supervisor to restart on every kind of error
-pub fn always_restart<E>() -> AsyncClosure<E>
+/// What to do after the supervised actor stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restart {
+    /// Spawn a new instance of the actor right away.
+    Immediately,
+    /// Spawn a new instance of the actor after a fixed delay.
+    After(Duration),
+    /// Spawn a new instance of the actor after an exponentially growing delay.
+    ///
+    /// Once an instance of the actor ran for at least [`Backoff::max`], it is considered stable
+    /// and the backoff starts over.
+    WithBackoff(Backoff),
+    /// Don't spawn a new instance of the actor.
+    Never,
+}
+
+/// Policy that configures the supervisor to restart on every kind of error
+pub fn always_restart<E>() -> RestartPolicy<E>
 where
     E: Error + Send + Sync + 'static,
 {
-    Box::new(|_: &E| Box::pin(async move { true }))
+    RestartPolicy::new(|_: &E| Restart::Immediately)
 }
 
-/// Closure that configures the supervisor to restart on every kind of error,
+/// Policy that configures the supervisor to restart on every kind of error,
 /// after waiting for the specified `wait_time`.
 ///
 /// Useful for preventing tight loops.
-pub fn always_restart_after<E>(wait_time: Duration) -> AsyncClosure<E>
+pub fn always_restart_after<E>(wait_time: Duration) -> RestartPolicy<E>
 where
     E: Error + Send + Sync + 'static,
 {
-    let wait_time = wait_time;
-    Box::new(move |_: &E| {
-        Box::pin(async move {
-            tokio::time::sleep(wait_time).await;
-            true
-        })
-    })
+    RestartPolicy::new(move |_: &E| Restart::After(wait_time))
+}
+
+/// Policy that configures the supervisor to restart on every kind of error,
+/// backing off exponentially between consecutive restarts.
+pub fn always_restart_with_backoff<E>(backoff: Backoff) -> RestartPolicy<E>
+where
+    E: Error + Send + Sync + 'static,
+{
+    RestartPolicy::new(move |_: &E| Restart::WithBackoff(backoff))
+}
+
+/// Message sent to the parent of a supervisor when the supervised actor exceeded the maximum
+/// number of restarts and the supervisor gave up.
+#[derive(Debug, Clone)]
+pub struct Escalated {
+    /// The name of the supervised actor.
+    pub actor: String,
+    /// Why the supervised actor stopped the last time.
+    pub reason: String,
 }
 
 #[derive(Default, Clone, Copy)]
@@ -91,18 +170,7 @@ where
     /// should be restarted, set [`xtra::Actor::Stop`] to a more descriptive value and use
     /// [`Actor::with_policy`].
     pub fn new(ctor: impl (Fn() -> T) + Send + 'static) -> (Self, Address<T>) {
-        let (address, context) = Context::new(None);
-
-        let supervisor = Self {
-            context,
-            ctor: Box::new(ctor),
-            tasks: Tasks::default(),
-            restart_policy: always_restart(),
-            _actor: address.clone(),
-            metrics: Metrics::default(),
-        };
-
-        (supervisor, address)
+        Self::with_policy(ctor, always_restart())
     }
 }
 
@@ -119,7 +187,7 @@ where
     /// 2. When to construct an instance of the actor.
     pub fn with_policy(
         ctor: impl (Fn() -> T) + Send + 'static,
-        restart_policy: AsyncClosure<R>,
+        restart_policy: RestartPolicy<R>,
     ) -> (Self, Address<T>) {
         let (address, context) = Context::new(None);
 
@@ -130,11 +198,24 @@ where
             restart_policy,
             _actor: address.clone(),
             metrics: Metrics::default(),
+            spawned_at: Instant::now(),
+            backoff_attempts: 0,
+            recent_restarts: VecDeque::new(),
+            escalate_to: None,
         };
 
         (supervisor, address)
     }
 
+    /// Notify `parent` if the supervisor gives up on the actor because it exceeded the maximum
+    /// number of restarts.
+    pub fn escalate_to(self, parent: MessageChannel<Escalated, ()>) -> Self {
+        Self {
+            escalate_to: Some(parent),
+            ..self
+        }
+    }
+
     fn spawn_new(&mut self, ctx: &mut Context<Self>) {
         let actor_name = T::name();
         tracing::info!(actor = %&actor_name, "Spawning new actor instance");
@@ -143,6 +224,10 @@ where
         let actor = (self.ctor)();
 
         self.metrics.num_spawns += 1;
+        SPAWNS_COUNTER
+            .with(&HashMap::from([(ACTOR_LABEL, actor_name.as_str())]))
+            .inc();
+        self.spawned_at = Instant::now();
         self.tasks.add({
             let task = self.context.attach(actor);
 
@@ -162,6 +247,88 @@ where
             }
         });
     }
+
+    /// Restart the actor according to `restart`, unless doing so exceeds the maximum number of
+    /// restarts, in which case we escalate.
+    async fn restart(&mut self, restart: Restart, reason: String, ctx: &mut Context<Self>) {
+        let delay = match restart {
+            Restart::Never => return,
+            Restart::Immediately => Duration::ZERO,
+            Restart::After(delay) => delay,
+            Restart::WithBackoff(backoff) => {
+                if self.spawned_at.elapsed() >= backoff.max() {
+                    self.backoff_attempts = 0;
+                }
+
+                let delay = backoff.delay(self.backoff_attempts);
+                self.backoff_attempts = self.backoff_attempts.saturating_add(1);
+
+                delay
+            }
+        };
+
+        if !self.record_restart() {
+            self.escalate(reason, ctx).await;
+            return;
+        }
+
+        if delay.is_zero() {
+            self.spawn_new(ctx);
+            return;
+        }
+
+        let actor = T::name();
+        tracing::debug!(actor = %&actor, ?delay, "Delaying restart of actor");
+
+        let this = ctx.address().expect("we are alive");
+        self.tasks.add(async move {
+            tokio::time::sleep(delay).await;
+            let _ = this.send(Respawn).await;
+        });
+    }
+
+    /// Record a restart, returning `false` if it exceeds the maximum number of restarts.
+    fn record_restart(&mut self) -> bool {
+        let MaxRestarts { restarts, window } = match self.restart_policy.max_restarts {
+            Some(max_restarts) => max_restarts,
+            None => return true,
+        };
+
+        let now = Instant::now();
+        while let Some(restarted_at) = self.recent_restarts.front() {
+            if now.duration_since(*restarted_at) <= window {
+                break;
+            }
+
+            self.recent_restarts.pop_front();
+        }
+
+        if self.recent_restarts.len() >= restarts {
+            return false;
+        }
+
+        self.recent_restarts.push_back(now);
+
+        true
+    }
+
+    async fn escalate(&mut self, reason: String, ctx: &mut Context<Self>) {
+        let actor = T::name();
+
+        tracing::error!(actor = %&actor, %reason, "Actor exceeded maximum number of restarts, giving up");
+
+        ESCALATIONS_COUNTER
+            .with(&HashMap::from([(ACTOR_LABEL, actor.as_str())]))
+            .inc();
+
+        if let Some(parent) = &self.escalate_to {
+            if let Err(e) = parent.send_async_safe(Escalated { actor, reason }).await {
+                tracing::warn!("Failed to notify parent about escalation: {e:#}");
+            }
+        }
+
+        ctx.stop_self();
+    }
 }
 
 #[async_trait]
@@ -189,14 +356,16 @@ where
 {
     pub fn handle(&mut self, msg: Stopped<R>, ctx: &mut Context<Self>) {
         let actor = T::name();
-        let should_restart = (self.restart_policy)(&msg.reason).await;
+        let restart = (self.restart_policy.strategy)(&msg.reason);
         let reason_str = format!("{:#}", anyhow::Error::new(msg.reason)); // Anyhow will format the entire chain of errors when using `alternate` Display (`#`)
 
-        tracing::info!(actor = %&actor, reason = %reason_str, restart = %should_restart, "Actor stopped");
+        tracing::info!(actor = %&actor, reason = %reason_str, ?restart, "Actor stopped");
 
-        if should_restart {
-            self.spawn_new(ctx)
-        }
+        self.restart(restart, reason_str, ctx).await
+    }
+
+    pub fn handle(&mut self, _: Respawn, ctx: &mut Context<Self>) {
+        self.spawn_new(ctx)
     }
 }
 
@@ -231,14 +400,19 @@ where
         tracing::info!(actor = %&actor, %reason, restart = true, "Actor panicked");
 
         self.metrics.num_panics += 1;
-        self.spawn_new(ctx)
+        PANICS_COUNTER
+            .with(&HashMap::from([(ACTOR_LABEL, actor.as_str())]))
+            .inc();
+
+        self.restart(Restart::Immediately, reason.to_owned(), ctx)
+            .await
     }
 }
 
 /// Module private message to notify ourselves that an actor stopped.
 ///
-/// The given `reason` will be passed to the `restart_policy` configured in the supervisor. If it
-/// yields `true`, a new instance of the actor will be spawned.
+/// The given `reason` will be passed to the `restart_policy` configured in the supervisor, which
+/// decides whether and when a new instance of the actor will be spawned.
 #[derive(Debug)]
 struct Stopped<R> {
     pub reason: R,
@@ -250,14 +424,46 @@ struct Panicked {
     pub error: Box<dyn Any + Send>,
 }
 
+/// Module private message to notify ourselves that a delayed restart is due.
+#[derive(Debug)]
+struct Respawn;
+
 /// Return the metrics tracked by this supervisor.
 ///
-/// Currently private because it is a feature only used for testing. If we want to expose metrics
-/// about the supervisor, we should look into creating a [`tracing::Subscriber`] that processes the
-/// events we are emitting.
+/// Currently private because it is a feature only used for testing. The same metrics are exported
+/// to Prometheus, labelled with the name of the supervised actor.
 #[derive(Debug)]
 struct GetMetrics;
 
+const ACTOR_LABEL: &str = "actor";
+
+static SPAWNS_COUNTER: Lazy<prometheus::IntCounterVec> = Lazy::new(|| {
+    prometheus::register_int_counter_vec!(
+        "supervisor_spawns_total",
+        "The number of times a supervisor spawned an instance of its actor.",
+        &[ACTOR_LABEL]
+    )
+    .unwrap()
+});
+
+static PANICS_COUNTER: Lazy<prometheus::IntCounterVec> = Lazy::new(|| {
+    prometheus::register_int_counter_vec!(
+        "supervisor_panics_total",
+        "The number of times a supervised actor shut down due to a panic.",
+        &[ACTOR_LABEL]
+    )
+    .unwrap()
+});
+
+static ESCALATIONS_COUNTER: Lazy<prometheus::IntCounterVec> = Lazy::new(|| {
+    prometheus::register_int_counter_vec!(
+        "supervisor_escalations_total",
+        "The number of times a supervisor gave up on its actor after too many restarts.",
+        &[ACTOR_LABEL]
+    )
+    .unwrap()
+});
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -384,6 +590,71 @@ mod tests {
         tokio::spawn(task);
     }
 
+    #[tokio::test]
+    async fn supervisor_escalates_after_max_restarts() {
+        let _guard = tracing_subscriber::fmt().with_test_writer().set_default();
+
+        let (parent, parent_task) = Parent::default().create(None).run();
+
+        #[allow(clippy::disallowed_methods)]
+        tokio::spawn(parent_task);
+
+        let (supervisor, address) = Actor::with_policy(
+            || RemoteShutdown,
+            always_restart::<io::Error>().with_max_restarts(1, Duration::from_secs(60)),
+        );
+        let (supervisor, task) = supervisor
+            .escalate_to(parent.clone().into())
+            .create(None)
+            .run();
+
+        #[allow(clippy::disallowed_methods)]
+        tokio::spawn(task);
+
+        address.send(Shutdown).await.unwrap();
+        let metrics = supervisor.send(GetMetrics).await.unwrap();
+        assert_eq!(metrics.num_spawns, 2, "first restart is within limit");
+
+        address.send(Shutdown).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while supervisor.is_connected() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("supervisor to stop after exceeding maximum number of restarts");
+
+        let escalations = parent.send(GetEscalations).await.unwrap();
+        assert_eq!(escalations.len(), 1);
+        assert_eq!(escalations[0].actor, RemoteShutdown::name());
+    }
+
+    #[tokio::test]
+    async fn policy_can_decide_based_on_stop_reason() {
+        let _guard = tracing_subscriber::fmt().with_test_writer().set_default();
+
+        let (supervisor, address) = Actor::with_policy(
+            || RemoteShutdown,
+            RestartPolicy::new(|error: &io::Error| match error.kind() {
+                io::ErrorKind::Other => Restart::Never,
+                _ => Restart::Immediately,
+            }),
+        );
+        let (supervisor, task) = supervisor.create(None).run();
+
+        #[allow(clippy::disallowed_methods)]
+        tokio::spawn(task);
+
+        address.send(Shutdown).await.unwrap();
+
+        let metrics = supervisor.send(GetMetrics).await.unwrap();
+        assert_eq!(
+            metrics.num_spawns, 1,
+            "actor should not have been restarted"
+        );
+        assert!(supervisor.is_connected(), "supervisor should keep running");
+    }
+
     /// An actor that can be shutdown remotely.
     struct RemoteShutdown;
 
@@ -441,4 +712,34 @@ mod tests {
 
         async fn stopped(self) -> Self::Stop {}
     }
+
+    /// An actor that records the escalations of the supervisors it is the parent of.
+    #[derive(Default)]
+    struct Parent {
+        escalations: Vec<Escalated>,
+    }
+
+    #[derive(Debug)]
+    struct GetEscalations;
+
+    #[async_trait]
+    impl xtra::Actor for Parent {
+        type Stop = ();
+
+        async fn stopped(self) -> Self::Stop {}
+    }
+
+    #[xtra_productivity(message_impl = false)]
+    impl Parent {
+        fn handle(&mut self, msg: Escalated) {
+            self.escalations.push(msg)
+        }
+    }
+
+    #[xtra_productivity]
+    impl Parent {
+        fn handle(&mut self, _: GetEscalations) -> Vec<Escalated> {
+            self.escalations.clone()
+        }
+    }
 }